@@ -0,0 +1,101 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::Error;
+
+/// The base64 alphabet to encode with or decode from (RFC 4648).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Alphabet {
+    /// The standard alphabet, using `+` and `/`. Encoded output is padded with `=`.
+    #[default]
+    Standard,
+    /// The URL and filename safe alphabet, using `-` and `_`. Encoded output is not padded.
+    UrlSafe,
+}
+
+impl Alphabet {
+    fn symbols(self) -> &'static [u8; 64] {
+        match self {
+            Alphabet::Standard => {
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+            }
+            Alphabet::UrlSafe => {
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_"
+            }
+        }
+    }
+
+    fn value(self, symbol: u8) -> Option<u8> {
+        match symbol {
+            b'A'..=b'Z' => Some(symbol - b'A'),
+            b'a'..=b'z' => Some(symbol - b'a' + 26),
+            b'0'..=b'9' => Some(symbol - b'0' + 52),
+            b'+' if self == Alphabet::Standard => Some(62),
+            b'/' if self == Alphabet::Standard => Some(63),
+            b'-' if self == Alphabet::UrlSafe => Some(62),
+            b'_' if self == Alphabet::UrlSafe => Some(63),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes `input` as base64 using `alphabet`.
+pub fn encode(input: &[u8], alphabet: Alphabet) -> String {
+    let symbols = alphabet.symbols();
+    let mut encoded = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let indices = [
+            b[0] >> 2,
+            (b[0] & 0x03) << 4 | b[1] >> 4,
+            (b[1] & 0x0f) << 2 | b[2] >> 6,
+            b[2] & 0x3f,
+        ];
+        for &index in &indices[..chunk.len() + 1] {
+            encoded.push(symbols[usize::from(index)] as char);
+        }
+        if alphabet == Alphabet::Standard {
+            for _ in chunk.len()..3 {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes base64 `input` using `alphabet`. Padding is optional for either alphabet.
+///
+/// Returns [`Error::InvalidBase64`] on symbols outside the alphabet or an impossible length.
+pub fn decode(input: &str, alphabet: Alphabet) -> Result<Vec<u8>, Error> {
+    let input = input.as_bytes();
+    let unpadded = match input.iter().position(|&b| b == b'=') {
+        Some(i) if input.len().is_multiple_of(4) && input.len() - i <= 2 => {
+            if input[i..].iter().any(|&b| b != b'=') {
+                return Err(Error::InvalidBase64);
+            }
+            &input[..i]
+        }
+        Some(_) => return Err(Error::InvalidBase64),
+        None => input,
+    };
+
+    let mut decoded = Vec::with_capacity(unpadded.len() / 4 * 3 + 2);
+    for chunk in unpadded.chunks(4) {
+        if chunk.len() == 1 {
+            return Err(Error::InvalidBase64);
+        }
+        let mut bits = 0u32;
+        for &symbol in chunk {
+            let value = alphabet.value(symbol).ok_or(Error::InvalidBase64)?;
+            bits = bits << 6 | u32::from(value);
+        }
+        bits <<= 6 * (4 - chunk.len());
+        let bytes = bits.to_be_bytes();
+        decoded.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Ok(decoded)
+}
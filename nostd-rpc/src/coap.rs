@@ -0,0 +1,592 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::IpAddr;
+
+use smoltcp::iface::{Interface, SocketSet};
+use smoltcp::phy::Device;
+use smoltcp::socket::udp::{PacketBuffer, PacketMetadata, SendError, Socket};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{IpAddress, IpEndpoint};
+
+use crate::net::LocalPorts;
+use crate::time::Clock;
+use crate::Error;
+
+const DEFAULT_PORT: u16 = 5683;
+const DEFAULT_TIMEOUT_SECONDS: u64 = 15;
+/// Transmission parameters of RFC 7252 section 4.8.
+const DEFAULT_ACK_TIMEOUT_SECONDS: u64 = 2;
+const DEFAULT_MAX_RETRANSMIT: u8 = 4;
+/// Largest message handled, the recommended upper bound of RFC 7252 section 4.6.
+const MAX_MESSAGE_SIZE: usize = 1152;
+
+const OPTION_URI_HOST: u16 = 3;
+const OPTION_URI_PATH: u16 = 11;
+const OPTION_CONTENT_FORMAT: u16 = 12;
+const OPTION_URI_QUERY: u16 = 15;
+const OPTION_ACCEPT: u16 = 17;
+
+/// Content format of JSON payloads (RFC 7252 section 12.3).
+pub const CONTENT_FORMAT_JSON: u16 = 50;
+/// Content format of CBOR payloads (RFC 8949 section 9.5).
+pub const CONTENT_FORMAT_CBOR: u16 = 60;
+
+/// The method of a CoAP request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Method {
+    #[default]
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+impl Method {
+    fn code(self) -> u8 {
+        match self {
+            Method::Get => 1,
+            Method::Post => 2,
+            Method::Put => 3,
+            Method::Delete => 4,
+        }
+    }
+}
+
+/// The type of a CoAP message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Confirmable,
+    NonConfirmable,
+    Acknowledgement,
+    Reset,
+}
+
+/// A CoAP message, as sent or received.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Message {
+    kind: Kind,
+    /// Class in the top 3 bits, detail in the bottom 5.
+    code: u8,
+    message_id: u16,
+    token: Vec<u8>,
+    /// Options in ascending order of number.
+    options: Vec<(u16, Vec<u8>)>,
+    payload: Vec<u8>,
+}
+
+impl Message {
+    /// Constructs an empty acknowledgement or reset of message `message_id`.
+    fn empty(kind: Kind, message_id: u16) -> Self {
+        Message {
+            kind,
+            code: 0,
+            message_id,
+            token: Vec::new(),
+            options: Vec::new(),
+            payload: Vec::new(),
+        }
+    }
+
+    /// Encodes the message (RFC 7252 section 3).
+    fn encode(&self) -> Vec<u8> {
+        let kind = match self.kind {
+            Kind::Confirmable => 0,
+            Kind::NonConfirmable => 1,
+            Kind::Acknowledgement => 2,
+            Kind::Reset => 3,
+        };
+        let mut data = Vec::with_capacity(16 + self.payload.len());
+        data.push(0x40 | (kind << 4) | self.token.len() as u8);
+        data.push(self.code);
+        data.extend_from_slice(&self.message_id.to_be_bytes());
+        data.extend_from_slice(&self.token);
+
+        let mut previous = 0;
+        for (number, value) in &self.options {
+            let (delta, delta_ext) = option_nibble(number - previous);
+            let (len, len_ext) = option_nibble(value.len() as u16);
+            data.push((delta << 4) | len);
+            data.extend_from_slice(&delta_ext);
+            data.extend_from_slice(&len_ext);
+            data.extend_from_slice(value);
+            previous = *number;
+        }
+        if !self.payload.is_empty() {
+            data.push(0xff);
+            data.extend_from_slice(&self.payload);
+        }
+        data
+    }
+
+    /// Parses a message, returning `None` if it is malformed.
+    fn parse(data: &[u8]) -> Option<Self> {
+        let (&first, rest) = data.split_first()?;
+        let token_len = usize::from(first & 0x0f);
+        if first >> 6 != 1 || token_len > 8 || rest.len() < 3 + token_len {
+            return None;
+        }
+        let kind = match (first >> 4) & 0x3 {
+            0 => Kind::Confirmable,
+            1 => Kind::NonConfirmable,
+            2 => Kind::Acknowledgement,
+            _ => Kind::Reset,
+        };
+        let code = rest[0];
+        let message_id = u16::from_be_bytes([rest[1], rest[2]]);
+        let token = rest[3..3 + token_len].to_vec();
+
+        let mut data = &rest[3 + token_len..];
+        let mut options = Vec::new();
+        let mut number = 0u16;
+        while let Some((&byte, rest)) = data.split_first() {
+            if byte == 0xff {
+                if rest.is_empty() {
+                    return None;
+                }
+                return Some(Message {
+                    kind,
+                    code,
+                    message_id,
+                    token,
+                    options,
+                    payload: rest.to_vec(),
+                });
+            }
+            data = rest;
+            let delta = option_value(byte >> 4, &mut data)?;
+            let len = usize::from(option_value(byte & 0x0f, &mut data)?);
+            number = number.checked_add(delta)?;
+            let value = data.get(..len)?;
+            options.push((number, value.to_vec()));
+            data = &data[len..];
+        }
+        Some(Message {
+            kind,
+            code,
+            message_id,
+            token,
+            options,
+            payload: Vec::new(),
+        })
+    }
+}
+
+/// Returns the nibble encoding an option delta or length, and the extended bytes following
+/// the option header.
+fn option_nibble(value: u16) -> (u8, Vec<u8>) {
+    match value {
+        0..=12 => (value as u8, Vec::new()),
+        13..=268 => (13, vec![(value - 13) as u8]),
+        _ => (14, (value - 269).to_be_bytes().to_vec()),
+    }
+}
+
+/// Decodes an option delta or length from its `nibble` and the extended bytes at the start
+/// of `data`, which are removed.
+fn option_value(nibble: u8, data: &mut &[u8]) -> Option<u16> {
+    match nibble {
+        0..=12 => Some(u16::from(nibble)),
+        13 => {
+            let (&byte, rest) = data.split_first()?;
+            *data = rest;
+            Some(u16::from(byte) + 13)
+        }
+        14 => {
+            let bytes = data.get(..2)?;
+            let value = u16::from_be_bytes([bytes[0], bytes[1]]).checked_add(269)?;
+            *data = &data[2..];
+            Some(value)
+        }
+        _ => None,
+    }
+}
+
+/// Encodes `value` as an unsigned integer option, without leading zero bytes.
+fn uint_option(value: u16) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    bytes[zeros..].to_vec()
+}
+
+/// A request to a CoAP server (RFC 7252).
+#[derive(Clone, Debug)]
+pub struct CoapRequest {
+    /// IP address of the server.
+    ip: Option<IpAddr>,
+    /// Port of the server.
+    port: u16,
+    /// Host name sent in the `Uri-Host` option, empty if not set.
+    host: String,
+    method: Method,
+    /// The resource path, sent as `Uri-Path` options.
+    path: String,
+    /// The query, sent as `Uri-Query` options.
+    query: String,
+    content_format: Option<u16>,
+    accept: Option<u16>,
+    payload: Vec<u8>,
+    /// Whether the request is confirmable, i.e. retransmitted until acknowledged.
+    confirmable: bool,
+    /// Initial time to wait for an acknowledgement before retransmitting.
+    ack_timeout: Duration,
+    /// Number of retransmissions of a confirmable request before giving up.
+    max_retransmit: u8,
+    /// How long to wait for the response once the request has been acknowledged, or sent if
+    /// it is not confirmable.
+    timeout: Duration,
+    /// Chooses the local port of the request.
+    ports: LocalPorts,
+}
+
+impl Default for CoapRequest {
+    fn default() -> Self {
+        CoapRequest {
+            ip: None,
+            port: DEFAULT_PORT,
+            host: String::new(),
+            method: Method::default(),
+            path: String::new(),
+            query: String::new(),
+            content_format: None,
+            accept: None,
+            payload: Vec::new(),
+            confirmable: true,
+            ack_timeout: Duration::from_secs(DEFAULT_ACK_TIMEOUT_SECONDS),
+            max_retransmit: DEFAULT_MAX_RETRANSMIT,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECONDS),
+            ports: LocalPorts::new([0; 8]),
+        }
+    }
+}
+
+impl CoapRequest {
+    /// Constructs a new confirmable GET [`CoapRequest`] to port 5683.
+    pub fn new() -> Self {
+        CoapRequest::default()
+    }
+
+    /// Sets the IPv4 address of the server.
+    pub fn ipv4(mut self, ip: [u8; 4]) -> Self {
+        self.ip = Some(IpAddr::from(ip));
+        self
+    }
+
+    /// Sets the IPv6 address of the server.
+    pub fn ipv6(mut self, ip: [u16; 8]) -> Self {
+        self.ip = Some(IpAddr::from(ip));
+        self
+    }
+
+    /// Sets the IP address of the server.
+    pub fn ip<A: Into<IpAddr>>(mut self, ip: A) -> Self {
+        self.ip = Some(ip.into());
+        self
+    }
+
+    /// Sets the port of the server.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets the host name sent to the server, for servers hosting several virtual hosts.
+    /// It is not resolved.
+    pub fn host(mut self, host: &str) -> Self {
+        self.host = String::from(host);
+        self
+    }
+
+    /// Sets the method.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sets the path of the resource, e.g. `/fw/state`, and its query after a `?`, with
+    /// `&` separating the query arguments.
+    pub fn path(mut self, path: &str) -> Self {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        self.path = String::from(path);
+        self.query = String::from(query);
+        self
+    }
+
+    /// Sets the payload and its content format, e.g. [`CONTENT_FORMAT_JSON`].
+    pub fn payload(mut self, payload: &[u8], content_format: u16) -> Self {
+        self.payload = payload.to_vec();
+        self.content_format = Some(content_format);
+        self
+    }
+
+    /// Asks for a response payload in `content_format`.
+    pub fn accept(mut self, content_format: u16) -> Self {
+        self.accept = Some(content_format);
+        self
+    }
+
+    /// Sets whether the request is confirmable, true by default. A non-confirmable request
+    /// is sent once and never acknowledged.
+    pub fn confirmable(mut self, confirmable: bool) -> Self {
+        self.confirmable = confirmable;
+        self
+    }
+
+    /// Sets the initial time to wait for an acknowledgement before retransmitting, 2 seconds
+    /// by default. It is randomized by up to half as much again, and doubles with each
+    /// retransmission.
+    pub fn ack_timeout<T: Into<Duration>>(mut self, timeout: T) -> Self {
+        self.ack_timeout = timeout.into();
+        self
+    }
+
+    /// Sets the number of retransmissions of a confirmable request before giving up, 4 by
+    /// default.
+    pub fn max_retransmit(mut self, retransmissions: u8) -> Self {
+        self.max_retransmit = retransmissions;
+        self
+    }
+
+    /// Sets how long to wait for the response once the request has been acknowledged, or
+    /// sent if it is not confirmable.
+    pub fn timeout<T: Into<Duration>>(mut self, timeout: T) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Seeds the choice of the local port of the request with `random`, which should come
+    /// from a random number generator, see [`LocalPorts`].
+    pub fn local_ports(mut self, random: [u8; 8]) -> Self {
+        self.ports = LocalPorts::new(random);
+        self
+    }
+
+    /// Constructs the message sending the request with `message_id` and `token`.
+    fn message(&self, message_id: u16, token: &[u8]) -> Message {
+        let mut options = Vec::new();
+        if !self.host.is_empty() {
+            options.push((OPTION_URI_HOST, self.host.as_bytes().to_vec()));
+        }
+        for segment in self.path.split('/').filter(|s| !s.is_empty()) {
+            options.push((OPTION_URI_PATH, segment.as_bytes().to_vec()));
+        }
+        if let Some(format) = self.content_format {
+            options.push((OPTION_CONTENT_FORMAT, uint_option(format)));
+        }
+        for argument in self.query.split('&').filter(|s| !s.is_empty()) {
+            options.push((OPTION_URI_QUERY, argument.as_bytes().to_vec()));
+        }
+        if let Some(format) = self.accept {
+            options.push((OPTION_ACCEPT, uint_option(format)));
+        }
+        Message {
+            kind: if self.confirmable {
+                Kind::Confirmable
+            } else {
+                Kind::NonConfirmable
+            },
+            code: self.method.code(),
+            message_id,
+            token: token.to_vec(),
+            options,
+            payload: self.payload.clone(),
+        }
+    }
+}
+
+/// The response to a [`CoapRequest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoapResponse {
+    code: u8,
+    options: Vec<(u16, Vec<u8>)>,
+    payload: Vec<u8>,
+}
+
+impl CoapResponse {
+    /// Returns the response code, e.g. `0x45` for 2.05 Content.
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+
+    /// Returns the class of the response code, e.g. 2 for 2.05 Content.
+    pub fn class(&self) -> u8 {
+        self.code >> 5
+    }
+
+    /// Returns the detail of the response code, e.g. 5 for 2.05 Content.
+    pub fn detail(&self) -> u8 {
+        self.code & 0x1f
+    }
+
+    /// Returns whether the response code is in the 2.xx Success class.
+    pub fn is_success(&self) -> bool {
+        self.class() == 2
+    }
+
+    /// Returns the value of the first option with `number`, if any.
+    pub fn option(&self, number: u16) -> Option<&[u8]> {
+        self.options
+            .iter()
+            .find(|(n, _)| *n == number)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// Returns the content format of the payload, if the server gave one.
+    pub fn content_format(&self) -> Option<u16> {
+        let value = self.option(OPTION_CONTENT_FORMAT)?;
+        if value.len() > 2 {
+            return None;
+        }
+        Some(value.iter().fold(0, |n, &b| (n << 8) | u16::from(b)))
+    }
+
+    /// Returns the payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+/// Sends [`CoapRequest`]s, giving each a new message ID and token.
+#[derive(Clone, Debug)]
+pub struct CoapClient {
+    next_message_id: u16,
+    /// State of the generator of tokens and retransmission timeouts.
+    random_state: u64,
+}
+
+impl CoapClient {
+    /// Constructs a client seeded with `random`, which must come from a random number
+    /// generator so that tokens and message IDs are hard to guess and differ across reboots.
+    pub fn new(random: [u8; 8]) -> Self {
+        CoapClient {
+            next_message_id: u16::from_le_bytes([random[0], random[1]]),
+            // The generator never leaves the all zero state.
+            random_state: u64::from_le_bytes(random) | 1,
+        }
+    }
+
+    /// Sends `request` over `iface` and `device`, which must already have their address and
+    /// routes configured, and waits for the response.
+    ///
+    /// A confirmable request is retransmitted with exponential back-off until the server
+    /// acknowledges it. The response may come with the acknowledgement or separately, in
+    /// which case it is acknowledged if confirmable.
+    ///
+    /// Returns [`Error::UnresolvedHost`] if the request has no IP address,
+    /// [`Error::ConnectionRefused`] if the server resets the request and
+    /// [`Error::ResponseTimeout`] if it is never acknowledged or answered in time.
+    pub fn send_with_device<D: Device, C: Clock>(
+        &mut self,
+        iface: &mut Interface,
+        device: &mut D,
+        request: &CoapRequest,
+        clock: &C,
+    ) -> Result<CoapResponse, Error> {
+        let ip = request.ip.ok_or(Error::UnresolvedHost)?;
+        let remote = IpEndpoint::new(IpAddress::from(ip), request.port);
+        let message_id = self.next_message_id;
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+        let token = self.next_random().to_le_bytes()[..4].to_vec();
+        let message = request.message(message_id, &token).encode();
+
+        let buffer = || {
+            PacketBuffer::new(
+                vec![PacketMetadata::EMPTY; 2],
+                vec![0; 2 * MAX_MESSAGE_SIZE],
+            )
+        };
+        let mut socket = Socket::new(buffer(), buffer());
+        socket
+            .bind(request.ports.clone().next(clock.now()))
+            .map_err(Error::Bind)?;
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(socket);
+
+        // A random initial timeout between ACK_TIMEOUT and 1.5 times as much (RFC 7252
+        // section 4.2), in thousandths.
+        let factor = 1000 + self.next_random() % 501;
+        let mut ack_timeout = request.ack_timeout * factor as u32 / 1000;
+        let mut transmissions = 0;
+        // When to retransmit, while the request is not acknowledged.
+        let mut retransmit_at = Some(clock.now());
+        // When to give up waiting for the response.
+        let mut deadline: Option<Instant> = None;
+        loop {
+            let timestamp = clock.now();
+            let socket = sockets.get_mut::<Socket>(handle);
+            if retransmit_at.is_some_and(|at| timestamp >= at) {
+                if transmissions > request.max_retransmit {
+                    debug!("CoAP request not acknowledged");
+                    return Err(Error::ResponseTimeout);
+                }
+                match socket.send_slice(&message, remote) {
+                    Ok(()) | Err(SendError::BufferFull) => {}
+                    Err(error) => return Err(Error::Datagram(error)),
+                }
+                trace!("sent CoAP request, transmission {}", transmissions + 1);
+                transmissions += 1;
+                if request.confirmable {
+                    retransmit_at = Some(timestamp + ack_timeout);
+                    ack_timeout *= 2;
+                } else {
+                    retransmit_at = None;
+                    deadline = Some(timestamp + request.timeout);
+                }
+            }
+            if deadline.is_some_and(|at| timestamp > at) {
+                return Err(Error::ResponseTimeout);
+            }
+            iface.poll(timestamp, device, &mut sockets);
+
+            let socket = sockets.get_mut::<Socket>(handle);
+            while let Ok((data, meta)) = socket.recv() {
+                if meta.endpoint != remote {
+                    continue;
+                }
+                let Some(reply) = Message::parse(data) else {
+                    trace!("ignoring malformed CoAP message");
+                    continue;
+                };
+                let answers_request = reply.message_id == message_id;
+                match reply.kind {
+                    Kind::Reset if answers_request => return Err(Error::ConnectionRefused),
+                    Kind::Acknowledgement if answers_request && reply.code == 0 => {
+                        debug!("CoAP request acknowledged, waiting for the response");
+                        retransmit_at = None;
+                        deadline = Some(timestamp + request.timeout);
+                        continue;
+                    }
+                    Kind::Acknowledgement if !answers_request => continue,
+                    Kind::Reset => continue,
+                    _ if reply.token != token || reply.code == 0 => continue,
+                    _ => {}
+                }
+                if reply.kind == Kind::Confirmable {
+                    let ack = Message::empty(Kind::Acknowledgement, reply.message_id).encode();
+                    match socket.send_slice(&ack, remote) {
+                        Ok(()) | Err(SendError::BufferFull) => {}
+                        Err(error) => return Err(Error::Datagram(error)),
+                    }
+                    iface.poll(clock.now(), device, &mut sockets);
+                }
+                debug!("CoAP response with code {}", reply.code);
+                return Ok(CoapResponse {
+                    code: reply.code,
+                    options: reply.options,
+                    payload: reply.payload,
+                });
+            }
+        }
+    }
+
+    /// Returns the next number from a xorshift generator seeded with the caller's random
+    /// bytes.
+    fn next_random(&mut self) -> u64 {
+        let mut x = self.random_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.random_state = x;
+        x
+    }
+}
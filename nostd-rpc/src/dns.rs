@@ -0,0 +1,115 @@
+use alloc::vec;
+use core::net::IpAddr;
+
+use smoltcp::iface::{Interface, SocketSet};
+use smoltcp::phy::Device;
+use smoltcp::socket::dns::{self, GetQueryResultError, StartQueryError};
+use smoltcp::time::Duration;
+use smoltcp::wire::{DnsQueryType, IpAddress, IpVersion};
+
+use crate::time::Clock;
+use crate::Error;
+
+/// Resolves the address of `name` by asking each of `servers` in turn, waiting up to
+/// `timeout` for each.
+///
+/// An IPv4 address is asked for if `iface` has one of its own, and an IPv6 address if it has
+/// an IPv6 address and no IPv4 address was found.
+///
+/// `iface` and `device` are polled with a socket set of their own, so other sockets on the
+/// interface are not served while the query runs.
+///
+/// Returns [`Error::InvalidHost`] if `name` cannot be sent in a query,
+/// [`Error::UnresolvedHost`] if every server fails to answer with an address and
+/// [`Error::DnsTimeout`] if the last server does not answer in time.
+pub fn resolve<D: Device, C: Clock>(
+    iface: &mut Interface,
+    device: &mut D,
+    servers: &[IpAddr],
+    name: &str,
+    timeout: Duration,
+    clock: &C,
+) -> Result<IpAddr, Error> {
+    let mut result = Err(Error::UnresolvedHost);
+    for server in servers {
+        result = query(iface, device, *server, name, timeout, clock);
+        if matches!(result, Ok(_) | Err(Error::InvalidHost)) {
+            break;
+        }
+    }
+    result
+}
+
+/// Asks `server` for the address of `name`, trying the address families of `iface` in turn.
+fn query<D: Device, C: Clock>(
+    iface: &mut Interface,
+    device: &mut D,
+    server: IpAddr,
+    name: &str,
+    timeout: Duration,
+    clock: &C,
+) -> Result<IpAddr, Error> {
+    let has_ipv4 = iface
+        .ip_addrs()
+        .iter()
+        .any(|cidr| cidr.address().version() == IpVersion::Ipv4);
+    let has_ipv6 = iface
+        .ip_addrs()
+        .iter()
+        .any(|cidr| cidr.address().version() == IpVersion::Ipv6);
+    let mut result = Err(Error::UnresolvedHost);
+    for (enabled, query_type) in [(has_ipv4, DnsQueryType::A), (has_ipv6, DnsQueryType::Aaaa)] {
+        if !enabled {
+            continue;
+        }
+        result = query_type_at(iface, device, server, name, query_type, timeout, clock);
+        if !matches!(result, Err(Error::UnresolvedHost)) {
+            break;
+        }
+    }
+    result
+}
+
+/// Asks `server` for an address of `query_type` for `name`.
+fn query_type_at<D: Device, C: Clock>(
+    iface: &mut Interface,
+    device: &mut D,
+    server: IpAddr,
+    name: &str,
+    query_type: DnsQueryType,
+    timeout: Duration,
+    clock: &C,
+) -> Result<IpAddr, Error> {
+    let mut socket = dns::Socket::new(&[IpAddress::from(server)], vec![None]);
+    let query = socket
+        .start_query(iface.context(), name, query_type)
+        .map_err(|e| match e {
+            StartQueryError::InvalidName | StartQueryError::NameTooLong => Error::InvalidHost,
+            StartQueryError::NoFreeSlot => Error::UnresolvedHost,
+        })?;
+    let mut sockets = SocketSet::new(vec![]);
+    let handle = sockets.add(socket);
+
+    let start = clock.now();
+    loop {
+        let timestamp = clock.now();
+        iface.poll(timestamp, device, &mut sockets);
+        match sockets
+            .get_mut::<dns::Socket>(handle)
+            .get_query_result(query)
+        {
+            Ok(addresses) => {
+                debug!("resolved {}", name);
+                return addresses
+                    .first()
+                    .map(|address| IpAddr::from(*address))
+                    .ok_or(Error::UnresolvedHost);
+            }
+            Err(GetQueryResultError::Failed) => return Err(Error::UnresolvedHost),
+            Err(GetQueryResultError::Pending) if timestamp - start > timeout => {
+                return Err(Error::DnsTimeout)
+            }
+            Err(GetQueryResultError::Pending) => {}
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use alloc::string::String;
+
+use crate::http::HttpRequest;
+use crate::response::HttpResponse;
+use crate::Error;
+
+/// Tracks how much of a resource has been downloaded, so an interrupted download can be
+/// resumed with a `Range` request rather than started again.
+///
+/// Each response is checked to continue the download where it stopped: a `206 Partial
+/// Content` must start at the current offset and agree with the total size and `ETag` seen
+/// so far.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResumableDownload {
+    /// Number of bytes downloaded so far.
+    offset: u64,
+    /// Size of the resource, once known.
+    total: Option<u64>,
+    /// Entity tag of the resource, if the server sent one.
+    etag: Option<String>,
+}
+
+impl ResumableDownload {
+    /// Constructs a new [`ResumableDownload`] starting at the beginning of the resource.
+    pub fn new() -> Self {
+        ResumableDownload::default()
+    }
+
+    /// Returns the number of bytes downloaded so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the size of the resource, once known.
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+
+    /// Returns the entity tag of the resource, if the server sent one.
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    /// Returns true once the whole resource has been downloaded.
+    pub fn is_complete(&self) -> bool {
+        self.total == Some(self.offset)
+    }
+
+    /// Adds the headers to `request` that fetch the rest of the resource.
+    ///
+    /// Once some of the resource has been downloaded this is a `Range` header from the current
+    /// offset, with `If-Range` so the server sends the whole resource if it has changed.
+    pub fn request(&self, request: HttpRequest) -> HttpRequest {
+        if self.offset == 0 {
+            return request;
+        }
+        self.if_range(request.range(self.offset, None))
+    }
+
+    /// Adds the headers to `request` that fetch the next `len` bytes of the resource.
+    ///
+    /// This is a `Range` header from the current offset, with `If-Range` once the entity tag
+    /// is known so the server sends the whole resource if it has changed.
+    pub fn request_block(&self, request: HttpRequest, len: u64) -> HttpRequest {
+        let end = self.offset + len.max(1) - 1;
+        self.if_range(request.range(self.offset, Some(end)))
+    }
+
+    /// Adds an `If-Range` header with the entity tag to `request`, if it is known and strong.
+    fn if_range(&self, request: HttpRequest) -> HttpRequest {
+        match &self.etag {
+            // Weak entity tags cannot be used with If-Range.
+            Some(etag) if !etag.starts_with("W/") => {
+                request.header(&alloc::format!("If-Range: {}", etag))
+            }
+            _ => request,
+        }
+    }
+
+    /// Checks that `response` continues the download and records its body as downloaded.
+    ///
+    /// Returns the body, to be appended to the data downloaded so far, or
+    /// [`Error::RangeMismatch`] if the response does not continue the download.
+    pub fn accept<'a>(&mut self, response: &'a HttpResponse) -> Result<&'a [u8], Error> {
+        if response.status() == 416 && self.is_complete() {
+            return Ok(&[]);
+        }
+        self.accept_head(response)?;
+        self.record(response.body().len());
+        Ok(response.body())
+    }
+
+    /// Checks that the status and headers of `response` continue the download, its body is
+    /// ignored. Use [`record`](Self::record) to count the body as it is received.
+    pub fn accept_head(&mut self, response: &HttpResponse) -> Result<(), Error> {
+        let total = match response.status() {
+            206 => {
+                let range = response
+                    .header("content-range")
+                    .ok_or(Error::RangeMismatch)?;
+                let (first, _, total) = parse_content_range(range).ok_or(Error::RangeMismatch)?;
+                if first != self.offset {
+                    return Err(Error::RangeMismatch);
+                }
+                total
+            }
+            200 if self.offset == 0 => response
+                .header("content-length")
+                .and_then(|len| len.parse().ok()),
+            _ => return Err(Error::RangeMismatch),
+        };
+
+        if let (Some(known), Some(total)) = (self.total, total) {
+            if known != total {
+                return Err(Error::RangeMismatch);
+            }
+        }
+        let etag = response.header("etag");
+        if let (Some(known), Some(etag)) = (&self.etag, etag) {
+            if known != etag {
+                return Err(Error::RangeMismatch);
+            }
+        }
+        self.total = self.total.or(total);
+        if self.etag.is_none() {
+            self.etag = etag.map(String::from);
+        }
+        Ok(())
+    }
+
+    /// Records `len` more bytes of the resource as downloaded.
+    pub fn record(&mut self, len: usize) {
+        self.offset += len as u64;
+    }
+}
+
+/// Parses a `Content-Range` value of the form `bytes first-last/total`, where the total may be
+/// `*` if unknown.
+fn parse_content_range(value: &str) -> Option<(u64, u64, Option<u64>)> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (range, total) = range.split_once('/')?;
+    let (first, last) = range.split_once('-')?;
+    let first: u64 = first.parse().ok()?;
+    let last: u64 = last.parse().ok()?;
+    let total = match total {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    if last < first || total.is_some_and(|total| last >= total) {
+        return None;
+    }
+    Some((first, last, total))
+}
@@ -0,0 +1,379 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{entities, Error};
+
+/// Decodes HTML character references: decimal (`&#65;`), hexadecimal (`&#x41;`) and named
+/// (`&amp;`). Invalid or unknown references are left as they are.
+pub fn html_decode(input: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = input;
+
+    while let Some(i) = rest.find('&') {
+        decoded.push_str(&rest[..i]);
+        rest = &rest[i..];
+        match decode_character_reference(rest, &mut decoded) {
+            Some(len) => rest = &rest[len..],
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+/// Decodes URL percent-encoding (`%41`) into bytes. Invalid escapes are left as they are.
+pub fn percent_decode_bytes(input: &str) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    let mut rest = input.as_bytes();
+
+    while let Some((&b, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| core::str::from_utf8(hex).ok())
+            .and_then(decode_hex_byte);
+        match escaped {
+            Some(byte) if b == b'%' => {
+                decoded.push(byte);
+                rest = &tail[2..];
+            }
+            _ => {
+                decoded.push(b);
+                rest = tail;
+            }
+        }
+    }
+
+    decoded
+}
+
+/// Decodes URL percent-encoding (`%41`) into text, returning [`Error::InvalidUtf8`] if the
+/// decoded bytes are not UTF-8. Invalid escapes are left as they are.
+pub fn percent_decode(input: &str) -> Result<String, Error> {
+    String::from_utf8(percent_decode_bytes(input)).map_err(|_| Error::InvalidUtf8)
+}
+
+/// Decodes a query key or value: `+` stands for a space and percent-encoding is decoded,
+/// as in `application/x-www-form-urlencoded` data. Returns [`Error::InvalidUtf8`] if the
+/// decoded bytes are not UTF-8.
+pub fn decode_url(input: &str) -> Result<String, Error> {
+    percent_decode(&input.replace('+', " "))
+}
+
+/// Decodes quoted-printable text (RFC 2045) into bytes: `=XX` escapes are decoded and soft
+/// line breaks (`=` at the end of a line) are removed. Invalid escapes are left as they are.
+pub fn quoted_printable_decode(input: &str) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(input.len());
+    for line in input.split_inclusive('\n') {
+        let (line, line_break) = match line.strip_suffix("\r\n") {
+            Some(line) => (line, "\r\n"),
+            None => match line.strip_suffix('\n') {
+                Some(line) => (line, "\n"),
+                None => (line, ""),
+            },
+        };
+        // Trailing whitespace is padding added in transport, not part of the text.
+        let line = line.trim_end_matches([' ', '\t']);
+        let (line, line_break) = match line.strip_suffix('=') {
+            Some(line) => (line, ""),
+            None => (line, line_break),
+        };
+
+        let mut rest = line.as_bytes();
+        while let Some((&b, tail)) = rest.split_first() {
+            let escaped = tail
+                .get(..2)
+                .and_then(|hex| core::str::from_utf8(hex).ok())
+                .and_then(decode_hex_byte);
+            match escaped {
+                Some(byte) if b == b'=' => {
+                    decoded.push(byte);
+                    rest = &tail[2..];
+                }
+                _ => {
+                    decoded.push(b);
+                    rest = tail;
+                }
+            }
+        }
+        decoded.extend_from_slice(line_break.as_bytes());
+    }
+    decoded
+}
+
+/// An incremental decoder, for decoding input that arrives in chunks such as a streamed
+/// response body without holding all of it in memory.
+pub trait Decoder {
+    /// Decodes `input` and appends the output to `out`. Bytes that may start an escape
+    /// continued in the next chunk are held back until the next call.
+    fn feed(&mut self, input: &[u8], out: &mut Vec<u8>);
+
+    /// Appends any held back bytes to `out` as they are, at the end of the input.
+    fn finish(&mut self, out: &mut Vec<u8>);
+}
+
+/// The longest character reference [`HtmlDecoder`] holds back, including the '&' and ';'.
+/// The longest HTML5 named reference is 33 bytes.
+const MAX_REFERENCE_LEN: usize = 40;
+
+/// An incremental [`html_decode`]. References longer than 40 bytes are left as they are.
+#[derive(Clone, Debug, Default)]
+pub struct HtmlDecoder {
+    pending: Vec<u8>,
+}
+
+impl HtmlDecoder {
+    /// Constructs a new [`HtmlDecoder`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, b: u8, out: &mut Vec<u8>) {
+        if self.pending.is_empty() {
+            if b == b'&' {
+                self.pending.push(b);
+            } else {
+                out.push(b);
+            }
+        } else if b == b';' {
+            self.pending.push(b);
+            let mut decoded = String::new();
+            let reference = core::str::from_utf8(&self.pending).ok();
+            match reference.and_then(|r| decode_character_reference(r, &mut decoded)) {
+                Some(_) => out.extend_from_slice(decoded.as_bytes()),
+                None => out.extend_from_slice(&self.pending),
+            }
+            self.pending.clear();
+        } else if (b.is_ascii_alphanumeric() || b == b'#') && self.pending.len() < MAX_REFERENCE_LEN
+        {
+            self.pending.push(b);
+        } else {
+            self.finish(out);
+            self.push(b, out);
+        }
+    }
+}
+
+impl Decoder for HtmlDecoder {
+    fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) {
+        for &b in input {
+            self.push(b, out);
+        }
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.pending);
+        self.pending.clear();
+    }
+}
+
+/// An incremental [`percent_decode_bytes`].
+#[derive(Clone, Debug, Default)]
+pub struct PercentDecoder {
+    pending: Vec<u8>,
+}
+
+impl PercentDecoder {
+    /// Constructs a new [`PercentDecoder`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, b: u8, out: &mut Vec<u8>) {
+        if self.pending.is_empty() && b != b'%' {
+            out.push(b);
+            return;
+        }
+        self.pending.push(b);
+        if self.pending.len() < 3 {
+            return;
+        }
+        let escaped = core::str::from_utf8(&self.pending[1..])
+            .ok()
+            .and_then(decode_hex_byte);
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                self.pending.clear();
+            }
+            None => {
+                // Not an escape: keep the '%' and decode what followed it again.
+                let tail = [self.pending[1], self.pending[2]];
+                self.pending.clear();
+                out.push(b'%');
+                for b in tail {
+                    self.push(b, out);
+                }
+            }
+        }
+    }
+}
+
+impl Decoder for PercentDecoder {
+    fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) {
+        for &b in input {
+            self.push(b, out);
+        }
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.pending);
+        self.pending.clear();
+    }
+}
+
+/// Escapes the characters that are special in HTML text and attribute values:
+/// `&`, `<`, `>`, `"` and `'`.
+pub fn html_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => encoded.push_str("&amp;"),
+            '<' => encoded.push_str("&lt;"),
+            '>' => encoded.push_str("&gt;"),
+            '"' => encoded.push_str("&quot;"),
+            '\'' => encoded.push_str("&#39;"),
+            _ => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+/// The ASCII characters left unencoded by [`percent_encode`], every other byte is
+/// percent-encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodeSet(u128);
+
+impl EncodeSet {
+    /// Keeps only the RFC 3986 unreserved characters, `A-Z a-z 0-9 - . _ ~`.
+    pub const UNRESERVED: EncodeSet = EncodeSet::unreserved_and(b"");
+    /// Keeps the characters allowed unencoded in a path segment.
+    pub const PATH_SEGMENT: EncodeSet = EncodeSet::unreserved_and(b"!$&'()*+,;=:@");
+    /// Keeps the characters allowed unencoded in a query key or value, excluding the
+    /// `&`, `=` and `+` delimiters.
+    pub const QUERY_COMPONENT: EncodeSet = EncodeSet::unreserved_and(b"!$'()*,;:@/?");
+
+    /// Returns a set that keeps the unreserved characters and the ASCII characters in `extra`.
+    pub const fn unreserved_and(extra: &[u8]) -> Self {
+        let mut mask = 0u128;
+        let mut b = 0u8;
+        while b < 128 {
+            if b.is_ascii_alphanumeric() || b == b'-' || b == b'.' || b == b'_' || b == b'~' {
+                mask |= 1 << b;
+            }
+            b += 1;
+        }
+        let mut i = 0;
+        while i < extra.len() {
+            if extra[i] < 128 {
+                mask |= 1 << extra[i];
+            }
+            i += 1;
+        }
+        EncodeSet(mask)
+    }
+
+    /// Returns true if `b` is left unencoded.
+    fn keeps(&self, b: u8) -> bool {
+        b < 128 && self.0 & (1 << b) != 0
+    }
+}
+
+/// Percent-encodes (RFC 3986) every byte of `input` that is not kept by `set`.
+pub fn percent_encode(input: &str, set: &EncodeSet) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    percent_encode_into(&mut encoded, input.as_bytes(), set);
+    encoded
+}
+
+/// Percent-encodes a query key or value, so that it can be placed between the `?`, `&` and
+/// `=` delimiters of a query string. Spaces are encoded as `%20`.
+pub fn encode_url(input: &str) -> String {
+    percent_encode(input, &EncodeSet::QUERY_COMPONENT)
+}
+
+/// Encodes `fields` as `application/x-www-form-urlencoded` data, e.g. `a=1&b=x+y`. Spaces
+/// are encoded as `+`, and every other byte that is not an RFC 3986 unreserved character is
+/// percent-encoded.
+pub fn form_encode(fields: &[(&str, &str)]) -> String {
+    let mut encoded = String::new();
+    for (i, (name, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            encoded.push('&');
+        }
+        form_encode_into(&mut encoded, name);
+        encoded.push('=');
+        form_encode_into(&mut encoded, value);
+    }
+    encoded
+}
+
+/// Appends the form encoding of `input` to `out`.
+fn form_encode_into(out: &mut String, input: &str) {
+    for (i, part) in input.split(' ').enumerate() {
+        if i > 0 {
+            out.push('+');
+        }
+        percent_encode_into(out, part.as_bytes(), &EncodeSet::UNRESERVED);
+    }
+}
+
+/// Appends `input` to `out`, percent-encoding every byte that is not kept by `set`.
+pub(crate) fn percent_encode_into(out: &mut String, input: &[u8], set: &EncodeSet) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    for &b in input {
+        if set.keeps(b) {
+            out.push(b as char);
+        } else {
+            out.push('%');
+            out.push(HEX[usize::from(b >> 4)] as char);
+            out.push(HEX[usize::from(b & 0xf)] as char);
+        }
+    }
+}
+
+/// Decodes the character reference at the start of `input`, which starts with '&', into
+/// `out`. Returns the length of the reference, or `None` if it is not valid.
+pub(crate) fn decode_character_reference(input: &str, out: &mut String) -> Option<usize> {
+    let end = input.find(';')?;
+    let reference = &input[1..end];
+    if let Some(number) = reference.strip_prefix('#') {
+        let (digits, radix) = match number.strip_prefix(['x', 'X']) {
+            Some(hex) if hex.bytes().all(|b| b.is_ascii_hexdigit()) => (hex, 16),
+            None if number.bytes().all(|b| b.is_ascii_digit()) => (number, 10),
+            _ => return None,
+        };
+        if digits.is_empty() {
+            return None;
+        }
+        out.push(char::from_u32(u32::from_str_radix(digits, radix).ok()?)?);
+    } else {
+        if !reference.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return None;
+        }
+        out.push_str(entities::lookup(reference)?);
+    }
+    Some(end + 1)
+}
+
+/// Decodes two hexadecimal digits into a byte.
+pub(crate) fn decode_hex_byte(hex: &str) -> Option<u8> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u8::from_str_radix(hex, 16).ok()
+}
+
+/// Encodes `bytes` as lowercase hexadecimal, two digits per byte.
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::with_capacity(2 * bytes.len());
+    for byte in bytes {
+        hex.push(char::from(DIGITS[usize::from(byte >> 4)]));
+        hex.push(char::from(DIGITS[usize::from(byte & 0x0f)]));
+    }
+    hex
+}
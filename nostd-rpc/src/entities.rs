@@ -0,0 +1,39 @@
+#[cfg(feature = "html5-entities")]
+mod html5;
+
+/// Commonly used named character references, sorted by name.
+const COMMON: &[(&str, &str)] = &[
+    ("amp", "&"),
+    ("apos", "'"),
+    ("copy", "\u{a9}"),
+    ("deg", "\u{b0}"),
+    ("euro", "\u{20ac}"),
+    ("gt", ">"),
+    ("hellip", "\u{2026}"),
+    ("laquo", "\u{ab}"),
+    ("ldquo", "\u{201c}"),
+    ("lsquo", "\u{2018}"),
+    ("lt", "<"),
+    ("mdash", "\u{2014}"),
+    ("nbsp", "\u{a0}"),
+    ("ndash", "\u{2013}"),
+    ("quot", "\""),
+    ("raquo", "\u{bb}"),
+    ("rdquo", "\u{201d}"),
+    ("reg", "\u{ae}"),
+    ("rsquo", "\u{2019}"),
+    ("times", "\u{d7}"),
+];
+
+/// Returns the text a named character reference stands for, `name` excludes the
+/// leading '&' and trailing ';'.
+///
+/// Only common entities are known unless the `html5-entities` feature is enabled.
+pub(crate) fn lookup(name: &str) -> Option<&'static str> {
+    #[cfg(feature = "html5-entities")]
+    let table = html5::ENTITIES;
+    #[cfg(not(feature = "html5-entities"))]
+    let table = COMMON;
+    let index = table.binary_search_by(|(n, _)| (*n).cmp(name)).ok()?;
+    Some(table[index].1)
+}
@@ -0,0 +1,332 @@
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+use core::fmt;
+
+use smoltcp::socket::{tcp, udp};
+#[cfg(feature = "alloc")]
+use smoltcp::time::Duration;
+
+use crate::response::ParseError;
+
+/// Errors returned while creating the network interface or sending a request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The TUN/TAP interface could not be created.
+    TunTap {
+        /// Number of attempts made before giving up.
+        attempts: u8,
+        /// The OS error code of the last failed attempt, if there was one.
+        os_error: Option<i32>,
+    },
+    /// The local IP address could not be added to the interface.
+    IpAddress,
+    /// The default route could not be added to the interface.
+    Route,
+    /// The TCP connection could not be initiated.
+    Connect(tcp::ConnectError),
+    /// A server socket could not listen for connections.
+    Listen(tcp::ListenError),
+    /// A UDP socket could not be bound to its port.
+    Bind(udp::BindError),
+    /// The TCP connection was not established before the timeout.
+    ConnectTimeout,
+    /// No DHCP lease was obtained before the timeout.
+    DhcpTimeout,
+    /// The server refused the TCP connection.
+    ConnectionRefused,
+    /// The server closed the connection before sending a response.
+    ConnectionClosed,
+    /// The server reset the connection before the response was complete.
+    ConnectionReset,
+    /// The request could not be written to the socket.
+    SendFailed(tcp::SendError),
+    /// A datagram could not be queued on a UDP socket.
+    Datagram(udp::SendError),
+    /// The request could not be sent before the timeout.
+    SendTimeout,
+    /// Data could not be read from the socket.
+    RecvFailed(tcp::RecvError),
+    /// The response was not received before the timeout.
+    ResponseTimeout,
+    /// The response is not valid UTF-8.
+    InvalidUtf8,
+    /// A request header is malformed, or conflicts with another header.
+    InvalidHeader,
+    /// The body of an HTTP/1.0 request has no known length, and HTTP/1.0 cannot send it
+    /// chunked.
+    LengthRequired,
+    /// A URL could not be parsed or uses an unsupported scheme.
+    InvalidUrl,
+    /// Base64 input contains a symbol outside the alphabet or has an invalid length.
+    InvalidBase64,
+    /// A hexadecimal quantity or byte string, e.g. in an Ethereum JSON-RPC result, is
+    /// malformed.
+    InvalidHex,
+    /// A host name has an empty or overlong label.
+    InvalidHost,
+    /// A response does not continue a resumed download where it stopped, or the resource
+    /// has changed since the download started.
+    RangeMismatch,
+    /// The server responded with an error status code.
+    Status(u16),
+    /// The server redirected the request in a loop, or more times than allowed.
+    TooManyRedirects,
+    /// Downloaded data does not have the expected hash.
+    HashMismatch,
+    /// Downloaded data could not be written to storage.
+    Storage,
+    /// A TLS record could not be processed or the handshake failed.
+    Tls,
+    /// A MAC address could not be parsed.
+    InvalidMac,
+    /// The host is a name rather than an IP address, and cannot be resolved.
+    UnresolvedHost,
+    /// The DNS server did not answer before the timeout.
+    DnsTimeout,
+    /// The response could not be parsed.
+    InvalidResponse(ParseError),
+    /// The response has more headers, or a larger header block, than allowed.
+    HeadersTooLarge,
+    /// The proxy refused to open a tunnel to the server, answering with this status code.
+    Proxy(u16),
+    /// The response is larger than the request allows.
+    ResponseTooLarge,
+    /// A buffer provided by the caller is too small for the request.
+    BufferTooSmall,
+    /// A [`HeadersInspector`](crate::hooks::HeadersInspector) aborted the response once its
+    /// head was received.
+    Aborted,
+    /// The server accepted a WebSocket upgrade without the headers RFC 6455 requires, or with
+    /// the wrong `Sec-WebSocket-Accept` key.
+    WebSocketHandshake,
+    /// A WebSocket frame from the server is malformed or breaks the protocol.
+    InvalidFrame,
+    /// An MQTT packet from the broker is malformed or unexpected.
+    InvalidPacket,
+    /// The MQTT broker refused the connection with this return code.
+    BrokerRefused(u8),
+    /// The server answered a JSON-RPC call with an error object.
+    #[cfg(feature = "alloc")]
+    Rpc {
+        /// The error code.
+        code: i64,
+        /// The error message.
+        message: String,
+    },
+    /// The response is not a valid response to the call, e.g. not JSON-RPC 2.0 or a
+    /// malformed gRPC-Web frame.
+    InvalidRpcResponse,
+    /// A Twirp server answered a call with an error.
+    #[cfg(feature = "alloc")]
+    Twirp {
+        /// The error code, e.g. `not_found`.
+        code: String,
+        /// The error message.
+        message: String,
+    },
+    /// A gRPC-Web call ended with a non-zero `grpc-status`.
+    #[cfg(feature = "alloc")]
+    Grpc {
+        /// The status code, e.g. 5 for `NOT_FOUND`.
+        status: u32,
+        /// The status message.
+        message: String,
+    },
+    /// The parameters of a call could not be serialized, or its result deserialized into the
+    /// type asked for.
+    InvalidJson,
+    /// A request failed part way through, see [`ErrorContext`] for how far it got.
+    #[cfg(feature = "alloc")]
+    Request {
+        /// The underlying error.
+        error: Box<Error>,
+        /// The state of the request when it failed.
+        context: Box<ErrorContext>,
+    },
+}
+
+impl Error {
+    /// Returns the underlying error, without any request context.
+    pub fn root(&self) -> &Error {
+        match self {
+            #[cfg(feature = "alloc")]
+            Error::Request { error, .. } => error.root(),
+            _ => self,
+        }
+    }
+
+    /// Returns the state of the request when it failed, if known.
+    #[cfg(feature = "alloc")]
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::Request { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+}
+
+/// The phase of a request in which an error occurred.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Phase {
+    /// Resolving the host name.
+    Resolve,
+    /// Establishing the TCP connection.
+    Connect,
+    /// Writing the request to the socket.
+    Write,
+    /// Reading the response from the socket.
+    Read,
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Phase {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{}", defmt::Display2Format(self))
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::Resolve => write!(f, "resolve"),
+            Phase::Connect => write!(f, "connect"),
+            Phase::Write => write!(f, "write"),
+            Phase::Read => write!(f, "read"),
+        }
+    }
+}
+
+/// How far a request got before it failed.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// The phase that failed.
+    pub phase: Phase,
+    /// Time from the start of the request until the failure.
+    pub elapsed: Duration,
+    /// Number of request bytes written to the socket.
+    pub bytes_sent: usize,
+    /// Number of response bytes read from the socket.
+    pub bytes_received: usize,
+    /// The response status line, if one was received.
+    pub status_line: Option<String>,
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "during {} after {}, {} bytes sent, {} bytes received",
+            self.phase, self.elapsed, self.bytes_sent, self.bytes_received
+        )?;
+        if let Some(status_line) = &self.status_line {
+            write!(f, ", status line \"{}\"", status_line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "alloc", feature = "defmt"))]
+impl defmt::Format for ErrorContext {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{}", defmt::Display2Format(self))
+    }
+}
+
+/// Errors are logged with their `Display` form, which only has the context of a failed
+/// request, not the error that caused it, see [`Error::root`].
+#[cfg(feature = "defmt")]
+impl defmt::Format for Error {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{}", defmt::Display2Format(self))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TunTap {
+                attempts,
+                os_error: Some(code),
+            } => write!(
+                f,
+                "failed to create TUN/TAP interface after {} attempts (os error {})",
+                attempts, code
+            ),
+            Error::TunTap { attempts, .. } => write!(
+                f,
+                "failed to create TUN/TAP interface after {} attempts",
+                attempts
+            ),
+            Error::IpAddress => write!(f, "failed to add IP address to the interface"),
+            Error::Route => write!(f, "failed to add default route"),
+            Error::Connect(e) => write!(f, "failed to connect: {}", e),
+            Error::Listen(e) => write!(f, "failed to listen: {}", e),
+            Error::Bind(e) => write!(f, "failed to bind: {}", e),
+            Error::ConnectTimeout => write!(f, "timed out connecting to the server"),
+            Error::DhcpTimeout => write!(f, "timed out waiting for a DHCP lease"),
+            Error::ConnectionRefused => write!(f, "connection refused by the server"),
+            Error::ConnectionClosed => write!(f, "connection closed by the server"),
+            Error::ConnectionReset => write!(f, "connection reset by the server"),
+            Error::SendFailed(e) => write!(f, "failed to send request: {}", e),
+            Error::Datagram(e) => write!(f, "failed to send datagram: {}", e),
+            Error::SendTimeout => write!(f, "timed out sending the request"),
+            Error::RecvFailed(e) => write!(f, "failed to receive response: {}", e),
+            Error::ResponseTimeout => write!(f, "timed out waiting for the response"),
+            Error::InvalidUtf8 => write!(f, "response is not valid UTF-8"),
+            Error::InvalidHeader => write!(f, "invalid request header"),
+            Error::LengthRequired => write!(f, "HTTP/1.0 request body needs a known length"),
+            Error::InvalidUrl => write!(f, "invalid or unsupported URL"),
+            Error::InvalidBase64 => write!(f, "invalid base64"),
+            Error::InvalidHex => write!(f, "invalid hexadecimal"),
+            Error::InvalidHost => write!(f, "invalid host name"),
+            Error::RangeMismatch => write!(f, "response does not continue the download"),
+            Error::Status(status) => write!(f, "server responded with status {}", status),
+            Error::TooManyRedirects => write!(f, "too many redirects"),
+            Error::HashMismatch => write!(f, "downloaded data does not have the expected hash"),
+            Error::Storage => write!(f, "failed to write to storage"),
+            Error::Tls => write!(f, "TLS error"),
+            Error::InvalidMac => write!(f, "invalid MAC address"),
+            #[cfg(feature = "alloc")]
+            Error::Rpc { code, message } => write!(f, "RPC error {}: {}", code, message),
+            Error::InvalidRpcResponse => write!(f, "invalid RPC response"),
+            #[cfg(feature = "alloc")]
+            Error::Twirp { code, message } => write!(f, "Twirp error {}: {}", code, message),
+            #[cfg(feature = "alloc")]
+            Error::Grpc { status, message } => write!(f, "gRPC status {}: {}", status, message),
+            Error::InvalidJson => write!(f, "JSON does not match the expected type"),
+            Error::UnresolvedHost => write!(f, "host name cannot be resolved"),
+            Error::DnsTimeout => write!(f, "timed out resolving the host name"),
+            Error::InvalidResponse(e) => write!(f, "invalid response: {}", e),
+            Error::HeadersTooLarge => write!(f, "response headers exceed the configured limits"),
+            Error::Proxy(status) => write!(f, "proxy responded with status {}", status),
+            Error::ResponseTooLarge => write!(f, "response exceeds the size limit"),
+            Error::BufferTooSmall => write!(f, "request does not fit in the buffer"),
+            Error::Aborted => write!(f, "response aborted after its headers"),
+            Error::WebSocketHandshake => write!(f, "invalid WebSocket handshake response"),
+            Error::InvalidFrame => write!(f, "invalid WebSocket frame"),
+            Error::InvalidPacket => write!(f, "invalid MQTT packet"),
+            Error::BrokerRefused(code) => {
+                write!(f, "broker refused the connection with return code {}", code)
+            }
+            #[cfg(feature = "alloc")]
+            Error::Request { context, .. } => write!(f, "request failed {}", context),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        // Other errors wrapping one, such as those of smoltcp, which only implements `Error`
+        // with `std` enabled, include it in their message instead.
+        match self {
+            #[cfg(feature = "alloc")]
+            Error::Request { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
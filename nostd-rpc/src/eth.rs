@@ -0,0 +1,147 @@
+//! Typed helpers for common Ethereum JSON-RPC calls, sent over a [`Transport`].
+//!
+//! Ethereum encodes numbers as hexadecimal quantities, e.g. `"0x1b4"`, and byte strings as
+//! hexadecimal data, e.g. `"0x00ff"`. [`encode_quantity`], [`decode_quantity`],
+//! [`encode_data`] and [`decode_data`] convert them for calls without a helper.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::encoding::{self, decode_hex_byte};
+use crate::json;
+use crate::transport::{Request, Transport};
+use crate::Error;
+
+/// An account address.
+pub type Address = [u8; 20];
+
+/// The block whose state a call reads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Block {
+    /// The most recent block.
+    Latest,
+    /// The block being mined.
+    Pending,
+    /// The genesis block.
+    Earliest,
+    /// The block with the given number.
+    Number(u64),
+}
+
+impl Block {
+    /// Returns the block as a JSON parameter.
+    fn param(self) -> String {
+        match self {
+            Block::Latest => String::from("\"latest\""),
+            Block::Pending => String::from("\"pending\""),
+            Block::Earliest => String::from("\"earliest\""),
+            Block::Number(number) => format!("\"{}\"", encode_quantity(number.into())),
+        }
+    }
+}
+
+/// Sends Ethereum JSON-RPC calls over a [`Transport`], numbering them from 1.
+pub struct EthClient<T: Transport> {
+    transport: T,
+    /// ID of the next call.
+    next_id: u64,
+}
+
+impl<T: Transport> EthClient<T> {
+    /// Constructs a client sending calls over `transport`.
+    pub fn new(transport: T) -> Self {
+        EthClient {
+            transport,
+            next_id: 1,
+        }
+    }
+
+    /// Returns the transport the calls are sent over.
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Returns the number of the most recent block, with `eth_blockNumber`.
+    pub fn block_number(&mut self) -> Result<u64, Error> {
+        let result = self.call_method("eth_blockNumber", "[]")?;
+        let number = decode_quantity(&string_result(&result)?)?;
+        u64::try_from(number).map_err(|_| Error::InvalidHex)
+    }
+
+    /// Returns the balance of `address` in wei at `block`, with `eth_getBalance`.
+    pub fn get_balance(&mut self, address: &Address, block: Block) -> Result<u128, Error> {
+        let params = format!("[\"{}\",{}]", encode_data(address), block.param());
+        let result = self.call_method("eth_getBalance", &params)?;
+        decode_quantity(&string_result(&result)?)
+    }
+
+    /// Executes a call of contract `to` with `data` at `block`, without creating a
+    /// transaction, returning the data the contract returned, with `eth_call`.
+    pub fn call(&mut self, to: &Address, data: &[u8], block: Block) -> Result<Vec<u8>, Error> {
+        let params = format!(
+            "[{{\"to\":\"{}\",\"data\":\"{}\"}},{}]",
+            encode_data(to),
+            encode_data(data),
+            block.param()
+        );
+        let result = self.call_method("eth_call", &params)?;
+        decode_data(&string_result(&result)?)
+    }
+
+    /// Calls `method` with `params`, a JSON array, returning the `result` as JSON text.
+    pub fn call_method(&mut self, method: &str, params: &str) -> Result<String, Error> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = Request { method, params, id };
+        self.transport.send_request(request)?.result
+    }
+}
+
+/// Returns the JSON string `result` without its quotes and escapes.
+fn string_result(result: &str) -> Result<String, Error> {
+    json::parse_whole_string(result).ok_or(Error::InvalidRpcResponse)
+}
+
+/// Encodes `value` as a hexadecimal quantity, with no leading zeros, e.g. `0x1b4`.
+pub fn encode_quantity(value: u128) -> String {
+    format!("0x{:x}", value)
+}
+
+/// Decodes a hexadecimal quantity, e.g. `0x1b4`.
+///
+/// Returns [`Error::InvalidHex`] without the `0x` prefix or any digits, for a character that
+/// is not a hexadecimal digit, or if the value does not fit in a `u128`. Leading zeros are
+/// accepted.
+pub fn decode_quantity(hex: &str) -> Result<u128, Error> {
+    let digits = hex.strip_prefix("0x").ok_or(Error::InvalidHex)?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(Error::InvalidHex);
+    }
+    u128::from_str_radix(digits, 16).map_err(|_| Error::InvalidHex)
+}
+
+/// Encodes `data` as hexadecimal data, two lowercase digits per byte, e.g. `0x00ff`.
+pub fn encode_data(data: &[u8]) -> String {
+    let mut hex = String::from("0x");
+    hex.push_str(&encoding::encode_hex(data));
+    hex
+}
+
+/// Decodes hexadecimal data, e.g. `0x00ff`.
+///
+/// Returns [`Error::InvalidHex`] without the `0x` prefix, for an odd number of digits, or for
+/// a character that is not a hexadecimal digit.
+pub fn decode_data(hex: &str) -> Result<Vec<u8>, Error> {
+    let digits = hex.strip_prefix("0x").ok_or(Error::InvalidHex)?;
+    if digits.len() % 2 != 0 {
+        return Err(Error::InvalidHex);
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            let pair = digits.get(i..i + 2).ok_or(Error::InvalidHex)?;
+            decode_hex_byte(pair).ok_or(Error::InvalidHex)
+        })
+        .collect()
+}
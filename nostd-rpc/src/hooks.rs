@@ -0,0 +1,262 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use smoltcp::time::Instant;
+
+use crate::http::{SignedParts, Signer};
+use crate::metrics::{Metrics, Timings};
+use crate::time::{self, ClockSync};
+use crate::Error;
+
+/// A step in the lifecycle of a request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event<'a> {
+    /// The host name is being resolved through DNS.
+    Resolving,
+    /// The TCP connection is being opened.
+    Connecting,
+    /// The TCP connection has been established.
+    Connected,
+    /// The TLS handshake has started.
+    TlsHandshake,
+    /// The status line and headers of the response have been received.
+    HeadersReceived {
+        /// The response status code.
+        status: u16,
+    },
+    /// Response data was read from the socket.
+    Chunk(&'a [u8]),
+    /// The response was received in full.
+    Complete,
+    /// An attempt failed and the request is tried again after a wait.
+    Retrying {
+        /// The attempt about to be made, counting from 1.
+        attempt: u8,
+        /// The error the previous attempt failed with.
+        error: &'a Error,
+    },
+    /// The request failed.
+    Failed(&'a Error),
+}
+
+/// Receives request lifecycle events, e.g. to drive a progress display or structured logs.
+pub trait EventObserver {
+    /// Called with the time at which `event` happened.
+    fn event(&mut self, timestamp: Instant, event: Event<'_>);
+}
+
+impl<F: FnMut(Instant, Event<'_>)> EventObserver for F {
+    fn event(&mut self, timestamp: Instant, event: Event<'_>) {
+        self(timestamp, event)
+    }
+}
+
+/// The direction of a transfer reported to a [`ProgressObserver`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transfer {
+    /// The request is being sent.
+    Upload,
+    /// The response is being received.
+    Download,
+}
+
+/// Receives byte counts while a request is sent and its response received, e.g. to drive a
+/// progress bar or kick a watchdog during long transfers.
+pub trait ProgressObserver {
+    /// Called with the bytes transferred so far in `direction` and the total, if known.
+    ///
+    /// The download total is only known once the response head with a `Content-Length`
+    /// has been received, and includes the head.
+    fn progress(&mut self, direction: Transfer, transferred: usize, total: Option<usize>);
+}
+
+impl<F: FnMut(Transfer, usize, Option<usize>)> ProgressObserver for F {
+    fn progress(&mut self, direction: Transfer, transferred: usize, total: Option<usize>) {
+        self(direction, transferred, total)
+    }
+}
+
+/// Whether to keep receiving a response after inspecting its head.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Receive the body.
+    Continue,
+    /// Reset the connection and fail the request with [`Error::Aborted`].
+    Abort,
+}
+
+/// Inspects the status and headers of a response before its body is received, e.g. to
+/// abort the transfer if the `Content-Type` is unexpected or the `Content-Length` too large.
+pub trait HeadersInspector {
+    /// Called once the status line and headers have been received, with each header's name
+    /// and value.
+    fn inspect(&mut self, status: u16, headers: &[(String, String)]) -> Verdict;
+}
+
+impl<F: FnMut(u16, &[(String, String)]) -> Verdict> HeadersInspector for F {
+    fn inspect(&mut self, status: u16, headers: &[(String, String)]) -> Verdict {
+        self(status, headers)
+    }
+}
+
+/// Optional observers that are notified while a request is in progress.
+#[derive(Default)]
+pub struct Hooks<'a> {
+    /// Receives request counters.
+    metrics: Option<&'a mut dyn Metrics>,
+    /// Receives request lifecycle events.
+    observer: Option<&'a mut dyn EventObserver>,
+    /// Receives the server time from `Date` headers.
+    clock_sync: Option<&'a mut dyn ClockSync>,
+    /// Receives transfer progress.
+    progress: Option<&'a mut dyn ProgressObserver>,
+    /// Decides whether to receive the body of a response.
+    inspector: Option<&'a mut dyn HeadersInspector>,
+    /// Signs requests before they are sent.
+    signer: Option<&'a mut dyn Signer>,
+}
+
+impl<'a> Hooks<'a> {
+    /// Constructs a new [`Hooks`] with no observers.
+    pub fn new() -> Self {
+        Hooks::default()
+    }
+
+    /// Sets the receiver of request counters.
+    pub fn metrics(mut self, metrics: &'a mut dyn Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Sets the receiver of request lifecycle events.
+    pub fn observer(mut self, observer: &'a mut dyn EventObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Sets the receiver of the server time from `Date` headers.
+    pub fn clock_sync(mut self, clock_sync: &'a mut dyn ClockSync) -> Self {
+        self.clock_sync = Some(clock_sync);
+        self
+    }
+
+    /// Sets the receiver of transfer progress.
+    pub fn progress(mut self, progress: &'a mut dyn ProgressObserver) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Sets the inspector deciding from the head of a response whether to receive its body.
+    pub fn on_headers(mut self, inspector: &'a mut dyn HeadersInspector) -> Self {
+        self.inspector = Some(inspector);
+        self
+    }
+
+    /// Sets the signer adding signature headers to each request before it is sent.
+    pub fn signer(mut self, signer: &'a mut dyn Signer) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Returns hooks that report to the same receivers, except for transfer progress.
+    pub(crate) fn without_progress(&mut self) -> Hooks<'_> {
+        Hooks {
+            metrics: self.metrics.as_deref_mut().map(|m| m as &mut dyn Metrics),
+            observer: self
+                .observer
+                .as_deref_mut()
+                .map(|m| m as &mut dyn EventObserver),
+            clock_sync: self
+                .clock_sync
+                .as_deref_mut()
+                .map(|m| m as &mut dyn ClockSync),
+            progress: None,
+            inspector: self
+                .inspector
+                .as_deref_mut()
+                .map(|m| m as &mut dyn HeadersInspector),
+            signer: self.signer.as_deref_mut().map(|m| m as &mut dyn Signer),
+        }
+    }
+
+    /// Reports the server time if `date` is a valid HTTP date, `local` is when it was received.
+    pub(crate) fn date(&mut self, local: Instant, date: &str) {
+        let Some(clock_sync) = &mut self.clock_sync else {
+            return;
+        };
+        if let Some(unix_seconds) = time::parse_http_date(date) {
+            let offset_micros = (unix_seconds as i64)
+                .saturating_mul(1_000_000)
+                .saturating_sub(local.total_micros());
+            clock_sync.server_time(unix_seconds, offset_micros);
+        }
+    }
+
+    pub(crate) fn event(&mut self, timestamp: Instant, event: Event<'_>) {
+        if let Some(observer) = &mut self.observer {
+            observer.event(timestamp, event);
+        }
+    }
+
+    /// Asks the inspector, if any, whether to receive the body of a response with `status`
+    /// and `headers`.
+    pub(crate) fn headers(&mut self, status: u16, headers: &[(String, String)]) -> Verdict {
+        match &mut self.inspector {
+            Some(inspector) => inspector.inspect(status, headers),
+            None => Verdict::Continue,
+        }
+    }
+
+    /// Returns the headers the signer, if any, adds to the request with `parts`.
+    pub(crate) fn sign(&mut self, parts: &SignedParts<'_>) -> Option<Vec<(String, String)>> {
+        self.signer.as_mut().map(|signer| signer.sign(parts))
+    }
+
+    pub(crate) fn transfer(
+        &mut self,
+        direction: Transfer,
+        transferred: usize,
+        total: Option<usize>,
+    ) {
+        if let Some(progress) = &mut self.progress {
+            progress.progress(direction, transferred, total);
+        }
+    }
+
+    pub(crate) fn request_started(&mut self) {
+        if let Some(metrics) = &mut self.metrics {
+            metrics.request_started();
+        }
+    }
+
+    pub(crate) fn request_completed(&mut self, timings: &Timings) {
+        if let Some(metrics) = &mut self.metrics {
+            metrics.request_completed(timings);
+        }
+    }
+
+    pub(crate) fn request_failed(&mut self, error: &Error) {
+        if let Some(metrics) = &mut self.metrics {
+            metrics.request_failed(error);
+        }
+    }
+
+    pub(crate) fn bytes_sent(&mut self, count: usize) {
+        if let Some(metrics) = &mut self.metrics {
+            metrics.bytes_sent(count);
+        }
+    }
+
+    pub(crate) fn bytes_received(&mut self, count: usize) {
+        if let Some(metrics) = &mut self.metrics {
+            metrics.bytes_received(count);
+        }
+    }
+
+    pub(crate) fn retransmits(&mut self, count: u32) {
+        if let Some(metrics) = &mut self.metrics {
+            metrics.retransmits(count);
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use alloc::string::String;
+
+/// Supplies the bearer token of each request an [`HttpClient`](super::HttpClient) sends, set
+/// with [`HttpClient::token_provider`](super::HttpClient::token_provider), e.g. the access
+/// token of an OAuth2 device flow.
+pub trait TokenProvider {
+    /// Returns the token to send in the `Authorization: Bearer` header, or `None` to send the
+    /// request with its own credentials.
+    fn token(&mut self) -> Option<String>;
+
+    /// Called when the server answered a request carrying `rejected` with `401 Unauthorized`.
+    /// Returns whether a new token is available, in which case the request is sent once more.
+    fn refresh(&mut self, rejected: &str) -> bool;
+}
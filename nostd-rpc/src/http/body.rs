@@ -0,0 +1,75 @@
+use crate::Error;
+
+/// Supplies a request body a part at a time, so a large or generated body need not be held
+/// in memory, see [`send_with_body`](super::send_with_body).
+pub trait BodyProvider {
+    /// Returns the length of the whole body if it is known up front, in which case it is sent
+    /// with a `Content-Length` header and exactly that many bytes must be provided. Otherwise
+    /// the body is sent with chunked transfer coding.
+    fn content_length(&self) -> Option<usize>;
+
+    /// Copies the next part of the body into `buf`, returning the number of bytes copied, or
+    /// 0 once the body is finished.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
+}
+
+impl BodyProvider for &[u8] {
+    fn content_length(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let n = buf.len().min(self.len());
+        let (part, rest) = self.split_at(n);
+        buf[..n].copy_from_slice(part);
+        *self = rest;
+        Ok(n)
+    }
+}
+
+/// A body of unknown length made of the chunks yielded by an iterator.
+pub struct IterBody<I: Iterator> {
+    chunks: I,
+    /// The chunk being copied out and how much of it has been.
+    current: Option<(I::Item, usize)>,
+}
+
+impl<I: Iterator> IterBody<I>
+where
+    I::Item: AsRef<[u8]>,
+{
+    /// Constructs a body from the chunks yielded by `chunks`.
+    pub fn new<T: IntoIterator<IntoIter = I>>(chunks: T) -> Self {
+        IterBody {
+            chunks: chunks.into_iter(),
+            current: None,
+        }
+    }
+}
+
+impl<I: Iterator> BodyProvider for IterBody<I>
+where
+    I::Item: AsRef<[u8]>,
+{
+    fn content_length(&self) -> Option<usize> {
+        None
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        loop {
+            if let Some((chunk, offset)) = &mut self.current {
+                let rest = &chunk.as_ref()[*offset..];
+                if !rest.is_empty() {
+                    let n = buf.len().min(rest.len());
+                    buf[..n].copy_from_slice(&rest[..n]);
+                    *offset += n;
+                    return Ok(n);
+                }
+            }
+            match self.chunks.next() {
+                Some(chunk) => self.current = Some((chunk, 0)),
+                None => return Ok(0),
+            }
+        }
+    }
+}
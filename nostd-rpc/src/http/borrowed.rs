@@ -0,0 +1,545 @@
+//! Requests and responses that borrow their data instead of allocating, for targets without a
+//! heap allocator.
+//!
+//! A [`RequestRef`] borrows its method, host, path, headers and body, and is serialized into
+//! a buffer provided by the caller. A [`ResponseRef`] is parsed in place from the buffer it
+//! was received into, with its headers in a `heapless::Vec` of at most `N` entries and a
+//! chunked body decoded within the buffer. [`send_borrowed`] sends a request over a socket
+//! set on the stack. Nothing here allocates, and without the `alloc` feature these are all
+//! the `http` module has.
+
+use core::fmt::{self, Write};
+use core::net::IpAddr;
+
+use heapless::Vec;
+use smoltcp::iface::{Interface, SocketSet, SocketStorage};
+use smoltcp::phy::Device;
+use smoltcp::socket::tcp;
+use smoltcp::time::Duration;
+use smoltcp::wire::IpAddress;
+
+use super::common::{expects_body, CLOSE_TIMEOUT, DEFAULT_PORT, DEFAULT_TIMEOUT_SECONDS};
+use crate::net::LocalPorts;
+use crate::response::{self, ParseError};
+use crate::time::Clock;
+use crate::Error;
+
+/// An HTTP request that borrows its data, see the [module](self) documentation.
+#[derive(Clone, Copy, Debug)]
+pub struct RequestRef<'a> {
+    ip: IpAddr,
+    port: u16,
+    method: &'a str,
+    /// Host name sent in the `Host` header, the IP address if empty.
+    host: &'a str,
+    path: &'a str,
+    headers: &'a [(&'a str, &'a str)],
+    body: &'a [u8],
+    /// Deadline of the request, from its start until the response is complete.
+    timeout: Duration,
+    /// Seed of the choice of the local port of the connection.
+    ports: [u8; 8],
+}
+
+impl<'a> RequestRef<'a> {
+    /// Constructs a POST request for `/` on port 80 of the server at `ip`, with an empty
+    /// body, as [`HttpRequest::new`](super::HttpRequest::new) does.
+    pub fn new(ip: IpAddr) -> Self {
+        RequestRef {
+            ip,
+            port: DEFAULT_PORT,
+            method: "POST",
+            host: "",
+            path: "/",
+            headers: &[],
+            body: &[],
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECONDS),
+            ports: [0; 8],
+        }
+    }
+
+    /// Sets the port of the server.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets the HTTP method.
+    pub fn method(mut self, method: &'a str) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sets the host name sent in the `Host` header.
+    pub fn host(mut self, host: &'a str) -> Self {
+        self.host = host;
+        self
+    }
+
+    /// Sets the path and query of the request, starting with '/'.
+    pub fn path(mut self, path: &'a str) -> Self {
+        self.path = path;
+        self
+    }
+
+    /// Sets the headers, each a name and value.
+    pub fn headers(mut self, headers: &'a [(&'a str, &'a str)]) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Sets the body, sent with a `Content-Length` header.
+    pub fn body(mut self, body: &'a [u8]) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Sets the deadline of the request, from its start until the response is complete.
+    pub fn timeout<T: Into<Duration>>(mut self, timeout: T) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Seeds the choice of the local port of the connection with `random`, which should come
+    /// from a random number generator, see [`LocalPorts`].
+    pub fn local_ports(mut self, random: [u8; 8]) -> Self {
+        self.ports = random;
+        self
+    }
+
+    /// Serializes the request into `buf`, returning its length.
+    ///
+    /// Fails with [`Error::BufferTooSmall`] if it does not fit, or [`Error::InvalidHeader`]
+    /// if a header name or value has characters not allowed there.
+    pub fn write_to(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        let invalid = |&(name, value): &(&str, &str)| {
+            name.is_empty()
+                || !name.bytes().all(response::is_token_byte)
+                || value.bytes().any(response::is_illegal_value_byte)
+        };
+        if self.headers.iter().any(invalid) {
+            return Err(Error::InvalidHeader);
+        }
+
+        let mut out = Cursor { buf, len: 0 };
+        self.write_head(&mut out)
+            .map_err(|_| Error::BufferTooSmall)?;
+        out.push(self.body).map_err(|_| Error::BufferTooSmall)?;
+        Ok(out.len)
+    }
+
+    fn write_head(&self, out: &mut Cursor<'_>) -> fmt::Result {
+        write!(out, "{} {} HTTP/1.1\r\nHost: ", self.method, self.path)?;
+        match (self.host.is_empty(), self.ip) {
+            (false, _) => out.write_str(self.host)?,
+            (true, IpAddr::V4(ip)) => write!(out, "{}", ip)?,
+            (true, IpAddr::V6(ip)) => write!(out, "[{}]", ip)?,
+        }
+        if self.port != DEFAULT_PORT {
+            write!(out, ":{}", self.port)?;
+        }
+        out.write_str("\r\n")?;
+        for (name, value) in self.headers {
+            write!(out, "{}: {}\r\n", name, value)?;
+        }
+        if !self.body.is_empty() || expects_body(self.method) {
+            write!(out, "Content-Length: {}\r\n", self.body.len())?;
+        }
+        out.write_str("Connection: close\r\n\r\n")
+    }
+}
+
+/// Writes to a fixed buffer, failing once it is full.
+struct Cursor<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl Cursor<'_> {
+    fn push(&mut self, data: &[u8]) -> fmt::Result {
+        let end = self.len + data.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(data);
+        self.len = end;
+        Ok(())
+    }
+}
+
+impl Write for Cursor<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes())
+    }
+}
+
+/// An HTTP response parsed in place from the buffer it was received into, with at most `N`
+/// headers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResponseRef<'a, const N: usize> {
+    status: u16,
+    reason: &'a str,
+    headers: Vec<(&'a str, &'a str), N>,
+    body: &'a [u8],
+}
+
+impl<'a, const N: usize> ResponseRef<'a, N> {
+    /// Parses the response received so far in `data`, returning `None` if it is not
+    /// complete. A chunked body is decoded within `data` once it is complete.
+    ///
+    /// A body delimited by the server closing the connection is never complete, see
+    /// [`ResponseRef::finish`].
+    pub fn parse(data: &'a mut [u8]) -> Result<Option<Self>, Error> {
+        parse(data, false, false)
+    }
+
+    /// Parses the response in `data` once the server has closed the connection, which ends
+    /// a body without a length.
+    pub fn finish(data: &'a mut [u8]) -> Result<Self, Error> {
+        parse(data, true, false)?.ok_or(Error::InvalidResponse(ParseError::Incomplete))
+    }
+
+    /// Returns the status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the reason phrase.
+    pub fn reason(&self) -> &'a str {
+        self.reason
+    }
+
+    /// Returns the headers in the order they were received.
+    pub fn headers(&self) -> &[(&'a str, &'a str)] {
+        &self.headers
+    }
+
+    /// Returns the value of the first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|&(_, value)| value)
+    }
+
+    /// Returns the body, decoded if it was chunked.
+    pub fn body(&self) -> &'a [u8] {
+        self.body
+    }
+}
+
+/// How the body of a response ends.
+enum Framing {
+    Length(usize),
+    Chunked,
+    Close,
+}
+
+/// The status line and headers of a response.
+struct Head<'a, const N: usize> {
+    status: u16,
+    reason: &'a str,
+    headers: Vec<(&'a str, &'a str), N>,
+    /// Length of the head, including the blank line that ends it.
+    len: usize,
+}
+
+impl<const N: usize> Head<'_, N> {
+    /// Returns how the body ends. The response to a HEAD request has no body.
+    fn framing(&self, head_request: bool) -> Result<Framing, Error> {
+        let header = |name: &str| {
+            self.headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|&(_, value)| value)
+        };
+        let bodiless =
+            head_request || (100..200).contains(&self.status) || matches!(self.status, 204 | 304);
+        if bodiless {
+            return Ok(Framing::Length(0));
+        }
+        if let Some(coding) = header("transfer-encoding") {
+            return match coding.eq_ignore_ascii_case("chunked") {
+                true => Ok(Framing::Chunked),
+                false => Err(Error::InvalidResponse(ParseError::Header)),
+            };
+        }
+        match header("content-length") {
+            Some(length) => length
+                .parse()
+                .map(Framing::Length)
+                .map_err(|_| Error::InvalidResponse(ParseError::Header)),
+            None => Ok(Framing::Close),
+        }
+    }
+}
+
+/// Parses the head at the start of `data`, returning `None` if it is not complete.
+fn parse_head<const N: usize>(data: &[u8]) -> Result<Option<Head<'_, N>>, Error> {
+    let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") else {
+        return Ok(None);
+    };
+    let mut lines = data[..end + 2]
+        .split_inclusive(|&b| b == b'\n')
+        .map(|line| {
+            line.strip_suffix(b"\r\n")
+                .ok_or(Error::InvalidResponse(ParseError::BareLineFeed))
+        });
+    let status_line = lines.next().transpose()?.unwrap_or_default();
+    let (status, reason) = parse_status_line(status_line)?;
+    let mut headers = Vec::new();
+    for line in lines {
+        headers
+            .push(parse_header(line?)?)
+            .map_err(|_| Error::HeadersTooLarge)?;
+    }
+    Ok(Some(Head {
+        status,
+        reason,
+        headers,
+        len: end + 4,
+    }))
+}
+
+/// Returns the decoded length of the body at the start of `body` if it is complete, `closed`
+/// telling whether the server has closed the connection.
+fn body_len(body: &mut [u8], framing: &Framing, closed: bool) -> Result<Option<usize>, Error> {
+    match *framing {
+        Framing::Length(length) => Ok((body.len() >= length).then_some(length)),
+        Framing::Chunked => dechunk(body, false),
+        Framing::Close => Ok(closed.then_some(body.len())),
+    }
+}
+
+/// Returns whether the response in `data` is complete, without decoding it.
+fn complete<const N: usize>(data: &mut [u8], head_request: bool) -> Result<bool, Error> {
+    let (len, framing) = match parse_head::<N>(data)? {
+        Some(head) => (head.len, head.framing(head_request)?),
+        None => return Ok(false),
+    };
+    Ok(body_len(&mut data[len..], &framing, false)?.is_some())
+}
+
+/// Parses the response in `data`, which is complete if `closed`, decoding a chunked body in
+/// place. The response to a HEAD request has no body.
+fn parse<const N: usize>(
+    data: &mut [u8],
+    closed: bool,
+    head_request: bool,
+) -> Result<Option<ResponseRef<'_, N>>, Error> {
+    let incomplete = match closed {
+        true => Err(Error::InvalidResponse(ParseError::Incomplete)),
+        false => Ok(None),
+    };
+    let (head_len, framing) = match parse_head::<N>(data)? {
+        Some(head) => (head.len, head.framing(head_request)?),
+        None => return incomplete,
+    };
+    let Some(body_len) = body_len(&mut data[head_len..], &framing, closed)? else {
+        return incomplete;
+    };
+    if let Framing::Chunked = framing {
+        dechunk(&mut data[head_len..], true)?;
+    }
+
+    let data: &[u8] = data;
+    let Some(head) = parse_head::<N>(data)? else {
+        return incomplete;
+    };
+    Ok(Some(ResponseRef {
+        status: head.status,
+        reason: head.reason,
+        headers: head.headers,
+        body: &data[head_len..head_len + body_len],
+    }))
+}
+
+fn parse_status_line(line: &[u8]) -> Result<(u16, &str), Error> {
+    let invalid = Error::InvalidResponse(ParseError::StatusLine);
+    let rest = line.strip_prefix(b"HTTP/1.").ok_or(invalid.clone())?;
+    match rest {
+        [minor, b' ', a, b, c, rest @ ..]
+            if minor.is_ascii_digit() && [a, b, c].iter().all(|d| d.is_ascii_digit()) =>
+        {
+            let status = [a, b, c]
+                .iter()
+                .fold(0u16, |acc, &&d| acc * 10 + u16::from(d - b'0'));
+            let reason = rest.strip_prefix(b" ").unwrap_or(rest);
+            let reason = core::str::from_utf8(reason).map_err(|_| invalid)?;
+            Ok((status, reason))
+        }
+        _ => Err(invalid),
+    }
+}
+
+fn parse_header(line: &[u8]) -> Result<(&str, &str), Error> {
+    let invalid = Error::InvalidResponse(ParseError::Header);
+    let colon = line
+        .iter()
+        .position(|&b| b == b':')
+        .ok_or(invalid.clone())?;
+    let (name, value) = (&line[..colon], response::trim(&line[colon + 1..]));
+    if name.is_empty()
+        || !name.iter().all(|&b| response::is_token_byte(b))
+        || value.iter().any(|&b| response::is_illegal_value_byte(b))
+    {
+        return Err(invalid);
+    }
+    let name = core::str::from_utf8(name).map_err(|_| invalid.clone())?;
+    let value = core::str::from_utf8(value).map_err(|_| invalid)?;
+    Ok((name, value))
+}
+
+/// Checks that the chunked body at the start of `data` is complete, returning its decoded
+/// length, or `None` if more data is needed. Only if `decode` are the chunks moved to the
+/// start of `data`, so that an incomplete body is left as it was.
+fn dechunk(data: &mut [u8], decode: bool) -> Result<Option<usize>, Error> {
+    let invalid = Error::InvalidResponse(ParseError::Chunk);
+    let line_end = |data: &[u8], from: usize| {
+        data[from..]
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .map(|i| from + i)
+    };
+    let (mut read, mut written) = (0, 0);
+    loop {
+        let Some(end) = line_end(data, read) else {
+            return Ok(None);
+        };
+        let line = &data[read..end];
+        let digits = line.split(|&b| b == b';').next().unwrap_or_default();
+        let digits = core::str::from_utf8(response::trim(digits)).map_err(|_| invalid.clone())?;
+        let size = usize::from_str_radix(digits, 16).map_err(|_| invalid.clone())?;
+        read = end + 2;
+        if size == 0 {
+            // Trailer fields are skipped up to the blank line.
+            loop {
+                let Some(end) = line_end(data, read) else {
+                    return Ok(None);
+                };
+                if end == read {
+                    return Ok(Some(written));
+                }
+                read = end + 2;
+            }
+        }
+        let Some(chunk_end) = read.checked_add(size).filter(|&end| end + 2 <= data.len()) else {
+            return Ok(None);
+        };
+        if &data[chunk_end..chunk_end + 2] != b"\r\n" {
+            return Err(invalid);
+        }
+        if decode {
+            data.copy_within(read..chunk_end, written);
+        }
+        written += size;
+        read = chunk_end + 2;
+    }
+}
+
+/// Sends `request` over `iface` and `device` and receives the response into `buffer`,
+/// reading the time from `clock`.
+///
+/// The request is serialized into `buffer` first, so it must fit both. The socket uses
+/// `rx_buffer` and `tx_buffer`, which can be smaller. Fails with [`Error::ResponseTooLarge`]
+/// if the response does not fit in `buffer`.
+pub fn send_borrowed<'b, D: Device, C: Clock, const N: usize>(
+    iface: &mut Interface,
+    device: &mut D,
+    request: &RequestRef<'_>,
+    rx_buffer: &mut [u8],
+    tx_buffer: &mut [u8],
+    buffer: &'b mut [u8],
+    clock: &C,
+) -> Result<ResponseRef<'b, N>, Error> {
+    let len = request.write_to(buffer)?;
+    let mut storage = [SocketStorage::EMPTY];
+    let mut sockets = SocketSet::new(&mut storage[..]);
+    let socket = tcp::Socket::new(
+        tcp::SocketBuffer::new(rx_buffer),
+        tcp::SocketBuffer::new(tx_buffer),
+    );
+    let handle = sockets.add(socket);
+    let start = clock.now();
+    sockets
+        .get_mut::<tcp::Socket>(handle)
+        .connect(
+            iface.context(),
+            (IpAddress::from(request.ip), request.port),
+            LocalPorts::new(request.ports).next(start),
+        )
+        .map_err(Error::Connect)?;
+    debug!("connecting to port {}", request.port);
+
+    let head_request = request.method.eq_ignore_ascii_case("HEAD");
+    let (mut sent, mut received, mut connected) = (0, 0, false);
+    let closed = loop {
+        let now = clock.now();
+        if now - start > request.timeout {
+            return Err(match (connected, sent < len) {
+                (false, _) => Error::ConnectTimeout,
+                (true, true) => Error::SendTimeout,
+                (true, false) => Error::ResponseTimeout,
+            });
+        }
+        iface.poll(now, device, &mut sockets);
+        let socket = sockets.get_mut::<tcp::Socket>(handle);
+        if !connected && socket.may_send() {
+            connected = true;
+            debug!("connected, sending request");
+        } else if !connected && !socket.is_active() {
+            return Err(Error::ConnectionRefused);
+        }
+        if sent < len {
+            if socket.can_send() {
+                sent += socket
+                    .send_slice(&buffer[sent..len])
+                    .map_err(Error::SendFailed)?;
+            } else if connected && !socket.may_send() {
+                return Err(Error::ConnectionClosed);
+            }
+            continue;
+        }
+        // The response is received over the request, which has been sent.
+        if socket.can_recv() {
+            if received == buffer.len() {
+                return Err(Error::ResponseTooLarge);
+            }
+            received += socket
+                .recv_slice(&mut buffer[received..])
+                .map_err(Error::RecvFailed)?;
+            if complete::<N>(&mut buffer[..received], head_request)? {
+                break false;
+            }
+        } else if !socket.may_recv() {
+            debug!("connection closed by server");
+            if received == 0 {
+                return Err(Error::ConnectionClosed);
+            }
+            // A reset leaves the socket closed, while a FIN leaves it waiting for our own.
+            if socket.state() == tcp::State::Closed {
+                warn!("connection reset by server");
+                return Err(Error::ConnectionReset);
+            }
+            break true;
+        }
+    };
+    // The connection is reset if the server does not close its end in time.
+    sockets.get_mut::<tcp::Socket>(handle).close();
+    let until = clock.now() + CLOSE_TIMEOUT;
+    loop {
+        let now = clock.now();
+        iface.poll(now, device, &mut sockets);
+        let socket = sockets.get_mut::<tcp::Socket>(handle);
+        if !socket.is_open() {
+            break;
+        }
+        if now >= until {
+            socket.abort();
+            iface.poll(now, device, &mut sockets);
+            break;
+        }
+    }
+    parse(&mut buffer[..received], closed, head_request)?
+        .ok_or(Error::InvalidResponse(ParseError::Incomplete))
+}
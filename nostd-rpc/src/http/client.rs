@@ -0,0 +1,633 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::{IpAddr, SocketAddr};
+
+use smoltcp::iface::{Interface, SocketHandle, SocketSet};
+use smoltcp::phy::Device;
+#[cfg(feature = "phy-tuntap_interface")]
+use smoltcp::phy::TunTapInterface;
+use smoltcp::socket::tcp;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::IpAddress;
+
+use super::{follow_redirects, idle_delay, remote_address, report};
+#[cfg(feature = "phy-tuntap_interface")]
+use super::{tuntap_interface, DEFAULT_TUNTAP_ATTEMPTS, DEFAULT_TUNTAP_RETRY_DELAY_SECONDS};
+use super::{CookieJar, TlsConnector, TokenProvider, CLOSE_TIMEOUT};
+use super::{HttpRequest, HttpResponse, Progress};
+use crate::hooks::{Event, Hooks, Transfer};
+use crate::metrics::Timings;
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::net::NetworkConfig;
+use crate::net::{self, LocalPorts};
+use crate::response;
+use crate::server::TlsSession;
+use crate::time::{Clock, IdleHook};
+use crate::{Error, Phase};
+
+/// The server a kept-alive connection goes to.
+struct Endpoint {
+    host: String,
+    remote: IpAddr,
+    port: u16,
+    tls: bool,
+    proxy: Option<SocketAddr>,
+}
+
+impl Endpoint {
+    /// Returns whether `request` can be sent over a connection to this endpoint.
+    fn serves(&self, request: &HttpRequest) -> bool {
+        let same_server = match request.ip {
+            Some(ip) if self.proxy.is_none() => ip == self.remote,
+            _ => self.host == request.host,
+        };
+        same_server
+            && self.port == request.port
+            && self.tls == request.tls
+            && self.proxy == request.proxy
+    }
+}
+
+/// Sends requests over an interface and socket it owns, keeping the connection open between
+/// requests to the same server.
+///
+/// Requests are sent with `Connection: keep-alive` unless they have their own `Connection`
+/// header. The connection is closed when the server asks for it, and if a kept-alive
+/// connection has been closed by the server before the next request is answered, the
+/// request is sent again over a new connection. Requests sent over a kept-alive connection
+/// have no connect time in their [`Timings`].
+///
+/// HTTPS requests are sent through the [`TlsConnector`] set with [`HttpClient::tls`], with the
+/// host of the request as the server name.
+pub struct HttpClient<D: Device> {
+    device: D,
+    iface: Interface,
+    sockets: SocketSet<'static>,
+    tcp_handle: SocketHandle,
+    /// The server the socket is connected to, while the connection is kept alive.
+    connected: Option<Endpoint>,
+    /// DNS servers from the DHCP lease, used after those of each request.
+    dns_servers: Vec<IpAddr>,
+    /// Chooses the local port of each connection.
+    ports: LocalPorts,
+    /// Creates the TLS sessions of HTTPS connections.
+    tls: Option<Box<dyn TlsConnector>>,
+    /// The TLS session of the open connection, if it uses TLS.
+    session: Option<Box<dyn TlsSession>>,
+    /// Cookies set by servers, sent back with later requests.
+    cookies: Option<CookieJar>,
+    /// Supplies the bearer token of each request.
+    tokens: Option<Box<dyn TokenProvider>>,
+    /// Called while waiting on the server, and between attempts of a request.
+    idle: Option<Box<dyn IdleHook>>,
+}
+
+#[cfg(feature = "phy-tuntap_interface")]
+impl HttpClient<TunTapInterface> {
+    /// Constructs a client on the `tap0` interface, configured with `network`.
+    ///
+    /// DNS servers offered by DHCP are used for every request, after those of the request.
+    pub fn tuntap<C: Clock, I: IdleHook>(
+        ethernet_mac: [u8; 6],
+        network: &NetworkConfig,
+        clock: &C,
+        idle: &mut I,
+    ) -> Result<Self, Error> {
+        let (device, iface, lease) = tuntap_interface(
+            ethernet_mac,
+            network,
+            DEFAULT_TUNTAP_ATTEMPTS,
+            Duration::from_secs(DEFAULT_TUNTAP_RETRY_DELAY_SECONDS),
+            clock,
+            idle,
+        )?;
+        let mut client = HttpClient::new(device, iface);
+        if let Some(lease) = lease {
+            client.dns_servers = lease.dns_servers.into_iter().map(IpAddr::V4).collect();
+        }
+        Ok(client)
+    }
+}
+
+impl<D: Device> HttpClient<D> {
+    /// Constructs a client sending requests over `iface` and `device`, which must already
+    /// have their address and routes configured.
+    pub fn new(device: D, iface: Interface) -> Self {
+        let size = net::buffer_size(&device.capabilities());
+        let tcp_rx_buffer = tcp::SocketBuffer::new(vec![0; size]);
+        let tcp_tx_buffer = tcp::SocketBuffer::new(vec![0; size]);
+        let mut sockets = SocketSet::new(vec![]);
+        let tcp_handle = sockets.add(tcp::Socket::new(tcp_rx_buffer, tcp_tx_buffer));
+        HttpClient {
+            device,
+            iface,
+            sockets,
+            tcp_handle,
+            connected: None,
+            dns_servers: Vec::new(),
+            ports: LocalPorts::new([0; 8]),
+            tls: None,
+            session: None,
+            cookies: None,
+            tokens: None,
+            idle: None,
+        }
+    }
+
+    /// Seeds the choice of local ports with `random`, which should come from a random number
+    /// generator, see [`LocalPorts`].
+    pub fn local_ports(mut self, random: [u8; 8]) -> Self {
+        self.ports = LocalPorts::new(random);
+        self
+    }
+
+    /// Sets the sizes of the TCP socket receive and transmit buffers, by default 1 KiB each or
+    /// less on devices with small packets, see [`net::buffer_size`], closing the open
+    /// connection.
+    pub fn buffer_sizes(mut self, rx_size: usize, tx_size: usize) -> Self {
+        self.close();
+        self.sockets.remove(self.tcp_handle);
+        let tcp_rx_buffer = tcp::SocketBuffer::new(vec![0; rx_size]);
+        let tcp_tx_buffer = tcp::SocketBuffer::new(vec![0; tx_size]);
+        self.tcp_handle = self
+            .sockets
+            .add(tcp::Socket::new(tcp_rx_buffer, tcp_tx_buffer));
+        self
+    }
+
+    /// Sets the TLS implementation used for requests with [`HttpRequest::tls`] set, which is
+    /// responsible for checking the server certificate against its root certificates.
+    pub fn tls<T: TlsConnector + 'static>(mut self, connector: T) -> Self {
+        self.tls = Some(Box::new(connector));
+        self
+    }
+
+    /// Keeps the cookies set by servers in `jar`, sending them back with later requests to the
+    /// same host.
+    pub fn cookie_jar(mut self, jar: CookieJar) -> Self {
+        self.cookies = Some(jar);
+        self
+    }
+
+    /// Sends each request with the bearer token from `provider`, asking it to refresh the
+    /// token and sending the request again if the server answers `401 Unauthorized`.
+    pub fn token_provider<T: TokenProvider + 'static>(mut self, provider: T) -> Self {
+        self.tokens = Some(Box::new(provider));
+        self
+    }
+
+    /// Calls `idle` while a request waits on the server, and before another attempt of a
+    /// request, as its [`RetryPolicy`](super::RetryPolicy) asks. Without one the interface is
+    /// polled continuously.
+    pub fn idle<I: IdleHook + 'static>(mut self, idle: I) -> Self {
+        self.idle = Some(Box::new(idle));
+        self
+    }
+
+    /// Returns the cookie jar, if one was set with [`HttpClient::cookie_jar`].
+    pub fn cookies(&mut self) -> Option<&mut CookieJar> {
+        self.cookies.as_mut()
+    }
+
+    /// Sends `request`, reusing the open connection if it goes to the same server.
+    ///
+    /// Of the request's [`NetworkConfig`] only the DNS servers are used.
+    pub fn send<C: Clock>(
+        &mut self,
+        request: &HttpRequest,
+        clock: &C,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<(HttpResponse, Timings), Error> {
+        report(clock, hooks, |hooks| {
+            follow_redirects(request, clock, |request| {
+                let with_cookies = self.with_cookies(request);
+                let request = with_cookies.as_ref().unwrap_or(request);
+                let mut refreshed = false;
+                loop {
+                    let token = self.tokens.as_mut().and_then(|tokens| tokens.token());
+                    let with_token = token.as_ref().map(|token| request.clone().bearer(token));
+                    let result =
+                        self.send_retrying(with_token.as_ref().unwrap_or(request), clock, hooks);
+                    let unauthorized =
+                        matches!(&result, Ok((response, _)) if response.status() == 401);
+                    let refresh = match (&token, &mut self.tokens) {
+                        (Some(token), Some(tokens)) if unauthorized && !refreshed => {
+                            tokens.refresh(token)
+                        }
+                        _ => false,
+                    };
+                    if !refresh {
+                        return result;
+                    }
+                    debug!("token rejected, sending again with a new one");
+                    refreshed = true;
+                }
+            })
+        })
+    }
+
+    /// Sends `request`, trying again after failures as its
+    /// [`RetryPolicy`](super::RetryPolicy) allows.
+    fn send_retrying<C: Clock>(
+        &mut self,
+        request: &HttpRequest,
+        clock: &C,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<(HttpResponse, Timings), Error> {
+        let mut attempt = 1;
+        loop {
+            let reused = self.connected.as_ref().is_some_and(|e| e.serves(request));
+            let result = match self.transact(request, clock, hooks) {
+                // The server closed the idle connection, so the request was never handled.
+                Err(error) if reused && *error.root() == Error::ConnectionClosed => {
+                    debug!("kept-alive connection failed, reconnecting");
+                    self.transact(request, clock, hooks)
+                }
+                result => result,
+            };
+            match result {
+                Err(error) if request.retry.retries(attempt, &error) => {
+                    let delay = request.retry.delay(attempt);
+                    attempt += 1;
+                    match error.context() {
+                        Some(context) => {
+                            warn!("request failed: {} {}, retrying", error.root(), context)
+                        }
+                        None => warn!("request failed: {}, retrying", error),
+                    }
+                    hooks.event(
+                        clock.now(),
+                        Event::Retrying {
+                            attempt,
+                            error: &error,
+                        },
+                    );
+                    self.close();
+                    self.wait(clock.now() + delay, clock);
+                }
+                result => {
+                    if let (Some(jar), Ok((response, _))) = (&mut self.cookies, &result) {
+                        jar.store(&request.host_name(), &request.path(), response);
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// Polls the interface until `until`, calling the idle hook in between.
+    fn wait<C: Clock>(&mut self, until: Instant, clock: &C) {
+        loop {
+            let now = clock.now();
+            if now >= until {
+                return;
+            }
+            self.iface.poll(now, &mut self.device, &mut self.sockets);
+            let delay = self.iface.poll_delay(now, &self.sockets);
+            let delay = delay.map_or(until - now, |delay| delay.min(until - now));
+            if let Some(idle) = &mut self.idle {
+                idle.idle(delay);
+            }
+        }
+    }
+
+    /// Closes the open connection, if there is one.
+    pub fn close(&mut self) {
+        self.sockets.get_mut::<tcp::Socket>(self.tcp_handle).abort();
+        self.connected = None;
+        self.session = None;
+    }
+
+    /// Returns the interface the client sends requests over.
+    pub fn interface(&mut self) -> &mut Interface {
+        &mut self.iface
+    }
+
+    /// Returns the device the client sends requests over.
+    pub fn device(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    fn transact<C: Clock>(
+        &mut self,
+        request: &HttpRequest,
+        clock: &C,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<(HttpResponse, Timings), Error> {
+        if request.tls && self.tls.is_none() {
+            warn!("TLS request without a TlsConnector");
+            return Err(Error::Tls);
+        }
+        let mut progress = Progress::new(clock.now());
+        let remote = match &self.connected {
+            Some(endpoint) if endpoint.serves(request) => endpoint.remote,
+            _ => {
+                let mut dns_servers = request.network.dns_servers.clone();
+                dns_servers.extend_from_slice(&self.dns_servers);
+                let (iface, device) = (&mut self.iface, &mut self.device);
+                remote_address(
+                    iface,
+                    device,
+                    request,
+                    &dns_servers,
+                    clock,
+                    hooks,
+                    &mut progress,
+                )?
+            }
+        };
+        match self.exchange(remote, request, clock, hooks, &mut progress) {
+            Ok(response) => Ok((response, progress.timings(clock.now()))),
+            Err(error) => {
+                self.close();
+                Err(progress.fail(error, clock.now()))
+            }
+        }
+    }
+
+    /// Connects to `remote` unless already connected, then writes `request` and reads the
+    /// response.
+    fn exchange<C: Clock>(
+        &mut self,
+        remote: IpAddr,
+        request: &HttpRequest,
+        clock: &C,
+        hooks: &mut Hooks<'_>,
+        progress: &mut Progress,
+    ) -> Result<HttpResponse, Error> {
+        request.check_headers()?;
+        let start = progress.start;
+        let socket = self.sockets.get_mut::<tcp::Socket>(self.tcp_handle);
+        let reusable = self.connected.as_ref().is_some_and(|e| e.serves(request));
+        if reusable && socket.may_send() && socket.may_recv() {
+            debug!("reusing connection");
+            progress.enter(Phase::Write, start);
+        } else {
+            // Reset any previous connection before the socket is reused.
+            socket.abort();
+            self.iface.poll(start, &mut self.device, &mut self.sockets);
+            self.connected = None;
+            self.session = None;
+            let local_port = self.ports.next(start);
+            self.sockets
+                .get_mut::<tcp::Socket>(self.tcp_handle)
+                .connect(
+                    self.iface.context(),
+                    (IpAddress::from(remote), request.next_hop().1),
+                    local_port,
+                )
+                .map_err(Error::Connect)?;
+            progress.connecting = Some(start);
+            debug!("connecting to port {}", request.next_hop().1);
+            hooks.event(start, Event::Connecting);
+        }
+
+        let signed = request.signed(hooks);
+        let message = signed.as_ref().unwrap_or(request).construct("keep-alive");
+        // Data waiting to be written to the socket, and the size of the request in it.
+        let mut outgoing = Vec::new();
+        let mut request_len = 0;
+        if progress.phase == Phase::Write {
+            request_len = self.queue(&message, &mut outgoing)?;
+        }
+        let mut parser = request.response_parser();
+        let socket = self.sockets.get::<tcp::Socket>(self.tcp_handle);
+        let local_port = socket.local_endpoint().map_or(0, |local| local.port);
+        loop {
+            let timestamp = clock.now();
+            let (iface, device, sockets) = (&mut self.iface, &mut self.device, &mut self.sockets);
+            progress.poll(iface, device, sockets, local_port, timestamp, hooks);
+            let socket = self.sockets.get_mut::<tcp::Socket>(self.tcp_handle);
+            progress.observe(socket);
+
+            if progress.phase == Phase::Connect {
+                if progress.connected.is_none() && socket.may_send() {
+                    progress.connected = Some(timestamp);
+                    debug!("connected, sending request");
+                    hooks.event(timestamp, Event::Connected);
+                    if request.tls && request.proxy.is_some() {
+                        self.tunnel(request, clock, progress)?;
+                    }
+                    if request.tls {
+                        let Some(connector) = self.tls.as_mut() else {
+                            return Err(Error::Tls);
+                        };
+                        hooks.event(timestamp, Event::TlsHandshake);
+                        self.session = Some(connector.connect(&request.host, &mut outgoing)?);
+                    }
+                } else if !socket.is_active() && progress.connected.is_none() {
+                    return Err(Error::ConnectionRefused);
+                } else if !socket.is_active() {
+                    return Err(Error::ConnectionClosed);
+                }
+                let handshaking = self.session.as_ref().is_some_and(|s| s.is_handshaking());
+                if progress.connected.is_some() && !handshaking {
+                    request_len = self.queue(&message, &mut outgoing)?;
+                    progress.enter(Phase::Write, timestamp);
+                }
+            }
+
+            let socket = self.sockets.get_mut::<tcp::Socket>(self.tcp_handle);
+            if socket.can_send() && !outgoing.is_empty() {
+                let sent = socket.send_slice(&outgoing).map_err(Error::SendFailed)?;
+                outgoing.drain(..sent);
+                trace!("sent {} bytes", sent);
+                if progress.phase == Phase::Write {
+                    progress.sent(sent, timestamp);
+                    hooks.bytes_sent(sent);
+                    hooks.transfer(Transfer::Upload, progress.bytes_sent, Some(request_len));
+                }
+            } else if progress.phase == Phase::Write && !socket.may_send() {
+                return Err(Error::ConnectionClosed);
+            }
+            if progress.phase == Phase::Write && outgoing.is_empty() {
+                progress.enter(Phase::Read, timestamp);
+            }
+
+            if socket.can_recv() {
+                let session = &mut self.session;
+                let parsed = socket
+                    .recv(|data| {
+                        let result = match session {
+                            Some(session) => {
+                                let mut plaintext = Vec::new();
+                                session
+                                    .read_tls(data, &mut plaintext, &mut outgoing)
+                                    .map(|()| plaintext)
+                            }
+                            None => Ok(data.to_vec()),
+                        };
+                        let parsed = result.and_then(|plaintext| {
+                            if plaintext.is_empty() {
+                                return Ok(None);
+                            }
+                            progress.receive(&plaintext, timestamp, request, hooks);
+                            parser.feed(&plaintext)
+                        });
+                        (data.len(), parsed)
+                    })
+                    .map_err(Error::RecvFailed)?;
+                if let Err(error) = progress.check_size(request) {
+                    self.close();
+                    return Err(error);
+                }
+                if let Some(response) = parsed? {
+                    self.keep_alive(&response, remote, request, clock);
+                    return Ok(response);
+                }
+            } else if progress.phase == Phase::Read && !socket.may_recv() {
+                debug!("connection closed by server");
+                if progress.bytes_received == 0 {
+                    return Err(Error::ConnectionClosed);
+                }
+                // A reset leaves the socket closed, while a FIN leaves it waiting for our own.
+                if socket.state() == tcp::State::Closed {
+                    warn!("connection reset by server");
+                    return Err(Error::ConnectionReset);
+                }
+                let response = parser.finish()?;
+                self.shutdown(clock);
+                return Ok(response);
+            }
+            progress.check_timeout(request, timestamp)?;
+            let socket = self.sockets.get::<tcp::Socket>(self.tcp_handle);
+            if !socket.can_recv() && (outgoing.is_empty() || !socket.can_send()) {
+                self.pause(clock.now(), progress.deadline(request));
+            }
+        }
+    }
+
+    /// Calls the idle hook at `now` for as long as the interface need not be polled, up to
+    /// `until`.
+    fn pause(&mut self, now: Instant, until: Option<Instant>) {
+        if let Some(idle) = &mut self.idle {
+            let delay = idle_delay(&mut self.iface, &self.sockets, now, until);
+            if delay > Duration::ZERO {
+                idle.idle(delay);
+            }
+        }
+    }
+
+    /// Returns `request` with a `Cookie` header carrying the cookies stored for it, if any.
+    fn with_cookies(&self, request: &HttpRequest) -> Option<HttpRequest> {
+        let jar = self.cookies.as_ref()?;
+        let cookies = jar.header(&request.host_name(), &request.path(), request.tls)?;
+        let mut header = String::from("Cookie: ");
+        header.push_str(&cookies);
+        Some(request.clone().header(&header))
+    }
+
+    /// Asks the proxy, once connected, for a tunnel to the server of `request`, waiting until
+    /// the proxy accepts.
+    fn tunnel<C: Clock>(
+        &mut self,
+        request: &HttpRequest,
+        clock: &C,
+        progress: &Progress,
+    ) -> Result<(), Error> {
+        debug!("opening a tunnel through the proxy");
+        let mut connect = request.construct_connect().into_bytes();
+        let mut received = Vec::new();
+        loop {
+            let timestamp = clock.now();
+            progress.check_timeout(request, timestamp)?;
+            self.iface
+                .poll(timestamp, &mut self.device, &mut self.sockets);
+            let socket = self.sockets.get_mut::<tcp::Socket>(self.tcp_handle);
+            if !connect.is_empty() && socket.can_send() {
+                let sent = socket.send_slice(&connect).map_err(Error::SendFailed)?;
+                connect.drain(..sent);
+            }
+            if socket.can_recv() {
+                socket
+                    .recv(|data| {
+                        received.extend_from_slice(data);
+                        (data.len(), ())
+                    })
+                    .map_err(Error::RecvFailed)?;
+                let limits = &request.header_limits;
+                if let Some((head, _)) =
+                    response::parse_head(&received, request.parse_mode, limits)?
+                {
+                    if !(200..300).contains(&head.status) {
+                        warn!("proxy refused the tunnel with status {}", head.status);
+                        return Err(Error::Proxy(head.status));
+                    }
+                    return Ok(());
+                }
+            } else if !socket.may_recv() {
+                return Err(Error::ConnectionClosed);
+            }
+        }
+    }
+
+    /// Appends `plaintext` to `outgoing`, encrypted if the connection uses TLS, returning the
+    /// number of bytes appended.
+    fn queue(&mut self, plaintext: &[u8], outgoing: &mut Vec<u8>) -> Result<usize, Error> {
+        let len = outgoing.len();
+        match &mut self.session {
+            Some(session) => session.write(plaintext, outgoing)?,
+            None => outgoing.extend_from_slice(plaintext),
+        }
+        Ok(outgoing.len() - len)
+    }
+
+    /// Keeps the connection open after `response`, unless the server is closing it.
+    fn keep_alive<C: Clock>(
+        &mut self,
+        response: &HttpResponse,
+        remote: IpAddr,
+        request: &HttpRequest,
+        clock: &C,
+    ) {
+        let connection = response.header_combined("connection").unwrap_or_default();
+        let has_token = |token: &str| {
+            connection
+                .split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case(token))
+        };
+        // HTTP/1.0 servers close the connection unless they say otherwise.
+        let keep_alive = if response.minor_version() == 0 {
+            has_token("keep-alive")
+        } else {
+            !has_token("close")
+        };
+        if keep_alive {
+            self.connected = Some(Endpoint {
+                host: request.host.clone(),
+                remote,
+                port: request.port,
+                tls: request.tls,
+                proxy: request.proxy,
+            });
+        } else {
+            debug!("server closed the connection");
+            self.shutdown(clock);
+        }
+    }
+
+    /// Closes the open connection, waiting for the server to close its end too and resetting
+    /// the connection if it does not within [`CLOSE_TIMEOUT`].
+    fn shutdown<C: Clock>(&mut self, clock: &C) {
+        let until = clock.now() + CLOSE_TIMEOUT;
+        self.sockets.get_mut::<tcp::Socket>(self.tcp_handle).close();
+        loop {
+            let timestamp = clock.now();
+            self.iface
+                .poll(timestamp, &mut self.device, &mut self.sockets);
+            let socket = self.sockets.get_mut::<tcp::Socket>(self.tcp_handle);
+            if !socket.is_open() {
+                break;
+            }
+            if timestamp >= until {
+                debug!("connection not closed in time, resetting it");
+                socket.abort();
+                break;
+            }
+        }
+        self.connected = None;
+        self.session = None;
+    }
+}
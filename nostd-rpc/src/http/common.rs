@@ -0,0 +1,18 @@
+//! Defaults and rules shared by requests that own their data and those that borrow it, so
+//! that both are available without the `alloc` feature.
+
+use smoltcp::time::Duration;
+
+pub(crate) const DEFAULT_PORT: u16 = 80;
+pub(crate) const DEFAULT_TIMEOUT_SECONDS: u64 = 15;
+/// How long to wait for the server to acknowledge the close of a connection before
+/// resetting it.
+pub(crate) const CLOSE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Returns whether `method` defines a meaning for a request body, so that an empty one is
+/// still announced with `Content-Length: 0` (RFC 9110 section 8.6).
+pub(crate) fn expects_body(method: &str) -> bool {
+    !["GET", "HEAD", "DELETE", "OPTIONS", "TRACE", "CONNECT"]
+        .iter()
+        .any(|m| method.eq_ignore_ascii_case(m))
+}
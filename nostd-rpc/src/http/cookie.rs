@@ -0,0 +1,126 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::HttpResponse;
+
+/// A cookie set by a server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Cookie {
+    /// The host that set the cookie, the only one it is sent back to.
+    host: String,
+    name: String,
+    value: String,
+    /// The cookie is only sent with requests for paths under this one.
+    path: String,
+    /// Whether the cookie is only sent over TLS.
+    secure: bool,
+}
+
+/// Cookies captured from the `Set-Cookie` headers of responses, sent back in a `Cookie`
+/// header with later requests to the same host, e.g. the session cookie of a gateway that
+/// requires a login.
+///
+/// Cookies are only sent back to the host that set them: the `Domain` attribute is ignored.
+/// A cookie is removed when the server sets it again with `Max-Age=0`, other expiry times are
+/// not tracked.
+#[derive(Clone, Debug, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    /// Constructs an empty [`CookieJar`].
+    pub fn new() -> Self {
+        CookieJar::default()
+    }
+
+    /// Stores the cookies set by `response` to a request for `path` on `host`.
+    pub fn store(&mut self, host: &str, path: &str, response: &HttpResponse) {
+        for header in response.header_all("set-cookie") {
+            let mut attributes = header.split(';');
+            let Some((name, value)) = attributes.next().and_then(|c| c.split_once('=')) else {
+                continue;
+            };
+            let name = name.trim();
+            if name.is_empty() {
+                continue;
+            }
+            let mut cookie = Cookie {
+                host: host.to_ascii_lowercase(),
+                name: String::from(name),
+                value: String::from(value.trim()),
+                path: default_path(path),
+                secure: false,
+            };
+            let mut expired = false;
+            for attribute in attributes {
+                let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+                let (key, value) = (key.trim(), value.trim());
+                if key.eq_ignore_ascii_case("path") && value.starts_with('/') {
+                    cookie.path = String::from(value);
+                } else if key.eq_ignore_ascii_case("secure") {
+                    cookie.secure = true;
+                } else if key.eq_ignore_ascii_case("max-age") {
+                    expired = value.parse::<i64>().is_ok_and(|age| age <= 0);
+                }
+            }
+
+            self.cookies.retain(|c| {
+                !(c.host == cookie.host && c.name == cookie.name && c.path == cookie.path)
+            });
+            if !expired {
+                self.cookies.push(cookie);
+            }
+        }
+    }
+
+    /// Returns the value of the `Cookie` header for a request for `path` on `host`, sent over
+    /// TLS if `tls` is set, or `None` if no cookies apply.
+    pub fn header(&self, host: &str, path: &str, tls: bool) -> Option<String> {
+        let mut header = String::new();
+        let cookies = self.cookies.iter().filter(|c| {
+            c.host.eq_ignore_ascii_case(host) && path_matches(&c.path, path) && (tls || !c.secure)
+        });
+        for cookie in cookies {
+            if !header.is_empty() {
+                header.push_str("; ");
+            }
+            header.push_str(&cookie.name);
+            header.push('=');
+            header.push_str(&cookie.value);
+        }
+        (!header.is_empty()).then_some(header)
+    }
+
+    /// Returns the value of the cookie called `name` set by `host`, if any.
+    pub fn get(&self, host: &str, name: &str) -> Option<&str> {
+        self.cookies
+            .iter()
+            .find(|c| c.host.eq_ignore_ascii_case(host) && c.name == name)
+            .map(|c| c.value.as_str())
+    }
+
+    /// Removes all cookies, e.g. to log out.
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+}
+
+/// Returns the path of a cookie set without a `Path` attribute in response to a request for
+/// `path`: its directory (RFC 6265 section 5.1.4).
+fn default_path(path: &str) -> String {
+    let path = path.split_once('?').map_or(path, |(path, _)| path);
+    match path.rfind('/') {
+        Some(0) | None => String::from("/"),
+        Some(end) => String::from(&path[..end]),
+    }
+}
+
+/// Returns whether a cookie with `cookie_path` is sent with a request for `path`.
+fn path_matches(cookie_path: &str, path: &str) -> bool {
+    let path = path.split_once('?').map_or(path, |(path, _)| path);
+    match path.strip_prefix(cookie_path) {
+        Some(rest) => cookie_path.ends_with('/') || rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
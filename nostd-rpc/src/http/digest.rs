@@ -0,0 +1,242 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use smoltcp::time::Instant;
+
+use super::{HttpRequest, HttpResponse};
+use crate::{encoding, md5, sha256};
+
+impl HttpRequest {
+    /// Returns the request with an `Authorization` header answering the Digest challenge of
+    /// `response`, or `None` if it is not a `401 Unauthorized` response with a supported
+    /// challenge or the request has no credentials for it. SHA-256 is preferred over MD5.
+    ///
+    /// The client nonce is drawn from the random seed of the request and the time `now`.
+    pub(super) fn answer_digest(
+        &self,
+        response: &HttpResponse,
+        now: Instant,
+    ) -> Option<HttpRequest> {
+        let (user, password) = self.digest_auth.as_ref()?;
+        if response.status() != 401 {
+            return None;
+        }
+        let challenge = response
+            .header_all("www-authenticate")
+            .filter_map(Challenge::parse)
+            .max_by_key(|challenge| challenge.algorithm == Algorithm::Sha256)?;
+        let mut seed = Vec::from(self.digest_seed);
+        seed.extend_from_slice(&now.total_micros().to_le_bytes());
+        seed.extend_from_slice(challenge.nonce.as_bytes());
+        let cnonce = &encoding::encode_hex(&sha256::digest(&seed))[..16];
+        let mut answered = self.clone();
+        answered.authorization =
+            Some(challenge.authorization((user, password), cnonce, &self.method, &self.path()));
+        Some(answered)
+    }
+}
+
+/// The hash function of a Digest challenge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Algorithm {
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    /// Returns the hash of `data` as lowercase hexadecimal.
+    fn hash(self, data: &str) -> String {
+        match self {
+            Algorithm::Md5 => encoding::encode_hex(&md5::digest(data.as_bytes())),
+            Algorithm::Sha256 => encoding::encode_hex(&sha256::digest(data.as_bytes())),
+        }
+    }
+}
+
+/// A `WWW-Authenticate: Digest` challenge (RFC 7616).
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: Algorithm,
+    /// Whether the `-sess` variant of the algorithm is used.
+    session: bool,
+    /// Whether the server offered `qop=auth`, otherwise the RFC 2069 response is sent.
+    qop: bool,
+}
+
+impl Challenge {
+    /// Parses the Digest challenge in the value of a `WWW-Authenticate` header, or returns
+    /// `None` if there is none or its algorithm is not supported.
+    fn parse(value: &str) -> Option<Challenge> {
+        let lower = value.to_ascii_lowercase();
+        let start = lower
+            .match_indices("digest ")
+            .map(|(i, _)| i)
+            .find(|&i| i == 0 || lower[..i].ends_with([' ', ',']))?;
+        let params = parse_params(&value[start + 7..]);
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.clone())
+        };
+
+        let algorithm = param("algorithm").unwrap_or_else(|| String::from("MD5"));
+        let (name, session) = match algorithm.len().checked_sub(5) {
+            Some(i)
+                if algorithm
+                    .get(i..)
+                    .is_some_and(|s| s.eq_ignore_ascii_case("-sess")) =>
+            {
+                (&algorithm[..i], true)
+            }
+            _ => (algorithm.as_str(), false),
+        };
+        let algorithm = if name.eq_ignore_ascii_case("MD5") {
+            Algorithm::Md5
+        } else if name.eq_ignore_ascii_case("SHA-256") {
+            Algorithm::Sha256
+        } else {
+            return None;
+        };
+        let qop = match param("qop") {
+            Some(qop)
+                if qop
+                    .split(',')
+                    .any(|q| q.trim().eq_ignore_ascii_case("auth")) =>
+            {
+                true
+            }
+            // Only auth-int is offered, which needs a hash of the body.
+            Some(_) => return None,
+            None => false,
+        };
+        Some(Challenge {
+            realm: param("realm")?,
+            nonce: param("nonce")?,
+            opaque: param("opaque"),
+            algorithm,
+            session,
+            qop,
+        })
+    }
+
+    /// Returns the value of the `Authorization` header answering the challenge for `method`
+    /// and `uri` with `user` and `password`, and the client nonce `cnonce`.
+    fn authorization(
+        &self,
+        (user, password): (&str, &str),
+        cnonce: &str,
+        method: &str,
+        uri: &str,
+    ) -> String {
+        let hash = |data: &str| self.algorithm.hash(data);
+        let mut ha1 = hash(&join(&[user, &self.realm, password]));
+        if self.session {
+            ha1 = hash(&join(&[&ha1, &self.nonce, cnonce]));
+        }
+        let ha2 = hash(&join(&[method, uri]));
+        let response = if self.qop {
+            hash(&join(&[
+                &ha1,
+                &self.nonce,
+                NONCE_COUNT,
+                cnonce,
+                "auth",
+                &ha2,
+            ]))
+        } else {
+            hash(&join(&[&ha1, &self.nonce, &ha2]))
+        };
+
+        let mut algorithm = String::from(match self.algorithm {
+            Algorithm::Md5 => "MD5",
+            Algorithm::Sha256 => "SHA-256",
+        });
+        if self.session {
+            algorithm.push_str("-sess");
+        }
+        let mut header = String::from("Digest ");
+        push_quoted(&mut header, "username", user);
+        push_quoted(&mut header, "realm", &self.realm);
+        push_quoted(&mut header, "nonce", &self.nonce);
+        push_quoted(&mut header, "uri", uri);
+        header.push_str(", algorithm=");
+        header.push_str(&algorithm);
+        push_quoted(&mut header, "response", &response);
+        if self.qop {
+            header.push_str(", qop=auth, nc=");
+            header.push_str(NONCE_COUNT);
+            push_quoted(&mut header, "cnonce", cnonce);
+        }
+        if let Some(opaque) = &self.opaque {
+            push_quoted(&mut header, "opaque", opaque);
+        }
+        header
+    }
+}
+
+/// The nonce count of the response, as each challenge is answered once.
+const NONCE_COUNT: &str = "00000001";
+
+/// Returns `parts` joined by colons.
+fn join(parts: &[&str]) -> String {
+    parts.join(":")
+}
+
+/// Appends `name="value"` to the parameters in `header`, escaping quotes and backslashes.
+fn push_quoted(header: &mut String, name: &str, value: &str) {
+    if !header.ends_with(' ') {
+        header.push_str(", ");
+    }
+    header.push_str(name);
+    header.push_str("=\"");
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            header.push('\\');
+        }
+        header.push(c);
+    }
+    header.push('"');
+}
+
+/// Parses the comma separated `name=value` parameters of a challenge, where values may be
+/// quoted, stopping at the start of the next challenge.
+fn parse_params(mut rest: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        let Some((name, after)) = rest.split_once('=') else {
+            return params;
+        };
+        if name.is_empty() || name.contains([' ', ',']) {
+            return params;
+        }
+        let mut value = String::new();
+        rest = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                        c => value.push(c),
+                    }
+                }
+                &quoted[end..]
+            }
+            None => {
+                let end = after.find(',').unwrap_or(after.len());
+                value.push_str(after[..end].trim());
+                &after[end..]
+            }
+        };
+        params.push((String::from(name.trim()), value));
+    }
+}
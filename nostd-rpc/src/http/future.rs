@@ -0,0 +1,62 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use smoltcp::iface::Interface;
+use smoltcp::phy::Device;
+use smoltcp::time::Duration;
+
+use super::{HttpRequest, HttpResponse, HttpTransaction};
+use crate::hooks::Hooks;
+use crate::metrics::Timings;
+use crate::time::Clock;
+use crate::Error;
+
+/// Sends `request` like [`send_with_device`](super::send_with_device), yielding to the
+/// executor while it waits instead of blocking, e.g. in an embassy or RTIC task.
+///
+/// Between polls of the interface the request awaits `sleep` for the delay given by
+/// [`HttpTransaction::poll_delay`], e.g. `|delay| Timer::after_micros(delay.total_micros())`
+/// with embassy, and only yields without sleeping when it can move on straight away. The
+/// host must be an IP address, as for [`HttpTransaction`].
+pub async fn send_async<'a, D, C, S, F>(
+    iface: &'a mut Interface,
+    device: &'a mut D,
+    request: HttpRequest,
+    clock: &C,
+    mut sleep: S,
+    hooks: Hooks<'a>,
+) -> Result<(HttpResponse, Timings), Error>
+where
+    D: Device,
+    C: Clock,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    let mut transaction = HttpTransaction::new(iface, device, request, clock.now()).hooks(hooks);
+    loop {
+        if let Poll::Ready(result) = transaction.poll(clock.now()) {
+            return result.map(|response| (response, transaction.timings(clock.now())));
+        }
+        match transaction.poll_delay(clock.now()) {
+            Duration::ZERO => YieldNow(false).await,
+            delay => sleep(delay).await,
+        }
+    }
+}
+
+/// A future that is pending once, waking its task so it is polled again.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
@@ -0,0 +1,118 @@
+use alloc::string::String;
+
+use crate::response;
+
+/// Headers that must not be sent twice with different values. The hop-by-hop headers
+/// (RFC 9110 section 7.6.1) describe the connection itself, so a conflicting pair leaves the
+/// server to pick one.
+const SINGLE: [&str; 6] = [
+    "connection",
+    "content-type",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "upgrade",
+];
+/// Headers the request sends itself, from its host and the length of its body.
+const RESERVED: [&str; 3] = ["content-length", "host", "transfer-encoding"];
+
+/// A request header, added with [`HttpRequest::header`](super::HttpRequest::header).
+///
+/// A `&str` converts to [`Header::Raw`], so `.header("Accept: */*")` still works.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Header<'a> {
+    /// `Accept`, the media types accepted in the response.
+    Accept(&'a str),
+    /// `Accept-Encoding`, the content codings accepted in the response.
+    AcceptEncoding(&'a str),
+    /// `Authorization`, the credentials of the request.
+    Authorization(&'a str),
+    /// `Connection`, the options of the connection, e.g. `close`.
+    Connection(&'a str),
+    /// `Content-Type`, the media type of the body.
+    ContentType(&'a str),
+    /// `Cookie`, the cookies sent to the server.
+    Cookie(&'a str),
+    /// `User-Agent`, the software sending the request.
+    UserAgent(&'a str),
+    /// Any other header, given by name and value.
+    Custom(&'a str, &'a str),
+    /// A whole `Name: value` line.
+    Raw(&'a str),
+}
+
+impl<'a> Header<'a> {
+    /// Returns the name and value of the header, or `None` if a raw header has no colon.
+    pub fn parts(&self) -> Option<(&'a str, &'a str)> {
+        let parts = match *self {
+            Header::Accept(value) => ("Accept", value),
+            Header::AcceptEncoding(value) => ("Accept-Encoding", value),
+            Header::Authorization(value) => ("Authorization", value),
+            Header::Connection(value) => ("Connection", value),
+            Header::ContentType(value) => ("Content-Type", value),
+            Header::Cookie(value) => ("Cookie", value),
+            Header::UserAgent(value) => ("User-Agent", value),
+            Header::Custom(name, value) => (name, value),
+            Header::Raw(line) => {
+                let (name, value) = line.split_once(':')?;
+                (name, value.trim_matches([' ', '\t']))
+            }
+        };
+        Some(parts)
+    }
+}
+
+impl<'a> From<&'a str> for Header<'a> {
+    fn from(line: &'a str) -> Self {
+        Header::Raw(line)
+    }
+}
+
+impl<'a> From<&'a String> for Header<'a> {
+    fn from(line: &'a String) -> Self {
+        Header::Raw(line)
+    }
+}
+
+/// What to do with a header added to a request.
+pub(super) enum Check {
+    /// Send the header as the line given.
+    Add(String),
+    /// Leave the header out, it repeats one already added.
+    Repeat,
+    /// The header is malformed, set by the request itself, or conflicts with one already
+    /// added.
+    Invalid,
+}
+
+/// Returns whether `name` is a token and `value` has no control characters.
+pub(super) fn is_valid(name: &str, value: &str) -> bool {
+    !name.is_empty()
+        && name.bytes().all(response::is_token_byte)
+        && !value.bytes().any(response::is_illegal_value_byte)
+}
+
+/// Checks `header` against the headers already added, each a `Name: value` line.
+pub(super) fn check(header: Header<'_>, headers: &[String]) -> Check {
+    let Some((name, value)) = header.parts() else {
+        return Check::Invalid;
+    };
+    if !is_valid(name, value) || RESERVED.iter().any(|r| name.eq_ignore_ascii_case(r)) {
+        return Check::Invalid;
+    }
+    if SINGLE.iter().any(|s| name.eq_ignore_ascii_case(s)) {
+        let existing = headers.iter().find_map(|line| {
+            let (n, v) = line.split_once(':')?;
+            n.eq_ignore_ascii_case(name).then(|| v.trim())
+        });
+        match existing {
+            Some(existing) if existing == value => return Check::Repeat,
+            Some(_) => return Check::Invalid,
+            None => {}
+        }
+    }
+    let mut line = String::from(name);
+    line.push_str(": ");
+    line.push_str(value);
+    Check::Add(line)
+}
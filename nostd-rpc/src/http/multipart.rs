@@ -0,0 +1,160 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::BodyProvider;
+use crate::Error;
+
+/// A part of a form, with the headers that precede it.
+struct Part {
+    /// The part's headers and the blank line that ends them.
+    head: String,
+    data: Vec<u8>,
+}
+
+/// A `multipart/form-data` body made of text fields and files, sent with
+/// [`send_with_body`](super::send_with_body) after setting its `Content-Type` with
+/// [`HttpRequest::multipart`](super::HttpRequest::multipart).
+///
+/// The boundary is chosen so that it does not occur in any part, and the length of the body
+/// is known up front, so it is sent with a `Content-Length` header.
+pub struct MultipartBody {
+    parts: Vec<Part>,
+    boundary: String,
+    /// Number of boundaries tried, so that the next one differs.
+    attempts: u32,
+    /// Number of bytes of the body already read.
+    sent: usize,
+}
+
+impl Default for MultipartBody {
+    fn default() -> Self {
+        MultipartBody::new()
+    }
+}
+
+impl MultipartBody {
+    /// Constructs an empty form.
+    pub fn new() -> Self {
+        MultipartBody {
+            parts: Vec::new(),
+            boundary: boundary(0),
+            attempts: 0,
+            sent: 0,
+        }
+    }
+
+    /// Adds a text field called `name`.
+    pub fn text(self, name: &str, value: &str) -> Self {
+        let head = format!(
+            "Content-Disposition: form-data; name=\"{}\"\r\n\r\n",
+            escape(name)
+        );
+        self.part(head, value.as_bytes())
+    }
+
+    /// Adds a field called `name` holding the file `filename` of type `content_type`, e.g.
+    /// `application/octet-stream`.
+    pub fn file(self, name: &str, filename: &str, content_type: &str, data: &[u8]) -> Self {
+        let head = format!(
+            "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+             Content-Type: {}\r\n\r\n",
+            escape(name),
+            escape(filename),
+            content_type
+        );
+        self.part(head, data)
+    }
+
+    /// Returns the boundary between the parts.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Returns the value of the `Content-Type` header the body is sent with.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// Adds a part, choosing another boundary if the current one occurs in it.
+    fn part(mut self, head: String, data: &[u8]) -> Self {
+        self.parts.push(Part {
+            head,
+            data: Vec::from(data),
+        });
+        while self.parts.iter().any(|part| {
+            contains(part.head.as_bytes(), self.boundary.as_bytes())
+                || contains(&part.data, self.boundary.as_bytes())
+        }) {
+            self.attempts += 1;
+            self.boundary = boundary(self.attempts);
+        }
+        self
+    }
+
+    /// Returns the pieces the body is made of, in order.
+    fn segments(&self) -> impl Iterator<Item = &[u8]> {
+        let boundary = self.boundary.as_bytes();
+        self.parts
+            .iter()
+            .flat_map(move |part| {
+                [
+                    b"--".as_slice(),
+                    boundary,
+                    b"\r\n",
+                    part.head.as_bytes(),
+                    &part.data,
+                    b"\r\n",
+                ]
+            })
+            .chain([b"--".as_slice(), boundary, b"--\r\n"])
+    }
+}
+
+impl BodyProvider for MultipartBody {
+    fn content_length(&self) -> Option<usize> {
+        Some(self.segments().map(<[u8]>::len).sum())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut skip = self.sent;
+        let mut n = 0;
+        for segment in self.segments() {
+            if skip >= segment.len() {
+                skip -= segment.len();
+                continue;
+            }
+            let rest = &segment[skip..];
+            skip = 0;
+            let len = rest.len().min(buf.len() - n);
+            buf[n..n + len].copy_from_slice(&rest[..len]);
+            n += len;
+            if n == buf.len() {
+                break;
+            }
+        }
+        self.sent += n;
+        Ok(n)
+    }
+}
+
+/// Returns the boundary tried after `attempts` others.
+fn boundary(attempts: u32) -> String {
+    format!("nostd-rpc-form-boundary-{:08x}", attempts)
+}
+
+/// Escapes a field name or file name for a quoted `Content-Disposition` parameter, as
+/// browsers do.
+fn escape(value: &str) -> String {
+    value
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Returns whether `needle` occurs in `haystack`.
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
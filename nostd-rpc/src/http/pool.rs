@@ -0,0 +1,244 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::task::Poll;
+
+use smoltcp::iface::{Interface, SocketHandle, SocketSet};
+use smoltcp::phy::Device;
+use smoltcp::socket::tcp;
+use smoltcp::time::Instant;
+
+use super::transaction::Exchange;
+use super::{HttpRequest, HttpResponse, Progress};
+use crate::hooks::Hooks;
+use crate::net::LocalPorts;
+use crate::Error;
+
+/// Identifies a request sent with [`ClientPool::send`] or
+/// [`TransactionSet::register`](super::TransactionSet::register).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RequestId(u32);
+
+/// A request in progress, with the socket it is sent over.
+struct Outstanding<'a> {
+    id: RequestId,
+    request: HttpRequest,
+    exchange: Exchange<'a>,
+}
+
+/// Requests in progress at once, each over a TCP socket of its own in a shared socket set.
+pub(super) struct Requests<'a> {
+    sockets: SocketSet<'a>,
+    outstanding: Vec<Outstanding<'a>>,
+    /// The ID of the next request.
+    next_id: u32,
+    /// Chooses the local port of each connection.
+    pub(super) ports: LocalPorts,
+}
+
+impl<'a> Requests<'a> {
+    pub(super) fn new() -> Self {
+        Requests {
+            sockets: SocketSet::new(vec![]),
+            outstanding: Vec::new(),
+            next_id: 0,
+            ports: LocalPorts::new([0; 8]),
+        }
+    }
+
+    /// Starts sending `request` at `now` over a new socket with the given buffers.
+    pub(super) fn add(
+        &mut self,
+        request: HttpRequest,
+        rx_buffer: tcp::SocketBuffer<'a>,
+        tx_buffer: tcp::SocketBuffer<'a>,
+        now: Instant,
+    ) -> RequestId {
+        let id = RequestId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        let remote = request.next_hop().0;
+        let exchange = Exchange::new(
+            &mut self.sockets,
+            (remote, &mut self.ports),
+            &request,
+            Progress::new(now),
+            rx_buffer,
+            tx_buffer,
+        );
+        debug!("request {} started", id.0);
+        self.outstanding.push(Outstanding {
+            id,
+            request,
+            exchange,
+        });
+        id
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.outstanding.len()
+    }
+
+    /// Abandons the request `id`, resetting its connection. Returns false if it is not in
+    /// progress.
+    pub(super) fn cancel<D: Device>(
+        &mut self,
+        iface: &mut Interface,
+        device: &mut D,
+        id: RequestId,
+        now: Instant,
+    ) -> bool {
+        let Some(index) = self.outstanding.iter().position(|o| o.id == id) else {
+            return false;
+        };
+        let handle = self.outstanding.remove(index).exchange.handle();
+        self.release(iface, device, handle, now);
+        true
+    }
+
+    /// Advances the requests in progress, starting with the one at `index`, until one
+    /// completes. Returns the index it was at and its response.
+    fn advance<D: Device>(
+        &mut self,
+        iface: &mut Interface,
+        device: &mut D,
+        mut index: usize,
+        now: Instant,
+    ) -> Option<(usize, RequestId, Result<HttpResponse, Error>)> {
+        while index < self.outstanding.len() {
+            let Outstanding {
+                request, exchange, ..
+            } = &mut self.outstanding[index];
+            let step = exchange.step(
+                iface,
+                device,
+                &mut self.sockets,
+                now,
+                request,
+                &mut Hooks::new(),
+            );
+            let result = match step {
+                Poll::Pending => {
+                    index += 1;
+                    continue;
+                }
+                Poll::Ready(Ok(())) => exchange.progress.parse(request),
+                Poll::Ready(Err(error)) => Err(exchange.progress.fail(error, now)),
+            };
+            let outstanding = self.outstanding.remove(index);
+            self.release(iface, device, outstanding.exchange.handle(), now);
+            debug!("request {} finished", outstanding.id.0);
+            return Some((index, outstanding.id, result));
+        }
+        None
+    }
+
+    /// Advances every request in progress, returning the first response to complete.
+    pub(super) fn poll<D: Device>(
+        &mut self,
+        iface: &mut Interface,
+        device: &mut D,
+        now: Instant,
+    ) -> Option<(RequestId, Result<HttpResponse, Error>)> {
+        let (_, id, result) = self.advance(iface, device, 0, now)?;
+        Some((id, result))
+    }
+
+    /// Advances every request in progress once, returning the responses that completed in
+    /// the order the requests were added.
+    pub(super) fn poll_all<D: Device>(
+        &mut self,
+        iface: &mut Interface,
+        device: &mut D,
+        now: Instant,
+    ) -> Vec<(RequestId, Result<HttpResponse, Error>)> {
+        let mut completed = Vec::new();
+        let mut index = 0;
+        while let Some((next, id, result)) = self.advance(iface, device, index, now) {
+            completed.push((id, result));
+            index = next;
+        }
+        completed
+    }
+
+    /// Resets the connection of the socket `handle` at `now` if it is still open, then
+    /// removes the socket.
+    fn release<D: Device>(
+        &mut self,
+        iface: &mut Interface,
+        device: &mut D,
+        handle: SocketHandle,
+        now: Instant,
+    ) {
+        self.sockets.get_mut::<tcp::Socket>(handle).abort();
+        iface.poll(now, device, &mut self.sockets);
+        self.sockets.remove(handle);
+    }
+}
+
+/// Sends requests to several servers at once over an interface it owns, each over a TCP
+/// socket of its own in a shared socket set, so that they are all driven from one poll loop.
+///
+/// Host names are not resolved: each request is sent to its IP address, see
+/// [`dns::resolve`](crate::dns::resolve) to look one up first, as a request without one fails
+/// with [`Error::UnresolvedHost`]. Each socket is removed once its response is complete.
+pub struct ClientPool<D: Device> {
+    device: D,
+    iface: Interface,
+    requests: Requests<'static>,
+}
+
+impl<D: Device> ClientPool<D> {
+    /// Constructs a pool sending requests over `iface` and `device`, which must already have
+    /// their address and routes configured.
+    pub fn new(device: D, iface: Interface) -> Self {
+        ClientPool {
+            device,
+            iface,
+            requests: Requests::new(),
+        }
+    }
+
+    /// Seeds the choice of local ports with `random`, which should come from a random number
+    /// generator, see [`LocalPorts`].
+    pub fn local_ports(mut self, random: [u8; 8]) -> Self {
+        self.requests.ports = LocalPorts::new(random);
+        self
+    }
+
+    /// Starts sending `request` at `now` over a new socket, returning the ID its response is
+    /// returned with by [`ClientPool::poll`].
+    pub fn send(&mut self, request: HttpRequest, now: Instant) -> RequestId {
+        let (rx_buffer, tx_buffer) = request.socket_buffers(&self.device.capabilities());
+        self.requests.add(request, rx_buffer, tx_buffer, now)
+    }
+
+    /// Returns the number of requests whose responses have not been returned yet.
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Returns whether there are no requests in progress.
+    pub fn is_empty(&self) -> bool {
+        self.requests.len() == 0
+    }
+
+    /// Abandons the request `id`, resetting its connection. Returns false if it is not in
+    /// progress.
+    pub fn cancel(&mut self, id: RequestId, now: Instant) -> bool {
+        self.requests
+            .cancel(&mut self.iface, &mut self.device, id, now)
+    }
+
+    /// Polls the interface and advances every request in progress, returning the first
+    /// response to complete along with the ID of its request.
+    ///
+    /// Call it again until it returns `None` to collect the responses of requests that
+    /// complete together.
+    pub fn poll(&mut self, now: Instant) -> Option<(RequestId, Result<HttpResponse, Error>)> {
+        self.requests.poll(&mut self.iface, &mut self.device, now)
+    }
+
+    /// Returns the interface the pool sends requests over.
+    pub fn interface(&mut self) -> &mut Interface {
+        &mut self.iface
+    }
+}
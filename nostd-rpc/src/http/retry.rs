@@ -0,0 +1,85 @@
+use smoltcp::time::Duration;
+
+use crate::Error;
+
+/// How long to wait before each retry of a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backoff {
+    /// Waits the same time before every retry.
+    Fixed(Duration),
+    /// Waits `initial` before the first retry, doubling the wait before each further retry
+    /// up to `max`.
+    Exponential {
+        /// The wait before the first retry.
+        initial: Duration,
+        /// The longest wait.
+        max: Duration,
+    },
+}
+
+/// Retries a request that failed connecting, sending or receiving, e.g. because the
+/// network dropped out briefly.
+///
+/// The default policy makes a single attempt. A request whose body is streamed is not
+/// retried, since the body cannot be sent or received again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u8,
+    backoff: Backoff,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(1, Backoff::Fixed(Duration::ZERO))
+    }
+}
+
+impl RetryPolicy {
+    /// Constructs a policy making up to `max_attempts` attempts in all, waiting as given by
+    /// `backoff` between them.
+    pub fn new(max_attempts: u8, backoff: Backoff) -> Self {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            backoff,
+        }
+    }
+
+    /// Returns the number of attempts made at most, including the first.
+    pub fn max_attempts(&self) -> u8 {
+        self.max_attempts
+    }
+
+    /// Returns whether another attempt is made after attempt number `attempt`, counting
+    /// from 1, failed with `error`.
+    pub fn retries(&self, attempt: u8, error: &Error) -> bool {
+        attempt < self.max_attempts && is_transient(error.root())
+    }
+
+    /// Returns the wait before retry number `retry`, counting from 1.
+    pub fn delay(&self, retry: u8) -> Duration {
+        match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => {
+                let factor = 1u64 << retry.saturating_sub(1).min(63);
+                let micros = initial.total_micros().saturating_mul(factor);
+                Duration::from_micros(micros).min(max)
+            }
+        }
+    }
+}
+
+/// Returns whether `error` is a connect, send or receive failure that may not happen again.
+fn is_transient(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Connect(_)
+            | Error::ConnectTimeout
+            | Error::ConnectionRefused
+            | Error::ConnectionClosed
+            | Error::ConnectionReset
+            | Error::SendFailed(_)
+            | Error::SendTimeout
+            | Error::RecvFailed(_)
+            | Error::ResponseTimeout
+    )
+}
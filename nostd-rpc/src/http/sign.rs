@@ -0,0 +1,132 @@
+//! Signing of requests, for APIs that authenticate each request with a signature header.
+
+#[cfg(feature = "crypto")]
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{Header, HttpRequest};
+use crate::hooks::Hooks;
+#[cfg(feature = "crypto")]
+use crate::{encoding, sha256};
+
+/// The parts of a request that a [`Signer`] signs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SignedParts<'a> {
+    /// Method, e.g. `POST`.
+    pub method: &'a str,
+    /// Host the request is sent to, without the port.
+    pub host: &'a str,
+    /// Path with the query string, starting with `/`.
+    pub path: &'a str,
+    /// Body, empty if the body comes from a [`BodyProvider`](super::BodyProvider).
+    pub body: &'a [u8],
+}
+
+/// Signs requests just before they are serialized, returning the headers carrying the
+/// signature. Set with [`Hooks::signer`], each attempt of a request is signed again.
+pub trait Signer {
+    /// Returns the headers to add to the request with `parts`, as name and value pairs.
+    fn sign(&mut self, parts: &SignedParts<'_>) -> Vec<(String, String)>;
+}
+
+impl<F: FnMut(&SignedParts<'_>) -> Vec<(String, String)>> Signer for F {
+    fn sign(&mut self, parts: &SignedParts<'_>) -> Vec<(String, String)> {
+        self(parts)
+    }
+}
+
+impl HttpRequest {
+    /// Returns the request with the headers of the signer of `hooks` added, or `None` if
+    /// there is no signer.
+    pub(super) fn signed(&self, hooks: &mut Hooks<'_>) -> Option<HttpRequest> {
+        let (host, path) = (self.host_name(), self.path());
+        let parts = SignedParts {
+            method: &self.method,
+            host: &host,
+            path: &path,
+            body: &self.body,
+        };
+        let headers = hooks.sign(&parts)?;
+        Some(headers.iter().fold(self.clone(), |request, (name, value)| {
+            request.header(Header::Custom(name, value))
+        }))
+    }
+}
+
+/// A [`Signer`] adding an HMAC-SHA256 signature over the method, path, timestamp and body of
+/// each request.
+///
+/// The signed message is the method, path, timestamp and body joined by newlines, and the
+/// signature is sent as lowercase hexadecimal in the `X-Signature` header, with the timestamp,
+/// in seconds since the Unix epoch, in `X-Timestamp`. APIs with another canonical form can
+/// implement [`Signer`] with [`hmac_sha256`].
+#[cfg(feature = "crypto")]
+pub struct HmacSigner<T: FnMut() -> u64> {
+    key: Vec<u8>,
+    /// Returns the current time in seconds since the Unix epoch.
+    now: T,
+    signature_header: String,
+    timestamp_header: String,
+}
+
+#[cfg(feature = "crypto")]
+impl<T: FnMut() -> u64> HmacSigner<T> {
+    /// Constructs a signer with the shared secret `key`, reading the time from `now`.
+    pub fn new(key: &[u8], now: T) -> Self {
+        HmacSigner {
+            key: key.into(),
+            now,
+            signature_header: String::from("X-Signature"),
+            timestamp_header: String::from("X-Timestamp"),
+        }
+    }
+
+    /// Sets the names of the signature and timestamp headers.
+    pub fn header_names(mut self, signature: &str, timestamp: &str) -> Self {
+        self.signature_header = signature.into();
+        self.timestamp_header = timestamp.into();
+        self
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl<T: FnMut() -> u64> Signer for HmacSigner<T> {
+    fn sign(&mut self, parts: &SignedParts<'_>) -> Vec<(String, String)> {
+        let timestamp = format!("{}", (self.now)());
+        let mut message = Vec::new();
+        for part in [
+            parts.method.as_bytes(),
+            parts.path.as_bytes(),
+            timestamp.as_bytes(),
+        ] {
+            message.extend_from_slice(part);
+            message.push(b'\n');
+        }
+        message.extend_from_slice(parts.body);
+        let signature = encoding::encode_hex(&hmac_sha256(&self.key, &message));
+        alloc::vec![
+            (self.signature_header.clone(), signature),
+            (self.timestamp_header.clone(), timestamp),
+        ]
+    }
+}
+
+/// Returns the HMAC-SHA256 (RFC 2104) of `message` with `key`.
+#[cfg(feature = "crypto")]
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = sha256::Sha256::new();
+    inner.update(&block.map(|b| b ^ 0x36));
+    inner.update(message);
+    let mut outer = sha256::Sha256::new();
+    outer.update(&block.map(|b| b ^ 0x5c));
+    outer.update(&inner.finalize());
+    outer.finalize()
+}
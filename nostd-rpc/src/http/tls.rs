@@ -0,0 +1,21 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::server::TlsSession;
+use crate::Error;
+
+/// Creates a [`TlsSession`] for each connection an [`HttpClient`](super::HttpClient) makes
+/// to an HTTPS server, usually holding the trusted root certificates.
+///
+/// This crate does not implement TLS itself, a connector wraps a TLS implementation such as
+/// `embedded-tls`.
+pub trait TlsConnector {
+    /// Returns a session for a new connection to `server_name`, which is sent in the SNI
+    /// extension and checked against the server certificate. The ClientHello is appended to
+    /// `outgoing`.
+    fn connect(
+        &mut self,
+        server_name: &str,
+        outgoing: &mut Vec<u8>,
+    ) -> Result<Box<dyn TlsSession>, Error>;
+}
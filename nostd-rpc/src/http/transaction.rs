@@ -0,0 +1,700 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::IpAddr;
+use core::task::Poll;
+
+use smoltcp::iface::{Interface, SocketHandle, SocketSet};
+use smoltcp::phy::Device;
+use smoltcp::socket::tcp;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::IpAddress;
+
+use super::pool::{RequestId, Requests};
+use super::{idle_delay, HttpVersion, CLOSE_TIMEOUT};
+use super::{report_outcome, BodyProvider, HttpRequest, HttpResponse, Progress};
+use crate::hooks::{Event, Hooks, Transfer};
+use crate::metrics::Timings;
+use crate::net::LocalPorts;
+use crate::response::{BodySink, ResponseParser};
+use crate::{Error, Phase};
+
+/// A request that is sent a step at a time by calling [`HttpTransaction::poll`], e.g. from a
+/// firmware main loop or a timer interrupt, instead of blocking until it completes.
+///
+/// Host names are not resolved: the request is sent to its IP address, see
+/// [`dns::resolve`](crate::dns::resolve) to look one up first. A request without one fails
+/// with [`Error::UnresolvedHost`].
+pub struct HttpTransaction<'a, D: Device> {
+    iface: &'a mut Interface,
+    device: &'a mut D,
+    sockets: SocketSet<'a>,
+    exchange: Exchange<'a>,
+    request: HttpRequest,
+    hooks: Hooks<'a>,
+    /// Whether the hooks have been told the request started.
+    started: bool,
+    /// Whether the transaction has returned its result.
+    finished: bool,
+}
+
+impl<'a, D: Device> HttpTransaction<'a, D> {
+    /// Constructs a transaction sending `request` over `iface` and `device`, which must
+    /// already have their address and routes configured, starting at `now`.
+    pub fn new(
+        iface: &'a mut Interface,
+        device: &'a mut D,
+        request: HttpRequest,
+        now: Instant,
+    ) -> Self {
+        let (rx_buffer, tx_buffer) = request.socket_buffers(&device.capabilities());
+        HttpTransaction::with_socket_buffers(iface, device, request, rx_buffer, tx_buffer, now)
+    }
+
+    /// Constructs a transaction like [`HttpTransaction::new`], using `rx_buffer` and
+    /// `tx_buffer` as the TCP socket buffers instead of allocating them, whatever buffer
+    /// sizes the request has.
+    pub fn with_buffers(
+        iface: &'a mut Interface,
+        device: &'a mut D,
+        request: HttpRequest,
+        rx_buffer: &'a mut [u8],
+        tx_buffer: &'a mut [u8],
+        now: Instant,
+    ) -> Self {
+        let rx_buffer = tcp::SocketBuffer::new(rx_buffer);
+        let tx_buffer = tcp::SocketBuffer::new(tx_buffer);
+        HttpTransaction::with_socket_buffers(iface, device, request, rx_buffer, tx_buffer, now)
+    }
+
+    fn with_socket_buffers(
+        iface: &'a mut Interface,
+        device: &'a mut D,
+        request: HttpRequest,
+        rx_buffer: tcp::SocketBuffer<'a>,
+        tx_buffer: tcp::SocketBuffer<'a>,
+        now: Instant,
+    ) -> Self {
+        let remote = request.next_hop().0;
+        let progress = Progress::new(now);
+        let mut sockets = SocketSet::new(vec![]);
+        let exchange = Exchange::new(
+            &mut sockets,
+            (remote, &mut request.ports.clone()),
+            &request,
+            progress,
+            rx_buffer,
+            tx_buffer,
+        );
+        HttpTransaction {
+            iface,
+            device,
+            sockets,
+            exchange,
+            request,
+            hooks: Hooks::new(),
+            started: false,
+            finished: false,
+        }
+    }
+
+    /// Passes the response body to `on_body` as it is received instead of keeping it, so the
+    /// response returned has an empty body. Chunked bodies are decoded before they are passed
+    /// on.
+    pub fn on_body(mut self, on_body: &'a mut dyn BodySink) -> Self {
+        self.exchange.stream_body(on_body);
+        self
+    }
+
+    /// Sends the body supplied by `body` instead of the request's own, as the socket's
+    /// transmit buffer drains.
+    pub fn send_body(mut self, body: &'a mut dyn BodyProvider) -> Self {
+        self.exchange.send_body(body);
+        self
+    }
+
+    /// Sets the hooks notified while the request is in progress.
+    pub fn hooks(mut self, hooks: Hooks<'a>) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Polls the interface and advances the request, returning the response once it is
+    /// complete.
+    ///
+    /// Once the transaction has returned its result, further polls fail with
+    /// [`Error::ConnectionClosed`].
+    pub fn poll(&mut self, now: Instant) -> Poll<Result<HttpResponse, Error>> {
+        if self.finished {
+            return Poll::Ready(Err(Error::ConnectionClosed));
+        }
+        if !self.started {
+            self.started = true;
+            self.hooks.request_started();
+        }
+        let HttpTransaction {
+            iface,
+            device,
+            sockets,
+            exchange,
+            request,
+            hooks,
+            ..
+        } = self;
+        let result = match exchange.step(iface, *device, sockets, now, request, hooks) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Ok(())) => self.exchange.progress.parse(&self.request),
+            Poll::Ready(Err(error)) => Err(error),
+        };
+        self.finished = true;
+        let progress = &self.exchange.progress;
+        let result = result
+            .map(|response| (response, progress.timings(now)))
+            .map_err(|error| progress.fail(error, now));
+        report_outcome(now, &mut self.hooks, &result);
+        Poll::Ready(result.map(|(response, _)| response))
+    }
+
+    /// Returns how long the caller can wait at `now` before polling again, zero if the
+    /// request can move on straight away.
+    ///
+    /// The delay is short enough for frames received in the meantime to be handled promptly
+    /// if the device cannot wake the caller.
+    pub fn poll_delay(&mut self, now: Instant) -> Duration {
+        let HttpTransaction {
+            iface,
+            sockets,
+            exchange,
+            request,
+            ..
+        } = self;
+        exchange.poll_delay(iface, sockets, now, request)
+    }
+
+    /// Returns the time taken by each phase of the request so far.
+    pub fn timings(&self, now: Instant) -> Timings {
+        self.exchange.progress.timings(now)
+    }
+}
+
+/// Requests sent at once over one interface, each over a TCP socket of its own in a shared
+/// socket set, and advanced together by [`TransactionSet::poll_all`] so that no request
+/// holds up the others.
+///
+/// Host names are not resolved, as with [`HttpTransaction`].
+pub struct TransactionSet<'a, D: Device> {
+    iface: &'a mut Interface,
+    device: &'a mut D,
+    requests: Requests<'a>,
+}
+
+impl<'a, D: Device> TransactionSet<'a, D> {
+    /// Constructs an empty set sending requests over `iface` and `device`, which must
+    /// already have their address and routes configured.
+    pub fn new(iface: &'a mut Interface, device: &'a mut D) -> Self {
+        TransactionSet {
+            iface,
+            device,
+            requests: Requests::new(),
+        }
+    }
+
+    /// Starts sending `request` at `now`, returning the ID its response is returned with by
+    /// [`TransactionSet::poll_all`].
+    pub fn register(&mut self, request: HttpRequest, now: Instant) -> RequestId {
+        let (rx_buffer, tx_buffer) = request.socket_buffers(&self.device.capabilities());
+        self.requests.add(request, rx_buffer, tx_buffer, now)
+    }
+
+    /// Starts sending `request` like [`TransactionSet::register`], using `rx_buffer` and
+    /// `tx_buffer` as the TCP socket buffers instead of allocating them.
+    pub fn register_with_buffers(
+        &mut self,
+        request: HttpRequest,
+        rx_buffer: &'a mut [u8],
+        tx_buffer: &'a mut [u8],
+        now: Instant,
+    ) -> RequestId {
+        let rx_buffer = tcp::SocketBuffer::new(rx_buffer);
+        let tx_buffer = tcp::SocketBuffer::new(tx_buffer);
+        self.requests.add(request, rx_buffer, tx_buffer, now)
+    }
+
+    /// Returns the number of requests whose responses have not been returned yet.
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Returns whether there are no requests in progress.
+    pub fn is_empty(&self) -> bool {
+        self.requests.len() == 0
+    }
+
+    /// Abandons the request `id`, resetting its connection. Returns false if it is not in
+    /// progress.
+    pub fn cancel(&mut self, id: RequestId, now: Instant) -> bool {
+        self.requests.cancel(self.iface, self.device, id, now)
+    }
+
+    /// Polls the interface and advances every request in progress, returning the responses
+    /// of those that completed, in the order they were registered.
+    pub fn poll_all(&mut self, now: Instant) -> Vec<(RequestId, Result<HttpResponse, Error>)> {
+        self.requests.poll_all(self.iface, self.device, now)
+    }
+}
+
+/// The state of a request sent over a socket of its own.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting until the given time to try the request again.
+    Backoff(Instant),
+    Connect,
+    Request,
+    Write,
+    Response,
+    /// Waiting until the given time for the connection to close after the response.
+    Close(Instant),
+}
+
+/// The bodies of a request and its response when they are streamed rather than kept in
+/// memory.
+#[derive(Default)]
+pub(super) struct Streams<'a> {
+    /// Supplies the request body.
+    pub(super) body: Option<&'a mut dyn BodyProvider>,
+    /// Receives the response body.
+    pub(super) on_body: Option<&'a mut dyn BodySink>,
+}
+
+/// The part of a request still to be sent.
+struct Outgoing<'a> {
+    /// Bytes ready to send.
+    data: Vec<u8>,
+    /// Supplies the rest of the body, until it is finished.
+    body: Option<&'a mut dyn BodyProvider>,
+    chunked: bool,
+    /// The length of the whole request if known.
+    total: Option<usize>,
+    /// Until when the body is held back waiting for the server's `100 Continue`, if it is.
+    expect: Option<Instant>,
+    /// The request's own body while it is held back.
+    held: Vec<u8>,
+}
+
+impl Outgoing<'_> {
+    /// Sends as much of the request as `socket` can take, returning true once all of it is
+    /// sent.
+    fn write(
+        &mut self,
+        socket: &mut tcp::Socket<'_>,
+        timestamp: Instant,
+        progress: &mut Progress,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<bool, Error> {
+        loop {
+            if self.data.is_empty() {
+                self.fill()?;
+                if self.data.is_empty() {
+                    return Ok(true);
+                }
+            }
+            if !socket.can_send() {
+                return Ok(false);
+            }
+            let sent = socket.send_slice(&self.data).map_err(Error::SendFailed)?;
+            if sent == 0 {
+                return Ok(false);
+            }
+            self.data.drain(..sent);
+            progress.sent(sent, timestamp);
+            hooks.bytes_sent(sent);
+            hooks.transfer(Transfer::Upload, progress.bytes_sent, self.total);
+            trace!("sent {} bytes", sent);
+        }
+    }
+
+    /// Whether the body held back should now be sent: the server answered `100 Continue`,
+    /// or did not answer in time. It is never sent once a final response has begun.
+    fn may_resume(&self, parser: &ResponseParser, timestamp: Instant) -> bool {
+        self.expect
+            .is_some_and(|until| parser.continued() || (timestamp >= until && !parser.has_head()))
+    }
+
+    /// Sends the body held back.
+    fn resume(&mut self) {
+        self.expect = None;
+        self.data.append(&mut self.held);
+    }
+
+    /// Reads the next part of the body, framed as a chunk if the body is chunked.
+    fn fill(&mut self) -> Result<(), Error> {
+        if self.expect.is_some() {
+            return Ok(());
+        }
+        let Some(body) = &mut self.body else {
+            return Ok(());
+        };
+        let mut buf = [0; 512];
+        let n = body.read(&mut buf)?;
+        if n == 0 {
+            self.body = None;
+            if self.chunked {
+                self.data.extend_from_slice(b"0\r\n\r\n");
+            }
+        } else if self.chunked {
+            let size = alloc::format!("{:x}\r\n", n);
+            self.data.extend_from_slice(size.as_bytes());
+            self.data.extend_from_slice(&buf[..n]);
+            self.data.extend_from_slice(b"\r\n");
+        } else {
+            self.data.extend_from_slice(&buf[..n]);
+        }
+        Ok(())
+    }
+}
+
+/// The connect, write and read phases of a request, driven by [`Exchange::step`] over a
+/// socket of its own in a socket set that may be shared with other requests.
+pub(super) struct Exchange<'a> {
+    tcp_handle: SocketHandle,
+    /// The address to connect to, `None` if the request has no IP address.
+    remote: Option<IpAddr>,
+    local_port: u16,
+    /// Chooses the local port of each attempt after the first.
+    ports: LocalPorts,
+    state: State,
+    /// Tells when the response is complete. Unless the body is streamed, the response is
+    /// parsed again once received.
+    parser: ResponseParser,
+    /// Receives the body if it is streamed.
+    on_body: Option<&'a mut dyn BodySink>,
+    outgoing: Outgoing<'a>,
+    /// The attempt in progress, counting from 1.
+    attempt: u8,
+    /// Whether the request can be tried again, i.e. neither body is streamed.
+    retryable: bool,
+    pub(super) progress: Progress,
+}
+
+impl<'a> Exchange<'a> {
+    /// Constructs an exchange connecting to `remote` from a local port chosen by `ports`,
+    /// adding its socket to `sockets`. Without a remote address the exchange fails with
+    /// [`Error::UnresolvedHost`].
+    pub(super) fn new(
+        sockets: &mut SocketSet<'a>,
+        (remote, ports): (Option<IpAddr>, &mut LocalPorts),
+        request: &HttpRequest,
+        progress: Progress,
+        rx_buffer: tcp::SocketBuffer<'a>,
+        tx_buffer: tcp::SocketBuffer<'a>,
+    ) -> Self {
+        let tcp_handle = sockets.add(tcp::Socket::new(rx_buffer, tx_buffer));
+        Exchange {
+            tcp_handle,
+            remote,
+            local_port: ports.next(progress.start),
+            ports: ports.clone(),
+            state: State::Connect,
+            parser: request.response_parser(),
+            on_body: None,
+            outgoing: Outgoing {
+                data: Vec::new(),
+                body: None,
+                chunked: false,
+                total: None,
+                expect: None,
+                held: Vec::new(),
+            },
+            attempt: 1,
+            retryable: true,
+            progress,
+        }
+    }
+
+    /// Streams the bodies given in `streams`.
+    pub(super) fn stream(&mut self, streams: Streams<'a>) {
+        if let Some(body) = streams.body {
+            self.send_body(body);
+        }
+        if let Some(on_body) = streams.on_body {
+            self.stream_body(on_body);
+        }
+    }
+
+    /// Sends the body supplied by `body` instead of the request's own.
+    pub(super) fn send_body(&mut self, body: &'a mut dyn BodyProvider) {
+        self.outgoing.body = Some(body);
+        self.retryable = false;
+    }
+
+    /// Passes the response body to `on_body` instead of keeping it.
+    pub(super) fn stream_body(&mut self, on_body: &'a mut dyn BodySink) {
+        self.on_body = Some(on_body);
+        self.retryable = false;
+        self.progress.keep_body = false;
+    }
+
+    /// Returns the handle of the socket in the socket set.
+    pub(super) fn handle(&self) -> SocketHandle {
+        self.tcp_handle
+    }
+
+    /// Polls the interface at `timestamp` and advances `request`, until the response is
+    /// complete or the server closes the connection.
+    pub(super) fn step<D: Device>(
+        &mut self,
+        iface: &mut Interface,
+        device: &mut D,
+        sockets: &mut SocketSet<'a>,
+        timestamp: Instant,
+        request: &HttpRequest,
+        hooks: &mut Hooks<'_>,
+    ) -> Poll<Result<(), Error>> {
+        match self.advance(iface, device, sockets, timestamp, request, hooks) {
+            Ok(true) => Poll::Ready(Ok(())),
+            Ok(false) => Poll::Pending,
+            Err(error) if self.retryable && request.retry.retries(self.attempt, &error) => {
+                let delay = request.retry.delay(self.attempt);
+                self.attempt += 1;
+                warn!(
+                    "request failed: {}, retrying in {} ms",
+                    error,
+                    delay.total_millis()
+                );
+                let attempt = self.attempt;
+                hooks.event(
+                    timestamp,
+                    Event::Retrying {
+                        attempt,
+                        error: &error,
+                    },
+                );
+                sockets.get_mut::<tcp::Socket>(self.tcp_handle).abort();
+                iface.poll(timestamp, device, sockets);
+                self.state = State::Backoff(timestamp + delay);
+                Poll::Pending
+            }
+            Err(error) => Poll::Ready(Err(error)),
+        }
+    }
+
+    /// Returns how long to wait at `timestamp` before stepping the exchange again: zero if it
+    /// can move on without waiting for the server, otherwise until the interface has to be
+    /// polled or the request times out.
+    pub(super) fn poll_delay(
+        &self,
+        iface: &mut Interface,
+        sockets: &SocketSet<'a>,
+        timestamp: Instant,
+        request: &HttpRequest,
+    ) -> Duration {
+        let socket = sockets.get::<tcp::Socket>(self.tcp_handle);
+        let ready = match self.state {
+            State::Connect => true,
+            State::Request => socket.may_send(),
+            State::Write => socket.can_send(),
+            State::Response => socket.can_recv() || !socket.may_recv(),
+            State::Backoff(_) | State::Close(_) => false,
+        };
+        if ready {
+            return Duration::ZERO;
+        }
+        let until = match self.state {
+            State::Backoff(until) | State::Close(until) => Some(until),
+            _ => self.progress.deadline(request),
+        };
+        let until = match (until, self.outgoing.expect) {
+            (Some(until), Some(expect)) => Some(until.min(expect)),
+            (until, expect) => until.or(expect),
+        };
+        idle_delay(iface, sockets, timestamp, until)
+    }
+
+    /// Advances `request` as [`Exchange::step`] does, returning true once it is finished.
+    fn advance<D: Device>(
+        &mut self,
+        iface: &mut Interface,
+        device: &mut D,
+        sockets: &mut SocketSet<'a>,
+        timestamp: Instant,
+        request: &HttpRequest,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<bool, Error> {
+        let Exchange {
+            tcp_handle,
+            remote,
+            local_port,
+            ports,
+            state,
+            parser,
+            on_body,
+            outgoing,
+            progress,
+            ..
+        } = self;
+        progress.poll(iface, device, sockets, *local_port, timestamp, hooks);
+
+        let socket = sockets.get_mut::<tcp::Socket>(*tcp_handle);
+        progress.observe(socket);
+        let cx = iface.context();
+
+        *state = match *state {
+            State::Backoff(until) if timestamp < until => return Ok(false),
+            State::Backoff(_) => {
+                *progress = Progress::new(timestamp);
+                *local_port = ports.next(timestamp);
+                *parser = request.response_parser();
+                outgoing.data.clear();
+                outgoing.expect = None;
+                outgoing.held.clear();
+                State::Connect
+            }
+            State::Connect => {
+                request.check_headers()?;
+                if request.tls {
+                    warn!("TLS requests need an HttpClient with a TlsConnector");
+                    return Err(Error::Tls);
+                }
+                let Some(remote) = *remote else {
+                    warn!("no IP address for {}", request.host.as_str());
+                    return Err(Error::UnresolvedHost);
+                };
+                if !socket.is_active() {
+                    let port = request.next_hop().1;
+                    socket
+                        .connect(cx, (IpAddress::from(remote), port), *local_port)
+                        .map_err(Error::Connect)?;
+                    progress.connecting = Some(timestamp);
+                    debug!("connecting to port {}", port);
+                    hooks.event(timestamp, Event::Connecting);
+                    State::Request
+                } else {
+                    State::Connect
+                }
+            }
+            State::Request => {
+                if socket.may_send() {
+                    progress.connected = Some(timestamp);
+                    debug!("connected, sending request");
+                    hooks.event(timestamp, Event::Connected);
+                    progress.enter(Phase::Write, timestamp);
+                    let signed = request.signed(hooks);
+                    let request = signed.as_ref().unwrap_or(request);
+                    let mut length = Some(request.body.len());
+                    let mut message = match &outgoing.body {
+                        Some(body) => {
+                            length = body.content_length();
+                            if length.is_none() && request.version == HttpVersion::Http10 {
+                                warn!("HTTP/1.0 request body without a length");
+                                return Err(Error::LengthRequired);
+                            }
+                            outgoing.chunked = length.is_none();
+                            let head = request.construct_head("close", length);
+                            outgoing.total = length.map(|length| head.len() + length);
+                            head.into_bytes()
+                        }
+                        None => {
+                            let message = request.construct("close");
+                            outgoing.total = Some(message.len());
+                            message
+                        }
+                    };
+                    if let Some(wait) = request.expect_continue {
+                        if request.expects_continue(length) {
+                            debug!("waiting for 100 Continue before the body");
+                            outgoing.expect = Some(timestamp + wait);
+                            if outgoing.body.is_none() {
+                                outgoing.held =
+                                    message.split_off(message.len() - request.body.len());
+                            }
+                        }
+                    }
+                    outgoing.data = message;
+                    Self::write(socket, outgoing, timestamp, progress, hooks)?
+                } else {
+                    State::Request
+                }
+            }
+            State::Write => Self::write(socket, outgoing, timestamp, progress, hooks)?,
+            State::Response if outgoing.may_resume(parser, timestamp) => {
+                debug!("sending the body");
+                outgoing.resume();
+                progress.enter(Phase::Write, timestamp);
+                Self::write(socket, outgoing, timestamp, progress, hooks)?
+            }
+            State::Response if socket.can_recv() => {
+                let parsed = socket
+                    .recv(|data| {
+                        progress.receive(data, timestamp, request, hooks);
+                        let parsed = match on_body {
+                            Some(on_body) => parser.feed_streaming(data, *on_body),
+                            None => parser.feed(data),
+                        };
+                        (data.len(), parsed)
+                    })
+                    .map_err(Error::RecvFailed)?;
+                progress.check_size(request)?;
+                let parsed = match parsed {
+                    // A streamed body is not kept to be parsed again.
+                    Ok(Some(response)) if on_body.is_some() => {
+                        progress.parsed = Some(response);
+                        Ok(Some(()))
+                    }
+                    Ok(response) => Ok(response.map(|_| ())),
+                    Err(error) if on_body.is_some() => return Err(error),
+                    Err(error) => Err(error),
+                };
+                // A response that cannot be parsed is read until the server closes the
+                // connection, and the error reported then.
+                if let Ok(Some(())) = parsed {
+                    debug!("response complete");
+                    socket.close();
+                    State::Close(timestamp + CLOSE_TIMEOUT)
+                } else {
+                    State::Response
+                }
+            }
+            // A reset leaves the socket closed, while a FIN leaves it waiting for our own.
+            State::Response if !socket.may_recv() && socket.state() == tcp::State::Closed => {
+                warn!("connection reset by server");
+                return Err(Error::ConnectionReset);
+            }
+            State::Response if !socket.may_recv() => {
+                debug!("connection closed by server");
+                if on_body.is_some() {
+                    progress.parsed = Some(parser.finish()?);
+                }
+                socket.close();
+                State::Close(timestamp + CLOSE_TIMEOUT)
+            }
+            State::Response => State::Response,
+            // The response is complete, so the request no longer times out.
+            State::Close(until) if socket.is_open() && timestamp < until => return Ok(false),
+            State::Close(_) => {
+                if socket.is_open() {
+                    debug!("connection not closed in time, resetting it");
+                    socket.abort();
+                    iface.poll(timestamp, device, sockets);
+                }
+                return Ok(true);
+            }
+        };
+        progress.check_timeout(request, timestamp)?;
+        Ok(false)
+    }
+
+    /// Sends what `socket` can take of `outgoing`, returning the state to continue in.
+    fn write(
+        socket: &mut tcp::Socket<'_>,
+        outgoing: &mut Outgoing<'_>,
+        timestamp: Instant,
+        progress: &mut Progress,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<State, Error> {
+        if outgoing.write(socket, timestamp, progress, hooks)? {
+            progress.enter(Phase::Read, timestamp);
+            Ok(State::Response)
+        } else {
+            Ok(State::Write)
+        }
+    }
+}
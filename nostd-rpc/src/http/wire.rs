@@ -0,0 +1,218 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{HttpRequest, HttpVersion, DEFAULT_HOST};
+use crate::response;
+
+impl HttpRequest {
+    /// Manually construct the HTTP request as a string. A body that is not UTF-8 is converted
+    /// lossily, but sent as it is.
+    pub fn construct_http_request(&self) -> String {
+        String::from_utf8_lossy(&self.construct("close")).into_owned()
+    }
+
+    /// Constructs the request with `connection` as the value of the `Connection` header,
+    /// unless the request has its own.
+    pub(super) fn construct(&self, connection: &str) -> Vec<u8> {
+        let mut request = self
+            .construct_head(connection, Some(self.body.len()))
+            .into_bytes();
+        request.extend_from_slice(&self.body);
+        request
+    }
+
+    /// Constructs the request line and headers, ending with the blank line. The body is sent
+    /// with a `Content-Length` header of `length` if known, otherwise chunked. An empty body
+    /// is not announced for methods like GET that do not expect one.
+    pub(super) fn construct_head(&self, connection: &str, length: Option<usize>) -> String {
+        let mut request = String::new();
+
+        if let Some(template) = self.template {
+            request.push_str(&String::from_utf8_lossy(template));
+        } else {
+            self.push_request_line(&mut request);
+        }
+
+        for header in &self.headers {
+            request.push_str(header);
+            request.push_str("\r\n");
+        }
+        self.push_credentials(&mut request);
+        // Through a tunnel the proxy only sees the CONNECT request.
+        if !self.tls {
+            self.push_proxy_auth(&mut request);
+        }
+
+        match length {
+            Some(0) if self.template.is_none() && !super::expects_body(&self.method) => {}
+            Some(length) => {
+                request.push_str("Content-Length: ");
+                request.push_str(&length.to_string());
+                request.push_str("\r\n");
+            }
+            None => request.push_str("Transfer-Encoding: chunked\r\n"),
+        }
+        if self.expects_continue(length) {
+            request.push_str("Expect: 100-continue\r\n");
+        }
+        if self.header_value("connection").is_none() {
+            request.push_str("Connection: ");
+            request.push_str(connection);
+            request.push_str("\r\n");
+        }
+
+        request.push_str("\r\n");
+        request
+    }
+
+    /// Whether a body of `length` waits for the server's `100 Continue`.
+    pub(super) fn expects_continue(&self, length: Option<usize>) -> bool {
+        self.expect_continue.is_some() && self.version == HttpVersion::Http11 && length != Some(0)
+    }
+
+    /// Appends the request line and `Host` header to `request`.
+    fn push_request_line(&self, request: &mut String) {
+        request.push_str(&self.method);
+        request.push(' ');
+
+        // A forward proxy is sent the absolute URL (RFC 9112 section 3.2.2).
+        if self.proxy.is_some() && !self.tls {
+            request.push_str("http://");
+            request.push_str(&self.host_header());
+        }
+        request.push_str(&self.path());
+        request.push_str(match self.version {
+            HttpVersion::Http10 => " HTTP/1.0\r\n",
+            HttpVersion::Http11 => " HTTP/1.1\r\n",
+        });
+        request.push_str("Host: ");
+        request.push_str(&self.host_header());
+        request.push_str("\r\n");
+    }
+
+    /// Returns the path and query of the request, starting with '/'.
+    pub(crate) fn path(&self) -> String {
+        let mut path = String::new();
+        if !self.url.starts_with('/') {
+            path.push('/');
+        }
+        path.push_str(&self.url);
+        path
+    }
+
+    /// Constructs the `CONNECT` request asking the proxy for a tunnel to the server.
+    pub(super) fn construct_connect(&self) -> String {
+        let mut authority = self.host_name();
+        authority.push(':');
+        authority.push_str(&self.port.to_string());
+
+        let mut request = String::from("CONNECT ");
+        request.push_str(&authority);
+        request.push_str(" HTTP/1.1\r\nHost: ");
+        request.push_str(&authority);
+        request.push_str("\r\n");
+        self.push_proxy_auth(&mut request);
+        request.push_str("\r\n");
+        request
+    }
+
+    /// Constructs the `GET` request asking the server to upgrade the connection to a
+    /// WebSocket, with `key` as the `Sec-WebSocket-Key` (RFC 6455 section 4.1). The method,
+    /// template and body of the request are not used.
+    pub(crate) fn construct_upgrade(&self, key: &str) -> String {
+        let mut request = String::from("GET ");
+        request.push_str(&self.path());
+        request.push_str(" HTTP/1.1\r\nHost: ");
+        request.push_str(&self.host_header());
+        request.push_str("\r\n");
+        for header in &self.headers {
+            request.push_str(header);
+            request.push_str("\r\n");
+        }
+        self.push_credentials(&mut request);
+        request.push_str("Upgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: ");
+        request.push_str(key);
+        request.push_str("\r\nSec-WebSocket-Version: 13\r\n\r\n");
+        request
+    }
+
+    /// Returns whether the header called `name` carries credentials.
+    fn is_credential(&self, name: &str) -> bool {
+        response::is_sensitive_header(name)
+            || (self.api_key.as_ref()).is_some_and(|(key, _)| key.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Appends the `Authorization` and API key headers to `request`, if set.
+    fn push_credentials(&self, request: &mut String) {
+        if let Some(authorization) = &self.authorization {
+            request.push_str("Authorization: ");
+            request.push_str(authorization);
+            request.push_str("\r\n");
+        }
+        if let Some((name, key)) = &self.api_key {
+            request.push_str(name);
+            request.push_str(": ");
+            request.push_str(key);
+            request.push_str("\r\n");
+        }
+    }
+
+    /// Appends the `Proxy-Authorization` header to `request` if the proxy has credentials.
+    fn push_proxy_auth(&self, request: &mut String) {
+        if let (Some(_), Some(credentials)) = (self.proxy, &self.proxy_auth) {
+            request.push_str("Proxy-Authorization: Basic ");
+            request.push_str(credentials);
+            request.push_str("\r\n");
+        }
+    }
+
+    /// Returns the value of the `Host` header: the host name, or the IP address if no host
+    /// was set, followed by the port unless it is the default for the scheme (RFC 9112
+    /// section 3.2).
+    pub(super) fn host_header(&self) -> String {
+        let mut value = self.host_name();
+        let default_port = if self.tls { 443 } else { 80 };
+        if self.port != default_port {
+            value.push(':');
+            value.push_str(&self.port.to_string());
+        }
+        value
+    }
+
+    /// Returns the host name, or the IP address if no host was set, with an IPv6 address
+    /// enclosed in brackets as in URLs.
+    pub(crate) fn host_name(&self) -> String {
+        let host = match (self.host.is_empty(), self.ip) {
+            (true, Some(ip)) => ip.to_string(),
+            (true, None) => String::from(DEFAULT_HOST),
+            (false, _) => self.host.clone(),
+        };
+        if host.contains(':') {
+            let mut bracketed = String::from("[");
+            bracketed.push_str(&host);
+            bracketed.push(']');
+            bracketed
+        } else {
+            host
+        }
+    }
+}
+
+impl fmt::Display for HttpRequest {
+    /// Formats the request exactly as it is sent, except that the values of headers carrying
+    /// credentials, including the API key, are redacted.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let request = self.construct_http_request();
+        let (head, body) = request.split_once("\r\n\r\n").unwrap_or((&request, ""));
+        for line in head.split("\r\n") {
+            match line.split_once(':') {
+                Some((name, _)) if self.is_credential(name) => {
+                    write!(f, "{}: {}\r\n", name, response::REDACTED)?
+                }
+                _ => write!(f, "{}\r\n", line)?,
+            }
+        }
+        write!(f, "\r\n{}", body)
+    }
+}
@@ -0,0 +1,126 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::Error;
+
+const BASE: u32 = 36;
+const TMIN: u32 = 1;
+const TMAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+/// The longest DNS label, in bytes.
+const MAX_LABEL_LEN: usize = 63;
+
+/// Converts a host name to its ASCII form for DNS and the `Host` header, converting each
+/// Unicode label to a punycode A-label (`bücher.example` becomes `xn--bcher-kva.example`).
+///
+/// Labels are lowercased, and the ideographic full stops are accepted as label separators.
+/// Full UTS #46 mapping and normalisation is not performed, so input should already be in
+/// NFC. IPv6 literals are returned unchanged. Returns [`Error::InvalidHost`] for empty or
+/// overlong labels.
+pub fn to_ascii(host: &str) -> Result<String, Error> {
+    if host.contains(':') {
+        return Ok(String::from(host));
+    }
+    let host = host
+        .strip_suffix(['.', '\u{3002}', '\u{ff0e}', '\u{ff61}'])
+        .unwrap_or(host);
+
+    let mut ascii = String::with_capacity(host.len());
+    for (i, label) in host
+        .split(['.', '\u{3002}', '\u{ff0e}', '\u{ff61}'])
+        .enumerate()
+    {
+        if i > 0 {
+            ascii.push('.');
+        }
+        let start = ascii.len();
+        if label.is_ascii() {
+            ascii.push_str(&label.to_ascii_lowercase());
+        } else {
+            let label: String = label.chars().flat_map(char::to_lowercase).collect();
+            ascii.push_str("xn--");
+            ascii.push_str(&punycode_encode(&label).ok_or(Error::InvalidHost)?);
+        }
+        if ascii.len() == start || ascii.len() - start > MAX_LABEL_LEN {
+            return Err(Error::InvalidHost);
+        }
+    }
+    Ok(ascii)
+}
+
+/// Encodes `input` with punycode (RFC 3492), without the `xn--` prefix.
+///
+/// Returns `None` if the encoding overflows, which only happens for very long input.
+pub fn punycode_encode(input: &str) -> Option<String> {
+    let code_points: Vec<u32> = input.chars().map(u32::from).collect();
+    let mut output: String = input.chars().filter(char::is_ascii).collect();
+    let basic = u32::try_from(output.len()).ok()?;
+    if basic > 0 {
+        output.push('-');
+    }
+
+    let mut n = INITIAL_N;
+    let mut delta = 0u32;
+    let mut bias = INITIAL_BIAS;
+    let mut handled = basic;
+    while (handled as usize) < code_points.len() {
+        let m = code_points.iter().copied().filter(|&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+        n = m;
+        for &c in &code_points {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = if k <= bias {
+                        TMIN
+                    } else if k >= bias + TMAX {
+                        TMAX
+                    } else {
+                        k - bias
+                    };
+                    if q < t {
+                        break;
+                    }
+                    output.push(encode_digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(encode_digit(q));
+                bias = adapt(delta, handled + 1, handled == basic);
+                delta = 0;
+                handled += 1;
+            }
+        }
+        delta = delta.checked_add(1)?;
+        n += 1;
+    }
+    Some(output)
+}
+
+/// Adapts the bias after each encoded code point (RFC 3492 section 6.1).
+fn adapt(delta: u32, num_points: u32, first_time: bool) -> u32 {
+    let mut delta = if first_time { delta / DAMP } else { delta / 2 };
+    delta += delta / num_points;
+    let mut k = 0;
+    while delta > ((BASE - TMIN) * TMAX) / 2 {
+        delta /= BASE - TMIN;
+        k += BASE;
+    }
+    k + (BASE - TMIN + 1) * delta / (delta + SKEW)
+}
+
+/// Returns the basic code point for a digit value `0..36`.
+fn encode_digit(digit: u32) -> char {
+    match digit {
+        0..=25 => char::from(b'a' + digit as u8),
+        _ => char::from(b'0' + (digit - 26) as u8),
+    }
+}
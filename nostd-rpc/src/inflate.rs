@@ -0,0 +1,368 @@
+//! DEFLATE decompression (RFC 1951), with the zlib (RFC 1950) and gzip (RFC 1952) wrappers
+//! used by the `deflate` and `gzip` content codings of HTTP responses.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::response::ParseError;
+use crate::Error;
+
+/// Base lengths of the length symbols 257 to 285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+/// Extra bits of the length symbols 257 to 285.
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distances of the distance symbols.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+/// Extra bits of the distance symbols.
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order code length code lengths are sent in by dynamic blocks.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+const MAX_BITS: usize = 15;
+
+/// Decompresses a gzip member of at most `limit` bytes, checking its CRC-32 and length.
+///
+/// Fails with [`Error::ResponseTooLarge`] if it is larger, or with
+/// [`ParseError::Encoding`] if `data` is not valid gzip.
+pub(crate) fn gunzip(data: &[u8], limit: usize) -> Result<Vec<u8>, Error> {
+    let mut limit = Limit::new(limit);
+    gunzip_within(data, &mut limit).ok_or_else(|| limit.error())
+}
+
+fn gunzip_within(data: &[u8], limit: &mut Limit) -> Option<Vec<u8>> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    let (header, mut rest) = data.split_at_checked(10)?;
+    if header[..3] != [0x1f, 0x8b, 8] {
+        return None;
+    }
+    let flags = header[3];
+    if flags & FEXTRA != 0 {
+        let len = usize::from(u16::from_le_bytes([*rest.first()?, *rest.get(1)?]));
+        rest = rest.get(2 + len..)?;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = rest.iter().position(|&b| b == 0)?;
+            rest = &rest[end + 1..];
+        }
+    }
+    if flags & FHCRC != 0 {
+        rest = rest.get(2..)?;
+    }
+    let (out, used) = inflate(rest, limit)?;
+    let trailer = rest.get(used..used + 8)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let len = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    (crc == crc32(&out) && len == out.len() as u32).then_some(out)
+}
+
+/// Decompresses at most `limit` bytes of zlib data, checking its Adler-32 checksum, or raw
+/// DEFLATE data as sent by some servers for the `deflate` coding.
+///
+/// Fails like [`gunzip`] if the data is larger or is neither.
+pub(crate) fn zlib_or_raw(data: &[u8], limit: usize) -> Result<Vec<u8>, Error> {
+    let mut limit = Limit::new(limit);
+    zlib_or_raw_within(data, &mut limit).ok_or_else(|| limit.error())
+}
+
+fn zlib_or_raw_within(data: &[u8], limit: &mut Limit) -> Option<Vec<u8>> {
+    const FDICT: u8 = 0x20;
+
+    let zlib = match data {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    };
+    if !zlib {
+        return inflate(data, limit).map(|(out, _)| out);
+    }
+    if data[1] & FDICT != 0 {
+        return None;
+    }
+    let (out, used) = inflate(&data[2..], limit)?;
+    let trailer = data.get(2 + used..2 + used + 4)?;
+    let checksum = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    (checksum == adler32(&out)).then_some(out)
+}
+
+/// The largest size decompressed data may reach, so that a small body cannot expand to
+/// exhaust memory.
+struct Limit {
+    max: usize,
+    /// Whether the data was larger.
+    exceeded: bool,
+}
+
+impl Limit {
+    fn new(max: usize) -> Self {
+        Limit {
+            max,
+            exceeded: false,
+        }
+    }
+
+    /// Returns `None` if `len` bytes exceed the limit.
+    fn check(&mut self, len: usize) -> Option<()> {
+        if len > self.max {
+            self.exceeded = true;
+            return None;
+        }
+        Some(())
+    }
+
+    /// Returns the error of decompression that stopped early.
+    fn error(&self) -> Error {
+        if self.exceeded {
+            warn!("decompressed body exceeds {} bytes", self.max);
+            Error::ResponseTooLarge
+        } else {
+            Error::InvalidResponse(ParseError::Encoding)
+        }
+    }
+}
+
+/// Decompresses raw DEFLATE data, returning it and the number of bytes of `data` used.
+fn inflate(data: &[u8], limit: &mut Limit) -> Option<(Vec<u8>, usize)> {
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => bits.stored(&mut out, limit)?,
+            1 => {
+                let (lengths, distances) = fixed_codes()?;
+                block(&mut bits, &mut out, limit, (&lengths, &distances))?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut bits)?;
+                block(&mut bits, &mut out, limit, (&lengths, &distances))?;
+            }
+            _ => return None,
+        }
+        if last {
+            return Some((out, bits.pos));
+        }
+    }
+}
+
+/// Reads the bits of DEFLATE data, least significant first.
+struct Bits<'a> {
+    data: &'a [u8],
+    /// The next byte to load into `buffer`.
+    pos: usize,
+    buffer: u32,
+    /// Number of bits in `buffer`, always fewer than 8 between reads.
+    count: u32,
+}
+
+impl Bits<'_> {
+    /// Takes the next `n` bits, at most 16.
+    fn take(&mut self, n: u32) -> Option<u32> {
+        while self.count < n {
+            let byte = *self.data.get(self.pos)?;
+            self.pos += 1;
+            self.buffer |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Some(value)
+    }
+
+    /// Copies a stored block to `out`, skipping to the next byte boundary first.
+    fn stored(&mut self, out: &mut Vec<u8>, limit: &mut Limit) -> Option<()> {
+        self.buffer = 0;
+        self.count = 0;
+        let header = self.data.get(self.pos..self.pos + 4)?;
+        let len = u16::from_le_bytes([header[0], header[1]]);
+        if len != !u16::from_le_bytes([header[2], header[3]]) {
+            return None;
+        }
+        let start = self.pos + 4;
+        let end = start + usize::from(len);
+        limit.check(out.len() + usize::from(len))?;
+        out.extend_from_slice(self.data.get(start..end)?);
+        self.pos = end;
+        Some(())
+    }
+}
+
+/// A canonical Huffman code, given by the number of codes of each length and the symbols in
+/// code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Constructs the code with the given code length for each symbol, 0 if it is unused.
+    /// Returns `None` if there are more codes of some length than fit.
+    fn new(lengths: &[u8]) -> Option<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[usize::from(len)] += 1;
+        }
+        counts[0] = 0;
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return None;
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                let offset = &mut offsets[usize::from(len)];
+                symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+        Some(Huffman { counts, symbols })
+    }
+
+    /// Reads the next symbol from `bits`.
+    fn decode(&self, bits: &mut Bits<'_>) -> Option<u16> {
+        // The first code of each length, and the index of its symbol.
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.take(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+/// Returns the literal/length and distance codes of fixed Huffman blocks.
+fn fixed_codes() -> Option<(Huffman, Huffman)> {
+    let mut lengths = [8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    Some((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+/// Reads the literal/length and distance codes of a dynamic Huffman block.
+fn dynamic_codes(bits: &mut Bits<'_>) -> Option<(Huffman, Huffman)> {
+    let literals = bits.take(5)? as usize + 257;
+    let distances = bits.take(5)? as usize + 1;
+    let code_lengths = bits.take(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return None;
+    }
+    let mut lengths = [0; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[symbol] = bits.take(3)? as u8;
+    }
+    let code = Huffman::new(&lengths)?;
+
+    let mut lengths = vec![0u8; literals + distances];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code.decode(bits)?;
+        let (len, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (*lengths.get(index.checked_sub(1)?)?, 3 + bits.take(2)?),
+            17 => (0, 3 + bits.take(3)?),
+            18 => (0, 11 + bits.take(7)?),
+            _ => return None,
+        };
+        let end = index + repeat as usize;
+        lengths.get_mut(index..end)?.fill(len);
+        index = end;
+    }
+    // A block without an end of block code could never finish.
+    if lengths[256] == 0 {
+        return None;
+    }
+    let (literal_lengths, distance_lengths) = lengths.split_at(literals);
+    Some((
+        Huffman::new(literal_lengths)?,
+        Huffman::new(distance_lengths)?,
+    ))
+}
+
+/// Decodes the symbols of a compressed block to `out` with the literal/length and distance
+/// codes, up to the end of block code.
+fn block(
+    bits: &mut Bits<'_>,
+    out: &mut Vec<u8>,
+    limit: &mut Limit,
+    (lengths, distances): (&Huffman, &Huffman),
+) -> Option<()> {
+    loop {
+        let symbol = lengths.decode(bits)?;
+        if symbol < 256 {
+            limit.check(out.len() + 1)?;
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Some(());
+        }
+        let symbol = usize::from(symbol - 257);
+        let len = usize::from(*LENGTH_BASE.get(symbol)?)
+            + bits.take(u32::from(LENGTH_EXTRA[symbol]))? as usize;
+        let symbol = usize::from(distances.decode(bits)?);
+        let distance = usize::from(*DISTANCE_BASE.get(symbol)?)
+            + bits.take(u32::from(DISTANCE_EXTRA[symbol]))? as usize;
+        let start = out.len().checked_sub(distance)?;
+        limit.check(out.len() + len)?;
+        // The copy may overlap what it produces, repeating the last `distance` bytes.
+        for i in start..start + len {
+            out.push(out[i]);
+        }
+    }
+}
+
+/// Returns the CRC-32 of `data`, as used by gzip.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Returns the Adler-32 checksum of `data`, as used by zlib.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
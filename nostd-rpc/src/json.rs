@@ -0,0 +1,191 @@
+//! A minimal JSON reader and writer, enough for flat request and response envelopes.
+//!
+//! Values are not parsed into a tree: objects are split into their members with each value
+//! kept as raw JSON text, which the caller parses further if it needs to.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Maximum nesting of arrays and objects skipped by [`skip_value`].
+const MAX_DEPTH: usize = 32;
+
+/// Appends `value` to `out` as a quoted JSON string.
+pub(crate) fn push_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => {
+                out.push_str("\\u00");
+                out.push(char::from(b"0123456789abcdef"[usize::from(c as u8 >> 4)]));
+                out.push(char::from(b"0123456789abcdef"[usize::from(c as u8 & 0xf)]));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Parses a JSON object, returning its members with the values as raw JSON text.
+///
+/// Returns `None` if `input` is not a single valid object.
+pub(crate) fn parse_object(input: &str) -> Option<Vec<(String, &str)>> {
+    let mut rest = input.trim().strip_prefix('{')?.trim_start();
+    let mut members = Vec::new();
+    if let Some(end) = rest.strip_prefix('}') {
+        return end.trim().is_empty().then_some(members);
+    }
+    loop {
+        let (name, after) = parse_string(rest)?;
+        let value = after.trim_start().strip_prefix(':')?.trim_start();
+        let after = skip_value(value, 0)?;
+        members.push((name, value[..value.len() - after.len()].trim_end()));
+        let after = after.trim_start();
+        if let Some(after) = after.strip_prefix(',') {
+            rest = after.trim_start();
+        } else {
+            let end = after.strip_prefix('}')?;
+            return end.trim().is_empty().then_some(members);
+        }
+    }
+}
+
+/// Parses a JSON array, returning its elements as raw JSON text.
+///
+/// Returns `None` if `input` is not a single valid array.
+pub(crate) fn parse_array(input: &str) -> Option<Vec<&str>> {
+    let mut rest = input.trim().strip_prefix('[')?.trim_start();
+    let mut elements = Vec::new();
+    if let Some(end) = rest.strip_prefix(']') {
+        return end.trim().is_empty().then_some(elements);
+    }
+    loop {
+        let after = skip_value(rest, 0)?;
+        elements.push(rest[..rest.len() - after.len()].trim_end());
+        let after = after.trim_start();
+        if let Some(after) = after.strip_prefix(',') {
+            rest = after.trim_start();
+        } else {
+            let end = after.strip_prefix(']')?;
+            return end.trim().is_empty().then_some(elements);
+        }
+    }
+}
+
+/// Parses `input` if it is exactly one JSON string.
+pub(crate) fn parse_whole_string(input: &str) -> Option<String> {
+    let (value, rest) = parse_string(input.trim())?;
+    rest.is_empty().then_some(value)
+}
+
+/// Parses the JSON string at the start of `input`, returning it and the rest of the input.
+pub(crate) fn parse_string(input: &str) -> Option<(String, &str)> {
+    let mut chars = input.strip_prefix('"')?.char_indices();
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &input[i + 2..])),
+            '\\' => {
+                let escaped = match chars.next()?.1 {
+                    '"' => '"',
+                    '\\' => '\\',
+                    '/' => '/',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => {
+                        let mut code = 0;
+                        for _ in 0..4 {
+                            code = code * 16 + chars.next()?.1.to_digit(16)?;
+                        }
+                        // Surrogate pairs are not combined, lone surrogates are invalid.
+                        char::from_u32(code)?
+                    }
+                    _ => return None,
+                };
+                value.push(escaped);
+            }
+            c if c < ' ' => return None,
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// Parses `input` if it is exactly one JSON integer.
+pub(crate) fn parse_integer(input: &str) -> Option<i64> {
+    let input = input.trim();
+    let digits = input.strip_prefix('-').unwrap_or(input);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    input.parse().ok()
+}
+
+/// Skips the JSON value at the start of `input`, returning the rest of the input.
+fn skip_value(input: &str, depth: usize) -> Option<&str> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let input = input.trim_start();
+    match input.as_bytes().first()? {
+        b'"' => parse_string(input).map(|(_, rest)| rest),
+        open @ (b'{' | b'[') => {
+            let close = if *open == b'{' { '}' } else { ']' };
+            let mut rest = input[1..].trim_start();
+            if let Some(rest) = rest.strip_prefix(close) {
+                return Some(rest);
+            }
+            loop {
+                if *open == b'{' {
+                    let (_, after) = parse_string(rest)?;
+                    rest = after.trim_start().strip_prefix(':')?;
+                }
+                rest = skip_value(rest, depth + 1)?.trim_start();
+                if let Some(after) = rest.strip_prefix(',') {
+                    rest = after.trim_start();
+                } else {
+                    return rest.strip_prefix(close);
+                }
+            }
+        }
+        _ => {
+            let end = input
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "+-.".contains(c)))
+                .unwrap_or(input.len());
+            let literal = &input[..end];
+            let valid = matches!(literal, "true" | "false" | "null") || is_number(literal);
+            valid.then_some(&input[end..])
+        }
+    }
+}
+
+/// Returns true if `literal` is a JSON number.
+fn is_number(literal: &str) -> bool {
+    let rest = literal.strip_prefix('-').unwrap_or(literal);
+    let (int, rest) = rest.split_at(rest.find(['.', 'e', 'E']).unwrap_or(rest.len()));
+    if int.is_empty()
+        || !int.bytes().all(|b| b.is_ascii_digit())
+        || (int.len() > 1 && int.starts_with('0'))
+    {
+        return false;
+    }
+    let (fraction, exponent) = match rest.find(['e', 'E']) {
+        Some(e) => (&rest[..e], Some(&rest[e + 1..])),
+        None => (rest, None),
+    };
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !fraction.is_empty() && !fraction.strip_prefix('.').is_some_and(digits) {
+        return false;
+    }
+    match exponent {
+        Some(exponent) => digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent)),
+        None => true,
+    }
+}
@@ -0,0 +1,405 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[cfg(feature = "serde")]
+use serde::{de::DeserializeOwned, Serialize};
+use smoltcp::iface::Interface;
+use smoltcp::phy::Device;
+
+use crate::hooks::Hooks;
+use crate::http::{self, HttpRequest, HttpResponse};
+use crate::stack::Stack;
+use crate::time::Clock;
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::time::{IdleHook, SystemClock, ThreadSleep};
+use crate::{json, Error};
+
+/// A JSON-RPC 2.0 client, sending calls as HTTP POST requests.
+#[derive(Clone, Debug)]
+pub struct JsonRpcClient {
+    /// MAC address used for the TUN/TAP interface.
+    #[cfg_attr(not(feature = "phy-tuntap_interface"), allow(dead_code))]
+    ethernet_mac: [u8; 6],
+    /// Request with the address, URL and headers of the server, used for every call.
+    request: HttpRequest,
+    /// ID of the next call.
+    next_id: u64,
+}
+
+impl JsonRpcClient {
+    /// Constructs a new [`JsonRpcClient`] sending calls to the server that `request` is
+    /// addressed to. The method and body of `request` are replaced for each call.
+    pub fn new(ethernet_mac: [u8; 6], request: HttpRequest) -> Self {
+        JsonRpcClient {
+            ethernet_mac,
+            request: request.header("Content-Type: application/json"),
+            next_id: 1,
+        }
+    }
+
+    /// Calls `method` with `params`, a JSON array or object, or "" for no parameters, using
+    /// the operating system clock.
+    ///
+    /// Returns the `result` of the call as JSON text.
+    #[cfg(feature = "phy-tuntap_interface")]
+    pub fn call(&mut self, method: &str, params: &str) -> Result<String, Error> {
+        self.call_with(
+            method,
+            params,
+            &SystemClock,
+            &mut ThreadSleep,
+            &mut Hooks::new(),
+        )
+    }
+
+    /// Calls `method` like [`JsonRpcClient::call`], reading the time from `clock` and calling
+    /// `idle` whenever it has to wait.
+    #[cfg(feature = "phy-tuntap_interface")]
+    pub fn call_with<C: Clock, I: IdleHook>(
+        &mut self,
+        method: &str,
+        params: &str,
+        clock: &C,
+        idle: &mut I,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<String, Error> {
+        let (request, id) = self.next_request(method, params);
+        let (response, _) = http::send_with(self.ethernet_mac, request, clock, idle, hooks)?;
+        parse_response(&response, id)
+    }
+
+    /// Calls `method` like [`JsonRpcClient::call_with`], over an interface and device set up
+    /// by the caller.
+    pub fn call_with_device<D: Device, C: Clock>(
+        &mut self,
+        iface: &mut Interface,
+        device: &mut D,
+        method: &str,
+        params: &str,
+        clock: &C,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<String, Error> {
+        let (request, id) = self.next_request(method, params);
+        let (response, _) = http::send_with_device(iface, device, request, clock, hooks)?;
+        parse_response(&response, id)
+    }
+
+    /// Calls `method` like [`JsonRpcClient::call_with`], over a [`Stack`] set up once and
+    /// reused by every call.
+    pub fn call_over<D: Device, C: Clock>(
+        &mut self,
+        stack: &mut Stack<D>,
+        method: &str,
+        params: &str,
+        clock: &C,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<String, Error> {
+        let (request, id) = self.next_request(method, params);
+        let (response, _) = stack.send(&request, clock, hooks)?;
+        parse_response(&response, id)
+    }
+
+    /// Calls `method` with `params` serialized as JSON, using the operating system clock, and
+    /// deserializes the `result` into `R`.
+    ///
+    /// `params` must serialize to a JSON array or object, or to `null` for no parameters,
+    /// e.g. from `()`.
+    #[cfg(all(feature = "serde", feature = "phy-tuntap_interface"))]
+    pub fn call_typed<P: Serialize, R: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, Error> {
+        let params = to_json(params)?;
+        from_json(&self.call(method, &params)?)
+    }
+
+    /// Calls `method` like [`JsonRpcClient::call_typed`], reading the time from `clock` and
+    /// calling `idle` whenever it has to wait.
+    #[cfg(all(feature = "serde", feature = "phy-tuntap_interface"))]
+    pub fn call_typed_with<P: Serialize, R: DeserializeOwned, C: Clock, I: IdleHook>(
+        &mut self,
+        method: &str,
+        params: &P,
+        clock: &C,
+        idle: &mut I,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<R, Error> {
+        let params = to_json(params)?;
+        from_json(&self.call_with(method, &params, clock, idle, hooks)?)
+    }
+
+    /// Calls `method` like [`JsonRpcClient::call_typed_with`], over an interface and device
+    /// set up by the caller.
+    #[cfg(feature = "serde")]
+    pub fn call_typed_with_device<P, R, D, C>(
+        &mut self,
+        iface: &mut Interface,
+        device: &mut D,
+        method: &str,
+        params: &P,
+        clock: &C,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<R, Error>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+        D: Device,
+        C: Clock,
+    {
+        let params = to_json(params)?;
+        from_json(&self.call_with_device(iface, device, method, &params, clock, hooks)?)
+    }
+
+    /// Starts a batch of calls, sent together in one HTTP request.
+    pub fn batch(&mut self) -> Batch<'_> {
+        Batch {
+            client: self,
+            body: String::from("["),
+            ids: Vec::new(),
+        }
+    }
+
+    /// Returns the HTTP request for the next call and its ID.
+    fn next_request(&mut self, method: &str, params: &str) -> (HttpRequest, u64) {
+        let id = self.next_id();
+        let body = request_body(method, params, id);
+        (self.request.clone().method("POST").body(&body), id)
+    }
+
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+/// Calls accumulated by [`JsonRpcClient::batch`], sent as a JSON array in one HTTP request.
+///
+/// The results are returned in the order the calls were added, matched to them by ID
+/// whatever order the server answers in. Each call can fail on its own with
+/// [`Error::Rpc`], or [`Error::InvalidRpcResponse`] if the server did not answer it.
+pub struct Batch<'a> {
+    client: &'a mut JsonRpcClient,
+    /// The array of request objects so far, without the closing bracket.
+    body: String,
+    /// IDs of the calls, in the order they were added.
+    ids: Vec<u64>,
+}
+
+impl Batch<'_> {
+    /// Adds a call to `method` with `params`, a JSON array or object, or "" for no
+    /// parameters.
+    pub fn call(mut self, method: &str, params: &str) -> Self {
+        let id = self.client.next_id();
+        if !self.ids.is_empty() {
+            self.body.push(',');
+        }
+        self.body.push_str(&request_body(method, params, id));
+        self.ids.push(id);
+        self
+    }
+
+    /// Returns the number of calls in the batch.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns true if no calls have been added.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Sends the calls using the operating system clock, returning the `result` of each as
+    /// JSON text.
+    #[cfg(feature = "phy-tuntap_interface")]
+    pub fn send(self) -> Result<Vec<Result<String, Error>>, Error> {
+        self.send_with(&SystemClock, &mut ThreadSleep, &mut Hooks::new())
+    }
+
+    /// Sends the calls like [`Batch::send`], reading the time from `clock` and calling
+    /// `idle` whenever it has to wait.
+    ///
+    /// An empty batch is not sent.
+    #[cfg(feature = "phy-tuntap_interface")]
+    pub fn send_with<C: Clock, I: IdleHook>(
+        self,
+        clock: &C,
+        idle: &mut I,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<Vec<Result<String, Error>>, Error> {
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ethernet_mac = self.client.ethernet_mac;
+        let (request, ids) = self.into_request();
+        let (response, _) = http::send_with(ethernet_mac, request, clock, idle, hooks)?;
+        parse_batch_response(&response, &ids)
+    }
+
+    /// Sends the calls like [`Batch::send_with`], over an interface and device set up by
+    /// the caller.
+    pub fn send_with_device<D: Device, C: Clock>(
+        self,
+        iface: &mut Interface,
+        device: &mut D,
+        clock: &C,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<Vec<Result<String, Error>>, Error> {
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (request, ids) = self.into_request();
+        let (response, _) = http::send_with_device(iface, device, request, clock, hooks)?;
+        parse_batch_response(&response, &ids)
+    }
+
+    /// Returns the HTTP request sending the calls, and their IDs.
+    fn into_request(mut self) -> (HttpRequest, Vec<u64>) {
+        self.body.push(']');
+        let request = self.client.request.clone().method("POST").body(&self.body);
+        (request, self.ids)
+    }
+}
+
+/// Largest parameters serialized by [`to_json`].
+#[cfg(feature = "serde")]
+const MAX_PARAMS_LEN: usize = 64 * 1024;
+
+/// Serializes `params` as JSON, returning "" for `null`.
+#[cfg(feature = "serde")]
+fn to_json<P: Serialize>(params: &P) -> Result<String, Error> {
+    // serde-json-core writes into a fixed buffer, grown until the parameters fit.
+    let mut buf = alloc::vec![0; 256];
+    loop {
+        match serde_json_core::to_slice(params, &mut buf) {
+            Ok(len) => {
+                let json = core::str::from_utf8(&buf[..len]).map_err(|_| Error::InvalidJson)?;
+                return Ok(if json == "null" {
+                    String::new()
+                } else {
+                    String::from(json)
+                });
+            }
+            Err(serde_json_core::ser::Error::BufferFull) if buf.len() < MAX_PARAMS_LEN => {
+                buf.resize(buf.len() * 2, 0);
+            }
+            Err(_) => return Err(Error::InvalidJson),
+        }
+    }
+}
+
+/// Deserializes the JSON text of a call result into `R`.
+#[cfg(feature = "serde")]
+fn from_json<R: DeserializeOwned>(json: &str) -> Result<R, Error> {
+    serde_json_core::from_str(json)
+        .map(|(result, _)| result)
+        .map_err(|_| Error::InvalidJson)
+}
+
+/// Returns the JSON-RPC 2.0 request object calling `method` with `params`, a JSON array or
+/// object, or "" for no parameters.
+pub fn request_body(method: &str, params: &str, id: u64) -> String {
+    let mut body = String::from("{\"jsonrpc\":\"2.0\",\"method\":");
+    json::push_string(&mut body, method);
+    if !params.trim().is_empty() {
+        body.push_str(",\"params\":");
+        body.push_str(params.trim());
+    }
+    body.push_str(",\"id\":");
+    body.push_str(&alloc::format!("{}", id));
+    body.push('}');
+    body
+}
+
+/// Returns the `result` of the response to call `id` as JSON text.
+///
+/// Returns [`Error::Rpc`] if the server returned an error object,
+/// [`Error::InvalidRpcResponse`] if the body is not a response to call `id`, and
+/// [`Error::Status`] if the HTTP request failed without a JSON-RPC response.
+pub fn parse_response(response: &HttpResponse, id: u64) -> Result<String, Error> {
+    let members = response
+        .body_text_strict()
+        .ok()
+        .and_then(json::parse_object);
+    let Some(members) = members else {
+        return Err(if response.is_success() {
+            Error::InvalidRpcResponse
+        } else {
+            Error::Status(response.status())
+        });
+    };
+    let member = |name: &str| {
+        members
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| *value)
+    };
+
+    // Errors for requests the server could not parse have a null ID.
+    let error = member("error").filter(|error| *error != "null");
+    let id_matches = member("id").and_then(json::parse_integer) == i64::try_from(id).ok();
+    let unparsed_request = error.is_some() && member("id") == Some("null");
+    if !id_matches && !unparsed_request {
+        return Err(Error::InvalidRpcResponse);
+    }
+    call_result(&members)
+}
+
+/// Returns the `result` of each call in a batch as JSON text, in the order of `ids`, from the
+/// array of responses to the calls.
+///
+/// Returns the error of the whole batch as [`parse_response`] does if the server did not
+/// answer with an array, e.g. because it could not parse the batch. A call the server did not
+/// answer fails with [`Error::InvalidRpcResponse`].
+pub fn parse_batch_response(
+    response: &HttpResponse,
+    ids: &[u64],
+) -> Result<Vec<Result<String, Error>>, Error> {
+    let body = response.body_text_strict().ok();
+    let Some(elements) = body.and_then(json::parse_array) else {
+        // A single error object answers the whole batch.
+        return match parse_response(response, 0) {
+            Ok(_) => Err(Error::InvalidRpcResponse),
+            Err(error) => Err(error),
+        };
+    };
+    let mut results: Vec<_> = ids.iter().map(|_| Err(Error::InvalidRpcResponse)).collect();
+    for element in elements {
+        let members = json::parse_object(element).ok_or(Error::InvalidRpcResponse)?;
+        let id = members
+            .iter()
+            .find(|(n, _)| n == "id")
+            .and_then(|(_, value)| json::parse_integer(value));
+        let position = id.and_then(|id| ids.iter().position(|&i| i64::try_from(i) == Ok(id)));
+        if let Some(position) = position {
+            results[position] = call_result(&members);
+        }
+    }
+    Ok(results)
+}
+
+/// Returns the `result` of a response object as JSON text, or its error.
+fn call_result(members: &[(String, &str)]) -> Result<String, Error> {
+    let member = |name: &str| {
+        members
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| *value)
+    };
+    let error = member("error").filter(|error| *error != "null");
+    if let Some(error) = error {
+        let error = json::parse_object(error).ok_or(Error::InvalidRpcResponse)?;
+        let field = |name: &str| error.iter().find(|(n, _)| n == name).map(|(_, v)| *v);
+        let code = field("code")
+            .and_then(json::parse_integer)
+            .ok_or(Error::InvalidRpcResponse)?;
+        let message = field("message")
+            .and_then(json::parse_whole_string)
+            .unwrap_or_default();
+        return Err(Error::Rpc { code, message });
+    }
+    member("result")
+        .map(String::from)
+        .ok_or(Error::InvalidRpcResponse)
+}
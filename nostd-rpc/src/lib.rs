@@ -1,6 +1,88 @@
 #![no_std]
 #![allow(dead_code)]
+#![deny(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
+#[macro_use]
+mod trace;
+
+#[cfg(feature = "alloc")]
+pub mod base64;
+#[cfg(feature = "alloc")]
+pub mod coap;
+#[cfg(feature = "alloc")]
+pub mod dns;
+#[cfg(feature = "alloc")]
+pub mod download;
+#[cfg(feature = "alloc")]
+pub mod encoding;
+#[cfg(feature = "alloc")]
+mod entities;
+mod error;
+#[cfg(feature = "eth")]
+pub mod eth;
+#[cfg(feature = "alloc")]
+pub mod hooks;
+#[cfg(feature = "alloc")]
 pub mod http;
+/// Requests and responses that borrow their data, all there is of HTTP without the `alloc`
+/// feature.
+#[cfg(all(feature = "no-alloc", not(feature = "alloc")))]
+pub mod http {
+    mod borrowed;
+    mod common;
+
+    pub use borrowed::{send_borrowed, RequestRef, ResponseRef};
+}
+#[cfg(feature = "alloc")]
+pub mod idna;
+#[cfg(feature = "compression")]
+mod inflate;
+#[cfg(feature = "alloc")]
+mod json;
+#[cfg(feature = "alloc")]
+pub mod jsonrpc;
+pub mod mac;
+mod md5;
+pub mod metrics;
+#[cfg(feature = "alloc")]
+pub mod mqtt;
+pub mod net;
+#[cfg(feature = "alloc")]
+pub mod ota;
+#[cfg(feature = "pcap")]
+pub mod pcap;
+#[cfg(feature = "alloc")]
+pub mod protorpc;
+#[cfg(feature = "alloc")]
+pub mod provisioning;
+pub mod response;
+#[cfg(feature = "alloc")]
+pub mod server;
+mod sha1;
+pub mod sha256;
+#[cfg(feature = "alloc")]
+pub mod stack;
+#[cfg(feature = "alloc")]
+pub mod tcp;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod time;
+#[cfg(feature = "alloc")]
+pub mod transport;
+#[cfg(feature = "alloc")]
+pub mod udp;
+#[cfg(feature = "alloc")]
+pub mod url;
+#[cfg(feature = "alloc")]
+pub mod websocket;
+
+#[cfg(feature = "alloc")]
+pub use error::ErrorContext;
+pub use error::{Error, Phase};
+#[cfg(feature = "alloc")]
+pub use hooks::Hooks;
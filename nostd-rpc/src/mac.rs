@@ -0,0 +1,96 @@
+use core::fmt;
+use core::str::FromStr;
+
+use crate::{sha256, Error};
+
+/// An Ethernet MAC address.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MacAddress([u8; 6]);
+
+impl MacAddress {
+    /// Constructs a new [`MacAddress`] from its octets.
+    pub const fn new(octets: [u8; 6]) -> Self {
+        MacAddress(octets)
+    }
+
+    /// Returns a locally administered unicast address made from `random` bytes, e.g. from a
+    /// hardware random number generator.
+    pub fn locally_administered(random: [u8; 6]) -> Self {
+        let mut octets = random;
+        octets[0] = (octets[0] | 0x02) & !0x01;
+        MacAddress(octets)
+    }
+
+    /// Returns a locally administered unicast address derived from a unique chip ID, so a
+    /// device without a MAC address of its own always uses the same one.
+    pub fn from_chip_id(chip_id: &[u8]) -> Self {
+        let mut hasher = sha256::Sha256::new();
+        hasher.update(b"nostd-rpc MAC address");
+        hasher.update(chip_id);
+        let hash = hasher.finalize();
+        let mut random = [0; 6];
+        random.copy_from_slice(&hash[..6]);
+        MacAddress::locally_administered(random)
+    }
+
+    /// Returns the octets of the address.
+    pub const fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+
+    /// Returns true if this is a multicast (or broadcast) address.
+    pub const fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 != 0
+    }
+
+    /// Returns true if the address is locally administered rather than assigned by a vendor.
+    pub const fn is_locally_administered(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+}
+
+impl From<[u8; 6]> for MacAddress {
+    fn from(octets: [u8; 6]) -> Self {
+        MacAddress(octets)
+    }
+}
+
+impl From<MacAddress> for [u8; 6] {
+    fn from(mac: MacAddress) -> Self {
+        mac.0
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = Error;
+
+    /// Parses six pairs of hexadecimal digits separated by ':' or '-'.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let separator = if s.contains('-') { '-' } else { ':' };
+        let mut octets = [0; 6];
+        let mut parts = s.split(separator);
+        for octet in &mut octets {
+            let part = parts.next().ok_or(Error::InvalidMac)?;
+            if part.len() != 2 || !part.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(Error::InvalidMac);
+            }
+            *octet = u8::from_str_radix(part, 16).map_err(|_| Error::InvalidMac)?;
+        }
+        if parts.next().is_some() {
+            return Err(Error::InvalidMac);
+        }
+        Ok(MacAddress(octets))
+    }
+}
+
+impl fmt::Display for MacAddress {
+    /// Formats the address as lowercase hexadecimal pairs separated by ':'.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            a, b, c, d, e, g
+        )
+    }
+}
@@ -0,0 +1,235 @@
+use smoltcp::phy::{self, Device, DeviceCapabilities, Medium};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{
+    EthernetFrame, EthernetProtocol, IpProtocol, Ipv4Packet, Ipv6Packet, TcpPacket,
+};
+
+use crate::{Error, Phase};
+
+/// When each phase of a request completed, measured from the start of the request, and
+/// how the link behaved while it was sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timings {
+    /// Time until the host name was resolved, if it was resolved.
+    pub dns: Option<Duration>,
+    /// Time until the TCP connection was established.
+    pub connect: Option<Duration>,
+    /// Time until the first byte of the response was received.
+    pub time_to_first_byte: Option<Duration>,
+    /// Time until the response was complete.
+    pub total: Duration,
+    /// Number of request bytes sent.
+    pub bytes_sent: usize,
+    /// Number of response bytes received.
+    pub bytes_received: usize,
+    /// Time until the whole request was written to the socket.
+    pub request_sent: Option<Duration>,
+    /// Round-trip time to the server, measured by the TCP handshake. Not known for a
+    /// request sent over a connection kept alive.
+    pub round_trip: Option<Duration>,
+    /// Number of TCP segments of the request sent again because they were not
+    /// acknowledged in time.
+    pub retransmits: u32,
+    /// Number of times the interface was polled.
+    pub polls: u32,
+}
+
+impl Timings {
+    /// Returns the response throughput in bytes per second, measured from the first to
+    /// the last byte received.
+    pub fn throughput(&self) -> Option<u64> {
+        let first_byte = self.time_to_first_byte?;
+        let micros = self
+            .total
+            .total_micros()
+            .checked_sub(first_byte.total_micros())?;
+        if micros == 0 {
+            return None;
+        }
+        Some(self.bytes_received as u64 * 1_000_000 / micros)
+    }
+
+    /// Returns the time spent in `phase`, if the request went through it. The read phase
+    /// includes waiting for the server to answer.
+    pub fn phase(&self, phase: Phase) -> Option<Duration> {
+        let connect = self.connect?;
+        let request_sent = self.request_sent?;
+        match phase {
+            Phase::Resolve => self.dns,
+            Phase::Connect => Some(connect - self.dns.unwrap_or_default()),
+            Phase::Write => Some(request_sent - connect),
+            Phase::Read => Some(self.total - request_sent),
+        }
+    }
+}
+
+/// Counts the segments of a TCP connection that are sent again, from the frames that carry
+/// them.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Segments {
+    /// The sequence number following the furthest segment sent.
+    next: Option<i32>,
+    /// Number of segments sent again.
+    pub(crate) retransmits: u32,
+}
+
+impl Segments {
+    /// Counts `frame` if it is a segment sent again from local `port`.
+    fn inspect(&mut self, medium: Medium, port: u16, frame: &[u8]) {
+        let Some((src_port, seq, len)) = tcp_segment(medium, frame) else {
+            return;
+        };
+        // Bare acknowledgements take up no sequence numbers.
+        if src_port != port || len == 0 {
+            return;
+        }
+        let end = seq.wrapping_add(len);
+        match self.next {
+            Some(next) if seq.wrapping_sub(next) < 0 => {
+                self.retransmits += 1;
+                if end.wrapping_sub(next) > 0 {
+                    self.next = Some(end);
+                }
+            }
+            _ => self.next = Some(end),
+        }
+    }
+}
+
+/// Returns the source port, sequence number and length in sequence numbers of the TCP
+/// segment in `frame`, if it carries one.
+fn tcp_segment(medium: Medium, frame: &[u8]) -> Option<(u16, i32, i32)> {
+    let packet = match medium {
+        Medium::Ethernet => {
+            let frame = EthernetFrame::new_checked(frame).ok()?;
+            match frame.ethertype() {
+                EthernetProtocol::Ipv4 | EthernetProtocol::Ipv6 => frame.payload(),
+                _ => return None,
+            }
+        }
+        Medium::Ip => frame,
+        _ => return None,
+    };
+    let segment = match packet.first()? >> 4 {
+        4 => {
+            let packet = Ipv4Packet::new_checked(packet).ok()?;
+            (packet.next_header() == IpProtocol::Tcp).then(|| packet.payload())?
+        }
+        6 => {
+            let packet = Ipv6Packet::new_checked(packet).ok()?;
+            (packet.next_header() == IpProtocol::Tcp).then(|| packet.payload())?
+        }
+        _ => return None,
+    };
+    let segment = TcpPacket::new_checked(segment).ok()?;
+    let len = segment.payload().len() + usize::from(segment.syn()) + usize::from(segment.fin());
+    let len = i32::try_from(len).ok()?;
+    Some((segment.src_port(), segment.seq_number().0, len))
+}
+
+/// A device that counts the [`Segments`] sent again on the connection from local `port`.
+pub(crate) struct Watched<'a, D> {
+    device: &'a mut D,
+    port: u16,
+    segments: &'a mut Segments,
+}
+
+impl<'a, D: Device> Watched<'a, D> {
+    pub(crate) fn new(device: &'a mut D, port: u16, segments: &'a mut Segments) -> Self {
+        Watched {
+            device,
+            port,
+            segments,
+        }
+    }
+}
+
+impl<D: Device> Device for Watched<'_, D> {
+    type RxToken<'a>
+        = D::RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = WatchedTxToken<'a, D::TxToken<'a>>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let medium = self.device.capabilities().medium;
+        let (rx, token) = self.device.receive(timestamp)?;
+        let tx = WatchedTxToken {
+            token,
+            medium,
+            port: self.port,
+            segments: self.segments,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        let medium = self.device.capabilities().medium;
+        let token = self.device.transmit(timestamp)?;
+        Some(WatchedTxToken {
+            token,
+            medium,
+            port: self.port,
+            segments: self.segments,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.device.capabilities()
+    }
+}
+
+/// Inspects the frame sent with the token of a [`Watched`] device.
+pub(crate) struct WatchedTxToken<'a, T> {
+    token: T,
+    medium: Medium,
+    port: u16,
+    segments: &'a mut Segments,
+}
+
+impl<T: phy::TxToken> phy::TxToken for WatchedTxToken<'_, T> {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, len: usize, f: F) -> R {
+        let WatchedTxToken {
+            token,
+            medium,
+            port,
+            segments,
+        } = self;
+        token.consume(len, |frame| {
+            let result = f(frame);
+            segments.inspect(medium, port, frame);
+            result
+        })
+    }
+}
+
+/// Counters reported by the client while sending requests, so they can be forwarded to
+/// any telemetry backend without this crate depending on it.
+///
+/// Every method has an empty default implementation.
+pub trait Metrics {
+    /// Called when a request is started.
+    fn request_started(&mut self) {}
+
+    /// Called when a request has completed successfully.
+    fn request_completed(&mut self, _timings: &Timings) {}
+
+    /// Called when a request has failed, use [`Error::root`] to count errors by kind.
+    fn request_failed(&mut self, _error: &Error) {}
+
+    /// Called whenever request bytes are written to the socket.
+    fn bytes_sent(&mut self, _count: usize) {}
+
+    /// Called whenever response bytes are read from the socket.
+    fn bytes_received(&mut self, _count: usize) {}
+
+    /// Called whenever `count` TCP segments of a request are sent again because they were
+    /// not acknowledged in time. The total of a completed request is also in its
+    /// [`Timings`].
+    fn retransmits(&mut self, _count: u32) {}
+}
+
+impl Metrics for () {}
@@ -0,0 +1,601 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::IpAddr;
+use core::task::Poll;
+
+use smoltcp::iface::{Interface, SocketHandle, SocketSet};
+use smoltcp::phy::Device;
+use smoltcp::socket::tcp;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::IpAddress;
+
+use crate::net::LocalPorts;
+use crate::Error;
+
+const DEFAULT_PORT: u16 = 1883;
+const DEFAULT_KEEP_ALIVE_SECONDS: u16 = 60;
+const DEFAULT_TIMEOUT_SECONDS: u64 = 15;
+const DEFAULT_BUFFER_SIZE: usize = 1024;
+const DEFAULT_MAX_PACKET_SIZE: usize = 16 * 1024;
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const PINGREQ: u8 = 12;
+const PINGRESP: u8 = 13;
+const DISCONNECT: u8 = 14;
+
+/// The delivery guarantee of a message.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QoS {
+    /// Delivered at most once, without acknowledgement.
+    #[default]
+    AtMostOnce,
+    /// Delivered at least once, acknowledged by the receiver.
+    AtLeastOnce,
+}
+
+/// How to connect to an MQTT broker.
+#[derive(Clone, Debug)]
+pub struct MqttOptions {
+    /// IP address of the broker.
+    ip: Option<IpAddr>,
+    /// Port of the broker.
+    port: u16,
+    client_id: String,
+    username: Option<String>,
+    password: Option<String>,
+    /// Whether the broker discards the session state of a previous connection.
+    clean_session: bool,
+    /// Longest time without sending a packet, after which a ping is sent.
+    keep_alive: u16,
+    /// How long to wait for the broker to accept the connection.
+    timeout: Duration,
+    /// Largest packet accepted from the broker.
+    max_packet_size: usize,
+    /// Size of the TCP socket receive buffer.
+    rx_buffer_size: usize,
+    /// Size of the TCP socket transmit buffer.
+    tx_buffer_size: usize,
+    /// Chooses the local port of the connection.
+    ports: LocalPorts,
+}
+
+impl Default for MqttOptions {
+    fn default() -> Self {
+        MqttOptions {
+            ip: None,
+            port: DEFAULT_PORT,
+            client_id: String::new(),
+            username: None,
+            password: None,
+            clean_session: true,
+            keep_alive: DEFAULT_KEEP_ALIVE_SECONDS,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECONDS),
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            rx_buffer_size: DEFAULT_BUFFER_SIZE,
+            tx_buffer_size: DEFAULT_BUFFER_SIZE,
+            ports: LocalPorts::new([0; 8]),
+        }
+    }
+}
+
+impl MqttOptions {
+    /// Constructs new [`MqttOptions`] for a broker on port 1883, with the client identifier
+    /// `client_id`. An empty identifier asks the broker to assign one.
+    pub fn new(client_id: &str) -> Self {
+        MqttOptions {
+            client_id: String::from(client_id),
+            ..MqttOptions::default()
+        }
+    }
+
+    /// Sets the IPv4 address of the broker.
+    pub fn ipv4(mut self, ip: [u8; 4]) -> Self {
+        self.ip = Some(IpAddr::from(ip));
+        self
+    }
+
+    /// Sets the IPv6 address of the broker.
+    pub fn ipv6(mut self, ip: [u16; 8]) -> Self {
+        self.ip = Some(IpAddr::from(ip));
+        self
+    }
+
+    /// Sets the IP address of the broker.
+    pub fn ip<A: Into<IpAddr>>(mut self, ip: A) -> Self {
+        self.ip = Some(ip.into());
+        self
+    }
+
+    /// Sets the port of the broker.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets the user name and password sent to the broker.
+    pub fn credentials(mut self, username: &str, password: &str) -> Self {
+        self.username = Some(String::from(username));
+        self.password = Some(String::from(password));
+        self
+    }
+
+    /// Sets whether the broker discards the subscriptions and queued messages of a previous
+    /// connection with the same client identifier, true by default.
+    pub fn clean_session(mut self, clean_session: bool) -> Self {
+        self.clean_session = clean_session;
+        self
+    }
+
+    /// Sets the keep alive interval in seconds, 60 by default. A ping is sent when nothing
+    /// else has been for this long, and the connection fails if the broker does not answer
+    /// it within the interval. Zero turns keep alive off.
+    pub fn keep_alive(mut self, seconds: u16) -> Self {
+        self.keep_alive = seconds;
+        self
+    }
+
+    /// Sets how long to wait for the broker to accept the connection.
+    pub fn timeout<T: Into<Duration>>(mut self, timeout: T) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Sets the largest packet accepted from the broker, 16 KiB by default.
+    pub fn max_packet_size(mut self, size: usize) -> Self {
+        self.max_packet_size = size;
+        self
+    }
+
+    /// Sets the size of the TCP socket receive buffer.
+    pub fn rx_buffer_size(mut self, size: usize) -> Self {
+        self.rx_buffer_size = size;
+        self
+    }
+
+    /// Sets the size of the TCP socket transmit buffer.
+    pub fn tx_buffer_size(mut self, size: usize) -> Self {
+        self.tx_buffer_size = size;
+        self
+    }
+
+    /// Seeds the choice of the local port of the connection with `random`, which should come
+    /// from a random number generator, see [`LocalPorts`].
+    pub fn local_ports(mut self, random: [u8; 8]) -> Self {
+        self.ports = LocalPorts::new(random);
+        self
+    }
+
+    /// Encodes the CONNECT packet (MQTT 3.1.1 section 3.1).
+    fn connect_packet(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.username.is_some() {
+            flags |= 0x80;
+        }
+        if self.password.is_some() {
+            flags |= 0x40;
+        }
+        if self.clean_session {
+            flags |= 0x02;
+        }
+        let mut body = Vec::new();
+        push_string(&mut body, "MQTT");
+        body.push(4);
+        body.push(flags);
+        body.extend_from_slice(&self.keep_alive.to_be_bytes());
+        push_string(&mut body, &self.client_id);
+        for field in [&self.username, &self.password].into_iter().flatten() {
+            push_string(&mut body, field);
+        }
+        packet(CONNECT << 4, &body)
+    }
+}
+
+/// Something that happened on an [`MqttClient`] connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Notification {
+    /// The broker accepted the connection, with the state of a previous session if
+    /// `session_present`.
+    Connected { session_present: bool },
+    /// A message was published to a topic the client subscribed to.
+    Message {
+        topic: String,
+        payload: Vec<u8>,
+        qos: QoS,
+        retain: bool,
+    },
+    /// The broker acknowledged the QoS 1 message published with `packet_id`.
+    Published { packet_id: u16 },
+    /// The broker answered the subscription with `packet_id`, granting the given QoS for
+    /// each topic filter or `None` if it refused it.
+    Subscribed {
+        packet_id: u16,
+        granted: Vec<Option<QoS>>,
+    },
+}
+
+/// The state of the connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Connect,
+    /// Waiting for the broker to accept the connection.
+    Handshake,
+    Open,
+    Closed,
+}
+
+/// An MQTT 3.1.1 client session with a broker, advanced a step at a time by calling
+/// [`MqttClient::poll`], e.g. for telemetry from firmware that already uses this crate for
+/// HTTP.
+///
+/// Packets published or subscriptions made before the broker accepts the connection are sent
+/// once it has. Incoming QoS 1 messages are acknowledged and pings are sent automatically.
+/// QoS 1 messages are not sent again if the connection is lost before they are
+/// acknowledged, and QoS 2 is not supported.
+pub struct MqttClient<'a, D: Device> {
+    iface: &'a mut Interface,
+    device: &'a mut D,
+    sockets: SocketSet<'a>,
+    handle: SocketHandle,
+    state: State,
+    start: Instant,
+    options: MqttOptions,
+    /// Data waiting to be written to the socket.
+    outgoing: Vec<u8>,
+    /// Packets sent before the broker accepted the connection.
+    queued: Vec<u8>,
+    /// Data received and not yet decoded.
+    incoming: Vec<u8>,
+    next_packet_id: u16,
+    /// When a packet was last sent.
+    last_sent: Instant,
+    /// When the unanswered ping was sent, if there is one.
+    ping_sent: Option<Instant>,
+}
+
+impl<'a, D: Device> MqttClient<'a, D> {
+    /// Starts connecting over `iface` and `device`, which must already have their address and
+    /// routes configured, to the broker of `options` at `now`.
+    ///
+    /// Returns [`Error::UnresolvedHost`] if the options have no IP address.
+    pub fn connect(
+        iface: &'a mut Interface,
+        device: &'a mut D,
+        options: MqttOptions,
+        now: Instant,
+    ) -> Result<Self, Error> {
+        let ip = options.ip.ok_or(Error::UnresolvedHost)?;
+        let rx_buffer = tcp::SocketBuffer::new(vec![0; options.rx_buffer_size]);
+        let tx_buffer = tcp::SocketBuffer::new(vec![0; options.tx_buffer_size]);
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(tcp::Socket::new(rx_buffer, tx_buffer));
+        sockets
+            .get_mut::<tcp::Socket>(handle)
+            .connect(
+                iface.context(),
+                (IpAddress::from(ip), options.port),
+                options.ports.clone().next(now),
+            )
+            .map_err(Error::Connect)?;
+        debug!("connecting to port {}", options.port);
+
+        Ok(MqttClient {
+            iface,
+            device,
+            sockets,
+            handle,
+            state: State::Connect,
+            start: now,
+            outgoing: options.connect_packet(),
+            options,
+            queued: Vec::new(),
+            incoming: Vec::new(),
+            next_packet_id: 1,
+            last_sent: now,
+            ping_sent: None,
+        })
+    }
+
+    /// Returns whether the broker has accepted the connection and it is still open.
+    pub fn is_connected(&self) -> bool {
+        self.state == State::Open
+    }
+
+    /// Publishes `payload` to `topic`, to be kept by the broker for future subscribers if
+    /// `retain`. Returns the packet ID of a QoS 1 message, which the broker acknowledges with
+    /// [`Notification::Published`].
+    pub fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        qos: QoS,
+        retain: bool,
+    ) -> Result<Option<u16>, Error> {
+        let mut body = Vec::with_capacity(topic.len() + payload.len() + 4);
+        push_string(&mut body, topic);
+        let packet_id = match qos {
+            QoS::AtMostOnce => None,
+            QoS::AtLeastOnce => Some(self.packet_id()),
+        };
+        if let Some(packet_id) = packet_id {
+            body.extend_from_slice(&packet_id.to_be_bytes());
+        }
+        body.extend_from_slice(payload);
+        let flags = (u8::from(qos == QoS::AtLeastOnce) << 1) | u8::from(retain);
+        self.send(packet((PUBLISH << 4) | flags, &body))?;
+        Ok(packet_id)
+    }
+
+    /// Subscribes to the topics matching `filter`, with `qos` as the highest QoS of the
+    /// messages delivered. Returns the packet ID, which the broker answers with
+    /// [`Notification::Subscribed`].
+    pub fn subscribe(&mut self, filter: &str, qos: QoS) -> Result<u16, Error> {
+        let packet_id = self.packet_id();
+        let mut body = Vec::with_capacity(filter.len() + 5);
+        body.extend_from_slice(&packet_id.to_be_bytes());
+        push_string(&mut body, filter);
+        body.push(u8::from(qos == QoS::AtLeastOnce));
+        self.send(packet((SUBSCRIBE << 4) | 0x2, &body))?;
+        Ok(packet_id)
+    }
+
+    /// Sends DISCONNECT and closes the connection once it is sent, which takes further polls.
+    /// A connection the broker has not accepted yet is aborted.
+    pub fn disconnect(&mut self) {
+        match self.state {
+            State::Open => self.outgoing.extend(packet(DISCONNECT << 4, &[])),
+            _ => self.sockets.get_mut::<tcp::Socket>(self.handle).abort(),
+        }
+        self.state = State::Closed;
+    }
+
+    /// Polls the interface and advances the session, returning the next notification.
+    ///
+    /// Fails with [`Error::BrokerRefused`] if the broker refuses the connection, with
+    /// [`Error::ResponseTimeout`] if it does not answer a ping in time, and with
+    /// [`Error::ConnectionClosed`] once the connection is closed.
+    pub fn poll(&mut self, now: Instant) -> Poll<Result<Notification, Error>> {
+        match self.step(now) {
+            Poll::Ready(Err(error)) => {
+                if self.state != State::Closed {
+                    warn!("MQTT session failed: {}", error);
+                    self.sockets.get_mut::<tcp::Socket>(self.handle).abort();
+                    self.iface.poll(now, self.device, &mut self.sockets);
+                    self.state = State::Closed;
+                }
+                Poll::Ready(Err(error))
+            }
+            result => result,
+        }
+    }
+
+    fn step(&mut self, now: Instant) -> Poll<Result<Notification, Error>> {
+        self.iface.poll(now, self.device, &mut self.sockets);
+        let socket = self.sockets.get_mut::<tcp::Socket>(self.handle);
+
+        if self.state == State::Connect {
+            if socket.may_send() {
+                debug!("connected, sending CONNECT");
+                self.state = State::Handshake;
+            } else if !socket.is_active() {
+                return Poll::Ready(Err(Error::ConnectionRefused));
+            } else if now - self.start > self.options.timeout {
+                return Poll::Ready(Err(Error::ConnectTimeout));
+            } else {
+                return Poll::Pending;
+            }
+        }
+
+        let keep_alive = Duration::from_secs(u64::from(self.options.keep_alive));
+        if self.state == State::Open && self.options.keep_alive > 0 {
+            match self.ping_sent {
+                Some(sent) if now - sent > keep_alive => {
+                    return Poll::Ready(Err(Error::ResponseTimeout));
+                }
+                None if now - self.last_sent >= keep_alive => {
+                    trace!("sending PINGREQ");
+                    self.outgoing.extend(packet(PINGREQ << 4, &[]));
+                    self.ping_sent = Some(now);
+                }
+                _ => {}
+            }
+        }
+
+        let socket = self.sockets.get_mut::<tcp::Socket>(self.handle);
+        if socket.can_send() && !self.outgoing.is_empty() {
+            let sent = socket
+                .send_slice(&self.outgoing)
+                .map_err(Error::SendFailed)?;
+            self.outgoing.drain(..sent);
+            self.last_sent = now;
+            trace!("sent {} bytes", sent);
+        }
+        if self.state == State::Closed {
+            if self.outgoing.is_empty() {
+                socket.close();
+            }
+            return Poll::Ready(Err(Error::ConnectionClosed));
+        }
+        if socket.can_recv() {
+            let incoming = &mut self.incoming;
+            socket
+                .recv(|data| {
+                    incoming.extend_from_slice(data);
+                    (data.len(), ())
+                })
+                .map_err(Error::RecvFailed)?;
+        }
+        let closed = !socket.may_recv();
+
+        while let Some((header, body)) = self.decode_packet()? {
+            if let Some(notification) = self.receive(header, body)? {
+                return Poll::Ready(Ok(notification));
+            }
+        }
+        if closed {
+            debug!("connection closed by broker");
+            self.state = State::Closed;
+            return Poll::Ready(Err(Error::ConnectionClosed));
+        }
+        if self.state == State::Handshake && now - self.start > self.options.timeout {
+            return Poll::Ready(Err(Error::ResponseTimeout));
+        }
+        Poll::Pending
+    }
+
+    /// Removes the first packet from the data received, once all of it has arrived, returning
+    /// the first byte of its fixed header and the rest of the packet.
+    fn decode_packet(&mut self) -> Result<Option<(u8, Vec<u8>)>, Error> {
+        let Some(&header) = self.incoming.first() else {
+            return Ok(None);
+        };
+        // The remaining length takes up to 4 bytes of 7 bits (MQTT 3.1.1 section 2.2.3).
+        let mut len = 0usize;
+        let mut pos = 1;
+        loop {
+            let Some(&byte) = self.incoming.get(pos) else {
+                return Ok(None);
+            };
+            len |= usize::from(byte & 0x7f) << (7 * (pos - 1));
+            pos += 1;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if pos == 5 {
+                return Err(Error::InvalidPacket);
+            }
+        }
+        if len + pos > self.options.max_packet_size {
+            return Err(Error::ResponseTooLarge);
+        }
+        if self.incoming.len() < pos + len {
+            return Ok(None);
+        }
+        let body = self.incoming[pos..pos + len].to_vec();
+        self.incoming.drain(..pos + len);
+        Ok(Some((header, body)))
+    }
+
+    /// Handles a packet, returning the notification it gives if any.
+    fn receive(&mut self, header: u8, body: Vec<u8>) -> Result<Option<Notification>, Error> {
+        let kind = header >> 4;
+        trace!("received packet of type {}, {} bytes", kind, body.len());
+        if self.state == State::Handshake && kind != CONNACK {
+            return Err(Error::InvalidPacket);
+        }
+        let packet_id = |body: &[u8]| match body {
+            [high, low, ..] => Ok(u16::from_be_bytes([*high, *low])),
+            _ => Err(Error::InvalidPacket),
+        };
+        match kind {
+            CONNACK if self.state == State::Handshake => {
+                let [flags, code] = body[..] else {
+                    return Err(Error::InvalidPacket);
+                };
+                if code != 0 {
+                    warn!("broker refused the connection with return code {}", code);
+                    return Err(Error::BrokerRefused(code));
+                }
+                debug!("MQTT session open");
+                self.state = State::Open;
+                self.outgoing.append(&mut self.queued);
+                Ok(Some(Notification::Connected {
+                    session_present: flags & 0x01 != 0,
+                }))
+            }
+            PUBLISH => {
+                let qos = match (header >> 1) & 0x3 {
+                    0 => QoS::AtMostOnce,
+                    1 => QoS::AtLeastOnce,
+                    _ => return Err(Error::InvalidPacket),
+                };
+                let topic_len = usize::from(packet_id(&body)?);
+                let topic = body.get(2..2 + topic_len).ok_or(Error::InvalidPacket)?;
+                let topic = String::from_utf8(topic.to_vec()).map_err(|_| Error::InvalidUtf8)?;
+                let mut rest = &body[2 + topic_len..];
+                if qos == QoS::AtLeastOnce {
+                    let id = packet_id(rest)?;
+                    rest = &rest[2..];
+                    self.outgoing.extend(packet(PUBACK << 4, &id.to_be_bytes()));
+                }
+                Ok(Some(Notification::Message {
+                    topic,
+                    payload: rest.to_vec(),
+                    qos,
+                    retain: header & 0x01 != 0,
+                }))
+            }
+            PUBACK => Ok(Some(Notification::Published {
+                packet_id: packet_id(&body)?,
+            })),
+            SUBACK => {
+                let granted = body
+                    .get(2..)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|code| match code {
+                        0 => Some(QoS::AtMostOnce),
+                        1 => Some(QoS::AtLeastOnce),
+                        _ => None,
+                    })
+                    .collect();
+                Ok(Some(Notification::Subscribed {
+                    packet_id: packet_id(&body)?,
+                    granted,
+                }))
+            }
+            PINGRESP => {
+                self.ping_sent = None;
+                Ok(None)
+            }
+            _ => Err(Error::InvalidPacket),
+        }
+    }
+
+    /// Queues `packet`, to be sent once the broker has accepted the connection.
+    fn send(&mut self, packet: Vec<u8>) -> Result<(), Error> {
+        match self.state {
+            State::Connect | State::Handshake => self.queued.extend(packet),
+            State::Open => self.outgoing.extend(packet),
+            State::Closed => return Err(Error::ConnectionClosed),
+        }
+        Ok(())
+    }
+
+    /// Returns the next packet ID, which is never zero.
+    fn packet_id(&mut self) -> u16 {
+        let id = self.next_packet_id;
+        self.next_packet_id = self.next_packet_id.checked_add(1).unwrap_or(1);
+        id
+    }
+}
+
+/// Encodes a packet with the first header byte `header` and `body` after the remaining
+/// length.
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(body.len() + 5);
+    packet.push(header);
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        if len == 0 {
+            packet.push(byte);
+            break;
+        }
+        packet.push(byte | 0x80);
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// Appends `value` to `data` as a length-prefixed UTF-8 string.
+fn push_string(data: &mut Vec<u8>, value: &str) {
+    data.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    data.extend_from_slice(value.as_bytes());
+}
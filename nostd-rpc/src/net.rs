@@ -0,0 +1,342 @@
+#[cfg(feature = "alloc")]
+use alloc::vec;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+#[cfg(feature = "alloc")]
+use smoltcp::iface::{Config, Interface, SocketSet};
+use smoltcp::phy::DeviceCapabilities;
+#[cfg(feature = "alloc")]
+use smoltcp::phy::{Device, Medium};
+#[cfg(feature = "alloc")]
+use smoltcp::socket::dhcpv4;
+#[cfg(feature = "alloc")]
+use smoltcp::time::Duration;
+use smoltcp::time::Instant;
+#[cfg(feature = "alloc")]
+use smoltcp::wire::{
+    EthernetAddress, HardwareAddress, Ieee802154Address, Ieee802154Pan, IpCidr, Ipv4Cidr, Ipv6Cidr,
+};
+
+#[cfg(feature = "alloc")]
+use crate::time::Clock;
+#[cfg(feature = "alloc")]
+use crate::Error;
+
+#[cfg(feature = "alloc")]
+const DEFAULT_DNS_TIMEOUT_SECONDS: u64 = 5;
+/// Largest default size of TCP socket buffers.
+const MAX_BUFFER_SIZE: usize = 1024;
+/// Number of packets a TCP socket buffer holds by default, on devices with small packets.
+const BUFFER_PACKETS: usize = 4;
+/// First port of the dynamic range (RFC 6335 section 6), used for the local end of
+/// connections.
+const FIRST_DYNAMIC_PORT: u16 = 49152;
+/// Number of recently allocated ports that are not allocated again.
+const RECENT_PORTS: usize = 32;
+
+/// The address and routes of the local network interface.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetworkConfig {
+    /// IPv4 address and prefix length.
+    ipv4: Option<(Ipv4Addr, u8)>,
+    /// Default IPv4 gateway.
+    gateway: Option<Ipv4Addr>,
+    /// IPv6 address and prefix length.
+    ipv6: Option<(Ipv6Addr, u8)>,
+    /// Default IPv6 gateway.
+    ipv6_gateway: Option<Ipv6Addr>,
+    /// How long to wait for a DHCP lease, if the IPv4 address is obtained by DHCP.
+    dhcp: Option<Duration>,
+    /// DNS servers used to resolve host names, before any given by DHCP.
+    pub(crate) dns_servers: Vec<IpAddr>,
+    /// How long to wait for each DNS server to answer.
+    pub(crate) dns_timeout: Duration,
+    /// Medium of the TUN/TAP device created for requests.
+    pub(crate) medium: Medium,
+    /// PAN ID of an IEEE 802.15.4 radio.
+    pan_id: Option<u16>,
+}
+
+/// The IPv4 configuration obtained from a DHCP server.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lease {
+    /// The leased address.
+    pub address: Ipv4Addr,
+    /// Length of the subnet prefix.
+    pub prefix_len: u8,
+    /// Default gateway, if the server gave one.
+    pub gateway: Option<Ipv4Addr>,
+    /// DNS servers, in order of preference.
+    pub dns_servers: Vec<Ipv4Addr>,
+}
+
+#[cfg(feature = "alloc")]
+impl Default for NetworkConfig {
+    /// Address 192.168.42.1/24 with gateway 192.168.42.100, matching the `tap0` setup in the
+    /// README.
+    fn default() -> Self {
+        NetworkConfig {
+            ipv4: Some((Ipv4Addr::new(192, 168, 42, 1), 24)),
+            gateway: Some(Ipv4Addr::new(192, 168, 42, 100)),
+            ipv6: None,
+            ipv6_gateway: None,
+            dhcp: None,
+            dns_servers: Vec::new(),
+            dns_timeout: Duration::from_secs(DEFAULT_DNS_TIMEOUT_SECONDS),
+            medium: Medium::Ethernet,
+            pan_id: None,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl NetworkConfig {
+    /// Constructs a new [`NetworkConfig`] with the default address and gateway.
+    pub fn new() -> Self {
+        NetworkConfig::default()
+    }
+
+    /// Constructs a new [`NetworkConfig`] that obtains the IPv4 address, gateway and DNS
+    /// servers by DHCP, failing with [`Error::DhcpTimeout`] if no lease is obtained within
+    /// `timeout`.
+    pub fn dhcp<T: Into<Duration>>(timeout: T) -> Self {
+        NetworkConfig {
+            ipv4: None,
+            gateway: None,
+            dhcp: Some(timeout.into()),
+            ..NetworkConfig::default()
+        }
+    }
+
+    /// Sets the local IPv4 address and the length of the subnet prefix, e.g. 24, instead of
+    /// using DHCP.
+    pub fn ipv4(mut self, ip: Ipv4Addr, prefix_len: u8) -> Self {
+        self.ipv4 = Some((ip, prefix_len));
+        self.dhcp = None;
+        self
+    }
+
+    /// Sets the default IPv4 gateway, instead of the one given by DHCP.
+    pub fn gateway(mut self, gateway: Ipv4Addr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /// Sets the local IPv6 address and the length of the subnet prefix, e.g. 64.
+    pub fn ipv6(mut self, ip: Ipv6Addr, prefix_len: u8) -> Self {
+        self.ipv6 = Some((ip, prefix_len));
+        self
+    }
+
+    /// Sets the default IPv6 gateway.
+    pub fn ipv6_gateway(mut self, gateway: Ipv6Addr) -> Self {
+        self.ipv6_gateway = Some(gateway);
+        self
+    }
+
+    /// Adds a DNS server, so that requests to a host name without an IP address are
+    /// resolved when they are sent. Servers are tried in the order they are added.
+    pub fn dns_server<A: Into<IpAddr>>(mut self, server: A) -> Self {
+        self.dns_servers.push(server.into());
+        self
+    }
+
+    /// Sets how long to wait for each DNS server to answer.
+    pub fn dns_timeout<T: Into<Duration>>(mut self, timeout: T) -> Self {
+        self.dns_timeout = timeout.into();
+        self
+    }
+
+    /// Sends requests over the TUN device `tun0`, which carries IP packets without Ethernet
+    /// framing, rather than the TAP device `tap0`.
+    pub fn tun(mut self) -> Self {
+        self.medium = Medium::Ip;
+        self
+    }
+
+    /// Sets the PAN ID of an IEEE 802.15.4 radio.
+    pub fn pan_id(mut self, pan_id: u16) -> Self {
+        self.pan_id = Some(pan_id);
+        self
+    }
+
+    /// Returns the configuration of an interface on a device with `capabilities`, whose
+    /// hardware address depends on the medium of the device: `ethernet_mac` for Ethernet, the
+    /// EUI-64 formed from it for IEEE 802.15.4, and none for IP.
+    pub fn interface_config(
+        &self,
+        capabilities: &DeviceCapabilities,
+        ethernet_mac: [u8; 6],
+    ) -> Config {
+        let address = match capabilities.medium {
+            Medium::Ethernet => HardwareAddress::Ethernet(EthernetAddress(ethernet_mac)),
+            Medium::Ip => HardwareAddress::Ip,
+            Medium::Ieee802154 => {
+                let [a, b, c, d, e, f] = ethernet_mac;
+                // An EUI-48 becomes an EUI-64 with FF-FE between its two halves.
+                let eui64 = [a, b, c, 0xff, 0xfe, d, e, f];
+                HardwareAddress::Ieee802154(Ieee802154Address::Extended(eui64))
+            }
+        };
+        let mut config = Config::new(address);
+        config.pan_id = self.pan_id.map(Ieee802154Pan);
+        config
+    }
+
+    /// Configures `iface` like [`NetworkConfig::apply`], then obtains a DHCP lease over
+    /// `device` if DHCP is enabled, adding its address and gateway to `iface`.
+    ///
+    /// The lease is not renewed, so it suits sending requests shortly afterwards. Devices
+    /// that stay online should run their own `dhcpv4::Socket`.
+    pub fn configure<D: Device, C: Clock>(
+        &self,
+        iface: &mut Interface,
+        device: &mut D,
+        clock: &C,
+    ) -> Result<Option<Lease>, Error> {
+        self.apply(iface)?;
+        let Some(timeout) = self.dhcp else {
+            return Ok(None);
+        };
+
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(dhcpv4::Socket::new());
+        let start = clock.now();
+        loop {
+            let timestamp = clock.now();
+            iface.poll(timestamp, device, &mut sockets);
+            let event = sockets.get_mut::<dhcpv4::Socket>(handle).poll();
+            if let Some(dhcpv4::Event::Configured(config)) = event {
+                let lease = Lease {
+                    address: config.address.address(),
+                    prefix_len: config.address.prefix_len(),
+                    gateway: self.gateway.or(config.router),
+                    dns_servers: config.dns_servers.iter().copied().collect(),
+                };
+                debug!("DHCP lease obtained");
+                let mut result = Ok(());
+                iface.update_ip_addrs(|addrs| {
+                    if addrs.push(IpCidr::Ipv4(config.address)).is_err() {
+                        result = Err(Error::IpAddress);
+                    }
+                });
+                result?;
+                if let (Some(gateway), None) = (lease.gateway, self.gateway) {
+                    iface
+                        .routes_mut()
+                        .add_default_ipv4_route(gateway)
+                        .map_err(|_| Error::Route)?;
+                }
+                return Ok(Some(lease));
+            }
+            if timestamp - start > timeout {
+                return Err(Error::DhcpTimeout);
+            }
+        }
+    }
+
+    /// Adds the static addresses and default routes to `iface`.
+    ///
+    /// Returns [`Error::IpAddress`] if a prefix length is too long or the interface has no
+    /// room for the address, and [`Error::Route`] if a route cannot be added.
+    pub fn apply(&self, iface: &mut Interface) -> Result<(), Error> {
+        let mut cidrs = [None, None];
+        if let Some((ip, prefix_len)) = self.ipv4 {
+            if prefix_len > 32 {
+                return Err(Error::IpAddress);
+            }
+            cidrs[0] = Some(IpCidr::Ipv4(Ipv4Cidr::new(ip, prefix_len)));
+        }
+        if let Some((ip, prefix_len)) = self.ipv6 {
+            if prefix_len > 128 {
+                return Err(Error::IpAddress);
+            }
+            cidrs[1] = Some(IpCidr::Ipv6(Ipv6Cidr::new(ip, prefix_len)));
+        }
+
+        let mut result = Ok(());
+        iface.update_ip_addrs(|addrs| {
+            for cidr in cidrs.into_iter().flatten() {
+                if addrs.push(cidr).is_err() {
+                    result = Err(Error::IpAddress);
+                }
+            }
+        });
+        result?;
+
+        let routes = iface.routes_mut();
+        if let Some(gateway) = self.gateway {
+            routes
+                .add_default_ipv4_route(gateway)
+                .map_err(|_| Error::Route)?;
+        }
+        if let Some(gateway) = self.ipv6_gateway {
+            routes
+                .add_default_ipv6_route(gateway)
+                .map_err(|_| Error::Route)?;
+        }
+        Ok(())
+    }
+}
+
+/// Allocates the local ports of connections at random from the dynamic range, so they are
+/// hard to guess (RFC 6056), without reusing a recently allocated port whose connection the
+/// server may still hold in TIME-WAIT.
+#[derive(Clone, Debug)]
+pub struct LocalPorts {
+    state: u64,
+    /// The last ports allocated, zero where none has been yet.
+    recent: [u16; RECENT_PORTS],
+    /// Index in `recent` of the oldest port.
+    oldest: usize,
+}
+
+impl LocalPorts {
+    /// Constructs an allocator seeded with `random`, which should come from a random number
+    /// generator, e.g. a hardware RNG.
+    pub fn new(random: [u8; 8]) -> Self {
+        LocalPorts {
+            state: u64::from_le_bytes(random),
+            recent: [0; RECENT_PORTS],
+            oldest: 0,
+        }
+    }
+
+    /// Returns the local port of a connection made at `now`. The time is mixed into the
+    /// choice, so ports differ between runs even without a random seed.
+    pub fn next(&mut self, now: Instant) -> u16 {
+        let count = u64::from(u16::MAX - FIRST_DYNAMIC_PORT) + 1;
+        loop {
+            // The remainder is below `count`, so the port is at most `u16::MAX`.
+            let port = FIRST_DYNAMIC_PORT + (self.next_random(now) % count) as u16;
+            if !self.recent.contains(&port) {
+                self.recent[self.oldest] = port;
+                self.oldest = (self.oldest + 1) % RECENT_PORTS;
+                return port;
+            }
+        }
+    }
+
+    /// Returns the next number from a SplitMix64 generator, advanced by the time as well.
+    fn next_random(&mut self, now: Instant) -> u64 {
+        self.state = self
+            .state
+            .wrapping_add(0x9e37_79b9_7f4a_7c15)
+            .wrapping_add(now.total_micros() as u64);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Returns the default size of TCP socket buffers on a device with `capabilities`: 1 KiB, or
+/// four packets if that is smaller, e.g. on an IEEE 802.15.4 radio.
+pub fn buffer_size(capabilities: &DeviceCapabilities) -> usize {
+    (BUFFER_PACKETS * capabilities.ip_mtu()).min(MAX_BUFFER_SIZE)
+}
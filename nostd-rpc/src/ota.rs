@@ -0,0 +1,119 @@
+use alloc::vec::Vec;
+
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::download::ResumableDownload;
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::hooks::{Hooks, Transfer};
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::http::{self, HttpRequest};
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::response::HttpResponse;
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::sha256::Sha256;
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::time::{Clock, IdleHook};
+use crate::Error;
+
+/// Bytes requested at a time, which bounds the memory used for the response.
+#[cfg(feature = "phy-tuntap_interface")]
+const BLOCK_SIZE: u64 = 16 * 1024;
+
+/// Consecutive failed requests for a block before the download is abandoned.
+#[cfg(feature = "phy-tuntap_interface")]
+const MAX_ATTEMPTS: u8 = 3;
+
+/// Where a downloaded firmware image is written, e.g. the inactive flash partition.
+pub trait Storage {
+    /// Writes `data` at `offset` from the start of the image.
+    ///
+    /// Blocks are written in order. Return [`Error::Storage`] if the write fails.
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Error>;
+}
+
+impl Storage for Vec<u8> {
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Error> {
+        let offset = usize::try_from(offset).map_err(|_| Error::Storage)?;
+        let end = offset + data.len();
+        if self.len() < end {
+            self.resize(end, 0);
+        }
+        self[offset..end].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Downloads the firmware image at `url` into `storage`, verifying that its SHA-256 hash is
+/// `expected_hash`. Returns the size of the image.
+///
+/// The image is fetched in 16 KiB `Range` requests, each written to `storage` as it arrives,
+/// so the whole image is never held in memory. A failed request is retried from where it
+/// stopped, up to three times in a row. Progress is reported to the progress observer in
+/// `hooks` once per block, the other hooks see each request.
+///
+/// Returns [`Error::Status`] if the server responds with an error, [`Error::RangeMismatch`]
+/// if the image changes during the download and [`Error::HashMismatch`] if the downloaded
+/// image does not have the expected hash.
+#[cfg(feature = "phy-tuntap_interface")]
+pub fn download<S: Storage, C: Clock, I: IdleHook>(
+    ethernet_mac: [u8; 6],
+    url: &str,
+    storage: &mut S,
+    expected_hash: &[u8; 32],
+    clock: &C,
+    idle: &mut I,
+    hooks: &mut Hooks<'_>,
+) -> Result<u64, Error> {
+    let request = HttpRequest::from_url(url)?.method("GET");
+    let mut download = ResumableDownload::new();
+    let mut hasher = Sha256::new();
+    let mut attempts = 0;
+
+    loop {
+        let block = download.request_block(request.clone(), BLOCK_SIZE);
+        let response = http::send_bytes(
+            ethernet_mac,
+            block,
+            clock,
+            idle,
+            &mut hooks.without_progress(),
+        )
+        .and_then(|(data, _)| {
+            HttpResponse::parse_with_limits(&data, request.parse_mode, &request.header_limits)
+        });
+        let response = match response {
+            Ok(response) => response,
+            Err(_) if attempts + 1 < MAX_ATTEMPTS => {
+                attempts += 1;
+                warn!("block at {} failed, retrying", download.offset());
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        attempts = 0;
+        if !response.is_success() {
+            return Err(Error::Status(response.status()));
+        }
+
+        let offset = download.offset();
+        let data = download.accept(&response)?;
+        storage.write(offset, data)?;
+        hasher.update(data);
+        trace!("stored {} bytes at {}", data.len(), offset);
+        hooks.transfer(
+            Transfer::Download,
+            download.offset() as usize,
+            download.total().map(|total| total as usize),
+        );
+
+        let short_block = (data.len() as u64) < BLOCK_SIZE;
+        if download.is_complete() || (download.total().is_none() && short_block) {
+            break;
+        }
+    }
+
+    if &hasher.finalize() != expected_hash {
+        error!("image hash mismatch after {} bytes", download.offset());
+        return Err(Error::HashMismatch);
+    }
+    Ok(download.offset())
+}
@@ -0,0 +1,85 @@
+//! Capture of the frames a device sends and receives in the libpcap format, so that traffic
+//! from the field can be analyzed in Wireshark.
+//!
+//! Wrap the device passed to e.g. [`send_with_device`](crate::http::send_with_device) or
+//! [`HttpClient::new`](crate::http::HttpClient::new) with [`capture`]. Frames are written to
+//! a [`PcapSink`], such as a [`PcapBuffer`] in RAM or, with the `std` feature, any
+//! `std::io::Write`, e.g. a file. Implement [`PcapSink`] to write elsewhere, e.g. to a
+//! semihosting file.
+
+use alloc::vec::Vec;
+
+use smoltcp::phy::Device;
+pub use smoltcp::phy::{PcapMode, PcapSink, PcapWriter};
+use smoltcp::time::Instant;
+
+/// Length of the libpcap header of each packet.
+const PACKET_HEADER_LEN: usize = 16;
+
+/// Wraps `device` so that every frame it sends or receives is written to `sink`, starting
+/// with the libpcap file header.
+pub fn capture<D: Device, S: PcapSink>(device: D, sink: S) -> PcapWriter<D, S> {
+    PcapWriter::new(device, sink, PcapMode::Both)
+}
+
+/// A capture held in RAM, up to a fixed size. Packets that no longer fit are left out
+/// whole, so that the capture stays readable.
+///
+/// Pass it by mutable reference to [`capture`] to read it once the capture is dropped.
+#[derive(Debug)]
+pub struct PcapBuffer {
+    data: Vec<u8>,
+    capacity: usize,
+    /// Number of packets left out.
+    dropped: usize,
+}
+
+impl PcapBuffer {
+    /// Constructs an empty buffer holding up to `capacity` bytes, including the 24 byte
+    /// file header.
+    pub fn new(capacity: usize) -> Self {
+        PcapBuffer {
+            data: Vec::new(),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    /// Returns the capture, in the libpcap file format.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the number of packets left out because the buffer was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+impl PcapSink for PcapBuffer {
+    fn write(&mut self, data: &[u8]) {
+        let len = data
+            .len()
+            .min(self.capacity.saturating_sub(self.data.len()));
+        self.data.extend_from_slice(&data[..len]);
+    }
+
+    fn packet(&mut self, timestamp: Instant, packet: &[u8]) {
+        if self.data.len() + PACKET_HEADER_LEN + packet.len() > self.capacity {
+            self.dropped += 1;
+            return;
+        }
+        self.packet_header(timestamp, packet.len());
+        self.write(packet);
+    }
+}
+
+impl PcapSink for &mut PcapBuffer {
+    fn write(&mut self, data: &[u8]) {
+        (**self).write(data)
+    }
+
+    fn packet(&mut self, timestamp: Instant, packet: &[u8]) {
+        (**self).packet(timestamp, packet)
+    }
+}
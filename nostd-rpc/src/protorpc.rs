@@ -0,0 +1,226 @@
+//! Binary RPC with protobuf messages over this crate's HTTP layer, framed as Twirp or
+//! gRPC-Web, for links where JSON is too large.
+//!
+//! Messages are encoded and decoded by the caller's protobuf implementation through
+//! [`Message`]. For `prost` messages it can be implemented with `encode_raw` and `decode`:
+//!
+//! ```ignore
+//! impl nostd_rpc::protorpc::Message for Reading {
+//!     fn encode(&self, buf: &mut Vec<u8>) {
+//!         prost::Message::encode_raw(self, buf)
+//!     }
+//!
+//!     fn decode(bytes: &[u8]) -> Result<Self, nostd_rpc::Error> {
+//!         prost::Message::decode(bytes).map_err(|_| nostd_rpc::Error::InvalidRpcResponse)
+//!     }
+//! }
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use smoltcp::phy::Device;
+
+use crate::encoding;
+use crate::hooks::Hooks;
+use crate::http::{Header, HttpClient, HttpRequest, HttpResponse};
+use crate::json;
+use crate::time::Clock;
+use crate::Error;
+
+/// A protobuf message sent or received by a [`ProtoClient`].
+pub trait Message: Sized {
+    /// Appends the encoded message to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes a message, returning [`Error::InvalidRpcResponse`] if `bytes` are not one.
+    fn decode(bytes: &[u8]) -> Result<Self, Error>;
+}
+
+/// Messages already encoded, sent and received as they are.
+impl Message for Vec<u8> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Vec::from(bytes))
+    }
+}
+
+/// How calls and their messages are carried over HTTP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// Twirp, with the message as the body and errors as JSON.
+    Twirp,
+    /// gRPC-Web, with the message in a length-prefixed frame and the status in trailers.
+    GrpcWeb,
+}
+
+/// Calls methods of a protobuf service over an [`HttpClient`], which keeps the connection
+/// to the server open between calls.
+pub struct ProtoClient<D: Device, C: Clock> {
+    client: HttpClient<D>,
+    /// Request with the address and path prefix of the server, used for every call.
+    request: HttpRequest,
+    clock: C,
+    protocol: Protocol,
+}
+
+impl<D: Device, C: Clock> ProtoClient<D, C> {
+    /// Constructs a client calling methods with `client` on the server that `request` is
+    /// addressed to, reading the time from `clock`.
+    ///
+    /// Calls are sent to the URL of `request` followed by `/{service}/{method}`, so for
+    /// Twirp the URL is usually `/twirp`. The method and body of `request` are replaced for
+    /// each call.
+    pub fn new(client: HttpClient<D>, request: HttpRequest, clock: C, protocol: Protocol) -> Self {
+        ProtoClient {
+            client,
+            request,
+            clock,
+            protocol,
+        }
+    }
+
+    /// Returns the client the calls are sent with.
+    pub fn client(&mut self) -> &mut HttpClient<D> {
+        &mut self.client
+    }
+
+    /// Calls `method` of `service`, its fully qualified name, e.g. `sensors.Telemetry`, with
+    /// `message`, returning the reply.
+    ///
+    /// Returns [`Error::Twirp`] or [`Error::Grpc`] if the server answered with an error,
+    /// and [`Error::InvalidRpcResponse`] if the reply is malformed.
+    pub fn call<M: Message, R: Message>(
+        &mut self,
+        service: &str,
+        method: &str,
+        message: &M,
+    ) -> Result<R, Error> {
+        let mut url = self.request.path();
+        while url.ends_with('/') {
+            url.pop();
+        }
+        for part in [service, method] {
+            url.push('/');
+            url.push_str(part);
+        }
+        let mut encoded = Vec::new();
+        message.encode(&mut encoded);
+
+        let request = self.request.clone().method("POST").url(&url);
+        let request = match self.protocol {
+            Protocol::Twirp => request
+                .header(Header::ContentType("application/protobuf"))
+                .body_bytes(&encoded),
+            Protocol::GrpcWeb => request
+                .header(Header::ContentType("application/grpc-web+proto"))
+                .header("X-Grpc-Web: 1")
+                .body_bytes(&grpc_web_frame(&encoded)),
+        };
+        let (response, _) = self.client.send(&request, &self.clock, &mut Hooks::new())?;
+        let reply = match self.protocol {
+            Protocol::Twirp => twirp_reply(&response)?,
+            Protocol::GrpcWeb => grpc_web_reply(&response)?,
+        };
+        R::decode(&reply)
+    }
+}
+
+/// Frame flag of a gRPC-Web frame carrying trailers rather than a message.
+const TRAILERS: u8 = 0x80;
+/// Frame flag of a compressed message.
+const COMPRESSED: u8 = 0x01;
+
+/// Returns `message` in a gRPC-Web data frame, prefixed with a flag byte and its length as
+/// a big-endian `u32`.
+pub fn grpc_web_frame(message: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(5 + message.len());
+    frame.push(0);
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(message);
+    frame
+}
+
+/// Returns the message of a Twirp reply, or the error the server answered with.
+fn twirp_reply(response: &HttpResponse) -> Result<Vec<u8>, Error> {
+    if response.status() == 200 {
+        return Ok(Vec::from(response.body()));
+    }
+    let members = response
+        .body_text_strict()
+        .ok()
+        .and_then(json::parse_object)
+        .ok_or(Error::Status(response.status()))?;
+    let member = |name: &str| {
+        members
+            .iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, value)| json::parse_whole_string(value))
+    };
+    Err(Error::Twirp {
+        code: member("code").ok_or(Error::Status(response.status()))?,
+        message: member("msg").unwrap_or_default(),
+    })
+}
+
+/// Returns the message of a gRPC-Web reply, or the error its `grpc-status` reports.
+///
+/// The status is read from the trailers frame, or from the headers of a reply without
+/// messages.
+fn grpc_web_reply(response: &HttpResponse) -> Result<Vec<u8>, Error> {
+    if response.status() != 200 {
+        return Err(Error::Status(response.status()));
+    }
+    let mut message = None;
+    let mut trailers = Vec::new();
+    let mut rest = response.body();
+    while !rest.is_empty() {
+        let (&flags, after) = rest.split_first().ok_or(Error::InvalidRpcResponse)?;
+        let length = after
+            .get(..4)
+            .and_then(|length| length.try_into().ok())
+            .map(|length| u32::from_be_bytes(length) as usize)
+            .ok_or(Error::InvalidRpcResponse)?;
+        let payload = after.get(4..4 + length).ok_or(Error::InvalidRpcResponse)?;
+        if flags & TRAILERS != 0 {
+            trailers = parse_trailers(payload);
+        } else if flags & COMPRESSED != 0 {
+            warn!("compressed gRPC-Web message");
+            return Err(Error::InvalidRpcResponse);
+        } else if message.is_none() {
+            message = Some(payload);
+        }
+        rest = &after[4 + length..];
+    }
+
+    let trailer = |name: &str| {
+        trailers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .or_else(|| response.header(name))
+    };
+    let status = trailer("grpc-status")
+        .and_then(|status| status.trim().parse::<u32>().ok())
+        .ok_or(Error::InvalidRpcResponse)?;
+    if status != 0 {
+        let message = trailer("grpc-message").unwrap_or_default();
+        return Err(Error::Grpc {
+            status,
+            message: encoding::percent_decode(message).unwrap_or_else(|_| String::from(message)),
+        });
+    }
+    message.map(Vec::from).ok_or(Error::InvalidRpcResponse)
+}
+
+/// Parses the `name: value` lines of a gRPC-Web trailers frame.
+fn parse_trailers(payload: &[u8]) -> Vec<(String, String)> {
+    String::from_utf8_lossy(payload)
+        .split("\r\n")
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (String::from(name.trim()), String::from(value.trim())))
+        .collect()
+}
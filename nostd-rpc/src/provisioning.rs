@@ -0,0 +1,254 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
+
+use smoltcp::iface::{SocketHandle, SocketSet};
+use smoltcp::socket::udp;
+
+use crate::server::{Handler, Request, Response, Server};
+use crate::Error;
+use crate::{encoding, json};
+
+const DNS_PORT: u16 = 53;
+/// Time to live of the answers given by the [`DnsResponder`], kept short so clients resolve
+/// names normally again soon after provisioning.
+const DNS_TTL_SECONDS: u32 = 60;
+/// Largest DNS message handled, the limit for DNS over UDP without EDNS.
+const MAX_DNS_MESSAGE: usize = 512;
+
+/// The page served by the [`Portal`] unless another is set.
+const DEFAULT_PAGE: &str = "<!DOCTYPE html>\
+<html><head><meta name=\"viewport\" content=\"width=device-width\"><title>Setup</title></head>\
+<body><h1>Device setup</h1><form method=\"post\" action=\"/provision\">\
+<p><label>Network <input name=\"ssid\"></label></p>\
+<p><label>Password <input name=\"password\" type=\"password\"></label></p>\
+<p><button>Save</button></p></form></body></html>";
+
+/// Answers every DNS query for an IPv4 address with the device's own address, so that a
+/// phone joining the device's network opens its captive portal page.
+#[derive(Debug)]
+pub struct DnsResponder {
+    ip: Ipv4Addr,
+    handle: Option<SocketHandle>,
+}
+
+impl DnsResponder {
+    /// Constructs a new [`DnsResponder`] answering with `ip`.
+    pub fn new(ip: Ipv4Addr) -> Self {
+        DnsResponder { ip, handle: None }
+    }
+
+    /// Adds a UDP socket bound to port 53 to `sockets`.
+    pub fn listen(&mut self, sockets: &mut SocketSet<'_>) -> Result<(), Error> {
+        let rx_buffer = udp::PacketBuffer::new(
+            vec![udp::PacketMetadata::EMPTY; 4],
+            vec![0; 4 * MAX_DNS_MESSAGE],
+        );
+        let tx_buffer = udp::PacketBuffer::new(
+            vec![udp::PacketMetadata::EMPTY; 4],
+            vec![0; 4 * MAX_DNS_MESSAGE],
+        );
+        let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+        socket.bind(DNS_PORT).map_err(Error::Bind)?;
+        self.handle = Some(sockets.add(socket));
+        Ok(())
+    }
+
+    /// Answers the queries received since the last poll. Malformed queries are ignored, and
+    /// answers that do not fit in the transmit buffer are dropped for the client to retry.
+    pub fn poll(&mut self, sockets: &mut SocketSet<'_>) {
+        let Some(handle) = self.handle else {
+            return;
+        };
+        let socket = sockets.get_mut::<udp::Socket>(handle);
+        while let Ok((query, meta)) = socket.recv() {
+            if let Some(answer) = dns_answer(query, self.ip) {
+                trace!("answering DNS query");
+                let _ = socket.send_slice(&answer, meta.endpoint);
+            }
+        }
+    }
+}
+
+/// Builds the answer to a DNS `query`, giving `ip` for A and ANY queries and no records for
+/// other types. Returns `None` if `query` is not a standard query with one question.
+fn dns_answer(query: &[u8], ip: Ipv4Addr) -> Option<Vec<u8>> {
+    if query.len() < 12 || query.len() > MAX_DNS_MESSAGE {
+        return None;
+    }
+    let flags = u16::from_be_bytes([query[2], query[3]]);
+    let is_response = flags & 0x8000 != 0;
+    let opcode = (flags >> 11) & 0xf;
+    let questions = u16::from_be_bytes([query[4], query[5]]);
+    if is_response || opcode != 0 || questions != 1 {
+        return None;
+    }
+
+    // The question name is a sequence of labels ending with an empty one.
+    let mut pos = 12;
+    loop {
+        let len = usize::from(*query.get(pos)?);
+        if len == 0 {
+            pos += 1;
+            break;
+        }
+        if len > 63 {
+            return None;
+        }
+        pos += 1 + len;
+    }
+    let question = query.get(12..pos + 4)?;
+    let qtype = u16::from_be_bytes([query[pos], query[pos + 1]]);
+    let qclass = u16::from_be_bytes([query[pos + 2], query[pos + 3]]);
+    let answered = (qtype == 1 || qtype == 255) && qclass == 1;
+
+    let mut answer = Vec::with_capacity(pos + 4 + 16);
+    answer.extend_from_slice(&query[..2]);
+    // Response, authoritative, recursion desired copied from the query.
+    answer.extend_from_slice(&(0x8400 | (flags & 0x0100)).to_be_bytes());
+    answer.extend_from_slice(&[0, 1, 0, u8::from(answered), 0, 0, 0, 0]);
+    answer.extend_from_slice(question);
+    if answered {
+        // A pointer to the question name at offset 12.
+        answer.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1]);
+        answer.extend_from_slice(&DNS_TTL_SECONDS.to_be_bytes());
+        answer.extend_from_slice(&[0, 4]);
+        answer.extend_from_slice(&ip.octets());
+    }
+    Some(answer)
+}
+
+/// Receives the settings submitted to the [`Portal`].
+pub trait Provision {
+    /// Called with the submitted fields, returns true if they were accepted.
+    fn provision(&mut self, fields: &[(String, String)]) -> bool;
+}
+
+impl<F: FnMut(&[(String, String)]) -> bool> Provision for F {
+    fn provision(&mut self, fields: &[(String, String)]) -> bool {
+        self(fields)
+    }
+}
+
+/// A captive portal for setting up an unconfigured device from a phone browser.
+///
+/// The device runs its own access point network with address `ip`. Every DNS query is
+/// answered with `ip` and every page redirects to the setup page, so phones joining the
+/// network show it automatically. The page posts its form to `/provision`, which also accepts
+/// a flat JSON object of strings, and the fields are passed to the [`Provision`] receiver.
+pub struct Portal<P: Provision> {
+    dns: DnsResponder,
+    server: Server,
+    handler: PortalHandler<P>,
+}
+
+impl<P: Provision> Portal<P> {
+    /// Constructs a new [`Portal`] for a device with address `ip`, passing submitted settings
+    /// to `provision`.
+    pub fn new(ip: Ipv4Addr, provision: P) -> Self {
+        Portal {
+            dns: DnsResponder::new(ip),
+            server: Server::new(80),
+            handler: PortalHandler {
+                ip,
+                page: DEFAULT_PAGE,
+                provision,
+                provisioned: false,
+            },
+        }
+    }
+
+    /// Sets the setup page, which should post a form to `/provision`.
+    pub fn page(mut self, page: &'static str) -> Self {
+        self.handler.page = page;
+        self
+    }
+
+    /// Adds the DNS and HTTP sockets to `sockets`.
+    pub fn listen(&mut self, sockets: &mut SocketSet<'_>) -> Result<(), Error> {
+        self.dns.listen(sockets)?;
+        self.server.listen(sockets)
+    }
+
+    /// Answers DNS queries and HTTP requests, call after each poll of the interface.
+    pub fn poll(&mut self, sockets: &mut SocketSet<'_>) -> Result<(), Error> {
+        self.dns.poll(sockets);
+        self.server.poll(sockets, &mut self.handler)
+    }
+
+    /// Returns true once settings have been submitted and accepted.
+    pub fn is_provisioned(&self) -> bool {
+        self.handler.provisioned
+    }
+}
+
+struct PortalHandler<P: Provision> {
+    ip: Ipv4Addr,
+    page: &'static str,
+    provision: P,
+    provisioned: bool,
+}
+
+impl<P: Provision> Handler for PortalHandler<P> {
+    fn handle(&mut self, request: &Request) -> Response {
+        if request.path() == "/provision" {
+            if request.method() != "POST" {
+                return Response::text(405, "method not allowed").header("Allow", "POST");
+            }
+            let Some(fields) = parse_fields(request) else {
+                return Response::text(400, "invalid settings");
+            };
+            if !self.provision.provision(&fields) {
+                return Response::text(422, "settings rejected");
+            }
+            self.provisioned = true;
+            info!("provisioned");
+            return Response::text(200, "settings saved");
+        }
+
+        // Requests for other hosts, e.g. connectivity checks, are sent to the setup page.
+        let host = request.header("host").unwrap_or_default();
+        let host = host.split(':').next().unwrap_or_default();
+        if host.parse() != Ok(self.ip) || request.path() != "/" {
+            let location = alloc::format!("http://{}/", self.ip);
+            return Response::new(302).header("Location", &location);
+        }
+        Response::new(200)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .header("Cache-Control", "no-store")
+            .static_body(self.page.as_bytes())
+    }
+}
+
+/// Parses the fields of a form or JSON request body.
+fn parse_fields(request: &Request) -> Option<Vec<(String, String)>> {
+    let body = core::str::from_utf8(request.body()).ok()?;
+    let content_type = request.header("content-type").unwrap_or_default();
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    if media_type.eq_ignore_ascii_case("application/json") {
+        parse_json_object(body)
+    } else {
+        parse_form(body)
+    }
+}
+
+/// Parses an `application/x-www-form-urlencoded` body.
+fn parse_form(body: &str) -> Option<Vec<(String, String)>> {
+    let decode = |s: &str| encoding::percent_decode(&s.replace('+', " ")).ok();
+    body.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((decode(name)?, decode(value)?))
+        })
+        .collect()
+}
+
+/// Parses a JSON object whose values are all strings.
+fn parse_json_object(body: &str) -> Option<Vec<(String, String)>> {
+    json::parse_object(body)?
+        .into_iter()
+        .map(|(name, value)| Some((name, json::parse_whole_string(value)?)))
+        .collect()
+}
@@ -0,0 +1,968 @@
+#[cfg(feature = "alloc")]
+use alloc::borrow::Cow;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "compression")]
+use crate::inflate;
+#[cfg(feature = "alloc")]
+use crate::Error;
+
+/// How strictly response heads are parsed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Tolerates the quirks of embedded and legacy servers: bare `\n` line endings, a
+    /// missing reason phrase, whitespace before the header colon, folded header lines and
+    /// control characters in header values.
+    #[default]
+    Lenient,
+    /// Rejects anything that does not follow RFC 9112.
+    Strict,
+}
+
+/// Number of body bytes shown when formatting a response with `{}`.
+const DISPLAY_BODY_LIMIT: usize = 256;
+
+const DEFAULT_MAX_HEADERS: usize = 64;
+const DEFAULT_MAX_HEADER_BYTES: usize = 8 * 1024;
+
+/// Bounds on the size of a response head, protecting small devices from broken or
+/// malicious servers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeaderLimits {
+    /// Maximum number of headers.
+    pub(crate) max_headers: usize,
+    /// Maximum size in bytes of the status line and headers, including line endings.
+    pub(crate) max_header_bytes: usize,
+}
+
+impl Default for HeaderLimits {
+    fn default() -> Self {
+        HeaderLimits {
+            max_headers: DEFAULT_MAX_HEADERS,
+            max_header_bytes: DEFAULT_MAX_HEADER_BYTES,
+        }
+    }
+}
+
+impl HeaderLimits {
+    /// Constructs a new [`HeaderLimits`] with default limits.
+    pub fn new() -> Self {
+        HeaderLimits::default()
+    }
+
+    /// Sets the maximum number of headers.
+    pub fn max_headers(mut self, max_headers: usize) -> Self {
+        self.max_headers = max_headers;
+        self
+    }
+
+    /// Sets the maximum size in bytes of the status line and headers.
+    pub fn max_header_bytes(mut self, max_header_bytes: usize) -> Self {
+        self.max_header_bytes = max_header_bytes;
+        self
+    }
+}
+
+/// The reason a response could not be parsed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The status line is malformed.
+    StatusLine,
+    /// A line ends with a bare `\n` instead of `\r\n`.
+    BareLineFeed,
+    /// A header line is malformed or contains illegal bytes.
+    Header,
+    /// The chunked transfer coding of the body is malformed.
+    Chunk,
+    /// The data ended before the response was complete.
+    Incomplete,
+    /// The body could not be decompressed as its `Content-Encoding` says.
+    Encoding,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::StatusLine => write!(f, "malformed status line"),
+            ParseError::BareLineFeed => write!(f, "line terminated by a bare LF"),
+            ParseError::Header => write!(f, "malformed header line"),
+            ParseError::Chunk => write!(f, "malformed chunked body"),
+            ParseError::Incomplete => write!(f, "incomplete response"),
+            ParseError::Encoding => write!(f, "malformed compressed body"),
+        }
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+/// A parsed HTTP response.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// Minor version of HTTP/1.x used by the server.
+    minor_version: u8,
+    /// Status code, e.g. 200.
+    status: u16,
+    /// Reason phrase, e.g. "OK".
+    reason: String,
+    /// Headers in the order they were received.
+    headers: Vec<(String, String)>,
+    /// Body of the response.
+    body: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl HttpResponse {
+    /// Parses a complete response, everything after the header block is taken as the body.
+    /// A compressed body is kept as it is.
+    pub fn parse(data: &[u8], mode: ParseMode) -> Result<Self, Error> {
+        HttpResponse::parse_with_limits(data, mode, &HeaderLimits::default())
+    }
+
+    /// Parses a complete response, rejecting heads that exceed `limits`.
+    pub fn parse_with_limits(
+        data: &[u8],
+        mode: ParseMode,
+        limits: &HeaderLimits,
+    ) -> Result<Self, Error> {
+        let (head, len) = parse_head(data, mode, limits)?
+            .ok_or(Error::InvalidResponse(ParseError::Incomplete))?;
+        respond(head, Vec::from(&data[len..]))
+    }
+
+    /// Returns the minor version of HTTP/1.x used by the server.
+    pub fn minor_version(&self) -> u8 {
+        self.minor_version
+    }
+
+    /// Returns the status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns true if the status code is 2xx.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Returns the reason phrase.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Returns all headers as `(name, value)` pairs, in the order they were received.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Returns the value of the first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the values of every header called `name`, ignoring case, in the order they
+    /// were received.
+    pub fn header_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the values of every header called `name` joined by `", "`, which is
+    /// equivalent to the separate headers for list-valued fields (RFC 9110 section 5.3).
+    ///
+    /// Returns `None` for `Set-Cookie`, whose values may contain commas and so cannot be
+    /// combined; use [`HttpResponse::header_all`] instead.
+    pub fn header_combined(&self, name: &str) -> Option<String> {
+        if name.eq_ignore_ascii_case("set-cookie") {
+            return None;
+        }
+        let mut values = self.header_all(name);
+        let mut combined = String::from(values.next()?);
+        for value in values {
+            combined.push_str(", ");
+            combined.push_str(value);
+        }
+        Some(combined)
+    }
+
+    /// Returns the body, byte for byte as received.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the body as text, replacing invalid UTF-8 sequences with U+FFFD.
+    pub fn body_text_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+
+    /// Returns the body as text, or [`Error::InvalidUtf8`] if it is not valid UTF-8.
+    pub fn body_text_strict(&self) -> Result<&str, Error> {
+        core::str::from_utf8(&self.body).map_err(|_| Error::InvalidUtf8)
+    }
+
+    /// Consumes the response, returning the body.
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for HttpResponse {
+    /// Formats the status line and headers, with credentials redacted, followed by the body
+    /// as text. The body is truncated unless the alternate form (`{:#}`) is used.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "HTTP/1.{} {} {}\r\n",
+            self.minor_version, self.status, self.reason
+        )?;
+        for (name, value) in &self.headers {
+            let value = if is_sensitive_header(name) {
+                REDACTED
+            } else {
+                value
+            };
+            write!(f, "{}: {}\r\n", name, value)?;
+        }
+        write!(f, "\r\n")?;
+        if f.alternate() || self.body.len() <= DISPLAY_BODY_LIMIT {
+            write!(f, "{}", self.body_text_lossy())
+        } else {
+            let shown = String::from_utf8_lossy(&self.body[..DISPLAY_BODY_LIMIT]);
+            let more = self.body.len() - DISPLAY_BODY_LIMIT;
+            write!(f, "{}... ({} more bytes)", shown, more)
+        }
+    }
+}
+
+/// Receives a response body as it is received, e.g. to process a large body without holding
+/// it in memory.
+pub trait BodySink {
+    /// Called with the next part of the decoded body.
+    fn body(&mut self, chunk: &[u8]);
+}
+
+impl<F: FnMut(&[u8])> BodySink for F {
+    fn body(&mut self, chunk: &[u8]) {
+        self(chunk)
+    }
+}
+
+/// An incremental response parser, for use with any transport.
+///
+/// Bytes are fed in as they arrive and the response is returned once complete. Bodies
+/// framed by `Content-Length` or chunked transfer coding complete on their own, bodies
+/// delimited by the server closing the connection are completed by
+/// [`ResponseParser::finish`]. Interim `1xx` responses are skipped.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Default)]
+pub struct ResponseParser {
+    /// How strictly the head is parsed.
+    mode: ParseMode,
+    /// Bounds on the size of the head and of chunked framing.
+    limits: HeaderLimits,
+    /// Whether the response answers a HEAD request, and so has no body.
+    head_request: bool,
+    /// Received bytes that have not been parsed yet.
+    buffer: Vec<u8>,
+    /// The head of the current response once parsed, and how its body is framed.
+    head: Option<(Head, Framing)>,
+    /// The decoded body received so far.
+    body: Vec<u8>,
+    /// Size of the chunked trailer section received so far.
+    trailer_bytes: usize,
+    /// The largest size a compressed body is decompressed to, if it is decompressed.
+    #[cfg(feature = "compression")]
+    decompress: Option<usize>,
+    /// Whether a `100 Continue` interim response was received.
+    continued: bool,
+}
+
+#[cfg(feature = "alloc")]
+impl ResponseParser {
+    /// Constructs a new [`ResponseParser`] using lenient parsing and default limits.
+    pub fn new() -> Self {
+        ResponseParser::default()
+    }
+
+    /// Sets how strictly the head is parsed.
+    pub fn mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the bounds on the size of the head.
+    pub fn limits(mut self, limits: HeaderLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets whether the response answers a HEAD request, whose response has no body
+    /// whatever its `Content-Length` says.
+    pub fn head_request(mut self, head_request: bool) -> Self {
+        self.head_request = head_request;
+        self
+    }
+
+    /// Decompresses bodies with a `Content-Encoding` of gzip or deflate, failing with
+    /// [`Error::ResponseTooLarge`] if one would be larger than `limit` bytes once
+    /// decompressed. Bodies are passed on as received by default.
+    #[cfg(feature = "compression")]
+    pub fn decompress(mut self, limit: usize) -> Self {
+        self.decompress = Some(limit);
+        self
+    }
+
+    /// Feeds bytes received from the connection to the parser.
+    ///
+    /// Returns the response once it is complete. Any bytes following it are kept as the
+    /// start of the next response.
+    pub fn feed(&mut self, data: &[u8]) -> Result<Option<HttpResponse>, Error> {
+        self.buffer.extend_from_slice(data);
+        self.advance()
+    }
+
+    /// Feeds bytes like [`ResponseParser::feed`], passing the decoded body to `on_body` as it
+    /// is received instead of keeping it, so the response returned has an empty body.
+    pub fn feed_streaming(
+        &mut self,
+        data: &[u8],
+        on_body: &mut dyn BodySink,
+    ) -> Result<Option<HttpResponse>, Error> {
+        self.buffer.extend_from_slice(data);
+        self.advance_with(Some(on_body))
+    }
+
+    /// Completes the response after the server has closed the connection.
+    pub fn finish(&mut self) -> Result<HttpResponse, Error> {
+        if let Some(response) = self.advance()? {
+            return Ok(response);
+        }
+        match self.head.take() {
+            Some((head, Framing::Close)) => {
+                let body = core::mem::take(&mut self.body);
+                self.respond(head, body)
+            }
+            _ => Err(Error::InvalidResponse(ParseError::Incomplete)),
+        }
+    }
+
+    /// Whether the server has answered `100 Continue` without a final response yet, telling
+    /// the client to send the body.
+    pub(crate) fn continued(&self) -> bool {
+        self.continued && !self.has_head()
+    }
+
+    /// Whether the head of the final response has been parsed.
+    pub(crate) fn has_head(&self) -> bool {
+        self.head.is_some()
+    }
+
+    /// Makes the response with `head` and its `body`, decompressed if asked to.
+    fn respond(&self, head: Head, body: Vec<u8>) -> Result<HttpResponse, Error> {
+        #[cfg(feature = "compression")]
+        if let Some(limit) = self.decompress {
+            let (head, body) = decompress(head, body, limit)?;
+            return respond(head, body);
+        }
+        respond(head, body)
+    }
+
+    fn advance(&mut self) -> Result<Option<HttpResponse>, Error> {
+        self.advance_with(None)
+    }
+
+    /// Parses as much of the buffer as possible, passing the body to `on_body` if given.
+    fn advance_with(
+        &mut self,
+        mut on_body: Option<&mut dyn BodySink>,
+    ) -> Result<Option<HttpResponse>, Error> {
+        loop {
+            let Some((_, framing)) = self.head else {
+                let Some((head, len)) = parse_head(&self.buffer, self.mode, &self.limits)? else {
+                    return Ok(None);
+                };
+                self.buffer.drain(..len);
+                if (100..200).contains(&head.status) && head.status != 101 {
+                    self.continued |= head.status == 100;
+                    continue;
+                }
+                let framing = if self.head_request {
+                    Framing::Length(0)
+                } else {
+                    body_framing(&head)?
+                };
+                self.head = Some((head, framing));
+                self.trailer_bytes = 0;
+                continue;
+            };
+
+            let (framing, complete) = match framing {
+                Framing::Length(remaining) => {
+                    let n = remaining.min(self.buffer.len());
+                    self.body.extend(self.buffer.drain(..n));
+                    (Framing::Length(remaining - n), remaining == n)
+                }
+                Framing::Chunked(chunk) => {
+                    let (chunk, complete) = self.dechunk(chunk)?;
+                    (Framing::Chunked(chunk), complete)
+                }
+                Framing::Close => {
+                    self.body.append(&mut self.buffer);
+                    (Framing::Close, false)
+                }
+            };
+            if let Some(on_body) = on_body.as_deref_mut() {
+                if !self.body.is_empty() {
+                    on_body.body(&self.body);
+                    self.body.clear();
+                }
+            }
+            if !complete {
+                if let Some((_, current)) = &mut self.head {
+                    *current = framing;
+                }
+                return Ok(None);
+            }
+            if let Some((head, _)) = self.head.take() {
+                let body = core::mem::take(&mut self.body);
+                return self.respond(head, body).map(Some);
+            }
+        }
+    }
+
+    /// Decodes as much of a chunked body as has been received, returning the new state and
+    /// whether the body is complete.
+    fn dechunk(&mut self, mut chunk: Chunk) -> Result<(Chunk, bool), Error> {
+        loop {
+            chunk = match chunk {
+                Chunk::Size => {
+                    let Some(line) = self.take_line()? else {
+                        return Ok((chunk, false));
+                    };
+                    match parse_chunk_size(&line) {
+                        Some(0) => Chunk::Trailers,
+                        Some(size) => Chunk::Data(size),
+                        None => return Err(Error::InvalidResponse(ParseError::Chunk)),
+                    }
+                }
+                Chunk::Data(remaining) => {
+                    let n = remaining.min(self.buffer.len());
+                    self.body.extend(self.buffer.drain(..n));
+                    if n < remaining {
+                        return Ok((Chunk::Data(remaining - n), false));
+                    }
+                    Chunk::DataEnd
+                }
+                Chunk::DataEnd => match self.take_line()? {
+                    None => return Ok((chunk, false)),
+                    Some(line) if line.is_empty() => Chunk::Size,
+                    Some(_) => return Err(Error::InvalidResponse(ParseError::Chunk)),
+                },
+                Chunk::Trailers => match self.take_line()? {
+                    None => return Ok((chunk, false)),
+                    Some(line) if line.is_empty() => return Ok((chunk, true)),
+                    Some(line) => {
+                        // Trailer fields are not used, but still count towards the limits.
+                        self.trailer_bytes += line.len();
+                        if self.trailer_bytes > self.limits.max_header_bytes {
+                            return Err(Error::HeadersTooLarge);
+                        }
+                        Chunk::Trailers
+                    }
+                },
+            };
+        }
+    }
+
+    /// Removes the next line from the buffer and returns it without its line ending.
+    fn take_line(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let Some(end) = self.buffer.iter().position(|&b| b == b'\n') else {
+            if self.buffer.len() > self.limits.max_header_bytes {
+                return Err(Error::HeadersTooLarge);
+            }
+            return Ok(None);
+        };
+        let mut line: Vec<u8> = self.buffer.drain(..=end).collect();
+        line.pop();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        } else if self.mode == ParseMode::Strict {
+            return Err(Error::InvalidResponse(ParseError::BareLineFeed));
+        }
+        Ok(Some(line))
+    }
+}
+
+/// How the end of a response body is found.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Framing {
+    /// The body has this many bytes left.
+    Length(usize),
+    /// The body uses chunked transfer coding.
+    Chunked(Chunk),
+    /// The body ends when the connection is closed.
+    Close,
+}
+
+/// Progress through a chunked body.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Chunk {
+    /// Waiting for a chunk size line.
+    Size,
+    /// Reading chunk data, with this many bytes left.
+    Data(usize),
+    /// Waiting for the line ending after the chunk data.
+    DataEnd,
+    /// Reading the trailer section after the last chunk.
+    Trailers,
+}
+
+#[cfg(feature = "http-compat")]
+impl TryFrom<HttpResponse> for ::http::Response<Vec<u8>> {
+    type Error = ::http::Error;
+
+    fn try_from(response: HttpResponse) -> Result<Self, ::http::Error> {
+        let version = match response.minor_version {
+            0 => ::http::Version::HTTP_10,
+            _ => ::http::Version::HTTP_11,
+        };
+        let mut builder = ::http::Response::builder()
+            .status(response.status)
+            .version(version);
+        for (name, value) in &response.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        builder.body(response.body)
+    }
+}
+
+#[cfg(feature = "http-compat")]
+impl From<::http::Response<Vec<u8>>> for HttpResponse {
+    fn from(response: ::http::Response<Vec<u8>>) -> Self {
+        let (parts, body) = response.into_parts();
+        HttpResponse {
+            minor_version: u8::from(parts.version != ::http::Version::HTTP_10),
+            status: parts.status.as_u16(),
+            reason: String::from(parts.status.canonical_reason().unwrap_or("")),
+            headers: parts
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        String::from(name.as_str()),
+                        String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                    )
+                })
+                .collect(),
+            body,
+        }
+    }
+}
+
+/// Determines how the body following `head` is framed, per RFC 9112 section 6.3.
+#[cfg(feature = "alloc")]
+fn body_framing(head: &Head) -> Result<Framing, Error> {
+    if head.status == 204 || head.status == 304 {
+        return Ok(Framing::Length(0));
+    }
+    let chunked = head
+        .headers
+        .iter()
+        .filter(|(n, _)| n.eq_ignore_ascii_case("transfer-encoding"))
+        .flat_map(|(_, v)| v.split(','))
+        .last()
+        .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"));
+    if chunked {
+        return Ok(Framing::Chunked(Chunk::Size));
+    }
+    let content_length = head
+        .headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case("content-length"));
+    match content_length {
+        Some((_, value)) => parse_decimal(value)
+            .map(Framing::Length)
+            .ok_or(Error::InvalidResponse(ParseError::Header)),
+        None => Ok(Framing::Close),
+    }
+}
+
+/// Parses a string made only of ASCII digits.
+fn parse_decimal(value: &str) -> Option<usize> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// Parses a chunk size line, ignoring any chunk extensions.
+fn parse_chunk_size(line: &[u8]) -> Option<usize> {
+    let size = match line.iter().position(|&b| b == b';') {
+        Some(i) => trim(&line[..i]),
+        None => trim(line),
+    };
+    if size.is_empty() {
+        return None;
+    }
+    size.iter().try_fold(0usize, |acc, &b| {
+        let digit = (b as char).to_digit(16)?;
+        acc.checked_mul(16)?.checked_add(digit as usize)
+    })
+}
+
+/// The status line and headers of a response.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Head {
+    pub(crate) minor_version: u8,
+    pub(crate) status: u16,
+    pub(crate) reason: String,
+    pub(crate) headers: Vec<(String, String)>,
+}
+
+#[cfg(feature = "alloc")]
+impl Head {
+    pub(crate) fn with_body(self, body: Vec<u8>) -> HttpResponse {
+        HttpResponse {
+            minor_version: self.minor_version,
+            status: self.status,
+            reason: self.reason,
+            headers: self.headers,
+            body,
+        }
+    }
+}
+
+/// Makes the response with `head` and its `body`.
+#[cfg(feature = "alloc")]
+fn respond(head: Head, body: Vec<u8>) -> Result<HttpResponse, Error> {
+    Ok(head.with_body(body))
+}
+
+/// Decompresses `body` to at most `limit` bytes if `head` gives a `Content-Encoding` of gzip
+/// or deflate, removing the `Content-Encoding` and `Content-Length` headers that describe the
+/// compressed body. Bodies in other encodings, and empty bodies such as those of streamed
+/// responses, are kept as they are.
+#[cfg(feature = "compression")]
+fn decompress(mut head: Head, body: Vec<u8>, limit: usize) -> Result<(Head, Vec<u8>), Error> {
+    let is = |name: &str, wanted: &str| name.eq_ignore_ascii_case(wanted);
+    let coding = head
+        .headers
+        .iter()
+        .find(|(name, _)| is(name, "content-encoding"))
+        .map(|(_, value)| value.trim());
+    let decoded = match coding {
+        _ if body.is_empty() => return Ok((head, body)),
+        Some(coding) if is(coding, "gzip") || is(coding, "x-gzip") => inflate::gunzip(&body, limit),
+        Some(coding) if is(coding, "deflate") => inflate::zlib_or_raw(&body, limit),
+        _ => return Ok((head, body)),
+    };
+    let decoded = decoded.inspect_err(|_| warn!("failed to decompress the response body"))?;
+    trace!("decompressed {} bytes to {}", body.len(), decoded.len());
+    head.headers
+        .retain(|(name, _)| !is(name, "content-encoding") && !is(name, "content-length"));
+    Ok((head, decoded))
+}
+
+/// Parses the status line and headers at the start of `data`.
+///
+/// Returns `None` if the header block is not complete yet, otherwise the head and the
+/// number of bytes it occupied including the terminating empty line.
+#[cfg(all(feature = "alloc", not(feature = "httparse")))]
+pub(crate) fn parse_head(
+    data: &[u8],
+    mode: ParseMode,
+    limits: &HeaderLimits,
+) -> Result<Option<(Head, usize)>, Error> {
+    let mut pos = 0;
+    let mut status = None;
+    let mut headers: Vec<(String, String)> = Vec::new();
+
+    loop {
+        let Some(end) = data[pos..].iter().position(|&b| b == b'\n') else {
+            if data.len() > limits.max_header_bytes {
+                return Err(Error::HeadersTooLarge);
+            }
+            return Ok(None);
+        };
+        let mut line = &data[pos..pos + end];
+        pos += end + 1;
+        if pos > limits.max_header_bytes {
+            return Err(Error::HeadersTooLarge);
+        }
+        match line.strip_suffix(b"\r") {
+            Some(stripped) => line = stripped,
+            None if mode == ParseMode::Strict => {
+                return Err(Error::InvalidResponse(ParseError::BareLineFeed))
+            }
+            None => {}
+        }
+
+        let Some((minor_version, code, reason)) = &status else {
+            // Some servers send stray empty lines before the status line.
+            if line.is_empty() && mode == ParseMode::Lenient {
+                continue;
+            }
+            status = Some(
+                parse_status_line(line, mode)
+                    .ok_or(Error::InvalidResponse(ParseError::StatusLine))?,
+            );
+            continue;
+        };
+
+        if line.is_empty() {
+            check_content_length(&headers)?;
+            let head = Head {
+                minor_version: *minor_version,
+                status: *code,
+                reason: reason.clone(),
+                headers,
+            };
+            return Ok(Some((head, pos)));
+        }
+
+        if line[0] == b' ' || line[0] == b'\t' {
+            // Obsolete line folding continues the previous header value.
+            let previous = match headers.last_mut() {
+                Some((_, value)) if mode == ParseMode::Lenient => value,
+                _ => return Err(Error::InvalidResponse(ParseError::Header)),
+            };
+            let continuation = trim(line);
+            if !continuation.is_empty() {
+                previous.push(' ');
+                previous.push_str(&String::from_utf8_lossy(continuation));
+            }
+            continue;
+        }
+
+        if headers.len() == limits.max_headers {
+            return Err(Error::HeadersTooLarge);
+        }
+        headers.push(parse_header(line, mode).ok_or(Error::InvalidResponse(ParseError::Header))?);
+    }
+}
+
+/// Parses the status line and headers at the start of `data` using `httparse`.
+///
+/// `httparse` always accepts bare `\n` line endings, leading empty lines and a missing
+/// reason phrase, so these are checked separately in strict mode. It rejects control
+/// characters in header values in both modes.
+#[cfg(feature = "httparse")]
+pub(crate) fn parse_head(
+    data: &[u8],
+    mode: ParseMode,
+    limits: &HeaderLimits,
+) -> Result<Option<(Head, usize)>, Error> {
+    // Check the size first, as the built-in parser does, so that an oversized head is
+    // reported as such rather than by whatever httparse trips over inside it.
+    match head_len(&data[..data.len().min(limits.max_header_bytes + 1)]) {
+        Some(len) if len > limits.max_header_bytes => return Err(Error::HeadersTooLarge),
+        None if data.len() > limits.max_header_bytes => return Err(Error::HeadersTooLarge),
+        _ => {}
+    }
+    let mut headers = alloc::vec![httparse::EMPTY_HEADER; limits.max_headers];
+    let mut response = httparse::Response::new(&mut headers);
+    let mut config = httparse::ParserConfig::default();
+    if mode == ParseMode::Lenient {
+        config
+            .allow_spaces_after_header_name_in_responses(true)
+            .allow_obsolete_multiline_headers_in_responses(true)
+            .allow_multiple_spaces_in_response_status_delimiters(true);
+    }
+    let len = match config.parse_response(&mut response, data) {
+        Ok(httparse::Status::Complete(len)) => len,
+        Ok(httparse::Status::Partial) if data.len() > limits.max_header_bytes => {
+            return Err(Error::HeadersTooLarge)
+        }
+        Ok(httparse::Status::Partial) => return Ok(None),
+        Err(httparse::Error::TooManyHeaders) => return Err(Error::HeadersTooLarge),
+        Err(httparse::Error::Status | httparse::Error::Version) => {
+            return Err(Error::InvalidResponse(ParseError::StatusLine))
+        }
+        Err(_) => return Err(Error::InvalidResponse(ParseError::Header)),
+    };
+    if len > limits.max_header_bytes {
+        return Err(Error::HeadersTooLarge);
+    }
+    if mode == ParseMode::Strict {
+        check_strict_lines(&data[..len])?;
+    }
+
+    let headers: Vec<(String, String)> = response
+        .headers
+        .iter()
+        .map(|header| {
+            // Folded lines are kept verbatim by httparse, join them with a single space.
+            let mut value = String::new();
+            for (i, line) in header.value.split(|&b| b == b'\n').enumerate() {
+                let line = trim(line.strip_suffix(b"\r").unwrap_or(line));
+                if i > 0 && !line.is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(&String::from_utf8_lossy(line));
+            }
+            (String::from(header.name), value)
+        })
+        .collect();
+    check_content_length(&headers)?;
+
+    let head = Head {
+        minor_version: response.version.unwrap_or(1),
+        status: response.code.unwrap_or(0),
+        reason: String::from(response.reason.unwrap_or("")),
+        headers,
+    };
+    Ok(Some((head, len)))
+}
+
+/// Returns the length of the head at the start of `data` including the empty line that
+/// ends it, or `None` if `data` holds no empty line.
+#[cfg(feature = "httparse")]
+fn head_len(data: &[u8]) -> Option<usize> {
+    (0..data.len()).find_map(|i| match &data[i..] {
+        [b'\n', b'\n', ..] => Some(i + 2),
+        [b'\n', b'\r', b'\n', ..] => Some(i + 3),
+        _ => None,
+    })
+}
+
+/// Applies the strict mode checks that `httparse` does not make: CRLF line endings, no
+/// leading empty lines, and a space after the status code.
+#[cfg(feature = "httparse")]
+fn check_strict_lines(head: &[u8]) -> Result<(), Error> {
+    if head
+        .iter()
+        .enumerate()
+        .any(|(i, &b)| b == b'\n' && (i == 0 || head[i - 1] != b'\r'))
+    {
+        return Err(Error::InvalidResponse(ParseError::BareLineFeed));
+    }
+    // "HTTP/1.x NNN " is 13 bytes long.
+    if head.get(12) != Some(&b' ') {
+        return Err(Error::InvalidResponse(ParseError::StatusLine));
+    }
+    Ok(())
+}
+
+/// Rejects responses with conflicting `Content-Length` headers, identical duplicates are
+/// allowed as permitted by RFC 9112 section 6.3.
+#[cfg(feature = "alloc")]
+pub(crate) fn check_content_length(headers: &[(String, String)]) -> Result<(), Error> {
+    let mut lengths = headers
+        .iter()
+        .filter(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+        .map(|(_, v)| v);
+    if let Some(first) = lengths.next() {
+        if lengths.any(|v| v != first) {
+            return Err(Error::InvalidResponse(ParseError::Header));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "alloc")]
+fn parse_status_line(line: &[u8], mode: ParseMode) -> Option<(u8, u16, String)> {
+    let rest = line.strip_prefix(b"HTTP/1.")?;
+    let (&minor, rest) = rest.split_first()?;
+    if !minor.is_ascii_digit() {
+        return None;
+    }
+    let rest = rest.strip_prefix(b" ")?;
+    if rest.len() < 3 || !rest[..3].iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let status = rest[..3]
+        .iter()
+        .fold(0u16, |acc, &d| acc * 10 + u16::from(d - b'0'));
+    let reason = match rest[3..].split_first() {
+        Some((b' ', reason)) => reason,
+        // RFC 9112 requires the space even when the reason phrase is empty.
+        None if mode == ParseMode::Lenient => &[],
+        _ => return None,
+    };
+    if mode == ParseMode::Strict && reason.iter().any(|&b| is_illegal_value_byte(b)) {
+        return None;
+    }
+    Some((
+        minor - b'0',
+        status,
+        String::from_utf8_lossy(reason).into_owned(),
+    ))
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) fn parse_header(line: &[u8], mode: ParseMode) -> Option<(String, String)> {
+    let colon = line.iter().position(|&b| b == b':')?;
+    let mut name = &line[..colon];
+    let value = trim(&line[colon + 1..]);
+    match mode {
+        ParseMode::Strict => {
+            if !name.iter().all(|&b| is_token_byte(b))
+                || value.iter().any(|&b| is_illegal_value_byte(b))
+            {
+                return None;
+            }
+        }
+        ParseMode::Lenient => {
+            name = trim(name);
+            if name.iter().any(|&b| b == b' ' || b == b'\t') {
+                return None;
+            }
+        }
+    }
+    if name.is_empty() {
+        return None;
+    }
+    Some((
+        String::from_utf8_lossy(name).into_owned(),
+        String::from_utf8_lossy(value).into_owned(),
+    ))
+}
+
+/// Replaces the values of sensitive headers when requests and responses are formatted.
+pub(crate) const REDACTED: &str = "[redacted]";
+
+/// Returns true if the header called `name` carries credentials that must not be logged.
+pub(crate) fn is_sensitive_header(name: &str) -> bool {
+    [
+        "authorization",
+        "proxy-authorization",
+        "cookie",
+        "set-cookie",
+    ]
+    .iter()
+    .any(|sensitive| name.trim().eq_ignore_ascii_case(sensitive))
+}
+
+/// Removes leading and trailing spaces and tabs.
+pub(crate) fn trim(mut bytes: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = bytes {
+        bytes = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = bytes {
+        bytes = rest;
+    }
+    bytes
+}
+
+/// Returns true if `b` may appear in a header name (an RFC 9110 `tchar`).
+pub(crate) fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// Returns true if `b` is a control character other than horizontal tab.
+pub(crate) fn is_illegal_value_byte(b: u8) -> bool {
+    (b < 0x20 && b != b'\t') || b == 0x7f
+}
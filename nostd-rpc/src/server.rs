@@ -0,0 +1,610 @@
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use smoltcp::iface::{SocketHandle, SocketSet};
+use smoltcp::socket::tcp;
+
+use crate::response::{self, HeaderLimits, ParseMode};
+use crate::Error;
+
+mod assets;
+mod router;
+mod tls;
+
+pub use assets::{Asset, StaticAssets};
+pub use router::{Params, Route, Router};
+pub use tls::{TlsAcceptor, TlsSession};
+
+const DEFAULT_CONNECTIONS: usize = 2;
+const DEFAULT_BUFFER_SIZE: usize = 1024;
+const DEFAULT_MAX_BODY: usize = 8 * 1024;
+/// Most plaintext encrypted at a time, the largest TLS record payload.
+const TLS_CHUNK: usize = 16 * 1024;
+
+/// An HTTP request received by the [`Server`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Request {
+    method: String,
+    target: String,
+    minor_version: u8,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// Returns the request method, e.g. "GET".
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Returns the request target, the path and query.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the path of the request target.
+    pub fn path(&self) -> &str {
+        self.target
+            .split_once('?')
+            .map_or(self.target.as_str(), |(path, _)| path)
+    }
+
+    /// Returns the query of the request target, without the '?'.
+    pub fn query(&self) -> Option<&str> {
+        self.target.split_once('?').map(|(_, query)| query)
+    }
+
+    /// Returns the minor version of HTTP/1.x the request was sent with.
+    pub fn minor_version(&self) -> u8 {
+        self.minor_version
+    }
+
+    /// Returns the headers in the order they were received.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Returns the value of the first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the body.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns true if the client wants the connection kept open after the response, which
+    /// is the default from HTTP/1.1.
+    pub fn keep_alive(&self) -> bool {
+        let has_option = |option: &str| {
+            self.header("connection").is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|v| v.trim().eq_ignore_ascii_case(option))
+            })
+        };
+        if self.minor_version == 0 {
+            has_option("keep-alive")
+        } else {
+            !has_option("close")
+        }
+    }
+}
+
+/// An HTTP response to be sent by the [`Server`].
+///
+/// `Content-Length` and `Connection` are set by the server and ignored if added as headers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Cow<'static, [u8]>,
+}
+
+impl Response {
+    /// Constructs a new [`Response`] with `status` and an empty body.
+    pub fn new(status: u16) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Cow::Borrowed(&[]),
+        }
+    }
+
+    /// Constructs a new [`Response`] with `status` and a plain text body.
+    pub fn text(status: u16, body: &str) -> Self {
+        Response::new(status)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(body.as_bytes())
+    }
+
+    /// Constructs a new [`Response`] with `status` and a JSON body.
+    pub fn json(status: u16, body: &str) -> Self {
+        Response::new(status)
+            .header("Content-Type", "application/json")
+            .body(body.as_bytes())
+    }
+
+    /// Adds a header.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((String::from(name), String::from(value)));
+        self
+    }
+
+    /// Sets the body.
+    pub fn body(mut self, body: &[u8]) -> Self {
+        self.body = Cow::Owned(Vec::from(body));
+        self
+    }
+
+    /// Sets the body to static data, e.g. in flash, which is sent without being copied.
+    pub fn static_body(mut self, body: &'static [u8]) -> Self {
+        self.body = Cow::Borrowed(body);
+        self
+    }
+
+    /// Returns the status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Serializes the response, without the body for a response to a HEAD request.
+    fn serialize(self, head_only: bool, keep_alive: bool) -> Outgoing {
+        let mut out = Vec::with_capacity(128);
+        out.extend_from_slice(
+            alloc::format!(
+                "HTTP/1.1 {} {}\r\n",
+                self.status,
+                reason_phrase(self.status)
+            )
+            .as_bytes(),
+        );
+        for (name, value) in &self.headers {
+            if name.eq_ignore_ascii_case("content-length")
+                || name.eq_ignore_ascii_case("connection")
+            {
+                continue;
+            }
+            out.extend_from_slice(alloc::format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        // 1xx, 204 and 304 responses cannot have a body.
+        if self.status >= 200 && self.status != 204 && self.status != 304 {
+            out.extend_from_slice(
+                alloc::format!("Content-Length: {}\r\n", self.body.len()).as_bytes(),
+            );
+        }
+        if !keep_alive {
+            out.extend_from_slice(b"Connection: close\r\n");
+        }
+        out.extend_from_slice(b"\r\n");
+        Outgoing {
+            head: out,
+            body: if head_only {
+                Cow::Borrowed(&[])
+            } else {
+                self.body
+            },
+            sent: 0,
+        }
+    }
+}
+
+/// Handles requests received by the [`Server`].
+pub trait Handler {
+    /// Returns the response to `request`.
+    fn handle(&mut self, request: &Request) -> Response;
+}
+
+impl<F: FnMut(&Request) -> Response> Handler for F {
+    fn handle(&mut self, request: &Request) -> Response {
+        self(request)
+    }
+}
+
+/// A serialized response being written to a socket.
+#[derive(Debug, Default)]
+struct Outgoing {
+    head: Vec<u8>,
+    body: Cow<'static, [u8]>,
+    /// Number of bytes written, of the head then the body.
+    sent: usize,
+}
+
+impl Outgoing {
+    /// Returns the next data to write.
+    fn remaining(&self) -> &[u8] {
+        match self.sent.checked_sub(self.head.len()) {
+            Some(body_sent) => &self.body[body_sent..],
+            None => &self.head[self.sent..],
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sent == self.head.len() + self.body.len()
+    }
+}
+
+/// A connection slot of the [`Server`].
+struct Connection {
+    handle: SocketHandle,
+    /// Whether the socket has accepted a connection since it last listened.
+    accepted: bool,
+    /// The TLS session of the connection, if the server uses TLS.
+    session: Option<Box<dyn TlsSession>>,
+    /// TLS records not yet written to the socket.
+    tls_out: Vec<u8>,
+    /// Data received that is not part of a handled request yet.
+    received: Vec<u8>,
+    /// Response data not yet written to the socket.
+    pending: Outgoing,
+    /// Whether to close the connection once `pending` has been written.
+    close: bool,
+}
+
+impl Connection {
+    fn new(handle: SocketHandle) -> Self {
+        Connection {
+            handle,
+            accepted: false,
+            session: None,
+            tls_out: Vec::new(),
+            received: Vec::new(),
+            pending: Outgoing::default(),
+            close: false,
+        }
+    }
+
+    /// Writes as much of the pending response as the socket accepts, encrypting it first if
+    /// the connection uses TLS.
+    fn write(&mut self, socket: &mut tcp::Socket<'_>) -> Result<(), Error> {
+        while socket.can_send() {
+            if !self.tls_out.is_empty() {
+                let sent = socket
+                    .send_slice(&self.tls_out)
+                    .map_err(Error::SendFailed)?;
+                self.tls_out.drain(..sent);
+                continue;
+            }
+            if self.pending.is_empty() {
+                break;
+            }
+            match &mut self.session {
+                Some(session) if session.is_handshaking() => break,
+                Some(session) => {
+                    let remaining = self.pending.remaining();
+                    let chunk = &remaining[..remaining.len().min(TLS_CHUNK)];
+                    session.write(chunk, &mut self.tls_out)?;
+                    self.pending.sent += chunk.len();
+                }
+                None => {
+                    let sent = socket
+                        .send_slice(self.pending.remaining())
+                        .map_err(Error::SendFailed)?;
+                    self.pending.sent += sent;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A small HTTP/1.1 server, serving a fixed number of connections at once on one port.
+///
+/// The server does not own the interface: add its sockets to the socket set with
+/// [`listen`](Server::listen), then call [`poll`](Server::poll) after each poll of the
+/// interface.
+pub struct Server {
+    port: u16,
+    max_connections: usize,
+    buffer_size: usize,
+    limits: HeaderLimits,
+    max_body: usize,
+    tls: Option<Box<dyn TlsAcceptor>>,
+    connections: Vec<Connection>,
+}
+
+impl Server {
+    /// Constructs a new [`Server`] for `port`, with 2 connections, 1 KiB socket buffers and
+    /// request bodies of up to 8 KiB.
+    pub fn new(port: u16) -> Self {
+        Server {
+            port,
+            max_connections: DEFAULT_CONNECTIONS,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            limits: HeaderLimits::default(),
+            max_body: DEFAULT_MAX_BODY,
+            tls: None,
+            connections: Vec::new(),
+        }
+    }
+
+    /// Sets the number of connections served at once.
+    pub fn connections(mut self, connections: usize) -> Self {
+        self.max_connections = connections;
+        self
+    }
+
+    /// Sets the size of the receive and transmit buffers of each socket.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Sets the limits on request headers, larger requests are rejected with status 431.
+    pub fn limits(mut self, limits: HeaderLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets the largest request body accepted, larger requests are rejected with status 413.
+    pub fn max_body(mut self, max_body: usize) -> Self {
+        self.max_body = max_body;
+        self
+    }
+
+    /// Serves HTTPS, terminating TLS with sessions from `acceptor`, which holds the device
+    /// certificate and private key.
+    pub fn tls<A: TlsAcceptor + 'static>(mut self, acceptor: A) -> Self {
+        self.tls = Some(Box::new(acceptor));
+        self
+    }
+
+    /// Adds a listening socket to `sockets` for each connection.
+    pub fn listen(&mut self, sockets: &mut SocketSet<'_>) -> Result<(), Error> {
+        while self.connections.len() < self.max_connections {
+            let rx_buffer = tcp::SocketBuffer::new(vec![0; self.buffer_size]);
+            let tx_buffer = tcp::SocketBuffer::new(vec![0; self.buffer_size]);
+            let mut socket = tcp::Socket::new(rx_buffer, tx_buffer);
+            socket.listen(self.port).map_err(Error::Listen)?;
+            self.connections.push(Connection::new(sockets.add(socket)));
+        }
+        Ok(())
+    }
+
+    /// Reads requests from the sockets, responds using `handler` and listens again on closed
+    /// sockets. Malformed requests are answered with an error status and the connection closed.
+    pub fn poll<H: Handler>(
+        &mut self,
+        sockets: &mut SocketSet<'_>,
+        handler: &mut H,
+    ) -> Result<(), Error> {
+        for connection in &mut self.connections {
+            let socket = sockets.get_mut::<tcp::Socket>(connection.handle);
+            if !socket.is_open() {
+                *connection = Connection::new(connection.handle);
+                socket.listen(self.port).map_err(Error::Listen)?;
+                continue;
+            }
+            if socket.is_active() && !connection.accepted {
+                connection.accepted = true;
+                debug!("accepted connection on port {}", self.port);
+                connection.session = self.tls.as_mut().map(|tls| tls.accept());
+            }
+
+            if socket.can_recv() {
+                let Connection {
+                    session,
+                    tls_out,
+                    received,
+                    ..
+                } = connection;
+                let result = socket
+                    .recv(|data| {
+                        let result = match session {
+                            Some(session) => session.read_tls(data, received, tls_out),
+                            None => {
+                                received.extend_from_slice(data);
+                                Ok(())
+                            }
+                        };
+                        (data.len(), result)
+                    })
+                    .map_err(Error::RecvFailed)?;
+                if result.is_err() {
+                    // The alert describing a TLS error is not sent, the connection is dropped.
+                    warn!("TLS error, aborting connection");
+                    socket.abort();
+                    continue;
+                }
+            }
+
+            // Requests are answered in order, the next once the previous response is written.
+            if connection.pending.is_empty() && !connection.close {
+                match parse_request(&connection.received, &self.limits, self.max_body) {
+                    Ok(Some((request, len))) => {
+                        connection.received.drain(..len);
+                        let keep_alive = request.keep_alive();
+                        let response = handler.handle(&request);
+                        debug!(
+                            "{} {} -> {}",
+                            request.method(),
+                            request.path(),
+                            response.status
+                        );
+                        connection.pending =
+                            response.serialize(request.method == "HEAD", keep_alive);
+                        connection.close = !keep_alive;
+                    }
+                    Ok(None) => {}
+                    Err(status) => {
+                        debug!("rejected request with status {}", status);
+                        connection.received.clear();
+                        connection.pending = Response::new(status).serialize(false, false);
+                        connection.close = true;
+                    }
+                }
+            }
+
+            if connection.write(socket).is_err() {
+                socket.abort();
+                continue;
+            }
+            // Close once the response is written if asked to, or if the client closed its side.
+            let finished = connection.close || socket.state() == tcp::State::CloseWait;
+            if connection.pending.is_empty() && connection.tls_out.is_empty() && finished {
+                match connection.session.take() {
+                    // Send the TLS close_notify alert before closing the socket.
+                    Some(mut session) => {
+                        session.close(&mut connection.tls_out);
+                        connection.close = true;
+                    }
+                    None => {
+                        debug!("closing connection");
+                        socket.close();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the request at the start of `data`.
+///
+/// Returns `None` if the request is not complete yet, otherwise the request and the number of
+/// bytes it occupied. Invalid requests return the status code to reject them with.
+pub(crate) fn parse_request(
+    data: &[u8],
+    limits: &HeaderLimits,
+    max_body: usize,
+) -> Result<Option<(Request, usize)>, u16> {
+    let mut pos = 0;
+    let mut request_line = None;
+    let mut headers = Vec::new();
+
+    let head_len = loop {
+        let Some(end) = data[pos..].iter().position(|&b| b == b'\n') else {
+            if data.len() > limits.max_header_bytes {
+                return Err(431);
+            }
+            return Ok(None);
+        };
+        let line = &data[pos..pos + end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        pos += end + 1;
+        if pos > limits.max_header_bytes {
+            return Err(431);
+        }
+
+        if request_line.is_none() {
+            // RFC 9112 allows empty lines before the request line to be ignored.
+            if !line.is_empty() {
+                request_line = Some(parse_request_line(line)?);
+            }
+            continue;
+        }
+        if line.is_empty() {
+            break pos;
+        }
+        if headers.len() == limits.max_headers {
+            return Err(431);
+        }
+        headers.push(response::parse_header(line, ParseMode::Strict).ok_or(400u16)?);
+    };
+    let Some((method, target, minor_version)) = request_line else {
+        return Ok(None);
+    };
+
+    response::check_content_length(&headers).map_err(|_| 400u16)?;
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    };
+    if header("transfer-encoding").is_some() {
+        return Err(501);
+    }
+    let body_len = match header("content-length") {
+        Some(len) if !len.is_empty() && len.bytes().all(|b| b.is_ascii_digit()) => {
+            len.parse().map_err(|_| 413u16)?
+        }
+        Some(_) => return Err(400),
+        None => 0,
+    };
+    if body_len > max_body {
+        return Err(413);
+    }
+    let Some(body) = data.get(head_len..head_len + body_len) else {
+        return Ok(None);
+    };
+
+    let request = Request {
+        method,
+        target,
+        minor_version,
+        headers,
+        body: Vec::from(body),
+    };
+    Ok(Some((request, head_len + body_len)))
+}
+
+/// Parses a request line into the method, target and minor version.
+fn parse_request_line(line: &[u8]) -> Result<(String, String, u8), u16> {
+    let line = core::str::from_utf8(line).map_err(|_| 400u16)?;
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(400);
+    };
+    if method.is_empty() || !method.bytes().all(response::is_token_byte) {
+        return Err(400);
+    }
+    if target.is_empty() || target.bytes().any(|b| b.is_ascii_control()) {
+        return Err(400);
+    }
+    let minor_version = match version {
+        "HTTP/1.0" => 0,
+        "HTTP/1.1" => 1,
+        _ if version.starts_with("HTTP/") => return Err(505),
+        _ => return Err(400),
+    };
+    Ok((String::from(method), String::from(target), minor_version))
+}
+
+/// Returns the reason phrase for common status codes.
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        _ => "",
+    }
+}
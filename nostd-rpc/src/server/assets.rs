@@ -0,0 +1,103 @@
+use super::{Handler, Request, Response};
+
+/// A file embedded in the firmware at compile time, e.g. with `include_bytes!`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Asset {
+    /// The request path the asset is served at, e.g. "/index.html".
+    pub path: &'static str,
+    /// The value of the `Content-Type` header, e.g. "text/html; charset=utf-8".
+    pub content_type: &'static str,
+    /// The contents of the file.
+    pub data: &'static [u8],
+    /// The contents compressed with gzip, served instead of `data` to clients that accept it.
+    pub gzip: Option<&'static [u8]>,
+}
+
+/// Serves [`Asset`]s from flash, for hosting a small web UI from the firmware.
+///
+/// "/" serves "/index.html". Assets are sent without being copied into RAM. If a build ID is
+/// set it is used as the entity tag of every asset, so browsers can revalidate cached assets
+/// with `If-None-Match` and get a `304 Not Modified` until the firmware changes.
+#[derive(Clone, Copy, Debug)]
+pub struct StaticAssets {
+    assets: &'static [Asset],
+    build_id: Option<&'static str>,
+}
+
+impl StaticAssets {
+    /// Constructs a new [`StaticAssets`] serving `assets`.
+    pub fn new(assets: &'static [Asset]) -> Self {
+        StaticAssets {
+            assets,
+            build_id: None,
+        }
+    }
+
+    /// Sets the build ID used as the entity tag, e.g. a commit hash set at compile time.
+    pub fn build_id(mut self, build_id: &'static str) -> Self {
+        self.build_id = Some(build_id);
+        self
+    }
+
+    /// Returns the asset served at `path`.
+    pub fn find(&self, path: &str) -> Option<&'static Asset> {
+        let path = if path == "/" { "/index.html" } else { path };
+        self.assets.iter().find(|asset| asset.path == path)
+    }
+}
+
+impl Handler for StaticAssets {
+    fn handle(&mut self, request: &Request) -> Response {
+        let Some(asset) = self.find(request.path()) else {
+            return Response::text(404, "not found");
+        };
+        if request.method() != "GET" && request.method() != "HEAD" {
+            return Response::text(405, "method not allowed").header("Allow", "GET, HEAD");
+        }
+
+        let etag = self.build_id.map(|id| alloc::format!("\"{}\"", id));
+        if let (Some(etag), Some(if_none_match)) = (&etag, request.header("if-none-match")) {
+            let matches = if_none_match
+                .split(',')
+                .any(|tag| tag.trim() == "*" || tag.trim().trim_start_matches("W/") == etag);
+            if matches {
+                return Response::new(304).header("ETag", etag);
+            }
+        }
+
+        let mut response = Response::new(200).header("Content-Type", asset.content_type);
+        if let Some(etag) = &etag {
+            response = response.header("ETag", etag);
+        }
+        match asset.gzip {
+            Some(gzip) => {
+                response = response.header("Vary", "Accept-Encoding");
+                if accepts_gzip(request) {
+                    response
+                        .header("Content-Encoding", "gzip")
+                        .static_body(gzip)
+                } else {
+                    response.static_body(asset.data)
+                }
+            }
+            None => response.static_body(asset.data),
+        }
+    }
+}
+
+/// Returns true if the `Accept-Encoding` header of `request` allows gzip.
+fn accepts_gzip(request: &Request) -> bool {
+    let Some(accept) = request.header("accept-encoding") else {
+        return false;
+    };
+    accept.split(',').any(|coding| {
+        let mut parts = coding.split(';');
+        let name = parts.next().unwrap_or_default().trim();
+        let rejected = parts.any(|p| {
+            p.trim()
+                .strip_prefix("q=")
+                .is_some_and(|q| q.trim().parse::<f32>().is_ok_and(|q| q == 0.0))
+        });
+        (name.eq_ignore_ascii_case("gzip") || name == "*") && !rejected
+    })
+}
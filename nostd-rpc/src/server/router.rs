@@ -0,0 +1,132 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{Handler, Request, Response};
+
+/// Path parameters captured by a route pattern, e.g. `id` in `/sensors/:id`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Params<'a> {
+    params: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Params<'a> {
+    /// Returns the value of the parameter called `name`.
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.params
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// Returns the parameters in the order they appear in the pattern.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> + '_ {
+        self.params.iter().copied()
+    }
+}
+
+/// Handles requests matched by a route of the [`Router`].
+pub trait Route {
+    /// Returns the response to `request`, whose path matched the route with `params`.
+    fn handle(&mut self, request: &Request, params: &Params<'_>) -> Response;
+}
+
+impl<F: FnMut(&Request, &Params<'_>) -> Response> Route for F {
+    fn handle(&mut self, request: &Request, params: &Params<'_>) -> Response {
+        self(request, params)
+    }
+}
+
+/// Dispatches requests to handlers by method and path.
+///
+/// Patterns are matched segment by segment, a segment starting with ':' captures any one
+/// path segment as a parameter. Routes are tried in the order they were added. GET routes also
+/// match HEAD requests. A path that matches a route for another method is answered with 405,
+/// anything else goes to the fallback handler, which responds with 404 by default.
+#[derive(Default)]
+pub struct Router<'a> {
+    routes: Vec<(String, String, Box<dyn Route + 'a>)>,
+    fallback: Option<Box<dyn Handler + 'a>>,
+}
+
+impl<'a> Router<'a> {
+    /// Constructs a new [`Router`] with no routes.
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    /// Adds a route for `method` requests to paths matching `pattern`.
+    pub fn route<R: Route + 'a>(mut self, method: &str, pattern: &str, route: R) -> Self {
+        self.routes
+            .push((String::from(method), String::from(pattern), Box::new(route)));
+        self
+    }
+
+    /// Adds a route for GET and HEAD requests.
+    pub fn get<R: Route + 'a>(self, pattern: &str, route: R) -> Self {
+        self.route("GET", pattern, route)
+    }
+
+    /// Adds a route for POST requests.
+    pub fn post<R: Route + 'a>(self, pattern: &str, route: R) -> Self {
+        self.route("POST", pattern, route)
+    }
+
+    /// Adds a route for PUT requests.
+    pub fn put<R: Route + 'a>(self, pattern: &str, route: R) -> Self {
+        self.route("PUT", pattern, route)
+    }
+
+    /// Adds a route for DELETE requests.
+    pub fn delete<R: Route + 'a>(self, pattern: &str, route: R) -> Self {
+        self.route("DELETE", pattern, route)
+    }
+
+    /// Sets the handler for requests that match no route.
+    pub fn fallback<H: Handler + 'a>(mut self, handler: H) -> Self {
+        self.fallback = Some(Box::new(handler));
+        self
+    }
+}
+
+impl Handler for Router<'_> {
+    fn handle(&mut self, request: &Request) -> Response {
+        let mut path_matched = false;
+        for (method, pattern, route) in &mut self.routes {
+            let Some(params) = match_path(pattern, request.path()) else {
+                continue;
+            };
+            let method_matches =
+                method == request.method() || (method == "GET" && request.method() == "HEAD");
+            if method_matches {
+                return route.handle(request, &params);
+            }
+            path_matched = true;
+        }
+        if path_matched {
+            return Response::text(405, "method not allowed");
+        }
+        match &mut self.fallback {
+            Some(fallback) => fallback.handle(request),
+            None => Response::text(404, "not found"),
+        }
+    }
+}
+
+/// Matches `path` against `pattern`, returning the captured parameters.
+fn match_path<'a>(pattern: &'a str, path: &'a str) -> Option<Params<'a>> {
+    let mut params = Params::default();
+    let mut segments = path.split('/');
+    for expected in pattern.split('/') {
+        let segment = segments.next()?;
+        match expected.strip_prefix(':') {
+            Some(name) if !segment.is_empty() => params.params.push((name, segment)),
+            None if expected == segment => {}
+            _ => return None,
+        }
+    }
+    if segments.next().is_some() {
+        return None;
+    }
+    Some(params)
+}
@@ -0,0 +1,39 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::Error;
+
+/// One side of a TLS connection, provided by a TLS implementation.
+///
+/// The [`Server`](super::Server) or [`HttpClient`](crate::http::HttpClient) passes records
+/// received from the peer to the session and writes the records it produces to the socket,
+/// the session never touches the socket itself.
+pub trait TlsSession {
+    /// Processes TLS records received from the peer, which may be partial. Decrypted
+    /// application data is appended to `plaintext`, and handshake records or alerts to send
+    /// in reply are appended to `outgoing`.
+    ///
+    /// An error, usually [`Error::Tls`], aborts the connection.
+    fn read_tls(
+        &mut self,
+        ciphertext: &[u8],
+        plaintext: &mut Vec<u8>,
+        outgoing: &mut Vec<u8>,
+    ) -> Result<(), Error>;
+
+    /// Returns true until the handshake is complete and application data can be written.
+    fn is_handshaking(&self) -> bool;
+
+    /// Encrypts application data, appending the records to `outgoing`.
+    fn write(&mut self, plaintext: &[u8], outgoing: &mut Vec<u8>) -> Result<(), Error>;
+
+    /// Appends a close_notify alert to `outgoing`.
+    fn close(&mut self, outgoing: &mut Vec<u8>);
+}
+
+/// Creates a [`TlsSession`] for each connection accepted by a server using TLS, usually
+/// holding the device certificate chain and private key.
+pub trait TlsAcceptor {
+    /// Returns a session for a newly accepted connection.
+    fn accept(&mut self) -> Box<dyn TlsSession>;
+}
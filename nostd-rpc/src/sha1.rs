@@ -0,0 +1,72 @@
+//! SHA-1 (FIPS 180-4), only used to check the `Sec-WebSocket-Accept` header of WebSocket
+//! handshakes. It is not collision resistant and must not be used for anything else.
+
+/// Initial hash value.
+const H0: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+/// Returns the SHA-1 hash of `data`.
+pub(crate) fn digest(data: &[u8]) -> [u8; 20] {
+    let mut state = H0;
+    let bit_len = (data.len() as u64).wrapping_mul(8);
+
+    let mut blocks = data.chunks_exact(64);
+    for block in &mut blocks {
+        let mut bytes = [0; 64];
+        bytes.copy_from_slice(block);
+        compress(&mut state, &bytes);
+    }
+
+    // The rest of the input, the 0x80 terminator and the length fill one or two blocks.
+    let rest = blocks.remainder();
+    let mut tail = [0; 128];
+    tail[..rest.len()].copy_from_slice(rest);
+    tail[rest.len()] = 0x80;
+    let tail_len = if rest.len() < 56 { 64 } else { 128 };
+    tail[tail_len - 8..tail_len].copy_from_slice(&bit_len.to_be_bytes());
+    for block in tail[..tail_len].chunks_exact(64) {
+        let mut bytes = [0; 64];
+        bytes.copy_from_slice(block);
+        compress(&mut state, &bytes);
+    }
+
+    let mut hash = [0; 20];
+    for (bytes, word) in hash.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    hash
+}
+
+/// Hashes one 64 byte block into `state`.
+fn compress(state: &mut [u32; 5], block: &[u8; 64]) {
+    let mut w = [0u32; 80];
+    for (w, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *w = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+    for (i, w) in w.iter().enumerate() {
+        let (f, k) = match i {
+            0..=19 => ((b & c) | (!b & d), 0x5a827999),
+            20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+            40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6),
+        };
+        let t = a
+            .rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(*w);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = t;
+    }
+    for (state, value) in state.iter_mut().zip([a, b, c, d, e]) {
+        *state = state.wrapping_add(value);
+    }
+}
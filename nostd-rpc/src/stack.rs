@@ -0,0 +1,143 @@
+//! A network stack set up once and shared by every request sent over it.
+//!
+//! [`http::send`](crate::http::send) creates the `tap0` device, an interface and a socket set
+//! for each request, and configures addresses and routes, or waits for a DHCP lease, each
+//! time. A [`Stack`] does all of this once, and HTTP and JSON-RPC requests then borrow it,
+//! adding their socket to its socket set and removing it when done.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::IpAddr;
+
+use smoltcp::iface::{Interface, SocketSet};
+use smoltcp::phy::Device;
+#[cfg(feature = "phy-tuntap_interface")]
+use smoltcp::phy::TunTapInterface;
+#[cfg(feature = "phy-tuntap_interface")]
+use smoltcp::time::Duration;
+
+use crate::hooks::Hooks;
+use crate::http::{self, HttpRequest, HttpResponse};
+use crate::metrics::Timings;
+use crate::net::{Lease, LocalPorts, NetworkConfig};
+use crate::time::{Clock, IdleHook};
+use crate::Error;
+
+/// A device with its configured interface and a socket set, owned together so they can be
+/// reused by every request.
+pub struct Stack<D: Device> {
+    pub(crate) device: D,
+    pub(crate) iface: Interface,
+    pub(crate) sockets: SocketSet<'static>,
+    /// DNS servers of the network configuration, followed by those of the DHCP lease.
+    pub(crate) dns_servers: Vec<IpAddr>,
+    pub(crate) ports: LocalPorts,
+    /// Called while a request waits on the server.
+    pub(crate) idle: Option<Box<dyn IdleHook>>,
+    lease: Option<Lease>,
+}
+
+#[cfg(feature = "phy-tuntap_interface")]
+impl Stack<TunTapInterface> {
+    /// Constructs a stack on the `tap0` interface, configured with `network`.
+    pub fn tuntap<C: Clock, I: IdleHook>(
+        ethernet_mac: [u8; 6],
+        network: NetworkConfig,
+        clock: &C,
+        idle: &mut I,
+    ) -> Result<Self, Error> {
+        let (device, iface, lease) = http::tuntap_interface(
+            ethernet_mac,
+            &network,
+            http::DEFAULT_TUNTAP_ATTEMPTS,
+            Duration::from_secs(http::DEFAULT_TUNTAP_RETRY_DELAY_SECONDS),
+            clock,
+            idle,
+        )?;
+        Ok(Stack::with_interface(device, iface, &network, lease))
+    }
+}
+
+impl<D: Device> Stack<D> {
+    /// Constructs a stack on `device`, configuring its addresses and routes with `network`,
+    /// waiting for a DHCP lease if it asks for one.
+    ///
+    /// The hardware address is chosen for the medium of the device, from `ethernet_mac`, see
+    /// [`NetworkConfig::interface_config`].
+    pub fn new<C: Clock>(
+        mut device: D,
+        ethernet_mac: [u8; 6],
+        network: NetworkConfig,
+        clock: &C,
+    ) -> Result<Self, Error> {
+        let config = network.interface_config(&device.capabilities(), ethernet_mac);
+        let mut iface = Interface::new(config, &mut device, clock.now());
+        let lease = network.configure(&mut iface, &mut device, clock)?;
+        Ok(Stack::with_interface(device, iface, &network, lease))
+    }
+
+    fn with_interface(
+        device: D,
+        iface: Interface,
+        network: &NetworkConfig,
+        lease: Option<Lease>,
+    ) -> Self {
+        let mut dns_servers = network.dns_servers.clone();
+        if let Some(lease) = &lease {
+            dns_servers.extend(lease.dns_servers.iter().copied().map(IpAddr::V4));
+        }
+        Stack {
+            device,
+            iface,
+            sockets: SocketSet::new(vec![]),
+            dns_servers,
+            ports: LocalPorts::new([0; 8]),
+            idle: None,
+            lease,
+        }
+    }
+
+    /// Seeds the choice of local ports with `random`, which should come from a random number
+    /// generator, see [`LocalPorts`].
+    pub fn local_ports(mut self, random: [u8; 8]) -> Self {
+        self.ports = LocalPorts::new(random);
+        self
+    }
+
+    /// Calls `idle` while a request waits on the server, for as long as the interface need not
+    /// be polled. Without one the interface is polled continuously.
+    pub fn idle<I: IdleHook + 'static>(mut self, idle: I) -> Self {
+        self.idle = Some(Box::new(idle));
+        self
+    }
+
+    /// Sends `request`, following redirects as the request allows.
+    ///
+    /// Host names are resolved with the DNS servers of the request, then those of the stack.
+    pub fn send<C: Clock>(
+        &mut self,
+        request: &HttpRequest,
+        clock: &C,
+        hooks: &mut Hooks<'_>,
+    ) -> Result<(HttpResponse, Timings), Error> {
+        http::send_in(self, request, clock, hooks)
+    }
+
+    /// Returns the interface and device, e.g. to pass to
+    /// [`http::send_streaming`](crate::http::send_streaming).
+    pub fn parts(&mut self) -> (&mut Interface, &mut D) {
+        (&mut self.iface, &mut self.device)
+    }
+
+    /// Returns the DNS servers of the network configuration, followed by those of the DHCP
+    /// lease.
+    pub fn dns_servers(&self) -> &[IpAddr] {
+        &self.dns_servers
+    }
+
+    /// Returns the DHCP lease, if the address was obtained by DHCP.
+    pub fn lease(&self) -> Option<&Lease> {
+        self.lease.as_ref()
+    }
+}
@@ -0,0 +1,146 @@
+use alloc::vec;
+use core::net::IpAddr;
+
+use smoltcp::iface::{Interface, SocketSet};
+use smoltcp::phy::Device;
+use smoltcp::socket::tcp::{Socket, SocketBuffer, State};
+use smoltcp::time::Duration;
+use smoltcp::wire::IpAddress;
+
+use crate::http;
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::http::{DEFAULT_TUNTAP_ATTEMPTS, DEFAULT_TUNTAP_RETRY_DELAY_SECONDS};
+use crate::net::LocalPorts;
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::net::NetworkConfig;
+use crate::time::{Clock, IdleHook};
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::time::{SystemClock, ThreadSleep};
+use crate::Error;
+
+/// Connects to `port` on `ip` and closes the connection as soon as it is established, returning
+/// the time the handshake took. Uses the operating system clock.
+///
+/// This is a cheap check that a server is reachable before committing to a full request.
+#[cfg(feature = "phy-tuntap_interface")]
+pub fn probe<A: Into<IpAddr>, T: Into<Duration>>(
+    ethernet_mac: [u8; 6],
+    ip: A,
+    port: u16,
+    timeout: T,
+) -> Result<Duration, Error> {
+    probe_with(
+        ethernet_mac,
+        &NetworkConfig::default(),
+        ip,
+        port,
+        timeout,
+        &SystemClock,
+        &mut ThreadSleep,
+    )
+}
+
+/// Probes `port` on `ip` like [`probe`] from an interface configured with `network`, reading
+/// the time from `clock` and calling `idle` whenever it has to wait.
+///
+/// Returns [`Error::ConnectTimeout`] if the handshake does not complete in time and
+/// [`Error::ConnectionRefused`] if the server resets the connection.
+#[cfg(feature = "phy-tuntap_interface")]
+pub fn probe_with<A: Into<IpAddr>, T: Into<Duration>, C: Clock, I: IdleHook>(
+    ethernet_mac: [u8; 6],
+    network: &NetworkConfig,
+    ip: A,
+    port: u16,
+    timeout: T,
+    clock: &C,
+    idle: &mut I,
+) -> Result<Duration, Error> {
+    let (mut device, mut iface, _) = http::tuntap_interface(
+        ethernet_mac,
+        network,
+        DEFAULT_TUNTAP_ATTEMPTS,
+        Duration::from_secs(DEFAULT_TUNTAP_RETRY_DELAY_SECONDS),
+        clock,
+        idle,
+    )?;
+    probe_over(
+        &mut iface,
+        &mut device,
+        ip.into(),
+        port,
+        timeout.into(),
+        clock,
+        idle,
+    )
+}
+
+/// Probes `port` on `ip` like [`probe_with`], over an interface and device set up by the
+/// caller. `iface` must already have its address and routes configured.
+pub fn probe_with_device<D: Device, A: Into<IpAddr>, T: Into<Duration>, C: Clock>(
+    iface: &mut Interface,
+    device: &mut D,
+    ip: A,
+    port: u16,
+    timeout: T,
+    clock: &C,
+) -> Result<Duration, Error> {
+    let mut idle = |_| {};
+    probe_over(
+        iface,
+        device,
+        ip.into(),
+        port,
+        timeout.into(),
+        clock,
+        &mut idle,
+    )
+}
+
+/// Probes `port` on `ip` over `iface` and `device`, calling `idle` between polls.
+fn probe_over<D: Device, C: Clock, I: IdleHook>(
+    iface: &mut Interface,
+    device: &mut D,
+    ip: IpAddr,
+    port: u16,
+    timeout: Duration,
+    clock: &C,
+    idle: &mut I,
+) -> Result<Duration, Error> {
+    let socket = Socket::new(
+        SocketBuffer::new(vec![0; 64]),
+        SocketBuffer::new(vec![0; 64]),
+    );
+    let mut sockets = SocketSet::new(vec![]);
+    let handle = sockets.add(socket);
+    let start = clock.now();
+    // Without a seed the port still differs between probes, as the time is mixed into it.
+    let local_port = LocalPorts::new([0; 8]).next(start);
+    sockets
+        .get_mut::<Socket>(handle)
+        .connect(iface.context(), (IpAddress::from(ip), port), local_port)
+        .map_err(Error::Connect)?;
+
+    loop {
+        let timestamp = clock.now();
+        iface.poll(timestamp, device, &mut sockets);
+        let socket = sockets.get_mut::<Socket>(handle);
+        match socket.state() {
+            State::Established => {
+                let elapsed = timestamp - start;
+                debug!("probe connected in {} ms", elapsed.total_millis());
+                // Abort rather than close, so no time is spent on the closing handshake.
+                socket.abort();
+                iface.poll(clock.now(), device, &mut sockets);
+                return Ok(elapsed);
+            }
+            State::Closed => return Err(Error::ConnectionRefused),
+            _ if timestamp - start > timeout => return Err(Error::ConnectTimeout),
+            _ => idle.idle(http::idle_delay(
+                iface,
+                &sockets,
+                timestamp,
+                Some(start + timeout),
+            )),
+        }
+    }
+}
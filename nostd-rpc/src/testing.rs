@@ -0,0 +1,295 @@
+//! A simulated network with an HTTP server stub, to test requests without a TUN/TAP device or
+//! a real server.
+//!
+//! [`MockServer`] is the device the client sends over. It delivers the client's frames to an
+//! interface of its own at [`SERVER_IP`], where a stub answers each request on port
+//! [`SERVER_PORT`] with a canned response and keeps the requests it received, so request
+//! construction, response parsing, chunked decoding and timeouts can all be tested byte for
+//! byte. The server runs whenever the client polls the device, so no extra polling is needed.
+//!
+//! smoltcp's `Loopback` device cannot host both ends, as an interface drops segments for
+//! sockets outside the socket set it is polled with.
+
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::Cell;
+
+use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
+use smoltcp::phy::{self, Device, DeviceCapabilities, Medium};
+use smoltcp::socket::tcp;
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr};
+
+use crate::response::HeaderLimits;
+use crate::server::{self, Request};
+use crate::time::Clock;
+
+/// Address of the client's interface, see [`MockServer::interface`].
+pub const CLIENT_IP: [u8; 4] = [10, 0, 0, 1];
+/// Address of the server stub.
+pub const SERVER_IP: [u8; 4] = [10, 0, 0, 2];
+/// Port the server stub listens on.
+pub const SERVER_PORT: u16 = 80;
+const BUFFER_SIZE: usize = 4096;
+const MTU: usize = 1514;
+
+/// Ethernet frames waiting to be received.
+type Frames = VecDeque<Vec<u8>>;
+
+/// A device linking the client to an HTTP server stub, see the [module](self) documentation.
+///
+/// The stub serves one connection at a time, closing it after each response. Requests with
+/// a chunked body are not supported and have their connection reset.
+pub struct MockServer {
+    /// Frames sent by the client.
+    to_server: Frames,
+    /// Frames sent by the server.
+    to_client: Frames,
+    iface: Interface,
+    sockets: SocketSet<'static>,
+    handle: SocketHandle,
+    /// The response sent to each request, none if requests are never answered.
+    response: Option<Vec<u8>>,
+    /// Whether frames reach the other end, rather than being lost.
+    reachable: bool,
+    /// Data received on the current connection, not yet parsed as a request.
+    received: Vec<u8>,
+    /// The rest of the response being sent.
+    outgoing: Vec<u8>,
+    /// Whether the connection is closed once `outgoing` has been sent.
+    closing: bool,
+    /// Whether the connection is reset rather than closed, once `outgoing` is acknowledged.
+    reset: bool,
+    requests: Vec<Request>,
+}
+
+impl MockServer {
+    /// Constructs a server answering every request with `response`, sent as it is, e.g.
+    /// `b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"`.
+    pub fn new(response: &[u8]) -> Self {
+        MockServer::with_response(Some(Vec::from(response)))
+    }
+
+    /// Constructs a server answering every request with `response`, then resetting the
+    /// connection once the client has acknowledged the response, rather than closing it.
+    pub fn resetting(response: &[u8]) -> Self {
+        let mut server = MockServer::new(response);
+        server.reset = true;
+        server
+    }
+
+    /// Constructs a server that accepts connections but never answers, so requests time out
+    /// waiting for the response.
+    pub fn silent() -> Self {
+        MockServer::with_response(None)
+    }
+
+    /// Constructs a server whose link loses every frame, so requests time out connecting.
+    pub fn unreachable() -> Self {
+        let mut server = MockServer::with_response(None);
+        server.reachable = false;
+        server
+    }
+
+    fn with_response(response: Option<Vec<u8>>) -> Self {
+        let (mut to_server, mut to_client) = (Frames::new(), Frames::new());
+        let mut device = Queues {
+            rx: &mut to_server,
+            tx: &mut to_client,
+        };
+        let iface = new_interface(&mut device, SERVER_IP);
+        let mut sockets = SocketSet::new(vec![]);
+        let rx_buffer = tcp::SocketBuffer::new(vec![0; BUFFER_SIZE]);
+        let tx_buffer = tcp::SocketBuffer::new(vec![0; BUFFER_SIZE]);
+        let handle = sockets.add(tcp::Socket::new(rx_buffer, tx_buffer));
+        MockServer {
+            to_server,
+            to_client,
+            iface,
+            sockets,
+            handle,
+            response,
+            reachable: true,
+            received: Vec::new(),
+            outgoing: Vec::new(),
+            closing: false,
+            reset: false,
+            requests: Vec::new(),
+        }
+    }
+
+    /// Returns an interface for the client at [`CLIENT_IP`], to pass along with the server to
+    /// e.g. [`send_with_device`](crate::http::send_with_device).
+    pub fn interface(&mut self) -> Interface {
+        new_interface(self, CLIENT_IP)
+    }
+
+    /// Returns the requests received so far, in order.
+    pub fn requests(&self) -> &[Request] {
+        &self.requests
+    }
+
+    /// Runs the server stub at `timestamp`.
+    fn serve(&mut self, timestamp: Instant) {
+        if !self.reachable {
+            self.to_server.clear();
+            return;
+        }
+        let mut device = Queues {
+            rx: &mut self.to_server,
+            tx: &mut self.to_client,
+        };
+        self.iface.poll(timestamp, &mut device, &mut self.sockets);
+
+        let socket = self.sockets.get_mut::<tcp::Socket>(self.handle);
+        if !socket.is_open() {
+            self.received.clear();
+            self.outgoing.clear();
+            self.closing = false;
+            // Listening on a closed socket with a non-zero port cannot fail.
+            let _ = socket.listen(SERVER_PORT);
+            return;
+        }
+        if socket.can_recv() {
+            let received = &mut self.received;
+            let _ = socket.recv(|data| {
+                received.extend_from_slice(data);
+                (data.len(), ())
+            });
+        }
+        match server::parse_request(&self.received, &HeaderLimits::default(), usize::MAX) {
+            Ok(Some((request, len))) => {
+                self.received.drain(..len);
+                self.requests.push(request);
+                if let Some(response) = &self.response {
+                    self.outgoing.extend_from_slice(response);
+                    self.closing = true;
+                }
+            }
+            Ok(None) => {}
+            Err(_) => socket.abort(),
+        }
+        if socket.can_send() && !self.outgoing.is_empty() {
+            let sent = socket.send_slice(&self.outgoing).unwrap_or(0);
+            self.outgoing.drain(..sent);
+        }
+        if self.closing && self.outgoing.is_empty() && !self.reset {
+            socket.close();
+            self.closing = false;
+        } else if self.closing && self.outgoing.is_empty() && socket.send_queue() == 0 {
+            socket.abort();
+            self.closing = false;
+        }
+    }
+}
+
+impl Device for MockServer {
+    type RxToken<'a> = RxToken;
+    type TxToken<'a> = TxToken<'a>;
+
+    fn receive(&mut self, timestamp: Instant) -> Option<(RxToken, TxToken<'_>)> {
+        self.serve(timestamp);
+        let frame = self.to_client.pop_front()?;
+        Some((RxToken(frame), TxToken(&mut self.to_server)))
+    }
+
+    fn transmit(&mut self, _: Instant) -> Option<TxToken<'_>> {
+        Some(TxToken(&mut self.to_server))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        capabilities()
+    }
+}
+
+/// The server's end of the link.
+struct Queues<'q> {
+    rx: &'q mut Frames,
+    tx: &'q mut Frames,
+}
+
+impl Device for Queues<'_> {
+    type RxToken<'a>
+        = RxToken
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _: Instant) -> Option<(RxToken, TxToken<'_>)> {
+        let frame = self.rx.pop_front()?;
+        Some((RxToken(frame), TxToken(self.tx)))
+    }
+
+    fn transmit(&mut self, _: Instant) -> Option<TxToken<'_>> {
+        Some(TxToken(self.tx))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        capabilities()
+    }
+}
+
+/// A frame received from the other end of the link.
+pub struct RxToken(Vec<u8>);
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F: FnOnce(&[u8]) -> R>(self, f: F) -> R {
+        f(&self.0)
+    }
+}
+
+/// Sends a frame to the other end of the link.
+pub struct TxToken<'a>(&'a mut Frames);
+
+impl phy::TxToken for TxToken<'_> {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, len: usize, f: F) -> R {
+        let mut frame = vec![0; len];
+        let result = f(&mut frame);
+        self.0.push_back(frame);
+        result
+    }
+}
+
+fn capabilities() -> DeviceCapabilities {
+    let mut capabilities = DeviceCapabilities::default();
+    capabilities.medium = Medium::Ethernet;
+    capabilities.max_transmission_unit = MTU;
+    capabilities
+}
+
+/// Returns an interface on `device` with the address `ip`, on a /24 network.
+fn new_interface<D: Device>(device: &mut D, ip: [u8; 4]) -> Interface {
+    let mac = EthernetAddress([0x02, 0, 0, 0, 0, ip[3]]);
+    let mut iface = Interface::new(Config::new(mac.into()), device, Instant::ZERO);
+    iface.update_ip_addrs(|addrs| {
+        let cidr = IpCidr::new(IpAddress::v4(ip[0], ip[1], ip[2], ip[3]), 24);
+        // The address list starts empty, so there is room for one.
+        let _ = addrs.push(cidr);
+    });
+    iface
+}
+
+/// A [`Clock`] that advances by a millisecond each time it is read, so that timeouts pass
+/// after a predictable number of polls.
+#[derive(Debug, Default)]
+pub struct TickingClock {
+    millis: Cell<i64>,
+}
+
+impl TickingClock {
+    /// Constructs a clock starting at zero.
+    pub fn new() -> Self {
+        TickingClock::default()
+    }
+}
+
+impl Clock for TickingClock {
+    fn now(&self) -> Instant {
+        self.millis.set(self.millis.get() + 1);
+        Instant::from_millis(self.millis.get())
+    }
+}
@@ -0,0 +1,171 @@
+use core::cell::RefCell;
+
+use smoltcp::time::{Duration, Instant};
+
+/// A source of the current time, used for all timeout and retry logic.
+///
+/// Nothing in the crate reads the operating system clock except through [`SystemClock`], so
+/// on bare metal the time can come from a SysTick counter or an RTC, e.g.
+/// `|| Instant::from_millis(ticks())`.
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+impl<F: Fn() -> Instant> Clock for F {
+    fn now(&self) -> Instant {
+        self()
+    }
+}
+
+/// A [`Clock`] reading the time from a closure that needs mutable access, e.g. to a timer
+/// peripheral driver.
+#[derive(Debug)]
+pub struct ClockCell<F>(RefCell<F>);
+
+impl<F: FnMut() -> Instant> ClockCell<F> {
+    /// Constructs a [`ClockCell`] reading the time from `now`.
+    pub fn new(now: F) -> Self {
+        ClockCell(RefCell::new(now))
+    }
+}
+
+impl<F: FnMut() -> Instant> Clock for ClockCell<F> {
+    fn now(&self) -> Instant {
+        (self.0.borrow_mut())()
+    }
+}
+
+/// A [`Clock`] backed by the operating system clock.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Called whenever the client has to wait, instead of spinning on the clock.
+///
+/// Implementations may sleep, enter a low power mode or kick a watchdog. Returning
+/// before `duration` has passed is allowed, the caller re-checks the clock.
+pub trait IdleHook {
+    /// Waits for up to `duration`.
+    fn idle(&mut self, duration: Duration);
+}
+
+impl<F: FnMut(Duration)> IdleHook for F {
+    fn idle(&mut self, duration: Duration) {
+        self(duration)
+    }
+}
+
+/// An [`IdleHook`] that puts the current thread to sleep.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadSleep;
+
+#[cfg(feature = "std")]
+impl IdleHook for ThreadSleep {
+    fn idle(&mut self, duration: Duration) {
+        std::thread::sleep(duration.into());
+    }
+}
+
+/// Receives the server time from the `Date` header of each response, giving devices
+/// without a real time clock a coarse time reference.
+pub trait ClockSync {
+    /// Called with the server time in seconds since the Unix epoch, and the offset in
+    /// microseconds to add to the local [`Clock`] reading to obtain it.
+    fn server_time(&mut self, unix_seconds: u64, offset_micros: i64);
+}
+
+impl<F: FnMut(u64, i64)> ClockSync for F {
+    fn server_time(&mut self, unix_seconds: u64, offset_micros: i64) {
+        self(unix_seconds, offset_micros)
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parses an HTTP date into seconds since the Unix epoch.
+///
+/// Accepts the preferred IMF-fixdate format (`Sun, 06 Nov 1994 08:49:37 GMT`) and the
+/// obsolete RFC 850 (`Sunday, 06-Nov-94 08:49:37 GMT`) and asctime
+/// (`Sun Nov  6 08:49:37 1994`) formats, as required by RFC 9110 section 5.6.7.
+pub fn parse_http_date(date: &str) -> Option<u64> {
+    let mut parts = date.split_ascii_whitespace();
+    let weekday = parts.next()?;
+    let (day, month, year, time) = if weekday.ends_with(',') {
+        let first = parts.next()?;
+        if let Some((day, rest)) = first.split_once('-') {
+            // RFC 850, two digit years are interpreted as 1970 to 2069.
+            let (month, year) = rest.split_once('-')?;
+            let year: u64 = parse_digits(year, 2)?;
+            let year = if year < 70 { 2000 + year } else { 1900 + year };
+            (parse_digits(day, 2)?, month, year, parts.next()?)
+        } else {
+            let month = parts.next()?;
+            let year = parse_digits(parts.next()?, 4)?;
+            (parse_digits(first, 2)?, month, year, parts.next()?)
+        }
+    } else {
+        // asctime, where the day is padded with a space rather than a zero.
+        let month = parts.next()?;
+        let day = parts.next()?;
+        let time = parts.next()?;
+        let year = parse_digits(parts.next()?, 4)?;
+        let day = if day.len() == 1 {
+            parse_digits(day, 1)?
+        } else {
+            parse_digits(day, 2)?
+        };
+        (day, month, year, time)
+    };
+    if weekday.ends_with(',') && parts.next() != Some("GMT") {
+        return None;
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+
+    let month = MONTHS.iter().position(|&m| m == month)? as u64 + 1;
+    let mut time = time.split(':');
+    let hour = parse_digits(time.next()?, 2)?;
+    let minute = parse_digits(time.next()?, 2)?;
+    let second = parse_digits(time.next()?, 2)?;
+    if time.next().is_some() || year < 1970 || !(1..=31).contains(&day) || hour > 23 {
+        return None;
+    }
+    if minute > 59 || second > 60 {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    Some(days * 86_400 + hour * 3_600 + minute * 60 + second)
+}
+
+/// Parses exactly `len` ASCII digits.
+fn parse_digits(s: &str, len: usize) -> Option<u64> {
+    if s.len() != len || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Returns the number of days from 1970-01-01 to the given date, which must not be earlier.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    // Howard Hinnant's algorithm, with years starting in March so leap days come last.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
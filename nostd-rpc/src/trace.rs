@@ -0,0 +1,44 @@
+//! Tracing macros that forward to `defmt` or `log`, whichever feature is enabled.
+//!
+//! With neither feature the macros expand to nothing, so tracing costs nothing. Levels are
+//! filtered at compile time by the backend: set `DEFMT_LOG` for `defmt`, or enable one of the
+//! `max_level_*` or `release_max_level_*` features of `log`. Arguments must implement both
+//! `defmt::Format` and `core::fmt::Display`, so only `{}` placeholders are used.
+//!
+//! HTTP requests trace connecting, the TCP state changes of their socket, the bytes sent and
+//! received, and timeouts. smoltcp reports its own retransmissions through `log`, which the
+//! `log` feature enables.
+
+// Only some levels are used by the code built without TUN/TAP support.
+#![cfg_attr(not(feature = "phy-tuntap_interface"), allow(unused_macros))]
+
+macro_rules! trace_with {
+    ($level:ident, $s:literal $(, $x:expr)* $(,)?) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::$level!($s $(, $x)*);
+        #[cfg(all(feature = "log", not(feature = "defmt")))]
+        ::log::$level!($s $(, $x)*);
+        #[cfg(not(any(feature = "log", feature = "defmt")))]
+        let _ = ($(&$x),*);
+    }};
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => { trace_with!(trace, $($arg)*) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { trace_with!(debug, $($arg)*) };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { trace_with!(info, $($arg)*) };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => { trace_with!(warn, $($arg)*) };
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => { trace_with!(error, $($arg)*) };
+}
@@ -0,0 +1,134 @@
+//! A JSON-RPC transport in the style of the `jsonrpc` crate used by rust-bitcoincore-rpc,
+//! sending requests over this crate's HTTP layer, so a device without std can drive e.g. a
+//! Bitcoin Core node.
+//!
+//! The `jsonrpc` crate's `Transport` takes `&self` and serde values, and needs std. The
+//! [`Transport`] here takes `&mut self`, as sending polls a device, and passes parameters and
+//! results as JSON text.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use smoltcp::phy::Device;
+
+use crate::hooks::Hooks;
+use crate::http::{HttpClient, HttpRequest, HttpResponse};
+use crate::jsonrpc::{parse_batch_response, parse_response, request_body};
+use crate::time::Clock;
+use crate::Error;
+
+/// A JSON-RPC request, sent with [`Transport::send_request`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Request<'a> {
+    /// Name of the method called.
+    pub method: &'a str,
+    /// Parameters as JSON text, an array or object, or "" for none.
+    pub params: &'a str,
+    /// ID the response is matched with.
+    pub id: u64,
+}
+
+/// The response to a JSON-RPC [`Request`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+    /// ID of the request answered.
+    pub id: u64,
+    /// The `result` as JSON text, or [`Error::Rpc`] if the server returned an error object.
+    pub result: Result<String, Error>,
+}
+
+/// Sends JSON-RPC requests to a server.
+pub trait Transport {
+    /// Sends `request`, returning the response to it.
+    fn send_request(&mut self, request: Request<'_>) -> Result<Response, Error>;
+
+    /// Sends `requests` together as a batch, returning the responses in the same order.
+    fn send_batch(&mut self, requests: &[Request<'_>]) -> Result<Vec<Response>, Error>;
+
+    /// Writes the URL requests are sent to, e.g. for logs.
+    fn fmt_target(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+/// A [`Transport`] sending requests as HTTP POST requests over an [`HttpClient`], which keeps
+/// the connection to the server open between requests.
+pub struct HttpTransport<D: Device, C: Clock> {
+    client: HttpClient<D>,
+    /// Request with the address, URL and credentials of the server, used for every call.
+    request: HttpRequest,
+    clock: C,
+}
+
+impl<D: Device, C: Clock> HttpTransport<D, C> {
+    /// Constructs a transport sending requests with `client` to the server that `request` is
+    /// addressed to, reading the time from `clock`. The method and body of `request` are
+    /// replaced for each call.
+    pub fn new(client: HttpClient<D>, request: HttpRequest, clock: C) -> Self {
+        HttpTransport {
+            client,
+            request: request.header("Content-Type: application/json"),
+            clock,
+        }
+    }
+
+    /// Returns the client the requests are sent with.
+    pub fn client(&mut self) -> &mut HttpClient<D> {
+        &mut self.client
+    }
+
+    /// Sends `body` to the server, returning the HTTP response.
+    fn post(&mut self, body: &str) -> Result<HttpResponse, Error> {
+        let request = self.request.clone().method("POST").body(body);
+        let (response, _) = self.client.send(&request, &self.clock, &mut Hooks::new())?;
+        Ok(response)
+    }
+}
+
+impl<D: Device, C: Clock> Transport for HttpTransport<D, C> {
+    fn send_request(&mut self, request: Request<'_>) -> Result<Response, Error> {
+        let body = request_body(request.method, request.params, request.id);
+        let response = self.post(&body)?;
+        // Errors of the call are part of the response, others fail the request.
+        let result = match parse_response(&response, request.id) {
+            Err(error @ Error::Rpc { .. }) => Err(error),
+            Err(error) => return Err(error),
+            Ok(result) => Ok(result),
+        };
+        Ok(Response {
+            id: request.id,
+            result,
+        })
+    }
+
+    fn send_batch(&mut self, requests: &[Request<'_>]) -> Result<Vec<Response>, Error> {
+        if requests.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut body = String::from("[");
+        for (i, request) in requests.iter().enumerate() {
+            if i > 0 {
+                body.push(',');
+            }
+            body.push_str(&request_body(request.method, request.params, request.id));
+        }
+        body.push(']');
+        let response = self.post(&body)?;
+        let ids: Vec<u64> = requests.iter().map(|request| request.id).collect();
+        let results = parse_batch_response(&response, &ids)?;
+        Ok(ids
+            .into_iter()
+            .zip(results)
+            .map(|(id, result)| Response { id, result })
+            .collect())
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.request.tls { "https" } else { "http" };
+        let (host, port, path) = (
+            self.request.host_name(),
+            self.request.port,
+            self.request.path(),
+        );
+        write!(f, "{}://{}:{}{}", scheme, host, port, path)
+    }
+}
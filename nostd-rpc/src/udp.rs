@@ -0,0 +1,289 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::IpAddr;
+
+use smoltcp::iface::{Interface, SocketSet};
+use smoltcp::phy::Device;
+use smoltcp::socket::udp::{PacketBuffer, PacketMetadata, SendError, Socket};
+use smoltcp::time::Duration;
+use smoltcp::wire::{IpAddress, IpEndpoint};
+
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::http::{self, DEFAULT_TUNTAP_ATTEMPTS, DEFAULT_TUNTAP_RETRY_DELAY_SECONDS};
+use crate::net::{LocalPorts, NetworkConfig};
+use crate::time::Clock;
+#[cfg(feature = "phy-tuntap_interface")]
+use crate::time::{IdleHook, SystemClock, ThreadSleep};
+use crate::{dns, Error};
+
+const DEFAULT_TIMEOUT_SECONDS: u64 = 5;
+/// The largest UDP payload that fits in an Ethernet frame without fragmentation over IPv4.
+const DEFAULT_MAX_REPLY_SIZE: usize = 1472;
+
+/// A datagram sent to a UDP service, e.g. a statsd metric or a query to a custom RPC
+/// server, and how to wait for its reply.
+#[derive(Clone, Debug)]
+pub struct UdpRequest {
+    /// IP address of the server, resolved from the host when not set.
+    ip: Option<IpAddr>,
+    /// Host name of the server, empty if not set.
+    host: String,
+    /// Port of the server.
+    port: u16,
+    /// The datagram sent.
+    payload: Vec<u8>,
+    /// Whether to wait for a reply.
+    expect_reply: bool,
+    /// How long to wait for the datagram to be sent and the reply to arrive.
+    timeout: Duration,
+    /// Size of the largest reply accepted, larger ones are dropped.
+    max_reply_size: usize,
+    /// Addresses and routes of the TUN/TAP interface.
+    network: NetworkConfig,
+    /// Number of attempts made to create the TUN/TAP interface.
+    #[cfg(feature = "phy-tuntap_interface")]
+    tuntap_attempts: u8,
+    /// Delay between attempts to create the TUN/TAP interface.
+    #[cfg(feature = "phy-tuntap_interface")]
+    tuntap_retry_delay: Duration,
+    /// Chooses the local port of the request.
+    ports: LocalPorts,
+}
+
+impl Default for UdpRequest {
+    fn default() -> Self {
+        UdpRequest {
+            ip: None,
+            host: String::new(),
+            port: 0,
+            payload: Vec::new(),
+            expect_reply: true,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECONDS),
+            max_reply_size: DEFAULT_MAX_REPLY_SIZE,
+            network: NetworkConfig::default(),
+            #[cfg(feature = "phy-tuntap_interface")]
+            tuntap_attempts: DEFAULT_TUNTAP_ATTEMPTS,
+            #[cfg(feature = "phy-tuntap_interface")]
+            tuntap_retry_delay: Duration::from_secs(DEFAULT_TUNTAP_RETRY_DELAY_SECONDS),
+            ports: LocalPorts::new([0; 8]),
+        }
+    }
+}
+
+impl UdpRequest {
+    /// Constructs a new [`UdpRequest`] with an empty payload, waiting 5 seconds for a reply.
+    pub fn new() -> Self {
+        UdpRequest::default()
+    }
+
+    /// Sets the IPv4 address of the server.
+    pub fn ipv4(mut self, ip: [u8; 4]) -> Self {
+        self.ip = Some(IpAddr::from(ip));
+        self
+    }
+
+    /// Sets the IPv6 address of the server.
+    pub fn ipv6(mut self, ip: [u16; 8]) -> Self {
+        self.ip = Some(IpAddr::from(ip));
+        self
+    }
+
+    /// Sets the IP address of the server.
+    pub fn ip<A: Into<IpAddr>>(mut self, ip: A) -> Self {
+        self.ip = Some(ip.into());
+        self
+    }
+
+    /// Sets the host name of the server, resolved with the DNS servers of the network
+    /// configuration unless an IP address is set.
+    pub fn host(mut self, host: &str) -> Self {
+        self.host = String::from(host);
+        self
+    }
+
+    /// Sets the port of the server.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets the datagram sent.
+    pub fn payload(mut self, payload: &[u8]) -> Self {
+        self.payload = payload.to_vec();
+        self
+    }
+
+    /// Sets whether to wait for a reply, true by default. Without one, sending returns as
+    /// soon as the datagram is handed to the device.
+    pub fn expect_reply(mut self, expect_reply: bool) -> Self {
+        self.expect_reply = expect_reply;
+        self
+    }
+
+    /// Sets how long to wait for the datagram to be sent and the reply to arrive.
+    pub fn timeout<T: Into<Duration>>(mut self, timeout: T) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Sets the size of the largest reply accepted, 1472 bytes by default. Larger replies
+    /// are dropped.
+    pub fn max_reply_size(mut self, size: usize) -> Self {
+        self.max_reply_size = size;
+        self
+    }
+
+    /// Seeds the choice of the local port of the request with `random`, which should come
+    /// from a random number generator, see [`LocalPorts`].
+    pub fn local_ports(mut self, random: [u8; 8]) -> Self {
+        self.ports = LocalPorts::new(random);
+        self
+    }
+
+    /// Sets the addresses and routes of the interface.
+    pub fn network(mut self, network: NetworkConfig) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Sets the number of attempts made to create the TUN/TAP interface.
+    #[cfg(feature = "phy-tuntap_interface")]
+    pub fn tuntap_attempts(mut self, attempts: u8) -> Self {
+        self.tuntap_attempts = attempts;
+        self
+    }
+
+    /// Sets the delay between attempts to create the TUN/TAP interface.
+    #[cfg(feature = "phy-tuntap_interface")]
+    pub fn tuntap_retry_delay<T: Into<Duration>>(mut self, delay: T) -> Self {
+        self.tuntap_retry_delay = delay.into();
+        self
+    }
+}
+
+/// Sends `request` using the operating system clock, sleeping the thread while waiting.
+///
+/// Returns the payload of the reply, or `None` if the request does not expect one.
+#[cfg(feature = "phy-tuntap_interface")]
+pub fn send(ethernet_mac: [u8; 6], request: UdpRequest) -> Result<Option<Vec<u8>>, Error> {
+    send_with(ethernet_mac, request, &SystemClock, &mut ThreadSleep)
+}
+
+/// Sends `request`, reading the time from `clock` and calling `idle` whenever it has to wait.
+#[cfg(feature = "phy-tuntap_interface")]
+pub fn send_with<C: Clock, I: IdleHook>(
+    ethernet_mac: [u8; 6],
+    request: UdpRequest,
+    clock: &C,
+    idle: &mut I,
+) -> Result<Option<Vec<u8>>, Error> {
+    let (mut device, mut iface, lease) = http::tuntap_interface(
+        ethernet_mac,
+        &request.network,
+        request.tuntap_attempts,
+        request.tuntap_retry_delay,
+        clock,
+        idle,
+    )?;
+    let mut dns_servers = request.network.dns_servers.clone();
+    if let Some(lease) = lease {
+        dns_servers.extend(lease.dns_servers.into_iter().map(IpAddr::V4));
+    }
+    exchange(&mut iface, &mut device, &request, &dns_servers, clock)
+}
+
+/// Sends `request` like [`send_with`], over an interface and device set up by the caller,
+/// e.g. an Ethernet MAC driver on an embedded target.
+///
+/// `iface` must already have its address and routes configured. Of the request's
+/// [`NetworkConfig`] only the DNS servers are used.
+///
+/// Returns [`Error::SendTimeout`] if the datagram cannot be sent in time, and
+/// [`Error::ResponseTimeout`] if no reply arrives from the server in time. Datagrams from
+/// other addresses are ignored.
+pub fn send_with_device<D: Device, C: Clock>(
+    iface: &mut Interface,
+    device: &mut D,
+    request: UdpRequest,
+    clock: &C,
+) -> Result<Option<Vec<u8>>, Error> {
+    let dns_servers = &request.network.dns_servers;
+    exchange(iface, device, &request, dns_servers, clock)
+}
+
+/// Sends `request` from a socket of its own, resolving the host with `dns_servers` if the
+/// request has no IP address, and waits for the reply.
+fn exchange<D: Device, C: Clock>(
+    iface: &mut Interface,
+    device: &mut D,
+    request: &UdpRequest,
+    dns_servers: &[IpAddr],
+    clock: &C,
+) -> Result<Option<Vec<u8>>, Error> {
+    let remote = match request.ip {
+        Some(ip) => ip,
+        None if !request.host.is_empty() && !dns_servers.is_empty() => {
+            let timeout = request.network.dns_timeout;
+            dns::resolve(iface, device, dns_servers, &request.host, timeout, clock)?
+        }
+        None => return Err(Error::UnresolvedHost),
+    };
+    let remote = IpEndpoint::new(IpAddress::from(remote), request.port);
+
+    // The transmit buffer holds exactly the request, the receive buffer one reply. Once the
+    // request has been sent the socket can send again.
+    let rx_buffer = PacketBuffer::new(
+        vec![PacketMetadata::EMPTY; 1],
+        vec![0; request.max_reply_size],
+    );
+    let tx_buffer = PacketBuffer::new(
+        vec![PacketMetadata::EMPTY; 1],
+        vec![0; request.payload.len()],
+    );
+    let mut socket = Socket::new(rx_buffer, tx_buffer);
+    socket
+        .bind(request.ports.clone().next(clock.now()))
+        .map_err(Error::Bind)?;
+    let mut sockets = SocketSet::new(vec![]);
+    let handle = sockets.add(socket);
+
+    let start = clock.now();
+    let mut sent = false;
+    loop {
+        let timestamp = clock.now();
+        let socket = sockets.get_mut::<Socket>(handle);
+        if !sent {
+            match socket.send_slice(&request.payload, remote) {
+                Ok(()) => {
+                    sent = true;
+                    debug!("sending {} byte datagram", request.payload.len());
+                }
+                Err(SendError::BufferFull) => {}
+                Err(error) => return Err(Error::Datagram(error)),
+            }
+        }
+        iface.poll(timestamp, device, &mut sockets);
+        let socket = sockets.get_mut::<Socket>(handle);
+
+        if sent && socket.can_send() {
+            if !request.expect_reply {
+                return Ok(None);
+            }
+            while let Ok((data, meta)) = socket.recv() {
+                if meta.endpoint == remote {
+                    debug!("received {} byte reply", data.len());
+                    return Ok(Some(data.to_vec()));
+                }
+                trace!("ignoring datagram from another address");
+            }
+        }
+        if timestamp - start > request.timeout {
+            return Err(if sent && socket.can_send() {
+                Error::ResponseTimeout
+            } else {
+                Error::SendTimeout
+            });
+        }
+    }
+}
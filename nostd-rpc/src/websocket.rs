@@ -0,0 +1,462 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::task::Poll;
+
+use smoltcp::iface::{Interface, SocketHandle, SocketSet};
+use smoltcp::phy::Device;
+use smoltcp::socket::tcp;
+use smoltcp::time::Instant;
+use smoltcp::wire::IpAddress;
+
+use crate::base64::{self, Alphabet};
+use crate::http::HttpRequest;
+use crate::response;
+use crate::{sha1, Error};
+
+/// Appended to the `Sec-WebSocket-Key` before hashing it into the `Sec-WebSocket-Accept`
+/// header (RFC 6455 section 1.3).
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest message accepted unless the request sets a
+/// [`max_response_bytes`](HttpRequest::max_response_bytes).
+const DEFAULT_MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// A message received from the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    /// The server closed the connection, with the status code it gave if any.
+    Close(Option<u16>),
+}
+
+/// The state of the connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Connect,
+    /// Waiting for the server to accept the upgrade.
+    Handshake,
+    Open,
+    /// A close frame has been sent, waiting for the server's.
+    Closing,
+    /// The closing handshake is over or the connection failed.
+    Closed,
+}
+
+/// A WebSocket client connection (RFC 6455), e.g. for JSON-RPC servers that push
+/// notifications to subscribers, advanced a step at a time by calling [`WebSocket::poll`].
+///
+/// The connection is opened with the path, host, headers and credentials of an
+/// [`HttpRequest`]. As for [`HttpTransaction`](crate::http::HttpTransaction), host names are
+/// not resolved, and TLS and proxies are not supported. The request timeout applies to the
+/// opening handshake, and its [`max_response_bytes`](HttpRequest::max_response_bytes)
+/// limits the size of messages, to 1 MiB if not set.
+///
+/// Pings are answered automatically. Messages sent before the server accepts the upgrade are
+/// sent once it has.
+pub struct WebSocket<'a, D: Device> {
+    iface: &'a mut Interface,
+    device: &'a mut D,
+    sockets: SocketSet<'a>,
+    handle: SocketHandle,
+    state: State,
+    start: Instant,
+    request: HttpRequest,
+    /// The `Sec-WebSocket-Key` of the opening handshake.
+    key: String,
+    /// Data waiting to be written to the socket.
+    outgoing: Vec<u8>,
+    /// Frames sent before the server accepted the upgrade.
+    queued: Vec<u8>,
+    /// Data received and not yet decoded.
+    incoming: Vec<u8>,
+    /// The opcode and payload so far of a fragmented message.
+    fragments: Option<(u8, Vec<u8>)>,
+    /// State of the generator of masking keys.
+    mask_state: u64,
+}
+
+impl<'a, D: Device> WebSocket<'a, D> {
+    /// Starts connecting over `iface` and `device`, which must already have their address and
+    /// routes configured, to the server of `request` at `now`.
+    ///
+    /// `random` must come from a random number generator: it becomes the handshake key and
+    /// seeds the masking keys of the frames sent, which protect proxies on the path from
+    /// cache poisoning. Fails with [`Error::UnresolvedHost`] if `request` has no IP address.
+    pub fn connect(
+        iface: &'a mut Interface,
+        device: &'a mut D,
+        request: &HttpRequest,
+        random: [u8; 16],
+        now: Instant,
+    ) -> Result<Self, Error> {
+        if request.tls {
+            warn!("WebSocket over TLS is not supported");
+            return Err(Error::Tls);
+        }
+        let (rx_buffer, tx_buffer) = request.socket_buffers(&device.capabilities());
+        let mut sockets = SocketSet::new(vec![]);
+        let handle = sockets.add(tcp::Socket::new(rx_buffer, tx_buffer));
+        let remote = request.ip.ok_or(Error::UnresolvedHost)?;
+        sockets
+            .get_mut::<tcp::Socket>(handle)
+            .connect(
+                iface.context(),
+                (IpAddress::from(remote), request.port),
+                request.ports.clone().next(now),
+            )
+            .map_err(Error::Connect)?;
+        debug!("connecting to port {}", request.port);
+
+        let key = base64::encode(&random, Alphabet::Standard);
+        let outgoing = request.construct_upgrade(&key).into_bytes();
+        let mut seed = [0; 8];
+        for (i, byte) in random.iter().enumerate() {
+            seed[i % 8] ^= byte;
+        }
+        Ok(WebSocket {
+            iface,
+            device,
+            sockets,
+            handle,
+            state: State::Connect,
+            start: now,
+            request: request.clone(),
+            key,
+            outgoing,
+            queued: Vec::new(),
+            incoming: Vec::new(),
+            fragments: None,
+            // The generator never leaves the all zero state.
+            mask_state: u64::from_le_bytes(seed) | 1,
+        })
+    }
+
+    /// Returns whether the server has accepted the upgrade and the connection is not closing.
+    pub fn is_open(&self) -> bool {
+        self.state == State::Open
+    }
+
+    /// Sends `text` as a text message.
+    pub fn send_text(&mut self, text: &str) -> Result<(), Error> {
+        self.send(OPCODE_TEXT, text.as_bytes())
+    }
+
+    /// Sends `data` as a binary message.
+    pub fn send_binary(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.send(OPCODE_BINARY, data)
+    }
+
+    /// Sends a ping, which the server answers with a pong carrying the same `data`, at most
+    /// 125 bytes.
+    pub fn ping(&mut self, data: &[u8]) -> Result<(), Error> {
+        if data.len() > 125 {
+            return Err(Error::InvalidFrame);
+        }
+        self.send(OPCODE_PING, data)
+    }
+
+    /// Starts the closing handshake with status `code`, e.g. 1000 for a normal closure. The
+    /// connection is closed once [`WebSocket::poll`] returns the server's [`Message::Close`].
+    pub fn close(&mut self, code: u16) -> Result<(), Error> {
+        self.send(OPCODE_CLOSE, &code.to_be_bytes())?;
+        self.state = State::Closing;
+        Ok(())
+    }
+
+    /// Polls the interface and advances the connection, returning the next message received.
+    ///
+    /// Fails with [`Error::Status`] if the server refuses the upgrade, and with
+    /// [`Error::ConnectionClosed`] once the connection is closed.
+    pub fn poll(&mut self, now: Instant) -> Poll<Result<Message, Error>> {
+        match self.step(now) {
+            Poll::Ready(Err(error)) => {
+                if self.state != State::Closed {
+                    warn!("WebSocket failed: {}", error);
+                    self.sockets.get_mut::<tcp::Socket>(self.handle).abort();
+                    self.iface.poll(now, self.device, &mut self.sockets);
+                    self.state = State::Closed;
+                }
+                Poll::Ready(Err(error))
+            }
+            result => result,
+        }
+    }
+
+    fn step(&mut self, now: Instant) -> Poll<Result<Message, Error>> {
+        self.iface.poll(now, self.device, &mut self.sockets);
+        let socket = self.sockets.get_mut::<tcp::Socket>(self.handle);
+
+        if self.state == State::Connect {
+            if socket.may_send() {
+                debug!("connected, sending handshake");
+                self.state = State::Handshake;
+            } else if !socket.is_active() {
+                return Poll::Ready(Err(Error::ConnectionRefused));
+            } else if self.request.expired(now - self.start) {
+                return Poll::Ready(Err(Error::ConnectTimeout));
+            } else {
+                return Poll::Pending;
+            }
+        }
+
+        if socket.can_send() && !self.outgoing.is_empty() {
+            let sent = socket
+                .send_slice(&self.outgoing)
+                .map_err(Error::SendFailed)?;
+            self.outgoing.drain(..sent);
+            trace!("sent {} bytes", sent);
+        }
+        if self.state == State::Closed {
+            if self.outgoing.is_empty() {
+                socket.close();
+            }
+            return Poll::Ready(Err(Error::ConnectionClosed));
+        }
+        if socket.can_recv() {
+            let incoming = &mut self.incoming;
+            socket
+                .recv(|data| {
+                    incoming.extend_from_slice(data);
+                    (data.len(), ())
+                })
+                .map_err(Error::RecvFailed)?;
+        }
+        let closed = !socket.may_recv();
+
+        if self.state == State::Handshake && !self.handshake()? {
+            if closed {
+                return Poll::Ready(Err(Error::ConnectionClosed));
+            }
+            if self.request.expired(now - self.start) {
+                return Poll::Ready(Err(Error::ResponseTimeout));
+            }
+            return Poll::Pending;
+        }
+
+        while let Some((fin, opcode, payload)) = self.decode_frame()? {
+            if let Some(message) = self.receive(fin, opcode, payload)? {
+                if let Message::Close(_) = message {
+                    let socket = self.sockets.get_mut::<tcp::Socket>(self.handle);
+                    if socket.can_send() && !self.outgoing.is_empty() {
+                        let sent = socket
+                            .send_slice(&self.outgoing)
+                            .map_err(Error::SendFailed)?;
+                        self.outgoing.drain(..sent);
+                    }
+                    if self.outgoing.is_empty() {
+                        socket.close();
+                    }
+                    self.state = State::Closed;
+                }
+                return Poll::Ready(Ok(message));
+            }
+        }
+        if closed {
+            debug!("connection closed by server");
+            self.state = State::Closed;
+            return Poll::Ready(Err(Error::ConnectionClosed));
+        }
+        Poll::Pending
+    }
+
+    /// Checks the server's answer to the upgrade request once its head is received,
+    /// returning whether the connection is open.
+    fn handshake(&mut self) -> Result<bool, Error> {
+        let (mode, limits) = (self.request.parse_mode, &self.request.header_limits);
+        let Some((head, len)) = response::parse_head(&self.incoming, mode, limits)? else {
+            return Ok(false);
+        };
+        self.incoming.drain(..len);
+        let response = head.with_body(Vec::new());
+        if response.status() != 101 {
+            warn!(
+                "server refused the upgrade with status {}",
+                response.status()
+            );
+            return Err(Error::Status(response.status()));
+        }
+
+        let has_token = |name: &str, token: &str| {
+            response.header_all(name).any(|value| {
+                value
+                    .split(',')
+                    .any(|t| t.trim().eq_ignore_ascii_case(token))
+            })
+        };
+        let mut expected = self.key.clone();
+        expected.push_str(ACCEPT_GUID);
+        let expected = base64::encode(&sha1::digest(expected.as_bytes()), Alphabet::Standard);
+        if !has_token("upgrade", "websocket")
+            || !has_token("connection", "upgrade")
+            || response.header("sec-websocket-accept") != Some(expected.as_str())
+        {
+            return Err(Error::WebSocketHandshake);
+        }
+
+        debug!("WebSocket open");
+        self.state = State::Open;
+        self.outgoing.append(&mut self.queued);
+        Ok(true)
+    }
+
+    /// Returns the size of the largest message accepted.
+    fn max_message_bytes(&self) -> usize {
+        self.request
+            .max_response_bytes
+            .unwrap_or(DEFAULT_MAX_MESSAGE_BYTES)
+    }
+
+    /// Removes the first frame from the data received, once all of it has arrived, returning
+    /// its FIN bit, opcode and payload.
+    fn decode_frame(&mut self) -> Result<Option<(bool, u8, Vec<u8>)>, Error> {
+        let data = &self.incoming;
+        if data.len() < 2 {
+            return Ok(None);
+        }
+        let (fin, opcode) = (data[0] & 0x80 != 0, data[0] & 0x0f);
+        // No extensions are negotiated, so the reserved bits must be clear, and servers must
+        // not mask their frames (RFC 6455 section 5.1).
+        if data[0] & 0x70 != 0 || data[1] & 0x80 != 0 {
+            return Err(Error::InvalidFrame);
+        }
+        let (len, header_len) = match data[1] & 0x7f {
+            126 if data.len() >= 4 => (u64::from(u16::from_be_bytes([data[2], data[3]])), 4),
+            127 if data.len() >= 10 => {
+                let mut len = [0; 8];
+                len.copy_from_slice(&data[2..10]);
+                (u64::from_be_bytes(len), 10)
+            }
+            126 | 127 => return Ok(None),
+            len => (u64::from(len), 2),
+        };
+        if opcode & 0x8 != 0 && (!fin || len > 125) {
+            return Err(Error::InvalidFrame);
+        }
+        let len = usize::try_from(len).map_err(|_| Error::ResponseTooLarge)?;
+        // Checked before the payload arrives, so a bogus length cannot make the frame buffer
+        // grow without bound.
+        if len > self.max_message_bytes() {
+            return Err(Error::ResponseTooLarge);
+        }
+        if data.len() - header_len < len {
+            return Ok(None);
+        }
+        let payload = data[header_len..header_len + len].to_vec();
+        self.incoming.drain(..header_len + len);
+        Ok(Some((fin, opcode, payload)))
+    }
+
+    /// Handles a frame, returning the message it completes if any.
+    fn receive(
+        &mut self,
+        fin: bool,
+        opcode: u8,
+        payload: Vec<u8>,
+    ) -> Result<Option<Message>, Error> {
+        trace!(
+            "received frame with opcode {}, {} bytes",
+            opcode,
+            payload.len()
+        );
+        let (opcode, payload) = match opcode {
+            OPCODE_PING => {
+                if self.state == State::Open {
+                    let frame = self.frame(OPCODE_PONG, &payload);
+                    self.outgoing.extend(frame);
+                }
+                return Ok(None);
+            }
+            OPCODE_PONG => return Ok(None),
+            OPCODE_CLOSE => {
+                let code = match payload.len() {
+                    0 => None,
+                    1 => return Err(Error::InvalidFrame),
+                    _ => Some(u16::from_be_bytes([payload[0], payload[1]])),
+                };
+                // Echo the status code, unless the close frame answers ours.
+                if self.state == State::Open {
+                    let reply = code.map(u16::to_be_bytes);
+                    let frame = self.frame(OPCODE_CLOSE, reply.as_ref().map_or(&[], |r| &r[..]));
+                    self.outgoing.extend(frame);
+                }
+                return Ok(Some(Message::Close(code)));
+            }
+            OPCODE_TEXT | OPCODE_BINARY if self.fragments.is_none() => (opcode, payload),
+            OPCODE_CONTINUATION => {
+                let Some((first, mut message)) = self.fragments.take() else {
+                    return Err(Error::InvalidFrame);
+                };
+                message.extend_from_slice(&payload);
+                if message.len() > self.max_message_bytes() {
+                    return Err(Error::ResponseTooLarge);
+                }
+                (first, message)
+            }
+            _ => return Err(Error::InvalidFrame),
+        };
+        if !fin {
+            self.fragments = Some((opcode, payload));
+            return Ok(None);
+        }
+        if opcode == OPCODE_TEXT {
+            let text = String::from_utf8(payload).map_err(|_| Error::InvalidUtf8)?;
+            Ok(Some(Message::Text(text)))
+        } else {
+            Ok(Some(Message::Binary(payload)))
+        }
+    }
+
+    /// Queues a frame with `opcode` and `payload`, to be sent once the connection is open.
+    fn send(&mut self, opcode: u8, payload: &[u8]) -> Result<(), Error> {
+        let frame = self.frame(opcode, payload);
+        match self.state {
+            State::Connect | State::Handshake => self.queued.extend(frame),
+            State::Open => self.outgoing.extend(frame),
+            State::Closing | State::Closed => return Err(Error::ConnectionClosed),
+        }
+        Ok(())
+    }
+
+    /// Encodes a single frame with `opcode` and `payload`, masked as frames from clients must
+    /// be.
+    fn frame(&mut self, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(payload.len() + 14);
+        frame.push(0x80 | opcode);
+        match payload.len() {
+            len @ 0..=125 => frame.push(0x80 | len as u8),
+            len if len <= usize::from(u16::MAX) => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        let mask = self.next_mask();
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+        frame
+    }
+
+    /// Returns the next masking key, from a xorshift generator seeded with the caller's
+    /// random bytes.
+    fn next_mask(&mut self) -> [u8; 4] {
+        let mut x = self.mask_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.mask_state = x;
+        let [a, b, c, d, ..] = x.to_le_bytes();
+        [a, b, c, d]
+    }
+}
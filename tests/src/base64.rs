@@ -0,0 +1,50 @@
+use nostd_rpc::Error;
+use nostd_rpc::base64::{Alphabet, decode, encode};
+
+#[test]
+fn base64_rfc4648_vectors() {
+    let vectors = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+    for (plain, encoded) in vectors {
+        assert_eq!(encode(plain.as_bytes(), Alphabet::Standard), encoded);
+        assert_eq!(
+            decode(encoded, Alphabet::Standard).unwrap(),
+            plain.as_bytes()
+        );
+        let unpadded = encoded.trim_end_matches('=');
+        assert_eq!(
+            decode(unpadded, Alphabet::Standard).unwrap(),
+            plain.as_bytes()
+        );
+    }
+}
+
+#[test]
+fn base64_url_safe() {
+    let bytes = [0xfb, 0xff, 0xbf, 0x01];
+    assert_eq!(encode(&bytes, Alphabet::Standard), "+/+/AQ==");
+    assert_eq!(encode(&bytes, Alphabet::UrlSafe), "-_-_AQ");
+    assert_eq!(decode("-_-_AQ==", Alphabet::UrlSafe).unwrap(), bytes);
+    assert_eq!(
+        decode("-_-_AQ", Alphabet::Standard),
+        Err(Error::InvalidBase64)
+    );
+}
+
+#[test]
+fn base64_invalid() {
+    for input in ["Z", "Zm9vY", "Zg=", "Z===", "Zg==Zg==", "Zm 9v"] {
+        assert_eq!(
+            decode(input, Alphabet::Standard),
+            Err(Error::InvalidBase64),
+            "{input}"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use std::net::Ipv4Addr;
+
+use nostd_rpc::Error;
+use nostd_rpc::http::{RequestRef, ResponseRef, send_borrowed};
+use nostd_rpc::response::ParseError;
+use nostd_rpc::testing::{MockServer, SERVER_IP, TickingClock};
+
+#[test]
+fn borrowed_request() {
+    let headers = [("Content-Type", "application/json")];
+    let request = RequestRef::new(Ipv4Addr::new(10, 0, 0, 2).into())
+        .port(8332)
+        .path("/rpc")
+        .headers(&headers)
+        .body(b"{}");
+    let mut buf = [0; 128];
+    let len = request.write_to(&mut buf).unwrap();
+    assert_eq!(
+        &buf[..len],
+        b"POST /rpc HTTP/1.1\r\nHost: 10.0.0.2:8332\r\nContent-Type: application/json\r\n\
+          Content-Length: 2\r\nConnection: close\r\n\r\n{}"
+    );
+    assert_eq!(
+        request.write_to(&mut buf[..len - 1]),
+        Err(Error::BufferTooSmall)
+    );
+
+    let get = request
+        .method("GET")
+        .host("node.local")
+        .body(b"")
+        .headers(&[]);
+    let len = get.write_to(&mut buf).unwrap();
+    assert_eq!(
+        &buf[..len],
+        b"GET /rpc HTTP/1.1\r\nHost: node.local:8332\r\nConnection: close\r\n\r\n"
+    );
+    let invalid = [("Bad Name", "x")];
+    assert_eq!(
+        get.headers(&invalid).write_to(&mut buf),
+        Err(Error::InvalidHeader)
+    );
+}
+
+#[test]
+fn borrowed_response() {
+    let mut data = *b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nX-Id: 7\r\n\r\n\
+                      4\r\nnost\r\n1;ext\r\nd\r\n0\r\nTrailer: 1\r\n\r\n";
+    // Nothing is decoded until the body is complete.
+    let end = data.len() - 2;
+    assert_eq!(ResponseRef::<4>::parse(&mut data[..end]), Ok(None));
+    let response = ResponseRef::<4>::parse(&mut data).unwrap().unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.reason(), "OK");
+    assert_eq!(response.header("x-id"), Some("7"));
+    assert_eq!(response.body(), b"nostd");
+
+    let mut data = *b"HTTP/1.1 200 OK\r\nA: 1\r\nB: 2\r\n\r\n";
+    assert_eq!(
+        ResponseRef::<1>::parse(&mut data),
+        Err(Error::HeadersTooLarge)
+    );
+    let mut data = *b"HTTP/1.0 200 OK\r\n\r\nuntil close";
+    assert_eq!(ResponseRef::<1>::parse(&mut data), Ok(None));
+    assert_eq!(
+        ResponseRef::<1>::finish(&mut data).unwrap().body(),
+        b"until close"
+    );
+    let mut data = *b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nshort";
+    assert_eq!(
+        ResponseRef::<1>::finish(&mut data),
+        Err(Error::InvalidResponse(ParseError::Incomplete))
+    );
+}
+
+#[test]
+fn borrowed_send() {
+    let mut server =
+        MockServer::new(b"HTTP/1.1 200 OK\r\nContent-Length: 11\r\nX-Id: 1\r\n\r\nhello world");
+    let mut iface = server.interface();
+    let request = RequestRef::new(SERVER_IP.into())
+        .method("GET")
+        .path("/hello");
+    let (mut rx, mut tx, mut buffer) = ([0; 256], [0; 256], [0; 512]);
+    let response = send_borrowed::<_, _, 8>(
+        &mut iface,
+        &mut server,
+        &request,
+        &mut rx,
+        &mut tx,
+        &mut buffer,
+        &TickingClock::new(),
+    )
+    .unwrap();
+    assert_eq!(response.header("x-id"), Some("1"));
+    assert_eq!(response.body(), b"hello world");
+    assert_eq!(server.requests()[0].path(), "/hello");
+    drop(response);
+
+    let mut server = MockServer::unreachable();
+    let mut iface = server.interface();
+    let request = request.timeout(smoltcp::time::Duration::from_millis(50));
+    let result = send_borrowed::<_, _, 8>(
+        &mut iface,
+        &mut server,
+        &request,
+        &mut rx,
+        &mut tx,
+        &mut buffer,
+        &TickingClock::new(),
+    );
+    assert_eq!(result, Err(Error::ConnectTimeout));
+}
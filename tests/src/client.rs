@@ -0,0 +1,433 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use nostd_rpc::hooks::Event;
+use nostd_rpc::http::{
+    Backoff, CookieJar, HttpClient, HttpRequest, RetryPolicy, TlsConnector, TokenProvider,
+};
+use nostd_rpc::server::{Server, TlsAcceptor, TlsSession};
+use nostd_rpc::{Error, Hooks};
+use smoltcp::time::{Duration, Instant};
+
+use crate::link::{self, Remote};
+use crate::server::{FakeAcceptor, FakeTls};
+
+/// Sends GET requests for `paths` in turn, restarting the server before the paths marked
+/// `true`, and returns the response bodies and the number of connections made.
+fn get(paths: &[(&str, bool)]) -> (Vec<String>, usize) {
+    let (mut local, remote) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let mut client = HttpClient::new(local, iface);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+
+    let mut bodies = Vec::new();
+    let mut connections = 0;
+    for &(path, restart) in paths {
+        if restart {
+            let restarted = remote.borrow_mut().take().unwrap().restart();
+            *remote.borrow_mut() = Some(restarted);
+        }
+        let mut request = HttpRequest::new()
+            .ipv4([10, 0, 0, 2])
+            .url(path)
+            .timeout(Duration::from_secs(1));
+        if path == "/bye" {
+            request = request.header("Connection: close");
+        }
+        let before = connections;
+        let mut observer = |_: Instant, event: Event<'_>| {
+            if let Event::Connecting = event {
+                connections += 1;
+            }
+        };
+        let mut hooks = Hooks::new().observer(&mut observer);
+        let (response, timings) = client.send(&request, &clock, &mut hooks).unwrap();
+        assert_eq!(response.status(), 200);
+        // The round trip is measured by the handshake of a new connection.
+        assert_eq!(timings.round_trip.is_some(), connections > before);
+        assert!(timings.polls > 0);
+        bodies.push(response.body_text_lossy().into_owned());
+    }
+    (bodies, connections)
+}
+
+#[test]
+fn client_keep_alive() {
+    let (bodies, connections) = get(&[("/one", false), ("/two", false), ("/three", false)]);
+    assert_eq!(bodies, ["/one", "/two", "/three"]);
+    assert_eq!(connections, 1);
+}
+
+#[test]
+fn client_connection_close() {
+    let (bodies, connections) = get(&[("/one", false), ("/bye", false), ("/two", false)]);
+    assert_eq!(bodies, ["/one", "/bye", "/two"]);
+    assert_eq!(connections, 2);
+}
+
+#[test]
+fn client_reconnects() {
+    let (bodies, connections) = get(&[("/one", false), ("/two", true)]);
+    assert_eq!(bodies, ["/one", "/two"]);
+    assert_eq!(connections, 2);
+}
+
+/// The client side of the fake TLS in the server tests: the client says HELLO, the server
+/// replies WELCOME, then data is XORed with 0x55.
+struct FakeClientTls {
+    welcome: Vec<u8>,
+}
+
+impl TlsSession for FakeClientTls {
+    fn read_tls(
+        &mut self,
+        mut ciphertext: &[u8],
+        plaintext: &mut Vec<u8>,
+        _: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        if self.is_handshaking() {
+            let n = ciphertext.len().min(7 - self.welcome.len());
+            self.welcome.extend_from_slice(&ciphertext[..n]);
+            ciphertext = &ciphertext[n..];
+            if self.welcome.len() == 7 && self.welcome != b"WELCOME" {
+                return Err(Error::Tls);
+            }
+        }
+        plaintext.extend(ciphertext.iter().map(|b| b ^ 0x55));
+        Ok(())
+    }
+
+    fn is_handshaking(&self) -> bool {
+        self.welcome.len() < 7
+    }
+
+    fn write(&mut self, plaintext: &[u8], outgoing: &mut Vec<u8>) -> Result<(), Error> {
+        outgoing.extend(plaintext.iter().map(|b| b ^ 0x55));
+        Ok(())
+    }
+
+    fn close(&mut self, _: &mut Vec<u8>) {}
+}
+
+/// Records the server names it connects to.
+#[derive(Default)]
+struct FakeConnector {
+    server_names: std::rc::Rc<RefCell<Vec<String>>>,
+}
+
+impl TlsConnector for FakeConnector {
+    fn connect(
+        &mut self,
+        server_name: &str,
+        outgoing: &mut Vec<u8>,
+    ) -> Result<Box<dyn TlsSession>, Error> {
+        self.server_names.borrow_mut().push(server_name.to_owned());
+        outgoing.extend_from_slice(b"HELLO");
+        Ok(Box::new(FakeClientTls {
+            welcome: Vec::new(),
+        }))
+    }
+}
+
+#[test]
+fn client_tls() {
+    let (mut local, remote) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let server = Server::new(80).tls(FakeAcceptor);
+    let remote = RefCell::new(Some(Remote::with_server(remote, server)));
+    let clock = link::clock(&remote);
+    let connector = FakeConnector::default();
+    let server_names = connector.server_names.clone();
+    let mut client = HttpClient::new(local, iface).tls(connector);
+
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .host("device.local")
+        .url("/secure")
+        .tls(true)
+        .timeout(Duration::from_secs(1));
+    let mut events = Vec::new();
+    for _ in 0..2 {
+        let mut observer = |_: Instant, event: Event<'_>| {
+            if let Event::Connecting | Event::Connected | Event::TlsHandshake = event {
+                events.push(format!("{event:?}"));
+            }
+        };
+        let mut hooks = Hooks::new().observer(&mut observer);
+        let (response, _) = client.send(&request, &clock, &mut hooks).unwrap();
+        assert_eq!(response.body(), b"/secure");
+    }
+    // Both requests are sent over one connection.
+    assert_eq!(*server_names.borrow(), ["device.local"]);
+    assert_eq!(events, ["Connecting", "Connected", "TlsHandshake"]);
+
+    // Without a connector TLS requests fail.
+    let (mut local, _) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let result = HttpClient::new(local, iface).send(&request, &clock, &mut Hooks::new());
+    assert_eq!(result.unwrap_err().root(), &Error::Tls);
+}
+
+/// A server session speaking the fake TLS if the client opens with its HELLO, plain HTTP
+/// otherwise, so one port serves both `https` and `http` URLs.
+enum SniffedTls {
+    Unknown,
+    Plain,
+    Tls(FakeTls),
+}
+
+impl TlsSession for SniffedTls {
+    fn read_tls(
+        &mut self,
+        ciphertext: &[u8],
+        plaintext: &mut Vec<u8>,
+        outgoing: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        if let (SniffedTls::Unknown, Some(&first)) = (&*self, ciphertext.first()) {
+            *self = match first {
+                b'H' => SniffedTls::Tls(FakeTls::default()),
+                _ => SniffedTls::Plain,
+            };
+        }
+        match self {
+            SniffedTls::Unknown => Ok(()),
+            SniffedTls::Plain => {
+                plaintext.extend_from_slice(ciphertext);
+                Ok(())
+            }
+            SniffedTls::Tls(tls) => tls.read_tls(ciphertext, plaintext, outgoing),
+        }
+    }
+
+    fn is_handshaking(&self) -> bool {
+        matches!(self, SniffedTls::Tls(tls) if tls.is_handshaking())
+    }
+
+    fn write(&mut self, plaintext: &[u8], outgoing: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            SniffedTls::Tls(tls) => tls.write(plaintext, outgoing),
+            _ => {
+                outgoing.extend_from_slice(plaintext);
+                Ok(())
+            }
+        }
+    }
+
+    fn close(&mut self, outgoing: &mut Vec<u8>) {
+        if let SniffedTls::Tls(tls) = self {
+            tls.close(outgoing);
+        }
+    }
+}
+
+struct SniffingAcceptor;
+
+impl TlsAcceptor for SniffingAcceptor {
+    fn accept(&mut self) -> Box<dyn TlsSession> {
+        Box::new(SniffedTls::Unknown)
+    }
+}
+
+#[test]
+fn client_tls_then_plain() {
+    let (mut local, remote) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let server = Server::new(80).tls(SniffingAcceptor);
+    let remote = RefCell::new(Some(Remote::with_server(remote, server)));
+    let clock = link::clock(&remote);
+    let mut client = HttpClient::new(local, iface).tls(FakeConnector::default());
+    let mut send = |tls| {
+        let request = HttpRequest::new()
+            .ipv4([10, 0, 0, 2])
+            .host("device.local")
+            .url("/mixed")
+            .tls(tls);
+        let (response, _) = client.send(&request, &clock, &mut Hooks::new()).unwrap();
+        response.body_text_lossy().into_owned()
+    };
+
+    // The plain request opens a new connection, which must not use the TLS session.
+    assert_eq!(send(true), "/mixed");
+    assert_eq!(send(false), "/mixed");
+}
+
+#[test]
+fn client_redirect_to_http_drops_credentials() {
+    let (mut local, remote) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let server = Server::new(80).tls(SniffingAcceptor);
+    let remote = RefCell::new(Some(Remote::with_server(remote, server)));
+    let clock = link::clock(&remote);
+    let mut client = HttpClient::new(local, iface).tls(FakeConnector::default());
+    let mut send = |tls| {
+        let request = HttpRequest::new()
+            .ipv4([10, 0, 0, 2])
+            .host("device.local")
+            .url("/absolute/credentials")
+            .tls(tls)
+            .bearer("secret")
+            .follow_redirects(1);
+        let (response, _) = client.send(&request, &clock, &mut Hooks::new()).unwrap();
+        response.body_text_lossy().into_owned()
+    };
+
+    // The server redirects to an `http` URL on the same host, which keeps the credentials
+    // of a plain request but not those of a TLS request.
+    assert_eq!(send(false), "Bearer secret");
+    assert_eq!(send(true), "none");
+}
+
+#[test]
+fn client_proxy_tunnel() {
+    use smoltcp::iface::SocketSet;
+    use smoltcp::socket::tcp;
+
+    // A proxy that records the CONNECT request and refuses it.
+    let (mut local, mut remote) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let proxy_iface = link::interface(&mut remote, 2);
+    let mut sockets = SocketSet::new(vec![]);
+    let buffer = || tcp::SocketBuffer::new(vec![0; 1024]);
+    let mut socket = tcp::Socket::new(buffer(), buffer());
+    socket.listen(3128).unwrap();
+    let handle = sockets.add(socket);
+    let proxy = RefCell::new((remote, proxy_iface, sockets, Vec::new()));
+    let millis = std::cell::Cell::new(0);
+    let clock = || {
+        millis.set(millis.get() + 1);
+        let now = Instant::from_millis(millis.get());
+        let (device, iface, sockets, received) = &mut *proxy.borrow_mut();
+        iface.poll(now, device, sockets);
+        let socket = sockets.get_mut::<tcp::Socket>(handle);
+        if socket.can_recv() {
+            socket
+                .recv(|data| {
+                    received.extend_from_slice(data);
+                    (data.len(), ())
+                })
+                .unwrap();
+            if received.ends_with(b"\r\n\r\n") {
+                let response = b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n";
+                socket.send_slice(response).unwrap();
+            }
+        }
+        now
+    };
+
+    let mut client = HttpClient::new(local, iface).tls(FakeConnector::default());
+    let request = HttpRequest::new()
+        .host("device.local")
+        .port(8443)
+        .url("/secure")
+        .tls(true)
+        .proxy([10, 0, 0, 2], 3128)
+        .proxy_auth("user", "secret")
+        .timeout(Duration::from_secs(1));
+    let result = client.send(&request, &clock, &mut Hooks::new());
+    assert_eq!(result.unwrap_err().root(), &Error::Proxy(407));
+    assert_eq!(
+        String::from_utf8_lossy(&proxy.borrow().3),
+        "CONNECT device.local:8443 HTTP/1.1\r\nHost: device.local:8443\r\n\
+         Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\n\r\n"
+    );
+}
+
+#[test]
+fn client_cookies() {
+    let (mut local, remote) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let mut client = HttpClient::new(local, iface).cookie_jar(CookieJar::new());
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let mut get = |path: &str| {
+        let request = HttpRequest::new().ipv4([10, 0, 0, 2]).url(path);
+        let (response, _) = client.send(&request, &clock, &mut Hooks::new()).unwrap();
+        response.body_text_lossy().into_owned()
+    };
+    assert_eq!(get("/whoami"), "anonymous");
+    assert_eq!(get("/login"), "welcome");
+    assert_eq!(get("/whoami"), "session=abc");
+    assert_eq!(
+        client.cookies().unwrap().get("10.0.0.2", "session"),
+        Some("abc")
+    );
+}
+
+/// Hands out `stale` until asked to refresh it, then `fresh`.
+struct Tokens {
+    current: &'static str,
+}
+
+impl TokenProvider for Tokens {
+    fn token(&mut self) -> Option<String> {
+        Some(String::from(self.current))
+    }
+
+    fn refresh(&mut self, rejected: &str) -> bool {
+        assert_eq!(rejected, self.current);
+        self.current = "fresh";
+        true
+    }
+}
+
+#[test]
+fn client_token_refresh() {
+    let (mut local, remote) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let tokens = Tokens { current: "stale" };
+    let mut client = HttpClient::new(local, iface).token_provider(tokens);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let request = HttpRequest::new().ipv4([10, 0, 0, 2]).url("/private");
+    for _ in 0..2 {
+        let (response, _) = client.send(&request, &clock, &mut Hooks::new()).unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), b"/private");
+    }
+}
+
+#[test]
+fn client_retry_waits_through_idle_hook() {
+    let (mut local, remote) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let waits = Rc::new(RefCell::new(Vec::new()));
+    let recorded = Rc::clone(&waits);
+    let idle = move |duration: Duration| recorded.borrow_mut().push(duration);
+    let mut client = HttpClient::new(local, iface).idle(idle);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::flaky_clock(&remote, 800);
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .url("/again")
+        .timeout(Duration::from_millis(500))
+        .retry(RetryPolicy::new(
+            3,
+            Backoff::Fixed(Duration::from_millis(100)),
+        ));
+
+    let (response, _) = client.send(&request, &clock, &mut Hooks::new()).unwrap();
+    assert_eq!(response.body(), b"/again");
+    let waits = waits.borrow();
+    assert!(!waits.is_empty());
+    assert!(waits.iter().all(|&wait| wait <= Duration::from_millis(100)));
+}
+
+#[test]
+fn client_waits_on_server_through_idle_hook() {
+    let (mut local, remote) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let waits = Rc::new(RefCell::new(Vec::new()));
+    let recorded = Rc::clone(&waits);
+    let idle = move |duration: Duration| recorded.borrow_mut().push(duration);
+    let mut client = HttpClient::new(local, iface).idle(idle);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let request = HttpRequest::new().ipv4([10, 0, 0, 2]).url("/waited");
+
+    let (response, _) = client.send(&request, &clock, &mut Hooks::new()).unwrap();
+    assert_eq!(response.body(), b"/waited");
+    let waits = waits.borrow();
+    assert!(!waits.is_empty());
+    assert!(waits.iter().all(|&wait| wait > Duration::ZERO));
+}
@@ -0,0 +1,130 @@
+use std::cell::{Cell, RefCell};
+
+use nostd_rpc::Error;
+use nostd_rpc::coap::{CONTENT_FORMAT_JSON, CoapClient, CoapRequest, CoapResponse, Method};
+use smoltcp::iface::SocketSet;
+use smoltcp::socket::udp::{PacketBuffer, PacketMetadata, Socket};
+use smoltcp::time::{Duration, Instant};
+
+use crate::link;
+
+/// Sends `request` to a CoAP server on 10.0.0.2 that answers the datagrams it receives with
+/// those returned by `answer`, given the datagram and the number received before it.
+/// Returns the result and the datagrams the server received.
+fn exchange(
+    request: CoapRequest,
+    answer: impl Fn(&[u8], usize) -> Vec<Vec<u8>>,
+) -> (Result<CoapResponse, Error>, Vec<Vec<u8>>) {
+    let (mut local, mut remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let server_iface = link::interface(&mut remote, 2);
+    let buffer = || PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 1024]);
+    let mut socket = Socket::new(buffer(), buffer());
+    socket.bind(5683).unwrap();
+    let mut sockets = SocketSet::new(vec![]);
+    let handle = sockets.add(socket);
+    let server = RefCell::new((remote, server_iface, sockets, Vec::new()));
+    let millis = Cell::new(0);
+    let clock = || {
+        millis.set(millis.get() + 1);
+        let now = Instant::from_millis(millis.get());
+        let (device, iface, sockets, received) = &mut *server.borrow_mut();
+        iface.poll(now, device, sockets);
+        let socket = sockets.get_mut::<Socket>(handle);
+        while let Ok((data, meta)) = socket.recv() {
+            let data = data.to_vec();
+            for reply in answer(&data, received.len()) {
+                socket.send_slice(&reply, meta.endpoint).unwrap();
+            }
+            received.push(data);
+        }
+        now
+    };
+
+    let mut client = CoapClient::new([7; 8]);
+    let request = request.ipv4([10, 0, 0, 2]);
+    let result = client.send_with_device(&mut iface, &mut local, &request, &clock);
+    // Let the server receive the last acknowledgement.
+    clock();
+    clock();
+    (result, server.into_inner().3)
+}
+
+/// Returns a message of `kind` with `code` answering `request`, with its message ID and
+/// token unless `separate`.
+fn reply(request: &[u8], kind: u8, code: u8, separate: bool, payload: &[u8]) -> Vec<u8> {
+    let mut message = vec![0x40 | (kind << 4) | 4, code];
+    if separate {
+        message.extend_from_slice(&[0x12, 0x34]);
+    } else {
+        message.extend_from_slice(&request[2..4]);
+    }
+    message.extend_from_slice(&request[4..8]);
+    if !payload.is_empty() {
+        message.push(0xff);
+        message.extend_from_slice(payload);
+    }
+    message
+}
+
+#[test]
+fn coap_piggybacked_response() {
+    let request = CoapRequest::new()
+        .path("/fw/state?v=1")
+        .accept(CONTENT_FORMAT_JSON);
+    let (result, received) = exchange(request, |request, _| {
+        vec![reply(request, 2, 0x45, false, b"{\"state\":\"idle\"}")]
+    });
+    let response = result.unwrap();
+    assert!(response.is_success());
+    assert_eq!((response.class(), response.detail()), (2, 5));
+    assert_eq!(response.payload(), b"{\"state\":\"idle\"}");
+
+    // A confirmable GET with a 4 byte token, then Uri-Path, Uri-Query and Accept.
+    let request = &received[0];
+    assert_eq!(request[..2], [0x44, 0x01]);
+    assert_eq!(request[8..], *b"\xb2fw\x05state\x43v=1\x21\x32");
+}
+
+#[test]
+fn coap_retransmission() {
+    let request = CoapRequest::new()
+        .method(Method::Post)
+        .path("/rpc")
+        .payload(b"{}", CONTENT_FORMAT_JSON)
+        .ack_timeout(Duration::from_millis(100));
+    // The first transmission is lost.
+    let (result, received) = exchange(request.clone(), |request, count| match count {
+        0 => vec![],
+        _ => vec![reply(request, 2, 0x44, false, b"")],
+    });
+    assert_eq!(result.unwrap().code(), 0x44);
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0], received[1]);
+
+    let request = request.max_retransmit(2);
+    let (result, received) = exchange(request, |_, _| vec![]);
+    assert_eq!(result.unwrap_err(), Error::ResponseTimeout);
+    assert_eq!(received.len(), 3);
+}
+
+#[test]
+fn coap_separate_response() {
+    let (result, received) = exchange(CoapRequest::new().path("/time"), |request, count| {
+        match count {
+            // An empty acknowledgement, then the response in a confirmable message.
+            0 => vec![
+                vec![0x60, 0, request[2], request[3]],
+                reply(request, 0, 0x45, true, b"1700000000"),
+            ],
+            _ => vec![],
+        }
+    });
+    assert_eq!(result.unwrap().payload(), b"1700000000");
+    assert_eq!(received[1], [0x60, 0, 0x12, 0x34]);
+
+    let (result, _) = exchange(CoapRequest::new(), |request, _| {
+        vec![vec![0x70, 0, request[2], request[3]]]
+    });
+    assert_eq!(result.unwrap_err(), Error::ConnectionRefused);
+}
@@ -0,0 +1,57 @@
+use nostd_rpc::http::CookieJar;
+use nostd_rpc::response::{HttpResponse, ParseMode};
+
+fn response(set_cookies: &[&str]) -> HttpResponse {
+    let mut data = String::from("HTTP/1.1 200 OK\r\n");
+    for cookie in set_cookies {
+        data.push_str(&format!("Set-Cookie: {cookie}\r\n"));
+    }
+    data.push_str("Content-Length: 0\r\n\r\n");
+    HttpResponse::parse(data.as_bytes(), ParseMode::Strict).unwrap()
+}
+
+#[test]
+fn cookie_jar() {
+    let mut jar = CookieJar::new();
+    jar.store(
+        "gw.local",
+        "/api/login",
+        &response(&[
+            "sid=1; HttpOnly",
+            "pref=dark; Path=/",
+            "token=t; Secure; Path=/",
+        ]),
+    );
+    assert_eq!(
+        jar.header("gw.local", "/api/data", false).as_deref(),
+        Some("sid=1; pref=dark")
+    );
+    assert_eq!(
+        jar.header("gw.local", "/other", false).as_deref(),
+        Some("pref=dark")
+    );
+    assert_eq!(
+        jar.header("GW.local", "/other", true).as_deref(),
+        Some("pref=dark; token=t")
+    );
+    assert_eq!(jar.header("other.local", "/", true), None);
+    // A path only matches at a segment boundary.
+    assert_eq!(
+        jar.header("gw.local", "/apis", false).as_deref(),
+        Some("pref=dark")
+    );
+
+    jar.store(
+        "gw.local",
+        "/",
+        &response(&["pref=light; Path=/", "token=; Max-Age=0; Path=/"]),
+    );
+    assert_eq!(
+        jar.header("gw.local", "/", true).as_deref(),
+        Some("pref=light")
+    );
+    assert_eq!(jar.get("gw.local", "sid"), Some("1"));
+
+    jar.clear();
+    assert_eq!(jar.header("gw.local", "/api/data", true), None);
+}
@@ -0,0 +1,153 @@
+use nostd_rpc::Error;
+use nostd_rpc::encoding::{
+    Decoder, EncodeSet, HtmlDecoder, PercentDecoder, decode_url, encode_url, form_encode,
+    html_decode, html_encode, percent_decode, percent_decode_bytes, percent_encode,
+    quoted_printable_decode,
+};
+
+#[test]
+fn decode_named_entities() {
+    assert_eq!(
+        html_decode("&lt;p class=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&nbsp;show&lt;/p&gt;"),
+        "<p class=\"x\">Tom & Jerry's\u{a0}show</p>"
+    );
+    // Unknown or unterminated references are left untouched.
+    assert_eq!(html_decode("&bogus; &amp"), "&bogus; &amp");
+}
+
+#[test]
+fn decode_numeric() {
+    assert_eq!(html_decode("&#72;&#105;%21"), "Hi%21");
+    assert_eq!(html_decode("100% &#;"), "100% &#;");
+}
+
+#[test]
+fn decode_percent() {
+    assert_eq!(
+        percent_decode("a%20b%2Fc%e2%82%AC").unwrap(),
+        "a b/c\u{20ac}"
+    );
+    assert_eq!(percent_decode("100% %zz %4").unwrap(), "100% %zz %4");
+    assert_eq!(percent_decode("&amp;%26").unwrap(), "&amp;&");
+    assert_eq!(percent_decode_bytes("%ff%00"), [0xff, 0x00]);
+    assert_eq!(percent_decode("%ff"), Err(Error::InvalidUtf8));
+}
+
+#[test]
+#[allow(deprecated)]
+fn decode_html_combined() {
+    assert_eq!(nostd_rpc::http::decode_html("&#72;&#105;%21 &lt;"), "Hi! <");
+}
+
+#[cfg(feature = "html5-entities")]
+#[test]
+fn decode_html5_entities() {
+    assert_eq!(
+        html_decode("&Aacute;&bigstar;&NotEqualTilde;"),
+        "\u{c1}\u{2605}\u{2242}\u{338}"
+    );
+}
+
+#[test]
+fn decode_hex_entities() {
+    assert_eq!(
+        html_decode("it&#x27;s &#X27;&#x1F600;&#xaB;&#XAb;"),
+        "it's '\u{1f600}\u{ab}\u{ab}"
+    );
+    // Missing terminators, missing digits and invalid code points are left untouched.
+    assert_eq!(
+        html_decode("&#x27 &#x; &#xZZ; &#x110000;"),
+        "&#x27 &#x; &#xZZ; &#x110000;"
+    );
+    assert_eq!(html_decode("&#x41"), "&#x41");
+}
+
+#[test]
+fn encode_html() {
+    let text = "<a href=\"x\">Tom & Jerry's</a>";
+    let encoded = html_encode(text);
+    assert_eq!(
+        encoded,
+        "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+    );
+    assert_eq!(html_decode(&encoded), text);
+}
+
+#[test]
+fn encode_percent() {
+    let text = "a b/c?d=e&f\u{20ac}";
+    assert_eq!(
+        percent_encode(text, &EncodeSet::UNRESERVED),
+        "a%20b%2Fc%3Fd%3De%26f%E2%82%AC"
+    );
+    assert_eq!(
+        percent_encode(text, &EncodeSet::QUERY_COMPONENT),
+        "a%20b/c?d%3De%26f%E2%82%AC"
+    );
+    assert_eq!(percent_encode("a=b:c", &EncodeSet::PATH_SEGMENT), "a=b:c");
+    assert_eq!(
+        percent_encode("a b", &EncodeSet::unreserved_and(b" ")),
+        "a b"
+    );
+    assert_eq!(
+        percent_decode(&percent_encode(text, &EncodeSet::UNRESERVED)).unwrap(),
+        text
+    );
+}
+
+#[test]
+fn encode_form() {
+    let fields = [("name", "Jane Doe"), ("note", "a&b=c+d\u{e9}"), ("", "")];
+    let encoded = form_encode(&fields);
+    assert_eq!(encoded, "name=Jane+Doe&note=a%26b%3Dc%2Bd%C3%A9&=");
+    assert_eq!(
+        decode_url("a%26b%3Dc%2Bd%C3%A9+x").unwrap(),
+        "a&b=c+d\u{e9} x"
+    );
+}
+
+#[test]
+fn encode_url_components() {
+    assert_eq!(
+        encode_url("value with spaces&more=1+1"),
+        "value%20with%20spaces%26more%3D1%2B1"
+    );
+    assert_eq!(
+        decode_url("value+with%20spaces%26more%3D1%2B1").unwrap(),
+        "value with spaces&more=1+1"
+    );
+    let text = "a+b c/\u{20ac}";
+    assert_eq!(decode_url(&encode_url(text)).unwrap(), text);
+}
+
+/// Feeds `input` to `decoder` split at `at`.
+fn decode_split(decoder: &mut impl Decoder, input: &str, at: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    decoder.feed(&input.as_bytes()[..at], &mut out);
+    decoder.feed(&input.as_bytes()[at..], &mut out);
+    decoder.finish(&mut out);
+    out
+}
+
+#[test]
+fn decode_streaming() {
+    let html = "&lt;p&gt;Tom &amp Jerry&#39;s &bogus; &#x20AC;&&amp;";
+    let percent = "a%20b%%41%zz%e2%82%AC%4";
+    for at in 0..=html.len() {
+        let decoded = decode_split(&mut HtmlDecoder::new(), html, at);
+        assert_eq!(String::from_utf8(decoded).unwrap(), html_decode(html));
+    }
+    for at in 0..=percent.len() {
+        let decoded = decode_split(&mut PercentDecoder::new(), percent, at);
+        assert_eq!(decoded, percent_decode_bytes(percent));
+    }
+}
+
+#[test]
+fn decode_quoted_printable() {
+    let encoded = "Caf=C3=A9 =3D 1  \r\nsoft=\r\n break=\nend =zz =4";
+    assert_eq!(
+        String::from_utf8(quoted_printable_decode(encoded)).unwrap(),
+        "Caf\u{e9} = 1\r\nsoft breakend =zz =4"
+    );
+}
@@ -0,0 +1,143 @@
+use nostd_rpc::Hooks;
+use nostd_rpc::http::{self, HttpRequest};
+use nostd_rpc::sha256;
+use nostd_rpc::testing::{MockServer, SERVER_IP, TickingClock};
+
+/// Nonce and opaque of the RFC 7616 example.
+const CHALLENGE: &str = "realm=\"http-auth@example.org\", \
+    nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+    opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"";
+
+/// Sends a GET request with digest credentials to a server answering every request with a
+/// challenge in each of `headers`, returning the status and the `Authorization` header of
+/// each request the server received.
+fn challenged(headers: &[&str]) -> (u16, Vec<Option<String>>) {
+    challenged_with_seed(headers, [7; 8])
+}
+
+/// Sends a request like [`challenged`], with client nonces drawn from `random`.
+fn challenged_with_seed(headers: &[&str], random: [u8; 8]) -> (u16, Vec<Option<String>>) {
+    let mut response = String::from("HTTP/1.1 401 Unauthorized\r\n");
+    for header in headers {
+        response.push_str(&format!("WWW-Authenticate: {header}\r\n"));
+    }
+    response.push_str("Content-Length: 0\r\n\r\n");
+    let mut server = MockServer::new(response.as_bytes());
+    let mut iface = server.interface();
+    let request = HttpRequest::new()
+        .ip(SERVER_IP)
+        .method("GET")
+        .url("/dir/index.html")
+        .digest_auth("Mufasa", "Circle of Life", random);
+    let (response, _) = http::send_with_device(
+        &mut iface,
+        &mut server,
+        request,
+        &TickingClock::new(),
+        &mut Hooks::new(),
+    )
+    .unwrap();
+    let authorizations = server
+        .requests()
+        .iter()
+        .map(|r| r.header("Authorization").map(String::from))
+        .collect();
+    (response.status(), authorizations)
+}
+
+#[test]
+fn digest_sha256() {
+    let (status, authorizations) = challenged(&[&format!(
+        "Digest {CHALLENGE}, qop=\"auth, auth-int\", algorithm=SHA-256"
+    )]);
+    // The challenge is answered once.
+    assert_eq!(status, 401);
+    assert_eq!(authorizations.len(), 2);
+    assert_eq!(authorizations[0], None);
+    let authorization = authorizations[1].as_deref().unwrap();
+    let cnonce = cnonce(authorization);
+    assert_eq!(cnonce.len(), 16);
+
+    let hash = |data: &str| -> String {
+        let digest = sha256::digest(data.as_bytes());
+        digest.iter().map(|b| format!("{b:02x}")).collect()
+    };
+    let ha1 = hash("Mufasa:http-auth@example.org:Circle of Life");
+    let ha2 = hash("GET:/dir/index.html");
+    let nonce = "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v";
+    let response = hash(&format!("{ha1}:{nonce}:00000001:{cnonce}:auth:{ha2}"));
+    assert_eq!(
+        authorization,
+        format!(
+            "Digest username=\"Mufasa\", realm=\"http-auth@example.org\", \
+             nonce=\"{nonce}\", uri=\"/dir/index.html\", algorithm=SHA-256, \
+             response=\"{response}\", qop=auth, nc=00000001, cnonce=\"{cnonce}\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""
+        )
+    );
+}
+
+/// Returns the client nonce of the Digest response `authorization`.
+fn cnonce(authorization: &str) -> &str {
+    let start = authorization.find("cnonce=\"").unwrap() + 8;
+    let len = authorization[start..].find('"').unwrap();
+    &authorization[start..start + len]
+}
+
+#[test]
+fn digest_cnonce_seeded() {
+    let header = format!("Digest {CHALLENGE}, qop=auth");
+    let cnonces = [[1; 8], [2; 8]].map(|random| {
+        let (_, authorizations) = challenged_with_seed(&[&header], random);
+        String::from(cnonce(authorizations[1].as_deref().unwrap()))
+    });
+    assert_ne!(cnonces[0], cnonces[1]);
+}
+
+#[test]
+fn digest_md5() {
+    let (_, authorizations) = challenged(&[&format!("Digest {CHALLENGE}")]);
+    assert_eq!(
+        authorizations[1].as_deref(),
+        Some(
+            "Digest username=\"Mufasa\", realm=\"http-auth@example.org\", \
+             nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", uri=\"/dir/index.html\", \
+             algorithm=MD5, response=\"7b2cc3b30e75b4777ea31027084363fd\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""
+        )
+    );
+
+    let (_, authorizations) =
+        challenged(&[&format!("Digest {CHALLENGE}, qop=auth, algorithm=MD5-sess")]);
+    let authorization = authorizations[1].as_deref().unwrap();
+    assert!(authorization.contains("algorithm=MD5-sess, response=\""));
+    assert!(authorization.contains("qop=auth, nc=00000001, cnonce=\""));
+}
+
+#[test]
+fn digest_challenge_choice() {
+    // SHA-256 is preferred, and other schemes are skipped.
+    let (_, authorizations) = challenged(&[
+        &format!("Basic realm=\"x\", Digest {CHALLENGE}"),
+        &format!("Digest {CHALLENGE}, algorithm=SHA-256"),
+    ]);
+    assert!(
+        authorizations[1]
+            .as_deref()
+            .unwrap()
+            .contains("algorithm=SHA-256")
+    );
+
+    // Unsupported challenges are not answered.
+    for header in [
+        "Basic realm=\"x\"",
+        &format!("Digest {CHALLENGE}, algorithm=SHA-512-256"),
+        &format!("Digest {CHALLENGE}, qop=auth-int"),
+        // The suffix is not split inside a multi-byte character.
+        &format!("Digest {CHALLENGE}, algorithm=éabcd"),
+    ] {
+        let (status, authorizations) = challenged(&[header]);
+        assert_eq!(status, 401);
+        assert_eq!(authorizations, [None]);
+    }
+}
@@ -0,0 +1,74 @@
+use nostd_rpc::Error;
+use nostd_rpc::download::ResumableDownload;
+use nostd_rpc::http::HttpRequest;
+use nostd_rpc::response::{HttpResponse, ParseMode};
+
+fn response(head: &str, body: &str) -> HttpResponse {
+    let data = format!("{head}\r\nContent-Length: {}\r\n\r\n{body}", body.len());
+    HttpResponse::parse(data.as_bytes(), ParseMode::Strict).unwrap()
+}
+
+#[test]
+fn range_header() {
+    let request = HttpRequest::new()
+        .range(10, Some(19))
+        .construct_http_request();
+    assert!(request.contains("\r\nRange: bytes=10-19\r\n"));
+    let request = HttpRequest::new().range(10, None).construct_http_request();
+    assert!(request.contains("\r\nRange: bytes=10-\r\n"));
+}
+
+#[test]
+fn resume_download() {
+    let mut download = ResumableDownload::new();
+
+    let first = response("HTTP/1.1 200 OK\r\nETag: \"v1\"", "0123456789");
+    // Interrupted after four bytes.
+    download.accept_head(&first).unwrap();
+    download.record(4);
+    assert_eq!(download.offset(), 4);
+    assert_eq!(download.total(), Some(10));
+    assert_eq!(download.etag(), Some("\"v1\""));
+
+    let request = download
+        .request(HttpRequest::new())
+        .construct_http_request();
+    assert!(request.contains("\r\nRange: bytes=4-\r\nIf-Range: \"v1\"\r\n"));
+
+    let rest = response(
+        "HTTP/1.1 206 Partial Content\r\nETag: \"v1\"\r\nContent-Range: bytes 4-9/10",
+        "456789",
+    );
+    assert_eq!(download.accept(&rest).unwrap(), b"456789");
+    assert!(download.is_complete());
+    let done = response("HTTP/1.1 416 Range Not Satisfiable", "");
+    assert_eq!(download.accept(&done).unwrap(), b"");
+}
+
+#[test]
+fn resume_mismatch() {
+    let mut download = ResumableDownload::new();
+    download
+        .accept_head(&response("HTTP/1.1 200 OK\r\nETag: \"v1\"", "0123456789"))
+        .unwrap();
+    download.record(4);
+
+    let mismatched = [
+        // The server ignored the range or the resource changed.
+        response("HTTP/1.1 200 OK\r\nETag: \"v2\"", "abcdefghij"),
+        // Wrong offset.
+        response(
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-9/10",
+            "56789",
+        ),
+        // Different entity tag.
+        response(
+            "HTTP/1.1 206 Partial Content\r\nETag: \"v2\"\r\nContent-Range: bytes 4-9/10",
+            "efghij",
+        ),
+        response("HTTP/1.1 206 Partial Content", "456789"),
+    ];
+    for response in &mismatched {
+        assert_eq!(download.clone().accept(response), Err(Error::RangeMismatch));
+    }
+}
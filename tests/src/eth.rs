@@ -0,0 +1,93 @@
+use std::fmt;
+
+use nostd_rpc::Error;
+use nostd_rpc::eth::{
+    Block, EthClient, decode_data, decode_quantity, encode_data, encode_quantity,
+};
+use nostd_rpc::transport::{Request, Response, Transport};
+
+/// Answers every request with `result`, keeping the method and parameters of each.
+struct Canned {
+    result: &'static str,
+    sent: Vec<(String, String, u64)>,
+}
+
+impl Transport for Canned {
+    fn send_request(&mut self, request: Request<'_>) -> Result<Response, Error> {
+        let sent = (request.method.into(), request.params.into(), request.id);
+        self.sent.push(sent);
+        Ok(Response {
+            id: request.id,
+            result: Ok(String::from(self.result)),
+        })
+    }
+
+    fn send_batch(&mut self, requests: &[Request<'_>]) -> Result<Vec<Response>, Error> {
+        requests.iter().map(|r| self.send_request(*r)).collect()
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("canned")
+    }
+}
+
+fn client(result: &'static str) -> EthClient<Canned> {
+    EthClient::new(Canned {
+        result,
+        sent: Vec::new(),
+    })
+}
+
+#[test]
+fn eth_hex_encoding() {
+    assert_eq!(encode_quantity(0), "0x0");
+    assert_eq!(encode_quantity(436), "0x1b4");
+    assert_eq!(decode_quantity("0x1b4"), Ok(436));
+    assert_eq!(decode_quantity("0x0"), Ok(0));
+    assert_eq!(decode_quantity("0x"), Err(Error::InvalidHex));
+    assert_eq!(decode_quantity("1b4"), Err(Error::InvalidHex));
+    assert_eq!(decode_quantity("0x+1"), Err(Error::InvalidHex));
+    assert_eq!(
+        decode_quantity(&format!("0x1{}", "0".repeat(32))),
+        Err(Error::InvalidHex)
+    );
+
+    assert_eq!(encode_data(&[]), "0x");
+    assert_eq!(encode_data(&[0x00, 0xff, 0x1a]), "0x00ff1a");
+    assert_eq!(decode_data("0x00FF1a"), Ok(vec![0x00, 0xff, 0x1a]));
+    assert_eq!(decode_data("0x"), Ok(vec![]));
+    assert_eq!(decode_data("0x0"), Err(Error::InvalidHex));
+    assert_eq!(decode_data("0xzz"), Err(Error::InvalidHex));
+}
+
+#[test]
+fn eth_calls() {
+    let mut eth = client("\"0x4b7\"");
+    assert_eq!(eth.block_number(), Ok(1207));
+    let mut address = [0; 20];
+    address[19] = 0x42;
+    assert_eq!(eth.get_balance(&address, Block::Number(16)), Ok(1207));
+    let sent = &eth.transport().sent;
+    assert_eq!(sent[0], ("eth_blockNumber".into(), "[]".into(), 1));
+    assert_eq!(
+        sent[1],
+        (
+            "eth_getBalance".into(),
+            "[\"0x0000000000000000000000000000000000000042\",\"0x10\"]".into(),
+            2
+        )
+    );
+
+    let mut eth = client("\"0x000000000000000000000000000000000000000000000000000000000000002a\"");
+    let data = eth
+        .call(&address, &[0x70, 0xa0, 0x82, 0x31], Block::Latest)
+        .unwrap();
+    assert_eq!(data.len(), 32);
+    assert_eq!(data[31], 42);
+    assert_eq!(
+        eth.transport().sent[0].1,
+        "[{\"to\":\"0x0000000000000000000000000000000000000042\",\"data\":\"0x70a08231\"},\"latest\"]"
+    );
+
+    assert_eq!(client("12").block_number(), Err(Error::InvalidRpcResponse));
+}
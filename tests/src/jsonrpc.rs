@@ -0,0 +1,115 @@
+use nostd_rpc::Error;
+use nostd_rpc::jsonrpc::{parse_batch_response, parse_response, request_body};
+use nostd_rpc::response::{HttpResponse, ParseMode};
+
+fn response(status: &str, body: &str) -> HttpResponse {
+    let data = format!(
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    HttpResponse::parse(data.as_bytes(), ParseMode::Strict).unwrap()
+}
+
+#[test]
+fn jsonrpc_request_body() {
+    assert_eq!(
+        request_body("getblock", "[\"00ab\", 1]", 7),
+        "{\"jsonrpc\":\"2.0\",\"method\":\"getblock\",\"params\":[\"00ab\", 1],\"id\":7}"
+    );
+    assert_eq!(
+        request_body("say \"hi\"", "", 1),
+        "{\"jsonrpc\":\"2.0\",\"method\":\"say \\\"hi\\\"\",\"id\":1}"
+    );
+}
+
+#[test]
+fn jsonrpc_parse_response() {
+    let body = r#"{"jsonrpc": "2.0", "result": {"height": 10, "tags": ["a", "b}"]}, "id": 3}"#;
+    assert_eq!(
+        parse_response(&response("200 OK", body), 3),
+        Ok(String::from(r#"{"height": 10, "tags": ["a", "b}"]}"#))
+    );
+    let body = r#"{"jsonrpc":"2.0","result":null,"error":null,"id":3}"#;
+    assert_eq!(
+        parse_response(&response("200 OK", body), 3),
+        Ok(String::from("null"))
+    );
+
+    // Servers such as bitcoind return errors with a 500 status.
+    let body = r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":3}"#;
+    assert_eq!(
+        parse_response(&response("500 Internal Server Error", body), 3),
+        Err(Error::Rpc {
+            code: -32601,
+            message: String::from("Method not found")
+        })
+    );
+    let body = r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error"},"id":null}"#;
+    assert!(matches!(
+        parse_response(&response("200 OK", body), 3),
+        Err(Error::Rpc { code: -32700, .. })
+    ));
+
+    let cases = [
+        (
+            "200 OK",
+            r#"{"jsonrpc":"2.0","result":1,"id":4}"#,
+            Error::InvalidRpcResponse,
+        ),
+        (
+            "200 OK",
+            r#"{"jsonrpc":"2.0","result":1,"id":3"#,
+            Error::InvalidRpcResponse,
+        ),
+        (
+            "200 OK",
+            r#"{"jsonrpc":"2.0","id":3}"#,
+            Error::InvalidRpcResponse,
+        ),
+        ("502 Bad Gateway", "<html></html>", Error::Status(502)),
+    ];
+    for (status, body, error) in cases {
+        assert_eq!(
+            parse_response(&response(status, body), 3),
+            Err(error),
+            "{body}"
+        );
+    }
+}
+
+#[test]
+fn jsonrpc_parse_batch_response() {
+    let body = r#"[
+        {"jsonrpc":"2.0","result":"b","id":2},
+        {"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":3},
+        {"jsonrpc":"2.0","result":["a"],"id":1}
+    ]"#;
+    let results = parse_batch_response(&response("200 OK", body), &[1, 2, 3, 4]).unwrap();
+    assert_eq!(
+        results,
+        [
+            Ok(String::from("[\"a\"]")),
+            Ok(String::from("\"b\"")),
+            Err(Error::Rpc {
+                code: -32601,
+                message: String::from("Method not found")
+            }),
+            Err(Error::InvalidRpcResponse),
+        ]
+    );
+
+    // The server answers a batch it cannot parse with a single error.
+    let body = r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error"},"id":null}"#;
+    assert!(matches!(
+        parse_batch_response(&response("200 OK", body), &[1]),
+        Err(Error::Rpc { code: -32700, .. })
+    ));
+    assert_eq!(
+        parse_batch_response(&response("200 OK", "[1, 2]"), &[1]),
+        Err(Error::InvalidRpcResponse)
+    );
+    assert_eq!(
+        parse_batch_response(&response("503 Service Unavailable", ""), &[1]),
+        Err(Error::Status(503))
+    );
+}
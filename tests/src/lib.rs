@@ -1,33 +1,107 @@
+#[cfg(test)]
+mod base64;
+#[cfg(all(test, feature = "no-alloc"))]
+mod borrowed;
+#[cfg(test)]
+mod client;
+#[cfg(test)]
+mod coap;
+#[cfg(all(test, feature = "http-compat"))]
+mod compat;
+#[cfg(test)]
+mod cookie;
+#[cfg(test)]
+mod decode;
+#[cfg(test)]
+mod digest;
+#[cfg(test)]
+mod download;
+#[cfg(all(test, feature = "eth"))]
+mod eth;
+#[cfg(test)]
+mod jsonrpc;
+#[cfg(test)]
+mod link;
+#[cfg(test)]
+mod mac;
+#[cfg(test)]
+mod mqtt;
+#[cfg(test)]
+mod net;
+#[cfg(test)]
+mod ota;
+#[cfg(all(test, feature = "pcap"))]
+mod pcap;
+#[cfg(test)]
+mod pool;
+#[cfg(test)]
+mod protorpc;
+#[cfg(test)]
+mod provisioning;
+#[cfg(test)]
+mod request;
+#[cfg(test)]
+mod response;
+#[cfg(test)]
+mod server;
+#[cfg(test)]
+mod sign;
+#[cfg(test)]
+mod stack;
+#[cfg(test)]
+mod tcp;
+#[cfg(test)]
+mod testing;
+#[cfg(test)]
+mod time;
+#[cfg(all(test, feature = "log"))]
+mod trace;
+#[cfg(test)]
+mod transport;
+#[cfg(all(test, feature = "serde"))]
+mod typed;
+#[cfg(test)]
+mod udp;
+#[cfg(test)]
+mod url;
+#[cfg(test)]
+mod websocket;
+
 #[cfg(test)]
 mod tests {
-    use nostd_rpc::http;
+    use nostd_rpc::mac::MacAddress;
+    use nostd_rpc::net::NetworkConfig;
+    use nostd_rpc::{encoding, http};
     use smoltcp::time::Duration;
 
+    /// A locally administered address for the tests.
+    const ETHERNET_MAC: MacAddress = MacAddress::new([0x02, 0x2d, 0x1e, 0xef, 0x5c, 0x45]);
+
+    /// Resolves host names with a public DNS server.
+    fn network() -> NetworkConfig {
+        NetworkConfig::new().dns_server([1, 1, 1, 1])
+    }
+
     #[test]
     fn get() {
         let request = http::HttpRequest::new()
-            .ipv4([104, 100, 168, 75]) // IP address for www.example.com (may change)
+            .network(network())
             .port(80)
             .host("www.example.com")
             .url("/index.html")
             .method("GET")
             .timeout(Duration::from_secs(5));
-        let ethernet_mac = [0x05, 0x2d, 0x1e, 0xef, 0x5c, 0x45];
-        let result = http::send(ethernet_mac, request).unwrap();
-        let parsed = http::decode_html(&result);
+        let result = http::send(ETHERNET_MAC.octets(), request).unwrap();
+        let parsed = encoding::html_decode(&result.body_text_lossy());
 
-        let expected_response_start = "Connected to server.\nHTTP/1.1 200 OK";
-        assert!(
-            result.starts_with(expected_response_start),
-            "Unexpected response: \n\n{}",
-            parsed
-        );
+        assert!(result.is_success(), "Unexpected response: \n\n{}", parsed);
+        assert_eq!(result.status(), 200);
     }
 
     #[test]
     fn post() {
         let request = http::HttpRequest::new()
-            .ipv4([54, 152, 142, 77]) // IP address for httpbin.org (may change)
+            .network(network())
             .port(80)
             .url("/post")
             .host("httpbin.org")
@@ -35,15 +109,10 @@ mod tests {
             .header("Content-Type: application/json")
             .body("{\"key1\": \"value1\", \"key2\": \"value2\"}")
             .timeout(Duration::from_secs(5));
-        let ethernet_mac = [0x05, 0x2d, 0x1e, 0xef, 0x5c, 0x45];
-        let result = http::send(ethernet_mac, request).unwrap();
-        let parsed = http::decode_html(&result);
+        let result = http::send(ETHERNET_MAC.octets(), request).unwrap();
+        let parsed = encoding::html_decode(&result.body_text_lossy());
 
-        let expected_response_start = "Connected to server.\nHTTP/1.1 200 OK";
-        assert!(
-            result.starts_with(expected_response_start),
-            "Unexpected response: \n\n{}",
-            parsed
-        );
+        assert!(result.is_success(), "Unexpected response: \n\n{}", parsed);
+        assert_eq!(result.status(), 200);
     }
 }
@@ -0,0 +1,206 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use nostd_rpc::server::{Request, Response, Server};
+use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
+use smoltcp::phy::{self, Device, DeviceCapabilities, Medium};
+use smoltcp::socket::udp;
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr};
+
+type Queue = Rc<RefCell<VecDeque<Vec<u8>>>>;
+
+/// One end of an Ethernet link between two interfaces.
+pub struct Pipe {
+    rx: Queue,
+    tx: Queue,
+}
+
+/// Returns the two ends of a link.
+pub fn pipe() -> (Pipe, Pipe) {
+    let (a, b) = (Queue::default(), Queue::default());
+    let one = Pipe {
+        rx: a.clone(),
+        tx: b.clone(),
+    };
+    (one, Pipe { rx: b, tx: a })
+}
+
+pub struct RxToken(Vec<u8>);
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F: FnOnce(&[u8]) -> R>(self, f: F) -> R {
+        f(&self.0)
+    }
+}
+
+pub struct TxToken(Queue);
+
+impl phy::TxToken for TxToken {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, len: usize, f: F) -> R {
+        let mut frame = vec![0; len];
+        let result = f(&mut frame);
+        self.0.borrow_mut().push_back(frame);
+        result
+    }
+}
+
+impl Device for Pipe {
+    type RxToken<'a> = RxToken;
+    type TxToken<'a> = TxToken;
+
+    fn receive(&mut self, _: Instant) -> Option<(RxToken, TxToken)> {
+        let frame = self.rx.borrow_mut().pop_front()?;
+        Some((RxToken(frame), TxToken(self.tx.clone())))
+    }
+
+    fn transmit(&mut self, _: Instant) -> Option<TxToken> {
+        Some(TxToken(self.tx.clone()))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut capabilities = DeviceCapabilities::default();
+        capabilities.medium = Medium::Ethernet;
+        capabilities.max_transmission_unit = 1514;
+        capabilities
+    }
+}
+
+/// Returns an interface on `device` with address 10.0.0.`host`.
+pub fn interface(device: &mut Pipe, host: u8) -> Interface {
+    let config = Config::new(EthernetAddress([0x02, 0, 0, 0, 0, host]).into());
+    let mut iface = Interface::new(config, device, Instant::ZERO);
+    iface.update_ip_addrs(|addrs| {
+        addrs
+            .push(IpCidr::new(IpAddress::v4(10, 0, 0, host), 24))
+            .unwrap();
+    });
+    iface
+}
+
+/// Echoes the request body, or the path if the body is empty. Paths under `/redirect`
+/// redirect to the rest of the path, those under `/absolute` to the same with the full URL
+/// of the host, and `/loop` redirects to itself. `/login` sets a
+/// session cookie, which `/whoami` echoes. `/private` is only served with the bearer token
+/// `fresh`, and `/credentials` echoes the `Authorization` header.
+fn handler(request: &Request) -> Response {
+    match request.path() {
+        "/private" if request.header("Authorization") != Some("Bearer fresh") => {
+            return Response::new(401);
+        }
+        "/login" => {
+            return Response::text(200, "welcome").header("Set-Cookie", "session=abc; Path=/");
+        }
+        "/whoami" => return Response::text(200, request.header("Cookie").unwrap_or("anonymous")),
+        "/credentials" => {
+            return Response::text(200, request.header("Authorization").unwrap_or("none"));
+        }
+        _ => {}
+    }
+    if let Some(target) = request.path().strip_prefix("/redirect") {
+        return Response::new(302).header("Location", target);
+    }
+    if let Some(target) = request.path().strip_prefix("/absolute") {
+        let host = request.header("Host").unwrap_or("");
+        return Response::new(302).header("Location", &format!("http://{host}{target}"));
+    }
+    if request.path() == "/loop" {
+        return Response::new(307).header("Location", "loop");
+    }
+    match std::str::from_utf8(request.body()) {
+        Ok(body) if !body.is_empty() => Response::text(200, body),
+        _ => Response::text(200, request.path()),
+    }
+}
+
+/// A server on 10.0.0.2, at the other end of the link from the client.
+pub struct Remote {
+    device: Pipe,
+    iface: Interface,
+    sockets: SocketSet<'static>,
+    server: Server,
+    /// Socket answering DNS queries, if any.
+    dns: Option<SocketHandle>,
+}
+
+impl Remote {
+    pub fn new(device: Pipe) -> Self {
+        Remote::with_server(device, Server::new(80))
+    }
+
+    /// Constructs a remote running `server`, which must listen on port 80.
+    pub fn with_server(mut device: Pipe, mut server: Server) -> Self {
+        let iface = interface(&mut device, 2);
+        let mut sockets = SocketSet::new(vec![]);
+        server.listen(&mut sockets).unwrap();
+        Remote {
+            device,
+            iface,
+            sockets,
+            server,
+            dns: None,
+        }
+    }
+
+    /// Also answers DNS queries on port 53, resolving every name to 10.0.0.2.
+    pub fn with_dns(mut self) -> Self {
+        let buffer = || udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 4], vec![0; 1024]);
+        let mut socket = udp::Socket::new(buffer(), buffer());
+        socket.bind(53).unwrap();
+        self.dns = Some(self.sockets.add(socket));
+        self
+    }
+
+    /// Forgets all connections without telling the clients, as a restarted server would.
+    pub fn restart(self) -> Self {
+        Remote::new(self.device)
+    }
+
+    pub fn poll(&mut self, timestamp: Instant) {
+        self.iface
+            .poll(timestamp, &mut self.device, &mut self.sockets);
+        self.server.poll(&mut self.sockets, &mut handler).unwrap();
+        if let Some(handle) = self.dns {
+            let socket = self.sockets.get_mut::<udp::Socket>(handle);
+            while let Ok((query, meta)) = socket.recv() {
+                let answer = dns_answer(query);
+                socket.send_slice(&answer, meta.endpoint).unwrap();
+            }
+        }
+    }
+}
+
+/// Returns the answer to a DNS `query` for one name: an A record for 10.0.0.2.
+fn dns_answer(query: &[u8]) -> Vec<u8> {
+    let mut answer = query[..2].to_vec();
+    // A response without errors, with the question and one answer.
+    answer.extend_from_slice(&[0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0]);
+    answer.extend_from_slice(&query[12..]);
+    // The name is a pointer to the question, then type A, class IN, a TTL of 60 s.
+    answer.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 10, 0, 0, 2]);
+    answer
+}
+
+/// Returns a clock that advances by a millisecond each time it is read, polling `remote`
+/// so the server runs while the client waits.
+pub fn clock(remote: &RefCell<Option<Remote>>) -> impl Fn() -> Instant + '_ {
+    flaky_clock(remote, 0)
+}
+
+/// Returns a clock like [`clock`], for a link that loses everything sent to `remote` until
+/// `up_millis`.
+pub fn flaky_clock(remote: &RefCell<Option<Remote>>, up_millis: i64) -> impl Fn() -> Instant + '_ {
+    let millis = Cell::new(0);
+    move || {
+        millis.set(millis.get() + 1);
+        let now = Instant::from_millis(millis.get());
+        let mut remote = remote.borrow_mut();
+        let remote = remote.as_mut().unwrap();
+        if millis.get() < up_millis {
+            remote.device.rx.borrow_mut().clear();
+        }
+        remote.poll(now);
+        now
+    }
+}
@@ -0,0 +1,35 @@
+use nostd_rpc::Error;
+use nostd_rpc::mac::MacAddress;
+
+#[test]
+fn mac_parse_and_format() {
+    let mac: MacAddress = "02:2D:1e:ef:5c:45".parse().unwrap();
+    assert_eq!(mac.octets(), [0x02, 0x2d, 0x1e, 0xef, 0x5c, 0x45]);
+    assert_eq!(mac.to_string(), "02:2d:1e:ef:5c:45");
+    assert_eq!("02-2d-1e-ef-5c-45".parse(), Ok(mac));
+    for invalid in [
+        "02:2d:1e:ef:5c",
+        "02:2d:1e:ef:5c:45:00",
+        "02:2d:1e:ef:5c:4",
+        "02:2d-1e:ef:5c:45",
+        "zz:2d:1e:ef:5c:45",
+    ] {
+        assert_eq!(
+            invalid.parse::<MacAddress>(),
+            Err(Error::InvalidMac),
+            "{invalid}"
+        );
+    }
+}
+
+#[test]
+fn mac_generated() {
+    let mac = MacAddress::locally_administered([0xff; 6]);
+    assert_eq!(mac.octets(), [0xfe, 0xff, 0xff, 0xff, 0xff, 0xff]);
+    assert!(mac.is_locally_administered() && !mac.is_multicast());
+
+    let mac = MacAddress::from_chip_id(b"chip 1");
+    assert_eq!(mac, MacAddress::from_chip_id(b"chip 1"));
+    assert_ne!(mac, MacAddress::from_chip_id(b"chip 2"));
+    assert!(mac.is_locally_administered() && !mac.is_multicast());
+}
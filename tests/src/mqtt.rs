@@ -0,0 +1,166 @@
+use std::cell::{Cell, RefCell};
+use std::task::Poll;
+
+use nostd_rpc::Error;
+use nostd_rpc::mqtt::{MqttClient, MqttOptions, Notification, QoS};
+use smoltcp::iface::SocketSet;
+use smoltcp::socket::tcp;
+use smoltcp::time::Instant;
+
+use crate::link;
+
+/// Removes the first packet from `data`, if all of it has arrived.
+fn next_packet(data: &mut Vec<u8>) -> Option<Vec<u8>> {
+    // The packets in these tests are short enough for a single byte remaining length.
+    let len = 2 + usize::from(*data.get(1)?);
+    (data.len() >= len).then(|| data.drain(..len).collect())
+}
+
+/// Connects with `options` to a broker on 10.0.0.2 that accepts the connection with return
+/// code `code`, and calls `session` with the client and a clock until it returns false.
+/// Returns the packets the broker received.
+fn session(
+    options: MqttOptions,
+    code: u8,
+    mut session: impl FnMut(&mut MqttClient<'_, link::Pipe>, &dyn Fn() -> Instant) -> bool,
+) -> Vec<Vec<u8>> {
+    let (mut local, mut remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let broker_iface = link::interface(&mut remote, 2);
+    let mut sockets = SocketSet::new(vec![]);
+    let buffer = || tcp::SocketBuffer::new(vec![0; 1024]);
+    let mut socket = tcp::Socket::new(buffer(), buffer());
+    socket.listen(1883).unwrap();
+    let handle = sockets.add(socket);
+    let broker = RefCell::new((remote, broker_iface, sockets, Vec::new(), Vec::new()));
+    let millis = Cell::new(0);
+    let clock = || {
+        millis.set(millis.get() + 1);
+        let now = Instant::from_millis(millis.get());
+        let (device, iface, sockets, received, packets) = &mut *broker.borrow_mut();
+        iface.poll(now, device, sockets);
+        let socket = sockets.get_mut::<tcp::Socket>(handle);
+        if socket.can_recv() {
+            socket
+                .recv(|data| {
+                    received.extend_from_slice(data);
+                    (data.len(), ())
+                })
+                .unwrap();
+        }
+        while let Some(packet) = next_packet(received) {
+            let answer: &[u8] = match packet[0] >> 4 {
+                1 => &[0x20, 0x02, 0x00, code],
+                // Grant QoS 1, then deliver a QoS 1 message on the subscribed topic.
+                8 => &[
+                    0x90, 0x03, packet[2], packet[3], 0x01, 0x32, 0x0b, 0x00, 0x05, b'c', b'm',
+                    b'd', b'/', b'x', 0x00, 0x07, b'o', b'n',
+                ],
+                3 if packet[0] & 0x06 != 0 => {
+                    let topic_len = usize::from(packet[3]);
+                    let id = &packet[4 + topic_len..6 + topic_len];
+                    &[0x40, 0x02, id[0], id[1]]
+                }
+                12 => &[0xd0, 0x00],
+                _ => &[],
+            };
+            socket.send_slice(answer).unwrap();
+            packets.push(packet);
+        }
+        now
+    };
+
+    let options = options.ipv4([10, 0, 0, 2]);
+    let mut client = MqttClient::connect(&mut iface, &mut local, options, clock()).unwrap();
+    while session(&mut client, &clock) {}
+    drop(client);
+    broker.into_inner().4
+}
+
+#[test]
+fn mqtt_publish_subscribe() {
+    let mut notifications = Vec::new();
+    let mut sent = false;
+    let packets = session(MqttOptions::new("sensor-1"), 0, |client, clock| {
+        if !sent {
+            sent = true;
+            assert_eq!(client.subscribe("cmd/#", QoS::AtLeastOnce), Ok(1));
+            let id = client.publish("telemetry", b"21.5", QoS::AtLeastOnce, false);
+            assert_eq!(id, Ok(Some(2)));
+            client
+                .publish("status", b"up", QoS::AtMostOnce, true)
+                .unwrap();
+        }
+        match client.poll(clock()) {
+            Poll::Ready(notification) => notifications.push(notification.unwrap()),
+            Poll::Pending => {}
+        }
+        if notifications.len() < 4 {
+            return true;
+        }
+        client.disconnect();
+        // Send the last acknowledgement and the DISCONNECT.
+        for _ in 0..10 {
+            let _ = client.poll(clock());
+        }
+        false
+    });
+
+    assert_eq!(
+        notifications,
+        [
+            Notification::Connected {
+                session_present: false
+            },
+            Notification::Subscribed {
+                packet_id: 1,
+                granted: vec![Some(QoS::AtLeastOnce)]
+            },
+            Notification::Message {
+                topic: "cmd/x".to_owned(),
+                payload: b"on".to_vec(),
+                qos: QoS::AtLeastOnce,
+                retain: false,
+            },
+            Notification::Published { packet_id: 2 },
+        ]
+    );
+    assert_eq!(
+        packets,
+        [
+            b"\x10\x14\x00\x04MQTT\x04\x02\x00\x3c\x00\x08sensor-1".to_vec(),
+            b"\x82\x0a\x00\x01\x00\x05cmd/#\x01".to_vec(),
+            b"\x32\x11\x00\x09telemetry\x00\x0221.5".to_vec(),
+            b"\x31\x0a\x00\x06statusup".to_vec(),
+            b"\x40\x02\x00\x07".to_vec(),
+            b"\xe0\x00".to_vec(),
+        ]
+    );
+}
+
+#[test]
+fn mqtt_keep_alive() {
+    let mut result = Poll::Pending;
+    let options = MqttOptions::new("").keep_alive(1).credentials("dev", "pw");
+    let packets = session(options, 0, |client, clock| {
+        result = client.poll(clock());
+        // Two keep alive intervals.
+        clock() < Instant::from_millis(2500)
+    });
+    assert_eq!(result, Poll::Pending);
+    assert_eq!(
+        packets,
+        [
+            b"\x10\x15\x00\x04MQTT\x04\xc2\x00\x01\x00\x00\x00\x03dev\x00\x02pw".to_vec(),
+            b"\xc0\x00".to_vec(),
+            b"\xc0\x00".to_vec(),
+        ]
+    );
+
+    let mut result = Poll::Pending;
+    session(MqttOptions::new("sensor-1"), 5, |client, clock| {
+        result = client.poll(clock());
+        result.is_pending()
+    });
+    assert_eq!(result, Poll::Ready(Err(Error::BrokerRefused(5))));
+}
@@ -0,0 +1,120 @@
+use std::cell::Cell;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use nostd_rpc::Error;
+use nostd_rpc::net::{self, LocalPorts, NetworkConfig};
+use smoltcp::iface::{Config, Interface};
+use smoltcp::phy::{Device, DeviceCapabilities, Loopback, Medium};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetAddress, HardwareAddress, Ieee802154Address, IpCidr};
+
+fn interface() -> Interface {
+    let mut device = Loopback::new(Medium::Ethernet);
+    let config = Config::new(EthernetAddress([0x02, 0, 0, 0, 0, 1]).into());
+    Interface::new(config, &mut device, Instant::ZERO)
+}
+
+#[test]
+fn network_config_apply() {
+    let mut iface = interface();
+    NetworkConfig::new()
+        .ipv4(Ipv4Addr::new(10, 0, 0, 7), 16)
+        .gateway(Ipv4Addr::new(10, 0, 0, 1))
+        .ipv6(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 7), 64)
+        .ipv6_gateway(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1))
+        .apply(&mut iface)
+        .unwrap();
+    assert_eq!(
+        iface.ip_addrs(),
+        [
+            IpCidr::new(Ipv4Addr::new(10, 0, 0, 7).into(), 16),
+            IpCidr::new(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 7).into(), 64),
+        ]
+    );
+
+    let invalid = NetworkConfig::new().ipv4(Ipv4Addr::new(10, 0, 0, 7), 33);
+    assert_eq!(invalid.apply(&mut interface()), Err(Error::IpAddress));
+}
+
+#[test]
+fn dhcp_timeout() {
+    let mut device = Loopback::new(Medium::Ethernet);
+    let config = Config::new(EthernetAddress([0x02, 0, 0, 0, 0, 1]).into());
+    let mut iface = Interface::new(config, &mut device, Instant::ZERO);
+    let millis = Cell::new(0);
+    let clock = || {
+        millis.set(millis.get() + 10);
+        Instant::from_millis(millis.get())
+    };
+    let network = NetworkConfig::dhcp(Duration::from_secs(1));
+    assert_eq!(
+        network.configure(&mut iface, &mut device, &clock),
+        Err(Error::DhcpTimeout)
+    );
+    assert!(iface.ip_addrs().is_empty());
+}
+
+#[test]
+fn local_ports() {
+    let mut ports = LocalPorts::new([7; 8]);
+    let allocated: Vec<u16> = (0..1000)
+        .map(|i| ports.next(Instant::from_millis(i)))
+        .collect();
+    assert!(allocated.iter().all(|&port| port >= 49152));
+    // No port is reused while it is among the recent ones.
+    for window in allocated.windows(32) {
+        let mut sorted = window.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 32);
+    }
+
+    // The same seed and times give the same ports, another seed others.
+    let mut same = LocalPorts::new([7; 8]);
+    assert_eq!(same.next(Instant::from_millis(0)), allocated[0]);
+    let mut other = LocalPorts::new([8; 8]);
+    let others: Vec<u16> = (0..4)
+        .map(|i| other.next(Instant::from_millis(i)))
+        .collect();
+    assert_ne!(others, allocated[..4]);
+}
+
+#[test]
+fn interface_config_media() {
+    let mac = [0x02, 0x11, 0x22, 0x33, 0x44, 0x55];
+    let network = NetworkConfig::new().pan_id(0xabcd);
+
+    let mut device = Loopback::new(Medium::Ethernet);
+    let config = network.interface_config(&device.capabilities(), mac);
+    let iface = Interface::new(config, &mut device, Instant::ZERO);
+    assert_eq!(iface.hardware_addr(), EthernetAddress(mac).into());
+
+    let mut device = Loopback::new(Medium::Ieee802154);
+    let config = network.interface_config(&device.capabilities(), mac);
+    assert_eq!(config.pan_id.map(|pan| pan.0), Some(0xabcd));
+    let iface = Interface::new(config, &mut device, Instant::ZERO);
+    let eui64 = [0x02, 0x11, 0x22, 0xff, 0xfe, 0x33, 0x44, 0x55];
+    assert_eq!(
+        iface.hardware_addr(),
+        HardwareAddress::Ieee802154(Ieee802154Address::Extended(eui64))
+    );
+
+    // An IP device has no hardware address, and the interface accepts the configuration.
+    let mut device = Loopback::new(Medium::Ip);
+    let config = network.interface_config(&device.capabilities(), mac);
+    assert_eq!(config.hardware_addr, HardwareAddress::Ip);
+    let mut iface = Interface::new(config, &mut device, Instant::ZERO);
+    NetworkConfig::new().apply(&mut iface).unwrap();
+}
+
+#[test]
+fn buffer_size_for_mtu() {
+    let mut capabilities = DeviceCapabilities::default();
+    capabilities.medium = Medium::Ethernet;
+    capabilities.max_transmission_unit = 1514;
+    assert_eq!(net::buffer_size(&capabilities), 1024);
+
+    capabilities.medium = Medium::Ieee802154;
+    capabilities.max_transmission_unit = 127;
+    assert_eq!(net::buffer_size(&capabilities), 508);
+}
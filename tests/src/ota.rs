@@ -0,0 +1,44 @@
+use nostd_rpc::ota::Storage;
+use nostd_rpc::sha256::{Sha256, digest};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[test]
+fn sha256_vectors() {
+    assert_eq!(
+        hex(&digest(b"")),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        hex(&digest(b"abc")),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        hex(&digest(
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+        )),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+
+    // Fed in uneven pieces.
+    let data = vec![b'a'; 1_000_000];
+    let mut hasher = Sha256::new();
+    for piece in data.chunks(997) {
+        hasher.update(piece);
+    }
+    assert_eq!(
+        hex(&hasher.finalize()),
+        "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+    );
+}
+
+#[test]
+fn vec_storage() {
+    let mut storage = Vec::new();
+    storage.write(0, b"abc").unwrap();
+    storage.write(3, b"def").unwrap();
+    storage.write(1, b"X").unwrap();
+    assert_eq!(storage, b"aXcdef");
+}
@@ -0,0 +1,68 @@
+use nostd_rpc::Hooks;
+use nostd_rpc::http::{self, HttpRequest};
+use nostd_rpc::pcap::{self, PcapBuffer};
+use nostd_rpc::testing::{MockServer, SERVER_IP, TickingClock};
+
+/// Returns the packets of a libpcap capture.
+fn packets(capture: &[u8]) -> Vec<&[u8]> {
+    let mut packets = Vec::new();
+    let mut rest = &capture[24..];
+    while !rest.is_empty() {
+        let len = u32::from_ne_bytes(rest[8..12].try_into().unwrap()) as usize;
+        packets.push(&rest[16..16 + len]);
+        rest = &rest[16 + len..];
+    }
+    packets
+}
+
+#[test]
+fn capture_frames() {
+    let mut buffer = PcapBuffer::new(64 * 1024);
+    let mut server = MockServer::new(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    let mut iface = server.interface();
+    let mut device = pcap::capture(server, &mut buffer);
+    let request = HttpRequest::new().ip(SERVER_IP).url("/captured");
+    let (response, _) = http::send_with_device(
+        &mut iface,
+        &mut device,
+        request,
+        &TickingClock::new(),
+        &mut Hooks::new(),
+    )
+    .unwrap();
+    assert_eq!(response.body(), b"ok");
+    drop(device);
+
+    let capture = buffer.as_bytes();
+    assert_eq!(&capture[..4], &0xa1b2c3d4u32.to_ne_bytes());
+    // Ethernet link type.
+    assert_eq!(&capture[20..24], &1u32.to_ne_bytes());
+    let frames = packets(capture);
+    let contains = |needle: &[u8]| {
+        frames
+            .iter()
+            .any(|packet| packet.windows(needle.len()).any(|w| w == needle))
+    };
+    assert!(contains(b"POST /captured HTTP/1.1\r\n"));
+    assert!(contains(b"\r\n\r\nok"));
+    assert_eq!(buffer.dropped(), 0);
+
+    // A full buffer leaves packets out whole.
+    let mut small = PcapBuffer::new(200);
+    let mut server = MockServer::new(b"HTTP/1.1 204 No Content\r\n\r\n");
+    let mut iface = server.interface();
+    let mut device = pcap::capture(server, &mut small);
+    let request = HttpRequest::new().ip(SERVER_IP);
+    http::send_with_device(
+        &mut iface,
+        &mut device,
+        request,
+        &TickingClock::new(),
+        &mut Hooks::new(),
+    )
+    .unwrap();
+    drop(device);
+    assert!(small.dropped() > 0);
+    assert!(small.as_bytes().len() <= 200);
+    assert!(!packets(small.as_bytes()).is_empty());
+}
@@ -0,0 +1,85 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use nostd_rpc::Error;
+use nostd_rpc::http::{ClientPool, HttpRequest, TransactionSet};
+use nostd_rpc::server::Server;
+use smoltcp::time::Duration;
+
+use crate::link::{self, Remote};
+
+#[test]
+fn pool_concurrent_requests() {
+    let (mut local, remote) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let server = Server::new(80).connections(4);
+    let remote = RefCell::new(Some(Remote::with_server(remote, server)));
+    let clock = link::clock(&remote);
+    let mut pool = ClientPool::new(local, iface);
+
+    let request = HttpRequest::new().ipv4([10, 0, 0, 2]).method("GET");
+    let first = pool.send(request.clone().url("/first"), clock());
+    let second = pool.send(request.clone().url("/second"), clock());
+    let cancelled = pool.send(request.url("/cancelled"), clock());
+    // Nothing answers on 10.0.0.3.
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 3])
+        .timeout(Duration::from_millis(100));
+    let unreachable = pool.send(request, clock());
+    assert_eq!(pool.len(), 4);
+    assert!(pool.cancel(cancelled, clock()));
+    assert!(!pool.cancel(cancelled, clock()));
+
+    let mut results = HashMap::new();
+    while !pool.is_empty() && clock().total_millis() < 1000 {
+        while let Some((id, result)) = pool.poll(clock()) {
+            results.insert(id, result);
+        }
+    }
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[&first].as_ref().unwrap().body(), b"/first");
+    assert_eq!(results[&second].as_ref().unwrap().body(), b"/second");
+    let error = results[&unreachable].as_ref().unwrap_err();
+    assert_eq!(error.root(), &Error::ConnectTimeout);
+}
+
+#[test]
+fn pool_request_without_ip() {
+    let (mut local, remote) = link::pipe();
+    let iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let mut pool = ClientPool::new(local, iface);
+
+    let id = pool.send(HttpRequest::new().host("device.local"), clock());
+    let (done, result) = pool.poll(clock()).unwrap();
+    assert_eq!(done, id);
+    assert_eq!(result.unwrap_err().root(), &Error::UnresolvedHost);
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn transaction_set_poll_all() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let server = Server::new(80).connections(3);
+    let remote = RefCell::new(Some(Remote::with_server(remote, server)));
+    let clock = link::clock(&remote);
+    let (mut rx_buffer, mut tx_buffer) = ([0; 512], [0; 512]);
+    let mut set = TransactionSet::new(&mut iface, &mut local);
+
+    let request = HttpRequest::new().ipv4([10, 0, 0, 2]);
+    let ids = [
+        set.register(request.clone().url("/one"), clock()),
+        set.register_with_buffers(request.url("/two"), &mut rx_buffer, &mut tx_buffer, clock()),
+    ];
+    let mut completed = Vec::new();
+    while !set.is_empty() && clock().total_millis() < 1000 {
+        completed.extend(set.poll_all(clock()));
+    }
+    assert_eq!(completed.len(), 2);
+    for (id, path) in ids.iter().zip(["/one", "/two"]) {
+        let (_, result) = completed.iter().find(|(done, _)| done == id).unwrap();
+        assert_eq!(result.as_ref().unwrap().body(), path.as_bytes());
+    }
+}
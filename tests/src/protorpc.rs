@@ -0,0 +1,134 @@
+use nostd_rpc::Error;
+use nostd_rpc::http::{HttpClient, HttpRequest};
+use nostd_rpc::protorpc::{Message, ProtoClient, Protocol, grpc_web_frame};
+use nostd_rpc::testing::{MockServer, SERVER_IP, TickingClock};
+
+/// A message with a single varint field 1, as protobuf encodes it.
+#[derive(Debug, PartialEq)]
+struct Reading(u8);
+
+impl Message for Reading {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&[0x08, self.0]);
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        match bytes {
+            [0x08, value] if *value < 0x80 => Ok(Reading(*value)),
+            _ => Err(Error::InvalidRpcResponse),
+        }
+    }
+}
+
+fn proto_client(
+    response: &[u8],
+    url: &str,
+    protocol: Protocol,
+) -> ProtoClient<MockServer, TickingClock> {
+    let mut server = MockServer::new(response);
+    let iface = server.interface();
+    let request = HttpRequest::new().ip(SERVER_IP).url(url);
+    ProtoClient::new(
+        HttpClient::new(server, iface),
+        request,
+        TickingClock::new(),
+        protocol,
+    )
+}
+
+#[test]
+fn twirp_call() {
+    let mut client = proto_client(
+        b"HTTP/1.1 200 OK\r\nContent-Type: application/protobuf\r\nContent-Length: 2\r\n\r\n\x08\x2a",
+        "/twirp/",
+        Protocol::Twirp,
+    );
+    let reply: Reading = client
+        .call("sensors.Telemetry", "Report", &Reading(7))
+        .unwrap();
+    assert_eq!(reply, Reading(42));
+
+    let sent = &client.client().device().requests()[0];
+    assert_eq!(sent.method(), "POST");
+    assert_eq!(sent.path(), "/twirp/sensors.Telemetry/Report");
+    assert_eq!(sent.header("Content-Type"), Some("application/protobuf"));
+    assert_eq!(sent.body(), b"\x08\x07");
+}
+
+#[test]
+fn twirp_error() {
+    let body = r#"{"code":"not_found","msg":"no such sensor"}"#;
+    let response = format!(
+        "HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let mut client = proto_client(response.as_bytes(), "/twirp", Protocol::Twirp);
+    let result: Result<Vec<u8>, _> = client.call("sensors.Telemetry", "Get", &Vec::new());
+    assert_eq!(
+        result.map_err(|e| e.root().clone()),
+        Err(Error::Twirp {
+            code: String::from("not_found"),
+            message: String::from("no such sensor"),
+        })
+    );
+}
+
+#[test]
+fn grpc_web_call() {
+    let mut body = grpc_web_frame(b"\x08\x2a");
+    let trailers = b"grpc-status: 0\r\ngrpc-message: \r\n";
+    body.push(0x80);
+    body.extend_from_slice(&(trailers.len() as u32).to_be_bytes());
+    body.extend_from_slice(trailers);
+    let mut response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/grpc-web+proto\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(&body);
+
+    let mut client = proto_client(&response, "/", Protocol::GrpcWeb);
+    let reply: Reading = client
+        .call("sensors.Telemetry", "Report", &Reading(7))
+        .unwrap();
+    assert_eq!(reply, Reading(42));
+
+    let sent = &client.client().device().requests()[0];
+    assert_eq!(sent.path(), "/sensors.Telemetry/Report");
+    assert_eq!(
+        sent.header("Content-Type"),
+        Some("application/grpc-web+proto")
+    );
+    assert_eq!(sent.body(), b"\x00\x00\x00\x00\x02\x08\x07");
+}
+
+#[test]
+fn grpc_web_errors() {
+    // A reply without messages has its status in the headers.
+    let mut client = proto_client(
+        b"HTTP/1.1 200 OK\r\ngrpc-status: 5\r\ngrpc-message: no%20such%20sensor\r\n\
+          Content-Length: 0\r\n\r\n",
+        "/",
+        Protocol::GrpcWeb,
+    );
+    let result: Result<Vec<u8>, _> = client.call("sensors.Telemetry", "Get", &Vec::new());
+    assert_eq!(
+        result.map_err(|e| e.root().clone()),
+        Err(Error::Grpc {
+            status: 5,
+            message: String::from("no such sensor"),
+        })
+    );
+
+    // A frame longer than the body.
+    let mut client = proto_client(
+        b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\n\x00\x00\x00\x00\x09\x08",
+        "/",
+        Protocol::GrpcWeb,
+    );
+    let result: Result<Vec<u8>, _> = client.call("sensors.Telemetry", "Get", &Vec::new());
+    assert_eq!(
+        result.map_err(|e| e.root().clone()),
+        Err(Error::InvalidRpcResponse)
+    );
+}
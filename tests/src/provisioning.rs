@@ -0,0 +1,159 @@
+use std::net::Ipv4Addr;
+
+use nostd_rpc::provisioning::{Portal, Provision};
+use nostd_rpc::response::{HttpResponse, ParseMode, ResponseParser};
+use smoltcp::iface::{Config, Interface, SocketSet};
+use smoltcp::phy::{Loopback, Medium};
+use smoltcp::socket::{tcp, udp};
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr};
+
+const DEVICE_IP: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 1);
+
+/// Sends a DNS query and an HTTP request to `portal` on a loopback interface, returning the
+/// DNS answer and the HTTP response.
+fn exchange<P: Provision>(
+    portal: &mut Portal<P>,
+    dns_query: &[u8],
+    request: &[u8],
+) -> (Vec<u8>, HttpResponse) {
+    let mut device = Loopback::new(Medium::Ethernet);
+    let config = Config::new(EthernetAddress([0x02, 0, 0, 0, 0, 1]).into());
+    let mut iface = Interface::new(config, &mut device, Instant::ZERO);
+    iface.update_ip_addrs(|addrs| {
+        addrs.push(IpCidr::new(DEVICE_IP.into(), 8)).unwrap();
+    });
+    let mut sockets = SocketSet::new(vec![]);
+    portal.listen(&mut sockets).unwrap();
+
+    let mut dns = udp::Socket::new(
+        udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 1024]),
+        udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 2], vec![0; 1024]),
+    );
+    dns.bind(5353).unwrap();
+    dns.send_slice(dns_query, (IpAddress::from(DEVICE_IP), 53))
+        .unwrap();
+    let dns = sockets.add(dns);
+
+    let mut http = tcp::Socket::new(
+        tcp::SocketBuffer::new(vec![0; 2048]),
+        tcp::SocketBuffer::new(vec![0; 2048]),
+    );
+    http.connect(iface.context(), (IpAddress::from(DEVICE_IP), 80), 49152)
+        .unwrap();
+    let http = sockets.add(http);
+
+    let mut answer = Vec::new();
+    let mut parser = ResponseParser::new().mode(ParseMode::Strict);
+    let mut response = None;
+    let mut sent = false;
+    for millis in 0..1000 {
+        iface.poll(Instant::from_millis(millis), &mut device, &mut sockets);
+        portal.poll(&mut sockets).unwrap();
+
+        if let Ok((data, _)) = sockets.get_mut::<udp::Socket>(dns).recv() {
+            answer = data.to_vec();
+        }
+        let socket = sockets.get_mut::<tcp::Socket>(http);
+        if socket.can_send() && !sent {
+            socket.send_slice(request).unwrap();
+            sent = true;
+        }
+        if socket.can_recv() {
+            let data = socket.recv(|data| (data.len(), data.to_vec())).unwrap();
+            response = response.or(parser.feed(&data).unwrap());
+        }
+        if response.is_some() && !answer.is_empty() {
+            break;
+        }
+    }
+    (answer, response.unwrap())
+}
+
+/// A DNS query with ID 0x1234 and recursion desired for `example.com` of type `qtype`.
+fn dns_query(qtype: u8) -> Vec<u8> {
+    let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    query.extend_from_slice(b"\x07example\x03com\x00");
+    query.extend_from_slice(&[0, qtype, 0, 1]);
+    query
+}
+
+#[test]
+fn portal_dns_and_redirect() {
+    let mut portal = Portal::new(DEVICE_IP, |_: &[(String, String)]| true);
+    let request = b"GET /generate_204 HTTP/1.1\r\nHost: connectivitycheck.example\r\n\r\n";
+    let (answer, response) = exchange(&mut portal, &dns_query(1), request);
+
+    let query = dns_query(1);
+    assert_eq!(&answer[..4], [0x12, 0x34, 0x85, 0x00]);
+    assert_eq!(&answer[4..12], [0, 1, 0, 1, 0, 0, 0, 0]);
+    assert_eq!(&answer[12..query.len()], &query[12..]);
+    assert_eq!(
+        &answer[query.len()..],
+        [0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 127, 0, 0, 1]
+    );
+
+    assert_eq!(response.status(), 302);
+    assert_eq!(response.header("location"), Some("http://127.0.0.1/"));
+    assert!(!portal.is_provisioned());
+}
+
+#[test]
+fn portal_provision() {
+    let mut received = Vec::new();
+    let mut portal = Portal::new(DEVICE_IP, |fields: &[(String, String)]| {
+        received = fields.to_vec();
+        true
+    });
+    let body = "{\"ssid\": \"Home \\\"5G\\\"\", \"password\": \"p\\u00e4ss\"}";
+    let request = format!(
+        "POST /provision HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Type: application/json\r\n\
+        Content-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    // AAAA queries get an empty answer.
+    let (answer, response) = exchange(&mut portal, &dns_query(28), request.as_bytes());
+    assert_eq!(&answer[4..12], [0, 1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(response.status(), 200);
+    assert!(portal.is_provisioned());
+    drop(portal);
+    assert_eq!(
+        received,
+        [
+            (String::from("ssid"), String::from("Home \"5G\"")),
+            (String::from("password"), String::from("p\u{e4}ss")),
+        ]
+    );
+
+    let mut received = Vec::new();
+    let mut portal = Portal::new(DEVICE_IP, |fields: &[(String, String)]| {
+        received = fields.to_vec();
+        false
+    });
+    let body = "ssid=Caf%C3%A9+Wifi&password=a%26b";
+    let request = format!(
+        "POST /provision HTTP/1.1\r\nHost: 127.0.0.1\r\n\
+        Content-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let (_, response) = exchange(&mut portal, &dns_query(1), request.as_bytes());
+    assert_eq!(response.status(), 422);
+    assert!(!portal.is_provisioned());
+    drop(portal);
+    assert_eq!(
+        received,
+        [
+            (String::from("ssid"), String::from("Caf\u{e9} Wifi")),
+            (String::from("password"), String::from("a&b")),
+        ]
+    );
+}
+
+#[test]
+fn portal_page() {
+    let mut portal = Portal::new(DEVICE_IP, |_: &[(String, String)]| true).page("<p>setup</p>");
+    let request = b"GET / HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n";
+    let (_, response) = exchange(&mut portal, &dns_query(1), request);
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body(), b"<p>setup</p>");
+}
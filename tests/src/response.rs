@@ -0,0 +1,325 @@
+use nostd_rpc::Error;
+use nostd_rpc::response::{HeaderLimits, HttpResponse, ParseError, ParseMode, ResponseParser};
+#[cfg(feature = "compression")]
+use nostd_rpc::{
+    Hooks,
+    http::{self, HttpRequest},
+    testing::{MockServer, SERVER_IP, TickingClock},
+};
+
+#[test]
+fn parse_response() {
+    let data = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello";
+    let response = HttpResponse::parse(data, ParseMode::Strict).unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert!(response.is_success());
+    assert_eq!(response.reason(), "OK");
+    assert_eq!(response.minor_version(), 1);
+    assert_eq!(response.header("content-type"), Some("text/plain"));
+    assert_eq!(response.body(), b"hello");
+}
+
+#[test]
+fn lenient_accepts_legacy_quirks() {
+    let data = b"\nHTTP/1.0 204\nServer : legacy\nX-Folded: a\n  b\n\n";
+    let response = HttpResponse::parse(data, ParseMode::Lenient).unwrap();
+
+    assert_eq!(response.status(), 204);
+    assert_eq!(response.reason(), "");
+    assert_eq!(response.header("Server"), Some("legacy"));
+    assert_eq!(response.header("X-Folded"), Some("a b"));
+}
+
+#[test]
+fn strict_rejects_legacy_quirks() {
+    let cases: [(&[u8], ParseError); 5] = [
+        (b"HTTP/1.1 200 OK\n\n", ParseError::BareLineFeed),
+        (b"HTTP/1.1 200\r\n\r\n", ParseError::StatusLine),
+        (b"HTTP/1.1 2OO OK\r\n\r\n", ParseError::StatusLine),
+        (
+            b"HTTP/1.1 200 OK\r\nServer : legacy\r\n\r\n",
+            ParseError::Header,
+        ),
+        (
+            b"HTTP/1.1 200 OK\r\nX-Bad: a\x01b\r\n\r\n",
+            ParseError::Header,
+        ),
+    ];
+    for (data, expected) in cases {
+        assert_eq!(
+            HttpResponse::parse(data, ParseMode::Strict),
+            Err(Error::InvalidResponse(expected)),
+            "{:?}",
+            String::from_utf8_lossy(data)
+        );
+    }
+}
+
+#[test]
+fn incomplete_head() {
+    let result = HttpResponse::parse(b"HTTP/1.1 200 OK\r\nServer: x\r\n", ParseMode::Lenient);
+    assert_eq!(result, Err(Error::InvalidResponse(ParseError::Incomplete)));
+}
+
+#[test]
+fn header_limits() {
+    let data = b"HTTP/1.1 200 OK\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n";
+
+    let limits = HeaderLimits::new().max_headers(3);
+    assert!(HttpResponse::parse_with_limits(data, ParseMode::Strict, &limits).is_ok());
+
+    let limits = HeaderLimits::new().max_headers(2);
+    let result = HttpResponse::parse_with_limits(data, ParseMode::Strict, &limits);
+    assert_eq!(result, Err(Error::HeadersTooLarge));
+
+    let limits = HeaderLimits::new().max_header_bytes(20);
+    let result = HttpResponse::parse_with_limits(data, ParseMode::Strict, &limits);
+    assert_eq!(result, Err(Error::HeadersTooLarge));
+
+    // An unterminated head is rejected as soon as it exceeds the limit.
+    let result = HttpResponse::parse_with_limits(&[b'x'; 32], ParseMode::Strict, &limits);
+    assert_eq!(result, Err(Error::HeadersTooLarge));
+}
+
+#[test]
+fn duplicate_headers() {
+    let data = b"HTTP/1.1 200 OK\r\n\
+        Cache-Control: no-cache\r\n\
+        Set-Cookie: a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT\r\n\
+        cache-control: no-store\r\n\
+        Set-Cookie: b=2\r\n\
+        \r\n";
+    let response = HttpResponse::parse(data, ParseMode::Strict).unwrap();
+
+    assert_eq!(response.header("Cache-Control"), Some("no-cache"));
+    let all: Vec<&str> = response.header_all("cache-control").collect();
+    assert_eq!(all, ["no-cache", "no-store"]);
+    assert_eq!(
+        response.header_combined("Cache-Control").as_deref(),
+        Some("no-cache, no-store")
+    );
+
+    let cookies: Vec<&str> = response.header_all("Set-Cookie").collect();
+    assert_eq!(
+        cookies,
+        ["a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT", "b=2"]
+    );
+    assert_eq!(response.header_combined("Set-Cookie"), None);
+}
+
+#[test]
+fn conflicting_content_length() {
+    let same = b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\nContent-Length: 1\r\n\r\nx";
+    assert!(HttpResponse::parse(same, ParseMode::Strict).is_ok());
+
+    let conflicting = b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nx";
+    let result = HttpResponse::parse(conflicting, ParseMode::Lenient);
+    assert_eq!(result, Err(Error::InvalidResponse(ParseError::Header)));
+}
+
+#[test]
+fn binary_body() {
+    let data = b"HTTP/1.1 200 OK\r\n\r\n\x00\xff\xfehi";
+    let response = HttpResponse::parse(data, ParseMode::Strict).unwrap();
+
+    assert_eq!(response.body(), b"\x00\xff\xfehi");
+    assert_eq!(response.body_text_lossy(), "\u{0}\u{fffd}\u{fffd}hi");
+    assert_eq!(response.body_text_strict(), Err(Error::InvalidUtf8));
+    assert_eq!(response.into_body(), b"\x00\xff\xfehi");
+}
+
+#[test]
+fn incremental_chunked() {
+    let data = b"HTTP/1.1 100 Continue\r\n\r\n\
+        HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+        5;ext=1\r\nhello\r\nA\r\n, chunked!\r\n0\r\nX-Trailer: 1\r\n\r\n";
+    let mut parser = ResponseParser::new().mode(ParseMode::Strict);
+
+    let (last, rest) = data.split_last().unwrap();
+    for byte in rest {
+        assert_eq!(parser.feed(&[*byte]).unwrap(), None);
+    }
+    let response = parser.feed(&[*last]).unwrap().unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body(), b"hello, chunked!");
+}
+
+#[test]
+fn streamed_chunked() {
+    let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+        5\r\nhello\r\nA\r\n, chunked!\r\n0\r\n\r\n";
+    let mut parser = ResponseParser::new();
+    let mut chunks = Vec::new();
+    let mut on_body = |chunk: &[u8]| chunks.push(chunk.to_vec());
+    let (head, rest) = data.split_at(53);
+    assert_eq!(parser.feed_streaming(head, &mut on_body).unwrap(), None);
+    let response = parser.feed_streaming(rest, &mut on_body).unwrap().unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body(), b"");
+    assert_eq!(chunks, [&b"hel"[..], b"lo, chunked!"]);
+}
+
+#[test]
+fn incremental_content_length() {
+    let mut parser = ResponseParser::new();
+    let first = parser
+        .feed(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nhiHTTP/1.1 204 No Content\r\n\r\n")
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.body(), b"hi");
+
+    // The bytes after the first response are the start of the next.
+    let second = parser.feed(b"").unwrap().unwrap();
+    assert_eq!(second.status(), 204);
+}
+
+#[test]
+fn incremental_close_delimited() {
+    let mut parser = ResponseParser::new();
+    assert_eq!(parser.feed(b"HTTP/1.0 200 OK\r\n\r\nuntil").unwrap(), None);
+    assert_eq!(parser.feed(b" close").unwrap(), None);
+    assert_eq!(parser.finish().unwrap().body(), b"until close");
+
+    let mut parser = ResponseParser::new();
+    parser
+        .feed(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort")
+        .unwrap();
+    assert_eq!(
+        parser.finish(),
+        Err(Error::InvalidResponse(ParseError::Incomplete))
+    );
+}
+
+#[test]
+fn display_response() {
+    let data =
+        b"HTTP/1.1 200 OK\r\nSet-Cookie: session=secret\r\nContent-Type: text/plain\r\n\r\nhello";
+    let response = HttpResponse::parse(data, ParseMode::Strict).unwrap();
+    assert_eq!(
+        response.to_string(),
+        "HTTP/1.1 200 OK\r\nSet-Cookie: [redacted]\r\nContent-Type: text/plain\r\n\r\nhello"
+    );
+
+    let mut data = b"HTTP/1.1 200 OK\r\n\r\n".to_vec();
+    data.extend_from_slice(&[b'a'; 300]);
+    let response = HttpResponse::parse(&data, ParseMode::Strict).unwrap();
+    let shown = response.to_string();
+    assert!(shown.ends_with(&format!("{}... (44 more bytes)", "a".repeat(256))));
+    assert!(format!("{response:#}").ends_with(&"a".repeat(300)));
+}
+
+/// Returns the bytes written in hexadecimal in `hex`.
+#[cfg(feature = "compression")]
+fn unhex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// A deflate body of 100 KB of zeros, compressed to 120 bytes.
+#[cfg(feature = "compression")]
+fn deflate_bomb() -> Vec<u8> {
+    unhex(&format!(
+        "78daedc13101000000c2a0f54f6d0d0fa0{}80570386af0001",
+        "00".repeat(96)
+    ))
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn decompress_sent_response() {
+    let bomb = deflate_bomb();
+    let mut response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Encoding: deflate\r\nContent-Length: {}\r\n\r\n",
+        bomb.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(&bomb);
+    let send = |request: HttpRequest| {
+        let mut server = MockServer::new(&response);
+        let mut iface = server.interface();
+        let request = request.ip(SERVER_IP);
+        http::send_with_device(
+            &mut iface,
+            &mut server,
+            request,
+            &TickingClock::new(),
+            &mut Hooks::new(),
+        )
+        .map(|(response, _)| response)
+        .map_err(|error| error.root().clone())
+    };
+    assert_eq!(send(HttpRequest::new()).unwrap().body().len(), 100_000);
+    // The limit on the response applies to the decompressed body too.
+    let limited = HttpRequest::new().max_response_bytes(50_000);
+    assert_eq!(send(limited), Err(Error::ResponseTooLarge));
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn decompress_body() {
+    // 1000 bytes of hexadecimal digits, gzip compressed with a dynamic Huffman block.
+    let expected: Vec<u8> = (0..1000)
+        .map(|i: usize| b"0123456789abcdef"[(i * i * 7 + i / 5) % 16])
+        .collect();
+    let gzip = unhex(
+        "1f8b0800000000000203edcc4701c0300800404b8430e530fd4ba88d3e72020eb416a0edd81c3c133716e9\
+         42521d62c2964651b9634b6ac6e0201e2e98a85169b7c8ab2b7838662705def7bef7fdeefb008dd91a23e8\
+         030000",
+    );
+    let mut data = format!(
+        "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+        gzip.len()
+    )
+    .into_bytes();
+    data.extend_from_slice(&gzip);
+    let parser = || ResponseParser::new().decompress(10_000);
+    let response = parser().feed(&data).unwrap().unwrap();
+    assert_eq!(response.body(), expected);
+    assert_eq!(response.header("Content-Encoding"), None);
+    assert_eq!(response.header("Content-Length"), None);
+    // Bodies are only decompressed when asked for.
+    let response = ResponseParser::new().feed(&data).unwrap().unwrap();
+    assert_eq!(response.body(), gzip);
+
+    // Both zlib and raw DEFLATE are accepted for the deflate coding.
+    for body in [
+        "789cab56cacf56b22a292a4dad050016e30411",
+        "ab56cacf56b22a292a4dad0500",
+    ] {
+        let mut data = b"HTTP/1.1 200 OK\r\nContent-Encoding: deflate\r\n\r\n".to_vec();
+        data.extend_from_slice(&unhex(body));
+        let mut parser = parser();
+        assert_eq!(parser.feed(&data), Ok(None));
+        assert_eq!(parser.finish().unwrap().body(), b"{\"ok\":true}");
+    }
+
+    let data = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 8\r\n\r\nnot gzip";
+    assert_eq!(
+        parser().feed(data),
+        Err(Error::InvalidResponse(ParseError::Encoding))
+    );
+
+    // 100 KB of zeros in 120 bytes expands past the limit.
+    let bomb = deflate_bomb();
+    let mut data = format!(
+        "HTTP/1.1 200 OK\r\nContent-Encoding: deflate\r\nContent-Length: {}\r\n\r\n",
+        bomb.len()
+    )
+    .into_bytes();
+    data.extend_from_slice(&bomb);
+    assert_eq!(parser().feed(&data), Err(Error::ResponseTooLarge));
+    let response = ResponseParser::new().decompress(100_000).feed(&data);
+    assert_eq!(response.unwrap().unwrap().body().len(), 100_000);
+    // Streamed bodies are passed on compressed.
+    let mut streamed = Vec::new();
+    let mut on_body = |chunk: &[u8]| streamed.extend_from_slice(chunk);
+    let data = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 3\r\n\r\nabc";
+    let response = ResponseParser::new()
+        .feed_streaming(data, &mut on_body)
+        .unwrap();
+    assert_eq!(response.unwrap().header("Content-Encoding"), Some("gzip"));
+    assert_eq!(streamed, b"abc");
+}
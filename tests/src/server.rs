@@ -0,0 +1,302 @@
+use nostd_rpc::Error;
+use nostd_rpc::response::{HttpResponse, ParseMode, ResponseParser};
+use nostd_rpc::server::{
+    Asset, Handler, Params, Request, Response, Router, Server, StaticAssets, TlsAcceptor,
+    TlsSession,
+};
+use smoltcp::iface::{Config, Interface, SocketSet};
+use smoltcp::phy::{Loopback, Medium};
+use smoltcp::socket::tcp;
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr};
+
+fn handler(request: &Request) -> Response {
+    match (request.method(), request.path()) {
+        ("GET" | "HEAD", "/status") => Response::json(200, "{\"ok\":true}"),
+        ("POST", "/echo") => Response::new(200).body(request.body()),
+        _ => Response::text(404, "not found"),
+    }
+}
+
+/// Sends `requests` to a server on a loopback interface over one connection, returning the
+/// data received until the server closes the connection.
+fn exchange<H: Handler>(requests: &[u8], handler: &mut H) -> Vec<u8> {
+    exchange_with(Server::new(80), requests, handler)
+}
+
+/// Sends `requests` like [`exchange`], to `server`, which must listen on port 80.
+fn exchange_with<H: Handler>(mut server: Server, requests: &[u8], handler: &mut H) -> Vec<u8> {
+    let mut device = Loopback::new(Medium::Ethernet);
+    let config = Config::new(EthernetAddress([0x02, 0, 0, 0, 0, 1]).into());
+    let mut iface = Interface::new(config, &mut device, Instant::ZERO);
+    iface.update_ip_addrs(|addrs| {
+        addrs
+            .push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8))
+            .unwrap();
+    });
+    let mut sockets = SocketSet::new(vec![]);
+
+    server.listen(&mut sockets).unwrap();
+
+    let client = tcp::Socket::new(
+        tcp::SocketBuffer::new(vec![0; 4096]),
+        tcp::SocketBuffer::new(vec![0; 4096]),
+    );
+    let client = sockets.add(client);
+    sockets
+        .get_mut::<tcp::Socket>(client)
+        .connect(iface.context(), (IpAddress::v4(127, 0, 0, 1), 80), 49152)
+        .unwrap();
+
+    let mut received = Vec::new();
+    let mut sent = 0;
+    for millis in 0..1000 {
+        iface.poll(Instant::from_millis(millis), &mut device, &mut sockets);
+        server.poll(&mut sockets, handler).unwrap();
+
+        let socket = sockets.get_mut::<tcp::Socket>(client);
+        if socket.can_send() && sent < requests.len() {
+            sent += socket.send_slice(&requests[sent..]).unwrap();
+        }
+        if socket.can_recv() {
+            socket
+                .recv(|data| {
+                    received.extend_from_slice(data);
+                    (data.len(), ())
+                })
+                .unwrap();
+        }
+        if sent > 0 && !socket.may_recv() {
+            break;
+        }
+    }
+    received
+}
+
+/// Parses the responses in `data`.
+fn responses(data: &[u8]) -> Vec<HttpResponse> {
+    let mut parser = ResponseParser::new().mode(ParseMode::Strict);
+    let mut responses = Vec::new();
+    let mut data = data;
+    while let Some(response) = parser.feed(data).unwrap() {
+        responses.push(response);
+        data = &[];
+    }
+    responses
+}
+
+#[test]
+fn server_keep_alive() {
+    let requests = b"GET /status HTTP/1.1\r\nHost: device\r\n\r\n\
+        POST /echo HTTP/1.1\r\nHost: device\r\nContent-Length: 5\r\n\r\nhello\
+        GET /missing HTTP/1.1\r\nHost: device\r\nConnection: close\r\n\r\n";
+    let responses = responses(&exchange(requests, &mut handler));
+    assert_eq!(responses.len(), 3);
+
+    assert_eq!(responses[0].status(), 200);
+    assert_eq!(
+        responses[0].header("content-type"),
+        Some("application/json")
+    );
+    assert_eq!(responses[0].body(), b"{\"ok\":true}");
+    assert_eq!(responses[1].body(), b"hello");
+    assert_eq!(responses[1].header("connection"), None);
+    assert_eq!(responses[2].status(), 404);
+    assert_eq!(responses[2].reason(), "Not Found");
+    assert_eq!(responses[2].header("connection"), Some("close"));
+}
+
+#[test]
+fn server_head_and_http10() {
+    let received = exchange(b"HEAD /status HTTP/1.0\r\n\r\n", &mut handler);
+    let received = String::from_utf8(received).unwrap();
+    assert!(received.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(received.contains("\r\nContent-Length: 11\r\n"));
+    // HTTP/1.0 connections are closed unless the client asks for keep-alive.
+    assert!(received.ends_with("\r\nConnection: close\r\n\r\n"));
+}
+
+#[test]
+fn server_rejects_bad_requests() {
+    let cases: [(&[u8], u16); 4] = [
+        (b"GET /status\r\n\r\n", 400),
+        (b"GET /status HTTP/2.0\r\n\r\n", 505),
+        (
+            b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n",
+            501,
+        ),
+        (
+            b"POST /echo HTTP/1.1\r\nContent-Length: 100000\r\n\r\n",
+            413,
+        ),
+    ];
+    for (request, status) in cases {
+        let responses = responses(&exchange(request, &mut handler));
+        assert_eq!(responses[0].status(), status);
+        assert_eq!(responses[0].header("connection"), Some("close"));
+    }
+}
+
+#[test]
+fn server_router() {
+    let sensors = [20, 21, 22];
+    let mut router = Router::new()
+        .get(
+            "/sensors/:id",
+            |_: &Request, params: &Params<'_>| match params
+                .get("id")
+                .and_then(|id| id.parse::<usize>().ok())
+            {
+                Some(id) if id < 3 => Response::text(200, &sensors[id].to_string()),
+                _ => Response::new(404),
+            },
+        )
+        .route(
+            "PUT",
+            "/sensors/:id/name/:name",
+            |_: &Request, params: &Params<'_>| {
+                let params: Vec<_> = params.iter().collect();
+                Response::text(200, &format!("{params:?}"))
+            },
+        )
+        .fallback(|request: &Request| Response::text(404, request.path()));
+    let requests = b"GET /sensors/1 HTTP/1.1\r\n\r\n\
+        GET /sensors/7 HTTP/1.1\r\n\r\n\
+        PUT /sensors/2/name/porch HTTP/1.1\r\n\r\n\
+        POST /sensors/1 HTTP/1.1\r\n\r\n\
+        GET /sensors HTTP/1.1\r\nConnection: close\r\n\r\n";
+    let responses = responses(&exchange(requests, &mut router));
+    let summary: Vec<_> = responses
+        .iter()
+        .map(|r| (r.status(), r.body_text_lossy().into_owned()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (200, String::from("21")),
+            (404, String::new()),
+            (
+                200,
+                String::from("[(\"id\", \"2\"), (\"name\", \"porch\")]")
+            ),
+            (405, String::from("method not allowed")),
+            (404, String::from("/sensors")),
+        ]
+    );
+}
+
+static ASSETS: [Asset; 2] = [
+    Asset {
+        path: "/index.html",
+        content_type: "text/html; charset=utf-8",
+        data: b"<h1>Device</h1>",
+        gzip: None,
+    },
+    Asset {
+        path: "/app.js",
+        content_type: "text/javascript",
+        data: b"console.log(1)",
+        gzip: Some(b"\x1f\x8b compressed"),
+    },
+];
+
+#[test]
+fn server_static_assets() {
+    let mut assets = StaticAssets::new(&ASSETS).build_id("abc123");
+    let requests = b"GET / HTTP/1.1\r\n\r\n\
+        GET /app.js HTTP/1.1\r\nAccept-Encoding: deflate, gzip;q=0.5\r\n\r\n\
+        GET /app.js HTTP/1.1\r\nAccept-Encoding: gzip;q=0\r\n\r\n\
+        GET /index.html HTTP/1.1\r\nIf-None-Match: \"old\", \"abc123\"\r\n\r\n\
+        GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n";
+    let responses = responses(&exchange(requests, &mut assets));
+    assert_eq!(responses.len(), 5);
+
+    assert_eq!(responses[0].status(), 200);
+    assert_eq!(
+        responses[0].header("content-type"),
+        Some("text/html; charset=utf-8")
+    );
+    assert_eq!(responses[0].header("etag"), Some("\"abc123\""));
+    assert_eq!(responses[0].body(), b"<h1>Device</h1>");
+    assert_eq!(responses[1].header("content-encoding"), Some("gzip"));
+    assert_eq!(responses[1].header("vary"), Some("Accept-Encoding"));
+    assert_eq!(responses[1].body(), b"\x1f\x8b compressed");
+    assert_eq!(responses[2].header("content-encoding"), None);
+    assert_eq!(responses[2].body(), b"console.log(1)");
+    assert_eq!(responses[3].status(), 304);
+    assert_eq!(responses[3].body(), b"");
+    assert_eq!(responses[4].status(), 404);
+}
+
+/// A stand-in for TLS: the client says HELLO, the server replies WELCOME, then data is
+/// XORed with 0x55 and the server says BYE when it closes.
+#[derive(Default)]
+pub struct FakeTls {
+    hello: Vec<u8>,
+}
+
+impl TlsSession for FakeTls {
+    fn read_tls(
+        &mut self,
+        mut ciphertext: &[u8],
+        plaintext: &mut Vec<u8>,
+        outgoing: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        if self.is_handshaking() {
+            let n = ciphertext.len().min(5 - self.hello.len());
+            self.hello.extend_from_slice(&ciphertext[..n]);
+            ciphertext = &ciphertext[n..];
+            if self.hello.len() == 5 {
+                if self.hello != b"HELLO" {
+                    return Err(Error::Tls);
+                }
+                outgoing.extend_from_slice(b"WELCOME");
+            }
+        }
+        plaintext.extend(ciphertext.iter().map(|b| b ^ 0x55));
+        Ok(())
+    }
+
+    fn is_handshaking(&self) -> bool {
+        self.hello.len() < 5
+    }
+
+    fn write(&mut self, plaintext: &[u8], outgoing: &mut Vec<u8>) -> Result<(), Error> {
+        outgoing.extend(plaintext.iter().map(|b| b ^ 0x55));
+        Ok(())
+    }
+
+    fn close(&mut self, outgoing: &mut Vec<u8>) {
+        outgoing.extend_from_slice(b"BYE");
+    }
+}
+
+pub struct FakeAcceptor;
+
+impl TlsAcceptor for FakeAcceptor {
+    fn accept(&mut self) -> Box<dyn TlsSession> {
+        Box::new(FakeTls::default())
+    }
+}
+
+#[test]
+fn server_tls() {
+    let request = b"GET /status HTTP/1.1\r\nConnection: close\r\n\r\n";
+    let mut client_data = b"HELLO".to_vec();
+    client_data.extend(request.iter().map(|b| b ^ 0x55));
+    let server = Server::new(80).tls(FakeAcceptor);
+    let received = exchange_with(server, &client_data, &mut handler);
+
+    let encrypted = received
+        .strip_prefix(b"WELCOME")
+        .and_then(|r| r.strip_suffix(b"BYE"))
+        .unwrap();
+    let decrypted: Vec<u8> = encrypted.iter().map(|b| b ^ 0x55).collect();
+    let responses = responses(&decrypted);
+    assert_eq!(responses[0].status(), 200);
+    assert_eq!(responses[0].body(), b"{\"ok\":true}");
+
+    // A failed handshake aborts the connection.
+    let server = Server::new(80).tls(FakeAcceptor);
+    assert_eq!(exchange_with(server, b"HI THERE", &mut handler), b"");
+}
@@ -0,0 +1,100 @@
+use std::net::Ipv4Addr;
+
+use nostd_rpc::Hooks;
+#[cfg(feature = "crypto")]
+use nostd_rpc::http::{HmacSigner, hmac_sha256};
+use nostd_rpc::http::{HttpRequest, SignedParts};
+use nostd_rpc::net::NetworkConfig;
+use nostd_rpc::stack::Stack;
+use nostd_rpc::testing::{CLIENT_IP, MockServer, SERVER_IP, TickingClock};
+
+fn stack() -> Stack<MockServer> {
+    let server = MockServer::new(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    let network = NetworkConfig::new().ipv4(Ipv4Addr::from(CLIENT_IP), 24);
+    Stack::new(server, [0x02, 0, 0, 0, 0, 1], network, &TickingClock::new()).unwrap()
+}
+
+fn request() -> HttpRequest {
+    HttpRequest::new()
+        .ip(SERVER_IP)
+        .url("/v1/telemetry?device=7")
+        .body("{\"t\":21}")
+}
+
+#[test]
+fn signer_sees_canonical_parts() {
+    let mut stack = stack();
+    let mut seen = Vec::new();
+    let mut signer = |parts: &SignedParts<'_>| {
+        seen.push((
+            parts.method.to_string(),
+            parts.host.to_string(),
+            parts.path.to_string(),
+            parts.body.to_vec(),
+        ));
+        vec![(String::from("X-Signature"), String::from("abc"))]
+    };
+    let (response, _) = stack
+        .send(
+            &request(),
+            &TickingClock::new(),
+            &mut Hooks::new().signer(&mut signer),
+        )
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        seen,
+        [(
+            String::from("POST"),
+            String::from("10.0.0.2"),
+            String::from("/v1/telemetry?device=7"),
+            b"{\"t\":21}".to_vec()
+        )]
+    );
+    let sent = &stack.parts().1.requests()[0];
+    assert_eq!(sent.header("X-Signature"), Some("abc"));
+    assert_eq!(sent.body(), b"{\"t\":21}");
+}
+
+#[cfg(feature = "crypto")]
+#[test]
+fn hmac_sha256_rfc4231() {
+    let hex = |bytes: [u8; 32]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+    assert_eq!(
+        hex(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    // A key longer than the block is hashed first.
+    assert_eq!(
+        hex(hmac_sha256(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First"
+        )),
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+}
+
+#[cfg(feature = "crypto")]
+#[test]
+fn hmac_signer_headers() {
+    let mut stack = stack();
+    let mut signer = HmacSigner::new(b"secret", || 1_700_000_000);
+    stack
+        .send(
+            &request(),
+            &TickingClock::new(),
+            &mut Hooks::new().signer(&mut signer),
+        )
+        .unwrap();
+
+    let expected: String = hmac_sha256(
+        b"secret",
+        b"POST\n/v1/telemetry?device=7\n1700000000\n{\"t\":21}",
+    )
+    .iter()
+    .map(|b| format!("{b:02x}"))
+    .collect();
+    let sent = &stack.parts().1.requests()[0];
+    assert_eq!(sent.header("X-Timestamp"), Some("1700000000"));
+    assert_eq!(sent.header("X-Signature"), Some(expected.as_str()));
+}
@@ -0,0 +1,73 @@
+use std::cell::Cell;
+use std::net::Ipv4Addr;
+use std::rc::Rc;
+
+use nostd_rpc::Hooks;
+use nostd_rpc::http::HttpRequest;
+use nostd_rpc::jsonrpc::JsonRpcClient;
+use nostd_rpc::net::NetworkConfig;
+use nostd_rpc::stack::Stack;
+use nostd_rpc::testing::{CLIENT_IP, MockServer, SERVER_IP, TickingClock};
+use smoltcp::time::Duration;
+
+const MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+
+fn network() -> NetworkConfig {
+    NetworkConfig::new().ipv4(Ipv4Addr::from(CLIENT_IP), 24)
+}
+
+#[test]
+fn stack_reused_by_requests() {
+    let clock = TickingClock::new();
+    let server = MockServer::new(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    let mut stack = Stack::new(server, MAC, network(), &clock).unwrap();
+    assert_eq!(stack.lease(), None);
+
+    for path in ["/a", "/b"] {
+        let request = HttpRequest::new().ip(SERVER_IP).url(path);
+        let (response, _) = stack.send(&request, &clock, &mut Hooks::new()).unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), b"ok");
+    }
+    let (_, server) = stack.parts();
+    let paths: Vec<_> = server.requests().iter().map(|r| r.path()).collect();
+    assert_eq!(paths, ["/a", "/b"]);
+}
+
+#[test]
+fn stack_jsonrpc_call() {
+    let clock = TickingClock::new();
+    let body = r#"{"jsonrpc":"2.0","result":42,"id":1}"#;
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let server = MockServer::new(response.as_bytes());
+    let network = network().dns_server(Ipv4Addr::new(10, 0, 0, 53));
+    let mut stack = Stack::new(server, MAC, network, &clock).unwrap();
+    assert_eq!(stack.dns_servers(), [Ipv4Addr::new(10, 0, 0, 53)]);
+
+    let mut client = JsonRpcClient::new(MAC, HttpRequest::new().ip(SERVER_IP));
+    let result = client.call_over(&mut stack, "getcount", "[]", &clock, &mut Hooks::new());
+    assert_eq!(result.as_deref(), Ok("42"));
+}
+
+#[test]
+fn stack_waits_through_idle_hook() {
+    let clock = TickingClock::new();
+    let server = MockServer::new(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    let waits = Rc::new(Cell::new(0));
+    let counted = Rc::clone(&waits);
+    let idle = move |duration: Duration| {
+        assert!(duration > Duration::ZERO);
+        counted.set(counted.get() + 1);
+    };
+    let mut stack = Stack::new(server, MAC, network(), &clock)
+        .unwrap()
+        .idle(idle);
+
+    let request = HttpRequest::new().ip(SERVER_IP).url("/");
+    let (response, _) = stack.send(&request, &clock, &mut Hooks::new()).unwrap();
+    assert_eq!(response.body(), b"ok");
+    assert!(waits.get() > 0);
+}
@@ -0,0 +1,991 @@
+use std::cell::{Cell, RefCell};
+use std::net::Ipv6Addr;
+use std::task::Poll;
+
+use nostd_rpc::hooks::{Event, Verdict};
+use nostd_rpc::http::{
+    self, Backoff, BodyProvider, Header, HttpRequest, HttpTransaction, HttpVersion, IterBody,
+    RetryPolicy,
+};
+use nostd_rpc::metrics::{Metrics, Timings};
+use nostd_rpc::net::NetworkConfig;
+use nostd_rpc::response::ParseError;
+use nostd_rpc::server::Server;
+use nostd_rpc::{Error, Hooks, Phase, tcp};
+use smoltcp::iface::{Config, Interface, SocketSet};
+use smoltcp::phy::{Loopback, Medium};
+use smoltcp::socket::tcp as tcp_socket;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr};
+
+use crate::link::{self, Remote};
+
+/// Returns a loopback device and an interface on 127.0.0.1.
+fn loopback() -> (Loopback, Interface) {
+    let mut device = Loopback::new(Medium::Ethernet);
+    let config = Config::new(EthernetAddress([0x02, 0, 0, 0, 0, 1]).into());
+    let mut iface = Interface::new(config, &mut device, Instant::ZERO);
+    iface.update_ip_addrs(|addrs| {
+        addrs
+            .push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8))
+            .unwrap();
+    });
+    (device, iface)
+}
+
+/// Returns a clock that advances by a millisecond each time it is read.
+fn ticking_clock() -> impl Fn() -> Instant {
+    let millis = Cell::new(0);
+    move || {
+        millis.set(millis.get() + 1);
+        Instant::from_millis(millis.get())
+    }
+}
+
+#[test]
+fn probe_refused() {
+    let (mut device, mut iface) = loopback();
+    let result = tcp::probe_with_device(
+        &mut iface,
+        &mut device,
+        [127, 0, 0, 1],
+        8080,
+        Duration::from_secs(1),
+        &ticking_clock(),
+    );
+    assert_eq!(result, Err(Error::ConnectionRefused));
+}
+
+#[test]
+fn send_with_device_times_out() {
+    let (mut device, mut iface) = loopback();
+    let request = HttpRequest::new()
+        .ipv4([127, 0, 0, 1])
+        .port(8080)
+        .timeout(Duration::from_millis(50));
+    let result = http::send_with_device(
+        &mut iface,
+        &mut device,
+        request,
+        &ticking_clock(),
+        &mut Hooks::new(),
+    );
+    match result {
+        // Nothing listens on the port, so the connection is never established.
+        Err(Error::Request { context, .. }) => assert_eq!(context.phase, Phase::Connect),
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+fn invalid_headers_fail() {
+    let invalid = [
+        HttpRequest::new().header("Content-Type application/json"),
+        HttpRequest::new().header(Header::Custom("X Trace", "1")),
+        HttpRequest::new().header(Header::UserAgent("agent\r\nX-Injected: 1")),
+        HttpRequest::new().header("Content-Length: 4"),
+        HttpRequest::new().bearer("token\r\nX-Injected: 1"),
+        HttpRequest::new().api_key("X API Key", "1"),
+        // HTTP/1.1 requires a Host header.
+        HttpRequest::new().template(b"GET /status HTTP/1.1\r\nAccept: */*\r\n"),
+        HttpRequest::new()
+            .header(Header::ContentType("text/plain"))
+            .header(Header::ContentType("application/json")),
+        HttpRequest::new()
+            .header("Connection: close")
+            .header(Header::Connection("keep-alive")),
+    ];
+    for request in invalid {
+        let (mut device, mut iface) = loopback();
+        let request = request.ipv4([127, 0, 0, 1]).port(8080);
+        let result = http::send_with_device(
+            &mut iface,
+            &mut device,
+            request,
+            &ticking_clock(),
+            &mut Hooks::new(),
+        );
+        match result {
+            Err(error) => assert!(matches!(error.root(), Error::InvalidHeader)),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    // A URL that would end the request line early is rejected.
+    for url in ["/a b\r\nX-Evil: 1", "/\0"] {
+        let (mut device, mut iface) = loopback();
+        let request = HttpRequest::new().url(url);
+        let request = request.ipv4([127, 0, 0, 1]).port(8080);
+        let result = http::send_with_device(
+            &mut iface,
+            &mut device,
+            request,
+            &ticking_clock(),
+            &mut Hooks::new(),
+        );
+        match result {
+            Err(error) => assert!(matches!(error.root(), Error::InvalidUrl)),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+}
+
+/// Sends `request` to a server on 10.0.0.2 that answers once it has received the request
+/// head, sending one byte of its response every `interval` milliseconds.
+fn trickle(request: HttpRequest, interval: i64) -> Result<String, Error> {
+    let (mut local, mut remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let server_iface = link::interface(&mut remote, 2);
+    let mut sockets = SocketSet::new(vec![]);
+    let buffer = || tcp_socket::SocketBuffer::new(vec![0; 1024]);
+    let mut socket = tcp_socket::Socket::new(buffer(), buffer());
+    socket.listen(80).unwrap();
+    let handle = sockets.add(socket);
+    let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+    let server = RefCell::new((remote, server_iface, sockets, Vec::new(), 0));
+    let millis = Cell::new(0);
+    let clock = || {
+        millis.set(millis.get() + 1);
+        let now = Instant::from_millis(millis.get());
+        let (device, iface, sockets, received, sent) = &mut *server.borrow_mut();
+        iface.poll(now, device, sockets);
+        let socket = sockets.get_mut::<tcp_socket::Socket>(handle);
+        if socket.can_recv() {
+            socket
+                .recv(|data| {
+                    received.extend_from_slice(data);
+                    (data.len(), ())
+                })
+                .unwrap();
+        }
+        let head_received = received.windows(4).any(|w| w == b"\r\n\r\n");
+        if head_received && millis.get() % interval == 0 && *sent < response.len() {
+            *sent += socket.send_slice(&response[*sent..*sent + 1]).unwrap();
+        }
+        now
+    };
+    let request = request.ipv4([10, 0, 0, 2]).method("GET");
+    let (response, _) =
+        http::send_with_device(&mut iface, &mut local, request, &clock, &mut Hooks::new())
+            .map_err(|error| error.root().clone())?;
+    Ok(String::from_utf8_lossy(response.body()).into_owned())
+}
+
+#[test]
+fn phase_timeouts() {
+    // A slow response completes as long as it does not stall for the read timeout.
+    let request = HttpRequest::new()
+        .timeout(Duration::from_secs(10))
+        .read_timeout(Duration::from_millis(50));
+    assert_eq!(trickle(request.clone(), 10), Ok("ok".to_owned()));
+    assert_eq!(trickle(request, 100), Err(Error::ResponseTimeout));
+
+    // The overall deadline still applies.
+    let request = HttpRequest::new().timeout(Duration::from_millis(200));
+    assert_eq!(trickle(request, 10), Err(Error::ResponseTimeout));
+
+    // Without it only the phase timeouts do.
+    let request = HttpRequest::new()
+        .timeout(Duration::from_millis(200))
+        .no_timeout()
+        .read_timeout(Duration::from_millis(50));
+    assert_eq!(trickle(request.clone(), 10), Ok("ok".to_owned()));
+    assert_eq!(trickle(request, 100), Err(Error::ResponseTimeout));
+
+    let (mut device, mut iface) = loopback();
+    let request = HttpRequest::new()
+        .ipv4([127, 0, 0, 1])
+        .port(8080)
+        .timeout(Duration::from_secs(10))
+        .connect_timeout(Duration::from_millis(20));
+    let error = http::send_with_device(
+        &mut iface,
+        &mut device,
+        request,
+        &ticking_clock(),
+        &mut Hooks::new(),
+    )
+    .unwrap_err();
+    assert_eq!(error.root(), &Error::ConnectTimeout);
+    match error {
+        Error::Request { context, .. } => assert!(context.elapsed < Duration::from_millis(100)),
+        other => panic!("unexpected error: {other:?}"),
+    }
+}
+
+#[test]
+fn request_error_source() {
+    let (mut device, mut iface) = loopback();
+    let network = NetworkConfig::new()
+        .dns_server([127, 0, 0, 1])
+        .dns_timeout(Duration::from_millis(20));
+    let request = HttpRequest::new().host("device.local").network(network);
+    let error = http::send_with_device(
+        &mut iface,
+        &mut device,
+        request,
+        &ticking_clock(),
+        &mut Hooks::new(),
+    )
+    .unwrap_err();
+
+    let source = std::error::Error::source(&error).unwrap();
+    assert_eq!(source.downcast_ref::<Error>(), Some(&Error::DnsTimeout));
+    assert!(source.source().is_none());
+    let shown = error.to_string();
+    assert!(
+        shown.starts_with("request failed during resolve"),
+        "{shown}"
+    );
+    assert!(!shown.contains(&source.to_string()), "{shown}");
+
+    let error = Error::InvalidResponse(ParseError::StatusLine);
+    assert!(std::error::Error::source(&error).is_none());
+    assert_eq!(error.to_string(), "invalid response: malformed status line");
+    let parse: &dyn std::error::Error = &ParseError::StatusLine;
+    assert!(parse.source().is_none());
+}
+
+#[test]
+fn send_with_device_resolves_host() {
+    let (mut device, mut iface) = loopback();
+    // Nothing answers DNS queries on the loopback interface.
+    let network = NetworkConfig::new()
+        .dns_server([127, 0, 0, 1])
+        .dns_timeout(Duration::from_millis(20));
+    let request = HttpRequest::new().host("device.local").network(network);
+    let mut events = Vec::new();
+    let mut observer = |_: Instant, event: Event<'_>| events.push(format!("{event:?}"));
+    let result = http::send_with_device(
+        &mut iface,
+        &mut device,
+        request,
+        &ticking_clock(),
+        &mut Hooks::new().observer(&mut observer),
+    );
+    let error = result.unwrap_err();
+    assert_eq!(error.root(), &Error::DnsTimeout);
+    assert_eq!(error.context().unwrap().phase, Phase::Resolve);
+    assert_eq!(events[0], "Resolving");
+    assert!(!events.iter().any(|event| event == "Connecting"));
+
+    let result = nostd_rpc::dns::resolve(
+        &mut iface,
+        &mut device,
+        &[[127, 0, 0, 1].into()],
+        "bad..name",
+        Duration::from_millis(20),
+        &ticking_clock(),
+    );
+    assert_eq!(result, Err(Error::InvalidHost));
+}
+
+#[test]
+fn send_with_device_completes_before_close() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    // The server keeps the connection open after responding.
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .url("/status")
+        .header("Connection: keep-alive")
+        .timeout(Duration::from_secs(5));
+    let (response, timings) = http::send_with_device(
+        &mut iface,
+        &mut local,
+        request,
+        &link::clock(&remote),
+        &mut Hooks::new(),
+    )
+    .unwrap();
+    assert_eq!(response.body(), b"/status");
+    assert!(timings.total < Duration::from_secs(1));
+}
+
+#[test]
+fn transaction_polls() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .method("HEAD")
+        .url("/status");
+    let mut transaction = HttpTransaction::new(&mut iface, &mut local, request, clock());
+
+    let mut polls = 0;
+    let response = loop {
+        polls += 1;
+        if let Poll::Ready(result) = transaction.poll(clock()) {
+            break result.unwrap();
+        }
+    };
+    assert!(polls > 1);
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.header("content-length"), Some("7"));
+    assert_eq!(response.body(), b"");
+    assert_eq!(
+        transaction.poll(clock()),
+        Poll::Ready(Err(Error::ConnectionClosed))
+    );
+}
+
+#[test]
+fn transaction_with_buffers() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    // The response is larger than the receive buffer.
+    let path = format!("/{}", "x".repeat(300));
+    let request = HttpRequest::new().ipv4([10, 0, 0, 2]).url(&path);
+    let (mut rx_buffer, mut tx_buffer) = ([0; 128], [0; 512]);
+    let mut transaction = HttpTransaction::with_buffers(
+        &mut iface,
+        &mut local,
+        request,
+        &mut rx_buffer,
+        &mut tx_buffer,
+        clock(),
+    );
+    let response = loop {
+        if let Poll::Ready(result) = transaction.poll(clock()) {
+            break result.unwrap();
+        }
+    };
+    assert_eq!(response.body(), path.as_bytes());
+}
+
+#[test]
+fn transaction_without_ip() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let request = HttpRequest::new().host("device.local").url("/");
+    let mut transaction = HttpTransaction::new(&mut iface, &mut local, request, clock());
+    let error = match transaction.poll(clock()) {
+        Poll::Ready(result) => result.unwrap_err(),
+        Poll::Pending => panic!("the request was sent"),
+    };
+    assert_eq!(error.root(), &Error::UnresolvedHost);
+}
+
+#[test]
+fn send_streaming() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let path = format!("/{}", "x".repeat(2000));
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .url(&path)
+        .tx_buffer_size(4096);
+    let mut body = Vec::new();
+    let mut chunks = 0;
+    let (response, timings) = http::send_streaming(
+        &mut iface,
+        &mut local,
+        request,
+        &link::clock(&remote),
+        &mut Hooks::new(),
+        |chunk: &[u8]| {
+            body.extend_from_slice(chunk);
+            chunks += 1;
+        },
+    )
+    .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body(), b"");
+    assert_eq!(body, path.as_bytes());
+    assert!(chunks > 1);
+    assert!(timings.bytes_received > path.len());
+}
+
+#[test]
+fn send_with_body() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .method("POST")
+        .url("/upload");
+
+    // Larger than the transmit buffer, so it is sent as the buffer drains.
+    let body = "y".repeat(3000);
+    let (response, timings) = http::send_with_body(
+        &mut iface,
+        &mut local,
+        request.clone(),
+        &clock,
+        &mut Hooks::new(),
+        body.as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(response.body(), body.as_bytes());
+    assert!(timings.bytes_sent > body.len());
+
+    // A body of unknown length is chunked, which the server does not support.
+    let chunks = IterBody::new(["one", "two"]);
+    let (response, _) = http::send_with_body(
+        &mut iface,
+        &mut local,
+        request.clone(),
+        &clock,
+        &mut Hooks::new(),
+        chunks,
+    )
+    .unwrap();
+    assert_eq!(response.status(), 501);
+
+    // HTTP/1.0 has no chunked transfer coding.
+    let result = http::send_with_body(
+        &mut iface,
+        &mut local,
+        request.version(HttpVersion::Http10),
+        &clock,
+        &mut Hooks::new(),
+        IterBody::new(["one"]),
+    );
+    assert_eq!(result.unwrap_err().root(), &Error::LengthRequired);
+}
+
+#[test]
+fn post_large_body() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let server = Server::new(80).max_body(100_000);
+    let remote = RefCell::new(Some(Remote::with_server(remote, server)));
+    let body = "q".repeat(70_000);
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .url("/upload")
+        .body(&body)
+        .rx_buffer_size(8192)
+        .tx_buffer_size(8192);
+    let (response, timings) = http::send_with_device(
+        &mut iface,
+        &mut local,
+        request,
+        &link::clock(&remote),
+        &mut Hooks::new(),
+    )
+    .unwrap();
+    assert_eq!(response.body(), body.as_bytes());
+    assert!(timings.bytes_sent > 70_000);
+}
+
+/// Posts a 2000 byte body expecting `100 Continue` to a server on 10.0.0.2 that sends
+/// `interim` once it has received the request head, and answers `ok` once it has received
+/// the whole request. Returns the status, the bytes received by the server and the time
+/// taken in milliseconds.
+fn expect_continue(interim: &'static [u8], wait: u64) -> (u16, Vec<u8>, i64) {
+    let (mut local, mut remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let server_iface = link::interface(&mut remote, 2);
+    let mut sockets = SocketSet::new(vec![]);
+    let buffer = || tcp_socket::SocketBuffer::new(vec![0; 4096]);
+    let mut socket = tcp_socket::Socket::new(buffer(), buffer());
+    socket.listen(80).unwrap();
+    let handle = sockets.add(socket);
+    let response: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+    let server = RefCell::new((remote, server_iface, sockets, Vec::new(), Vec::new()));
+    let millis = Cell::new(0);
+    let clock = || {
+        millis.set(millis.get() + 1);
+        let now = Instant::from_millis(millis.get());
+        let (device, iface, sockets, received, outgoing) = &mut *server.borrow_mut();
+        iface.poll(now, device, sockets);
+        let socket = sockets.get_mut::<tcp_socket::Socket>(handle);
+        if socket.can_recv() {
+            let before = received.len();
+            socket
+                .recv(|data| {
+                    received.extend_from_slice(data);
+                    (data.len(), ())
+                })
+                .unwrap();
+            let head = received.windows(4).position(|w| w == b"\r\n\r\n");
+            if let Some(head) = head.filter(|&head| before < head + 4) {
+                outgoing.extend_from_slice(interim);
+                if received.len() - (head + 4) == 2000 {
+                    outgoing.extend_from_slice(response);
+                }
+            } else if head.is_some_and(|head| received.len() - (head + 4) == 2000) {
+                outgoing.extend_from_slice(response);
+            }
+        }
+        if !outgoing.is_empty() && socket.can_send() {
+            let sent = socket.send_slice(outgoing).unwrap();
+            outgoing.drain(..sent);
+        }
+        now
+    };
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .url("/upload")
+        .body(&"z".repeat(2000))
+        .expect_continue(Duration::from_millis(wait));
+    let (response, _) =
+        http::send_with_device(&mut iface, &mut local, request, &clock, &mut Hooks::new()).unwrap();
+    let received = server.into_inner().3;
+    (response.status(), received, millis.get())
+}
+
+#[test]
+fn expect_continue_waits_for_server() {
+    let expect = b"Expect: 100-continue\r\n";
+
+    // The body is sent as soon as the server agrees.
+    let (status, received, millis) = expect_continue(b"HTTP/1.1 100 Continue\r\n\r\n", 5000);
+    assert_eq!(status, 200);
+    assert!(received.windows(expect.len()).any(|w| w == expect));
+    assert!(received.ends_with(&[b'z'; 2000]));
+    assert!(millis < 5000);
+
+    // A server refusing the upload answers before the body is sent.
+    let refusal = b"HTTP/1.1 413 Payload Too Large\r\nContent-Length: 0\r\n\r\n";
+    let (status, received, _) = expect_continue(refusal, 5000);
+    assert_eq!(status, 413);
+    assert!(received.ends_with(b"\r\n\r\n"));
+
+    // Without an answer the body is sent once the wait is over.
+    let (status, received, millis) = expect_continue(b"", 200);
+    assert_eq!(status, 200);
+    assert!(received.ends_with(&[b'z'; 2000]));
+    assert!(millis >= 200);
+}
+
+#[test]
+fn send_through_proxy() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    // The proxy resolves the host, so the request needs no IP address or DNS server.
+    let request = HttpRequest::new()
+        .host("example.com")
+        .url("/via/proxy")
+        .proxy([10, 0, 0, 2], 80);
+    let (response, _) = http::send_with_device(
+        &mut iface,
+        &mut local,
+        request,
+        &link::clock(&remote),
+        &mut Hooks::new(),
+    )
+    .unwrap();
+    assert_eq!(response.body(), b"http://example.com/via/proxy");
+}
+
+#[test]
+fn follow_redirects() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let mut send = |url: &str, max_hops| {
+        let request = HttpRequest::new()
+            .ipv4([10, 0, 0, 2])
+            .method("POST")
+            .url(url)
+            .body("data")
+            .follow_redirects(max_hops);
+        http::send_with_device(&mut iface, &mut local, request, &clock, &mut Hooks::new())
+            .map(|(response, _)| response)
+    };
+
+    // Redirects are not followed by default.
+    let response = send("/redirect/target", 0).unwrap();
+    assert_eq!(response.status(), 302);
+    assert_eq!(response.header("Location"), Some("/target"));
+
+    // A 302 response to a POST request is followed with a GET request without a body.
+    let response = send("/redirect/target", 1).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body(), b"/target");
+
+    let response = send("/redirect/redirect/target", 2).unwrap();
+    assert_eq!(response.body(), b"/target");
+    let error = send("/redirect/redirect/target", 1).unwrap_err();
+    assert_eq!(error.root(), &Error::TooManyRedirects);
+    let error = send("/loop", 5).unwrap_err();
+    assert_eq!(error.root(), &Error::TooManyRedirects);
+}
+
+#[test]
+fn redirect_to_same_host_keeps_ip() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .host("device.local")
+        .url("/absolute/target")
+        .follow_redirects(1);
+    let (response, _) =
+        http::send_with_device(&mut iface, &mut local, request, &clock, &mut Hooks::new()).unwrap();
+    assert_eq!(response.body(), b"/target");
+
+    // Without an IP address or a DNS server the host cannot be reached.
+    let request = HttpRequest::new().host("device.local").url("/target");
+    let error = http::send_with_device(&mut iface, &mut local, request, &clock, &mut Hooks::new())
+        .unwrap_err();
+    assert_eq!(error.root(), &Error::UnresolvedHost);
+}
+
+#[test]
+fn retry_after_network_failure() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::flaky_clock(&remote, 800);
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .url("/again")
+        .timeout(Duration::from_millis(500));
+
+    let result = http::send_with_device(
+        &mut iface,
+        &mut local,
+        request.clone(),
+        &clock,
+        &mut Hooks::new(),
+    );
+    assert!(result.is_err());
+
+    let mut retries = Vec::new();
+    let mut observer = |_: Instant, event: Event<'_>| {
+        if let Event::Retrying { attempt, error } = event {
+            retries.push((attempt, error.clone()));
+        }
+    };
+    let request = request.retry(RetryPolicy::new(
+        5,
+        Backoff::Fixed(Duration::from_millis(100)),
+    ));
+    let clock = link::flaky_clock(&remote, 1000);
+    let (response, _) = http::send_with_device(
+        &mut iface,
+        &mut local,
+        request,
+        &clock,
+        &mut Hooks::new().observer(&mut observer),
+    )
+    .unwrap();
+    assert_eq!(response.body(), b"/again");
+    assert!(!retries.is_empty());
+    assert_eq!(retries[0].0, 2);
+}
+
+#[test]
+fn request_timings() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .url("/upload")
+        .body(&"t".repeat(2000));
+    let mut send = |request: HttpRequest, clock: &dyn Fn() -> Instant| {
+        http::send_with_device(&mut iface, &mut local, request, &clock, &mut Hooks::new())
+            .unwrap()
+            .1
+    };
+
+    let timings = send(request.clone(), &link::clock(&remote));
+    assert!(timings.round_trip.unwrap() > Duration::ZERO);
+    assert_eq!(timings.retransmits, 0);
+    assert!(timings.polls > 0);
+    let phases = [Phase::Connect, Phase::Write, Phase::Read];
+    let total = phases.map(|phase| timings.phase(phase).unwrap());
+    assert_eq!(
+        total.into_iter().fold(Duration::ZERO, |a, b| a + b),
+        timings.total
+    );
+    assert_eq!(timings.phase(Phase::Resolve), None);
+
+    // The handshake is lost until the link comes up, so the SYN is sent again.
+    let timings = send(request, &link::flaky_clock(&remote, 1500));
+    assert!(timings.retransmits > 0);
+}
+
+/// Counts what requests report to [`Metrics`].
+#[derive(Default)]
+struct Counters {
+    started: u32,
+    completed: Vec<Timings>,
+    failed: Vec<Error>,
+    bytes_sent: usize,
+    bytes_received: usize,
+    retransmits: u32,
+}
+
+impl Metrics for Counters {
+    fn request_started(&mut self) {
+        self.started += 1;
+    }
+
+    fn request_completed(&mut self, timings: &Timings) {
+        self.completed.push(*timings);
+    }
+
+    fn request_failed(&mut self, error: &Error) {
+        self.failed.push(error.root().clone());
+    }
+
+    fn bytes_sent(&mut self, count: usize) {
+        self.bytes_sent += count;
+    }
+
+    fn bytes_received(&mut self, count: usize) {
+        self.bytes_received += count;
+    }
+
+    fn retransmits(&mut self, count: u32) {
+        self.retransmits += count;
+    }
+}
+
+/// Sends `request` over a link that loses everything sent to the server until `up_millis`,
+/// returning what it reported to [`Metrics`].
+fn metered(request: HttpRequest, up_millis: i64) -> Counters {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    // A first request resolves the server's Ethernet address, so that only TCP segments
+    // are lost.
+    let warm_up = request.clone();
+    http::send_with_device(
+        &mut iface,
+        &mut local,
+        warm_up,
+        &link::clock(&remote),
+        &mut Hooks::new(),
+    )
+    .unwrap();
+    let clock = link::flaky_clock(&remote, up_millis);
+    let mut counters = Counters::default();
+    let mut hooks = Hooks::new().metrics(&mut counters);
+    let _ = http::send_with_device(&mut iface, &mut local, request, &clock, &mut hooks);
+    counters
+}
+
+#[test]
+fn request_metrics() {
+    let request = HttpRequest::new().ipv4([10, 0, 0, 2]).url("/metered");
+    let counters = metered(request.clone(), 0);
+    assert_eq!(counters.started, 1);
+    assert!(counters.failed.is_empty());
+    let [timings] = counters.completed[..] else {
+        panic!("unexpected completions: {}", counters.completed.len());
+    };
+    assert_eq!(counters.bytes_sent, timings.bytes_sent);
+    assert_eq!(counters.bytes_received, timings.bytes_received);
+    assert_eq!(counters.retransmits, 0);
+
+    // The SYN is sent again once the link comes up, and counted as it is.
+    let counters = metered(request.clone(), 1500);
+    let [timings] = counters.completed[..] else {
+        panic!("unexpected completions: {}", counters.completed.len());
+    };
+    assert!(timings.retransmits > 0);
+    assert_eq!(counters.retransmits, timings.retransmits);
+
+    // Segments sent again by a request that fails are counted too.
+    let counters = metered(request.connect_timeout(Duration::from_millis(1500)), 2000);
+    assert!(counters.completed.is_empty());
+    assert_eq!(counters.failed, [Error::ConnectTimeout]);
+    assert!(counters.retransmits > 0);
+}
+
+#[test]
+fn request_timings_with_resolve() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote).with_dns()));
+    let network = NetworkConfig::new().dns_server([10, 0, 0, 2]);
+    let request = HttpRequest::new()
+        .host("device.example")
+        .url("/timed")
+        .network(network);
+    let (response, timings) = http::send_with_device(
+        &mut iface,
+        &mut local,
+        request,
+        &link::clock(&remote),
+        &mut Hooks::new(),
+    )
+    .unwrap();
+    assert_eq!(response.body(), b"/timed");
+
+    // Each phase ends after the one before it, on a clock that ticks at every read.
+    let dns = timings.dns.unwrap();
+    let connect = timings.connect.unwrap();
+    let first_byte = timings.time_to_first_byte.unwrap();
+    assert!(dns > Duration::ZERO);
+    assert!(connect > dns);
+    assert!(timings.request_sent.unwrap() >= connect);
+    assert!(first_byte > timings.request_sent.unwrap());
+    assert!(timings.total >= first_byte);
+    assert_eq!(timings.phase(Phase::Resolve), Some(dns));
+    let phases = [Phase::Resolve, Phase::Connect, Phase::Write, Phase::Read];
+    let total = phases.map(|phase| timings.phase(phase).unwrap());
+    assert_eq!(
+        total.into_iter().fold(Duration::ZERO, |a, b| a + b),
+        timings.total
+    );
+}
+
+#[test]
+fn inspect_headers() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let mut send = |path: &str, max_length: usize| {
+        let mut inspector = |status: u16, headers: &[(String, String)]| {
+            let length = headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.parse::<usize>().ok());
+            match status == 200 && length.is_some_and(|length| length <= max_length) {
+                true => Verdict::Continue,
+                false => Verdict::Abort,
+            }
+        };
+        let request = HttpRequest::new().ipv4([10, 0, 0, 2]).url(path);
+        http::send_with_device(
+            &mut iface,
+            &mut local,
+            request,
+            &clock,
+            &mut Hooks::new().on_headers(&mut inspector),
+        )
+    };
+
+    let (response, _) = send("/small", 16).unwrap();
+    assert_eq!(response.body(), b"/small");
+    let error = send("/much-too-long-for-the-limit", 16).unwrap_err();
+    assert_eq!(error.root(), &Error::Aborted);
+}
+
+#[test]
+fn retry_delays() {
+    let fixed = RetryPolicy::new(3, Backoff::Fixed(Duration::from_millis(100)));
+    assert_eq!(fixed.delay(1), Duration::from_millis(100));
+    assert_eq!(fixed.delay(2), Duration::from_millis(100));
+    assert!(fixed.retries(2, &Error::ConnectTimeout));
+    assert!(!fixed.retries(3, &Error::ConnectTimeout));
+    assert!(!fixed.retries(1, &Error::Status(500)));
+
+    let exponential = RetryPolicy::new(
+        10,
+        Backoff::Exponential {
+            initial: Duration::from_millis(100),
+            max: Duration::from_millis(500),
+        },
+    );
+    let delays: Vec<_> = (1..=5)
+        .map(|retry| exponential.delay(retry).millis())
+        .collect();
+    assert_eq!(delays, [100, 200, 400, 500, 500]);
+    assert_eq!(RetryPolicy::default().max_attempts(), 1);
+}
+
+#[test]
+fn max_response_bytes() {
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let path = format!("/{}", "z".repeat(500));
+    let mut send = |max| {
+        let request = HttpRequest::new()
+            .ipv4([10, 0, 0, 2])
+            .url(&path)
+            .max_response_bytes(max);
+        http::send_with_device(&mut iface, &mut local, request, &clock, &mut Hooks::new())
+    };
+
+    let (response, _) = send(1000).unwrap();
+    assert_eq!(response.body(), path.as_bytes());
+    let error = send(300).unwrap_err();
+    assert_eq!(error.root(), &Error::ResponseTooLarge);
+    assert_eq!(error.context().unwrap().phase, Phase::Read);
+}
+
+#[test]
+fn iter_body_chunks() {
+    let mut body = IterBody::new(["ab", "", "cde"]);
+    let mut buf = [0; 2];
+    let mut parts = Vec::new();
+    loop {
+        let n = body.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        parts.push(buf[..n].to_vec());
+    }
+    assert_eq!(parts, [&b"ab"[..], b"cd", b"e"]);
+    assert_eq!(body.content_length(), None);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn send_async() {
+    use std::future::Future;
+    use std::task::{Context, Waker};
+
+    let (mut local, remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let remote = RefCell::new(Some(Remote::new(remote)));
+    let clock = link::clock(&remote);
+    let request = HttpRequest::new().ipv4([10, 0, 0, 2]).url("/status");
+    let mut sleeps = Vec::new();
+    let sleep = |delay: Duration| {
+        sleeps.push(delay);
+        std::future::ready(())
+    };
+    let mut pending = 0;
+    let (response, _) = {
+        let future = http::send_async(&mut iface, &mut local, request, &clock, sleep, Hooks::new());
+        let mut future = std::pin::pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => break result.unwrap(),
+                Poll::Pending => pending += 1,
+            }
+        }
+    };
+    assert!(pending > 0);
+    assert_eq!(response.body(), b"/status");
+    // While waiting for the server the request sleeps rather than yielding straight away.
+    assert!(!sleeps.is_empty());
+    assert!(sleeps.iter().all(|&delay| delay > Duration::ZERO));
+}
+
+#[test]
+fn probe_ipv6() {
+    let mut device = Loopback::new(Medium::Ethernet);
+    let config = Config::new(EthernetAddress([0x02, 0, 0, 0, 0, 1]).into());
+    let mut iface = Interface::new(config, &mut device, Instant::ZERO);
+    NetworkConfig::new()
+        .ipv6(Ipv6Addr::LOCALHOST, 128)
+        .apply(&mut iface)
+        .unwrap();
+    let result = tcp::probe_with_device(
+        &mut iface,
+        &mut device,
+        Ipv6Addr::LOCALHOST,
+        8080,
+        Duration::from_secs(1),
+        &ticking_clock(),
+    );
+    assert_eq!(result, Err(Error::ConnectionRefused));
+}
@@ -0,0 +1,291 @@
+use nostd_rpc::hooks::Transfer;
+use nostd_rpc::http::{self, HttpClient, HttpRequest};
+use nostd_rpc::response::{HeaderLimits, ParseError, ParseMode};
+use nostd_rpc::tcp;
+use nostd_rpc::testing::{MockServer, SERVER_IP, SERVER_PORT, TickingClock};
+use nostd_rpc::time::Clock;
+use nostd_rpc::{Error, Hooks, Phase};
+use smoltcp::time::Duration;
+
+/// Sends `request` to `server`, reading the time from a ticking clock.
+fn send(server: &mut MockServer, request: HttpRequest) -> Result<http::HttpResponse, Error> {
+    let mut iface = server.interface();
+    let request = request.ip(SERVER_IP).timeout(Duration::from_millis(500));
+    http::send_with_device(
+        &mut iface,
+        server,
+        request,
+        &TickingClock::new(),
+        &mut Hooks::new(),
+    )
+    .map(|(response, _)| response)
+}
+
+#[test]
+fn mock_server_request() {
+    let mut server = MockServer::new(b"HTTP/1.1 204 No Content\r\n\r\n");
+    let request = HttpRequest::new()
+        .method("PUT")
+        .host("device.local")
+        .url("/config?id=7")
+        .header("Content-Type: application/json")
+        .body("{\"on\":true}");
+    let response = send(&mut server, request).unwrap();
+    assert_eq!(response.status(), 204);
+
+    let [received] = server.requests() else {
+        panic!("unexpected requests: {:?}", server.requests().len());
+    };
+    assert_eq!(received.method(), "PUT");
+    assert_eq!(received.path(), "/config");
+    assert_eq!(received.query(), Some("id=7"));
+    assert_eq!(received.header("host"), Some("device.local"));
+    assert_eq!(received.header("content-type"), Some("application/json"));
+    assert_eq!(received.body(), b"{\"on\":true}");
+}
+
+#[test]
+fn mock_server_chunked_response() {
+    let mut server = MockServer::new(
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nX-Id: 3\r\n\r\n\
+          5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\n\r\n",
+    );
+    let response = send(&mut server, HttpRequest::new().method("GET")).unwrap();
+    assert_eq!(response.header("x-id"), Some("3"));
+    assert_eq!(response.body(), b"hello, world");
+
+    // Every request is answered, on a new connection.
+    let response = send(&mut server, HttpRequest::new().method("GET")).unwrap();
+    assert_eq!(response.body(), b"hello, world");
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[test]
+fn mock_server_binary_response() {
+    // Every byte value, over several segments, is received unchanged.
+    let body: Vec<u8> = (0..3000).map(|i| (i * 7 % 256) as u8).collect();
+    let mut response =
+        format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).into_bytes();
+    response.extend_from_slice(&body);
+    let mut server = MockServer::new(&response);
+    let response = send(&mut server, HttpRequest::new().method("GET")).unwrap();
+    assert_eq!(response.body(), body);
+    assert_eq!(response.body_text_strict(), Err(Error::InvalidUtf8));
+
+    let mut server = MockServer::new(
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+          3\r\n\x00\xff\xfe\r\n2\r\n\r\n\r\n0\r\n\r\n",
+    );
+    let response = send(&mut server, HttpRequest::new().method("GET")).unwrap();
+    assert_eq!(response.into_body(), b"\x00\xff\xfe\r\n");
+}
+
+#[test]
+fn mock_server_timeouts() {
+    let error = send(&mut MockServer::unreachable(), HttpRequest::new()).unwrap_err();
+    assert_eq!(error.root(), &Error::ConnectTimeout);
+
+    let mut server = MockServer::silent();
+    let error = send(&mut server, HttpRequest::new().body("ping")).unwrap_err();
+    assert_eq!(error.root(), &Error::ResponseTimeout);
+    match error {
+        Error::Request { context, .. } => assert_eq!(context.phase, Phase::Read),
+        other => panic!("unexpected error: {other:?}"),
+    }
+    assert_eq!(server.requests()[0].body(), b"ping");
+
+    let mut server = MockServer::new(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort");
+    let error = send(&mut server, HttpRequest::new()).unwrap_err();
+    assert!(
+        matches!(error.root(), Error::InvalidResponse(_)),
+        "{error:?}"
+    );
+}
+
+#[test]
+fn mock_server_reset() {
+    // A body delimited by the end of the connection is truncated by a reset.
+    let mut server = MockServer::resetting(b"HTTP/1.1 200 OK\r\n\r\npartial");
+    let error = send(&mut server, HttpRequest::new()).unwrap_err();
+    assert_eq!(error.root(), &Error::ConnectionReset);
+
+    let mut server = MockServer::resetting(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort");
+    let iface = server.interface();
+    let mut client = HttpClient::new(server, iface);
+    let request = HttpRequest::new()
+        .ip(SERVER_IP)
+        .timeout(Duration::from_millis(500));
+    let error = client
+        .send(&request, &TickingClock::new(), &mut Hooks::new())
+        .unwrap_err();
+    assert_eq!(error.root(), &Error::ConnectionReset);
+}
+
+#[test]
+fn mock_server_close() {
+    // The response ends with the connection, which is closed on both ends.
+    let mut server = MockServer::new(b"HTTP/1.1 200 OK\r\n\r\nuntil closed");
+    let response = send(&mut server, HttpRequest::new()).unwrap();
+    assert_eq!(response.body(), b"until closed");
+
+    let iface = server.interface();
+    let mut client = HttpClient::new(server, iface);
+    let request = HttpRequest::new()
+        .ip(SERVER_IP)
+        .timeout(Duration::from_millis(500));
+    for _ in 0..2 {
+        let (response, _) = client
+            .send(&request, &TickingClock::new(), &mut Hooks::new())
+            .unwrap();
+        assert_eq!(response.body(), b"until closed");
+    }
+}
+
+#[test]
+fn mock_server_timings() {
+    let mut server = MockServer::new(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    let mut iface = server.interface();
+    let request = HttpRequest::new().ip(SERVER_IP).method("GET");
+    let clock = TickingClock::new();
+    let (_, timings) =
+        http::send_with_device(&mut iface, &mut server, request, &clock, &mut Hooks::new())
+            .unwrap();
+
+    // No name was resolved, and each later phase ends after the one before it.
+    assert_eq!(timings.dns, None);
+    let connect = timings.connect.unwrap();
+    let first_byte = timings.time_to_first_byte.unwrap();
+    assert!(connect > Duration::ZERO);
+    assert!(first_byte > connect);
+    assert!(timings.total >= first_byte);
+    // The clock ticks a millisecond at every read, the first of them at the start.
+    assert!(timings.total < Duration::from_millis(clock.now().total_millis() as u64));
+    assert_eq!(timings.bytes_received, 40);
+}
+
+#[test]
+fn mock_server_progress() {
+    // Both bodies span several segments and socket buffers.
+    let mut response = b"HTTP/1.1 200 OK\r\nContent-Length: 3000\r\n\r\n".to_vec();
+    response.extend_from_slice(&[b'r'; 3000]);
+    let mut server = MockServer::new(&response);
+    let mut iface = server.interface();
+    let request = HttpRequest::new()
+        .ip(SERVER_IP)
+        .url("/upload")
+        .body(&"u".repeat(4000))
+        .timeout(Duration::from_millis(500));
+    let request_len = request.construct_http_request().len();
+
+    let mut reports = Vec::new();
+    let mut observer =
+        |direction, transferred, total| reports.push((direction, transferred, total));
+    let (received, _) = http::send_with_device(
+        &mut iface,
+        &mut server,
+        request,
+        &TickingClock::new(),
+        &mut Hooks::new().progress(&mut observer),
+    )
+    .unwrap();
+    assert_eq!(received.body().len(), 3000);
+
+    // The whole request is reported sent before any of the response is received.
+    let split = reports
+        .iter()
+        .position(|(direction, _, _)| *direction == Transfer::Download)
+        .unwrap();
+    let (uploads, downloads) = reports.split_at(split);
+    assert!(uploads.len() > 1);
+    assert!(uploads.iter().all(|(direction, _, total)| {
+        *direction == Transfer::Upload && *total == Some(request_len)
+    }));
+    assert!(uploads.windows(2).all(|pair| pair[0].1 < pair[1].1));
+    assert_eq!(uploads.last().unwrap().1, request_len);
+
+    // The download total is known from the head onwards and includes it.
+    assert!(downloads.len() > 1);
+    assert!(
+        downloads
+            .iter()
+            .all(|(direction, _, _)| *direction == Transfer::Download)
+    );
+    assert!(downloads.windows(2).all(|pair| pair[0].1 < pair[1].1));
+    assert!(
+        downloads
+            .iter()
+            .all(|(_, _, total)| *total == Some(response.len()))
+    );
+    assert_eq!(downloads.last().unwrap().1, response.len());
+}
+
+#[test]
+fn mock_server_parse_mode() {
+    let response = b"HTTP/1.1 200 OK\nContent-Length: 2\n\nok";
+    let mut server = MockServer::new(response);
+    let response = send(&mut server, HttpRequest::new()).unwrap();
+    assert_eq!(response.body(), b"ok");
+
+    // A strict request rejects the bare line feeds, whether sent once or by a client.
+    let strict = HttpRequest::new().parse_mode(ParseMode::Strict);
+    let error = send(&mut server, strict.clone()).unwrap_err();
+    assert_eq!(
+        error.root(),
+        &Error::InvalidResponse(ParseError::BareLineFeed)
+    );
+    let iface = server.interface();
+    let mut client = HttpClient::new(server, iface);
+    let request = strict.ip(SERVER_IP).timeout(Duration::from_millis(500));
+    let error = client
+        .send(&request, &TickingClock::new(), &mut Hooks::new())
+        .unwrap_err();
+    assert_eq!(
+        error.root(),
+        &Error::InvalidResponse(ParseError::BareLineFeed)
+    );
+}
+
+#[test]
+fn mock_server_header_limits() {
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nX-Large: {}\r\nContent-Length: 2\r\n\r\nok",
+        "a".repeat(200)
+    );
+    let mut server = MockServer::new(response.as_bytes());
+    let response = send(&mut server, HttpRequest::new()).unwrap();
+    assert_eq!(response.body(), b"ok");
+
+    let limits = HeaderLimits::new().max_header_bytes(128);
+    let limited = HttpRequest::new().header_limits(limits);
+    let error = send(&mut server, limited.clone()).unwrap_err();
+    assert_eq!(error.root(), &Error::HeadersTooLarge);
+    let limits = HeaderLimits::new().max_headers(1);
+    let error = send(&mut server, HttpRequest::new().header_limits(limits)).unwrap_err();
+    assert_eq!(error.root(), &Error::HeadersTooLarge);
+
+    let iface = server.interface();
+    let mut client = HttpClient::new(server, iface);
+    let request = limited.ip(SERVER_IP).timeout(Duration::from_millis(500));
+    let error = client
+        .send(&request, &TickingClock::new(), &mut Hooks::new())
+        .unwrap_err();
+    assert_eq!(error.root(), &Error::HeadersTooLarge);
+}
+
+#[test]
+fn mock_server_probe() {
+    let probe = |server: &mut MockServer, port| {
+        let mut iface = server.interface();
+        let clock = TickingClock::new();
+        let timeout = Duration::from_millis(500);
+        tcp::probe_with_device(&mut iface, server, SERVER_IP, port, timeout, &clock)
+    };
+
+    let elapsed = probe(&mut MockServer::silent(), SERVER_PORT).unwrap();
+    assert!(elapsed < Duration::from_millis(500));
+    // Nothing listens on the other ports, so the server resets the connection.
+    let result = probe(&mut MockServer::silent(), SERVER_PORT + 1);
+    assert_eq!(result, Err(Error::ConnectionRefused));
+    let result = probe(&mut MockServer::unreachable(), SERVER_PORT);
+    assert_eq!(result, Err(Error::ConnectTimeout));
+}
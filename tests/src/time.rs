@@ -0,0 +1,37 @@
+use nostd_rpc::time::{Clock, ClockCell, parse_http_date};
+use smoltcp::time::Instant;
+
+#[test]
+fn http_date_formats() {
+    let expected = Some(784_111_777);
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+    assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+    assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), expected);
+
+    assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+    assert_eq!(
+        parse_http_date("Thu, 29 Feb 2024 12:00:00 GMT"),
+        Some(1_709_208_000)
+    );
+}
+
+#[test]
+fn invalid_http_dates() {
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+    assert_eq!(parse_http_date("Sun, 6 Nov 1994 08:49:37 GMT"), None);
+    assert_eq!(parse_http_date("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+    assert_eq!(parse_http_date(""), None);
+}
+
+#[test]
+fn clock_cell() {
+    // A tick counter that needs mutable access, as a timer driver would.
+    let mut ticks = 0;
+    let clock = ClockCell::new(|| {
+        ticks += 10;
+        Instant::from_millis(ticks)
+    });
+    assert_eq!(clock.now(), Instant::from_millis(10));
+    assert_eq!(clock.now(), Instant::from_millis(20));
+}
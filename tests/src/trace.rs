@@ -0,0 +1,93 @@
+use std::cell::{Cell, RefCell};
+use std::sync::Once;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use nostd_rpc::http::{self, HttpRequest};
+use nostd_rpc::{Error, Hooks};
+use smoltcp::time::{Duration, Instant};
+
+use crate::link::{self, Remote};
+
+thread_local! {
+    /// Messages logged by nostd-rpc on this thread.
+    static MESSAGES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records the messages of nostd-rpc, separately for each test thread.
+struct Recorder;
+
+impl Log for Recorder {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target().starts_with("nostd_rpc") && metadata.level() <= Level::Debug
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            MESSAGES.with(|messages| messages.borrow_mut().push(record.args().to_string()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Returns the messages logged on this thread while running `f`.
+fn record(f: impl FnOnce()) -> Vec<String> {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        log::set_logger(&Recorder).unwrap();
+        log::set_max_level(LevelFilter::Debug);
+    });
+    MESSAGES.with(|messages| messages.borrow_mut().clear());
+    f();
+    MESSAGES.with(|messages| messages.take())
+}
+
+#[test]
+fn trace_transport_states() {
+    let messages = record(|| {
+        let (mut local, remote) = link::pipe();
+        let mut iface = link::interface(&mut local, 1);
+        let remote = RefCell::new(Some(Remote::new(remote)));
+        let clock = link::clock(&remote);
+        let request = HttpRequest::new().ipv4([10, 0, 0, 2]).url("/traced");
+        let (response, _) =
+            http::send_with_device(&mut iface, &mut local, request, &clock, &mut Hooks::new())
+                .unwrap();
+        assert_eq!(response.body(), b"/traced");
+    });
+    for expected in [
+        "connecting to port 80",
+        "tcp CLOSED -> SYN-SENT",
+        "tcp SYN-SENT -> ESTABLISHED",
+        "response status 200",
+        "response complete",
+    ] {
+        assert!(
+            messages.iter().any(|m| m == expected),
+            "{expected:?} not in {messages:?}"
+        );
+    }
+
+    let messages = record(|| {
+        let (mut local, _remote) = link::pipe();
+        let mut iface = link::interface(&mut local, 1);
+        let request = HttpRequest::new()
+            .ipv4([10, 0, 0, 2])
+            .timeout(Duration::from_millis(20));
+        let millis = Cell::new(0);
+        let clock = || {
+            millis.set(millis.get() + 1);
+            Instant::from_millis(millis.get())
+        };
+        let error =
+            http::send_with_device(&mut iface, &mut local, request, &clock, &mut Hooks::new())
+                .unwrap_err();
+        assert_eq!(error.root(), &Error::ConnectTimeout);
+    });
+    assert!(
+        messages
+            .iter()
+            .any(|m| m.starts_with("connect timed out after")),
+        "{messages:?}"
+    );
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+
+use nostd_rpc::Error;
+use nostd_rpc::http::{HttpClient, HttpRequest};
+use nostd_rpc::testing::{MockServer, SERVER_IP, SERVER_PORT, TickingClock};
+use nostd_rpc::transport::{HttpTransport, Request, Response, Transport};
+
+fn transport(body: &str) -> HttpTransport<MockServer, TickingClock> {
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let mut server = MockServer::new(response.as_bytes());
+    let iface = server.interface();
+    let request = HttpRequest::new()
+        .ip(SERVER_IP)
+        .port(SERVER_PORT)
+        .url("/wallet/main")
+        .basic_auth("user", "pass");
+    HttpTransport::new(HttpClient::new(server, iface), request, TickingClock::new())
+}
+
+struct Target<'a>(&'a dyn Transport);
+
+impl fmt::Display for Target<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_target(f)
+    }
+}
+
+#[test]
+fn transport_send_request() {
+    let mut transport = transport(r#"{"result":812345,"error":null,"id":7}"#);
+    assert_eq!(
+        Target(&transport).to_string(),
+        "http://10.0.0.2:80/wallet/main"
+    );
+
+    let request = Request {
+        method: "getblockcount",
+        params: "",
+        id: 7,
+    };
+    let response = transport.send_request(request).unwrap();
+    assert_eq!(
+        response,
+        Response {
+            id: 7,
+            result: Ok(String::from("812345"))
+        }
+    );
+}
+
+#[test]
+fn transport_send_batch() {
+    let mut transport = transport(
+        r#"[{"result":null,"error":{"code":-32601,"message":"Method not found"},"id":2},
+            {"result":"00ab","error":null,"id":1}]"#,
+    );
+    let requests = [
+        Request {
+            method: "getbestblockhash",
+            params: "",
+            id: 1,
+        },
+        Request {
+            method: "nosuchmethod",
+            params: "[1]",
+            id: 2,
+        },
+    ];
+    let responses = transport.send_batch(&requests).unwrap();
+    assert_eq!(responses[0].result, Ok(String::from("\"00ab\"")));
+    assert_eq!(
+        responses[1].result,
+        Err(Error::Rpc {
+            code: -32601,
+            message: String::from("Method not found")
+        })
+    );
+    assert_eq!(transport.send_batch(&[]), Ok(Vec::new()));
+}
@@ -0,0 +1,73 @@
+use nostd_rpc::http::HttpRequest;
+use nostd_rpc::jsonrpc::JsonRpcClient;
+use nostd_rpc::testing::{MockServer, SERVER_IP, TickingClock};
+use nostd_rpc::{Error, Hooks};
+
+const MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+
+/// Returns a server answering every call with the JSON-RPC response `body`.
+fn server(body: &str) -> MockServer {
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    MockServer::new(response.as_bytes())
+}
+
+#[test]
+fn call_typed_with_device_result() {
+    let mut server = server(r#"{"jsonrpc":"2.0","result":[5,-1],"id":1}"#);
+    let mut iface = server.interface();
+    let mut client = JsonRpcClient::new(MAC, HttpRequest::new().ip(SERVER_IP));
+    let result: Result<(u32, i32), _> = client.call_typed_with_device(
+        &mut iface,
+        &mut server,
+        "add",
+        &[2, 3],
+        &TickingClock::new(),
+        &mut Hooks::new(),
+    );
+    assert_eq!(result, Ok((5, -1)));
+    let body = server.requests()[0].body();
+    assert_eq!(
+        body,
+        br#"{"jsonrpc":"2.0","method":"add","params":[2,3],"id":1}"#
+    );
+}
+
+#[test]
+fn call_typed_with_device_rpc_error() {
+    let body = r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":1}"#;
+    let mut server = server(body);
+    let mut iface = server.interface();
+    let mut client = JsonRpcClient::new(MAC, HttpRequest::new().ip(SERVER_IP));
+    let result: Result<u32, _> = client.call_typed_with_device(
+        &mut iface,
+        &mut server,
+        "missing",
+        &(),
+        &TickingClock::new(),
+        &mut Hooks::new(),
+    );
+    let error = Error::Rpc {
+        code: -32601,
+        message: "Method not found".into(),
+    };
+    assert_eq!(result, Err(error));
+}
+
+#[test]
+fn call_typed_with_device_wrong_shape() {
+    let mut server = server(r#"{"jsonrpc":"2.0","result":{"sum":5},"id":1}"#);
+    let mut iface = server.interface();
+    let mut client = JsonRpcClient::new(MAC, HttpRequest::new().ip(SERVER_IP));
+    let result: Result<u32, _> = client.call_typed_with_device(
+        &mut iface,
+        &mut server,
+        "add",
+        &[2, 3],
+        &TickingClock::new(),
+        &mut Hooks::new(),
+    );
+    assert_eq!(result, Err(Error::InvalidJson));
+}
@@ -0,0 +1,114 @@
+use std::cell::{Cell, RefCell};
+
+use nostd_rpc::Error;
+use nostd_rpc::udp::{self, UdpRequest};
+use smoltcp::iface::SocketSet;
+use smoltcp::socket::udp::{PacketBuffer, PacketMetadata, Socket};
+use smoltcp::time::{Duration, Instant};
+
+use crate::link;
+
+type Reply = Result<Option<Vec<u8>>, Error>;
+
+/// Sends `request` to a server on 10.0.0.2 port 8125 that answers each datagram with it in
+/// upper case if `answer` is set, returning the result and the datagrams the server received.
+fn exchange(request: UdpRequest, answer: bool) -> (Reply, Vec<Vec<u8>>) {
+    let (result, received) = exchange_from(request, answer);
+    (result, received.into_iter().map(|(_, data)| data).collect())
+}
+
+/// Like [`exchange`], also returning the source port of each datagram the server received.
+fn exchange_from(request: UdpRequest, answer: bool) -> (Reply, Vec<(u16, Vec<u8>)>) {
+    let (mut local, mut remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let server_iface = link::interface(&mut remote, 2);
+    let buffer = || PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 1024]);
+    let mut socket = Socket::new(buffer(), buffer());
+    socket.bind(8125).unwrap();
+    let mut sockets = SocketSet::new(vec![]);
+    let handle = sockets.add(socket);
+    let server = RefCell::new((remote, server_iface, sockets, Vec::new()));
+    let millis = Cell::new(0);
+    let clock = || {
+        millis.set(millis.get() + 1);
+        let now = Instant::from_millis(millis.get());
+        let (device, iface, sockets, received) = &mut *server.borrow_mut();
+        iface.poll(now, device, sockets);
+        let socket = sockets.get_mut::<Socket>(handle);
+        while let Ok((data, meta)) = socket.recv() {
+            let data = data.to_vec();
+            if answer {
+                socket
+                    .send_slice(&data.to_ascii_uppercase(), meta.endpoint)
+                    .unwrap();
+            }
+            received.push((meta.endpoint.port, data));
+        }
+        now
+    };
+
+    let result = udp::send_with_device(&mut iface, &mut local, request, &clock);
+    // Let the server receive datagrams that were not answered.
+    clock();
+    clock();
+    (result, server.into_inner().3)
+}
+
+#[test]
+fn udp_reply() {
+    let request = UdpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .port(8125)
+        .payload(b"ping");
+    let (result, received) = exchange(request, true);
+    assert_eq!(result, Ok(Some(b"PING".to_vec())));
+    assert_eq!(received, [b"ping".to_vec()]);
+}
+
+#[test]
+fn udp_without_ip() {
+    let request = UdpRequest::new().port(8125).payload(b"ping");
+    let (result, received) = exchange(request, true);
+    assert_eq!(result, Err(Error::UnresolvedHost));
+    assert!(received.is_empty());
+}
+
+#[test]
+fn udp_local_ports() {
+    let source_port = |random| {
+        let request = UdpRequest::new()
+            .ipv4([10, 0, 0, 2])
+            .port(8125)
+            .payload(b"ping")
+            .local_ports(random);
+        let (result, received) = exchange_from(request, true);
+        assert_eq!(result, Ok(Some(b"PING".to_vec())));
+        received[0].0
+    };
+    assert!(source_port([7; 8]) >= 49152);
+    assert_eq!(source_port([7; 8]), source_port([7; 8]));
+    assert_ne!(source_port([7; 8]), source_port([8; 8]));
+}
+
+#[test]
+fn udp_without_reply() {
+    let request = UdpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .port(8125)
+        .payload(b"requests:1|c")
+        .expect_reply(false);
+    let (result, received) = exchange(request, false);
+    assert_eq!(result, Ok(None));
+    assert_eq!(received, [b"requests:1|c".to_vec()]);
+}
+
+#[test]
+fn udp_reply_timeout() {
+    let request = UdpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .port(8125)
+        .payload(b"ping")
+        .timeout(Duration::from_millis(100));
+    let (result, _) = exchange(request, false);
+    assert_eq!(result, Err(Error::ResponseTimeout));
+}
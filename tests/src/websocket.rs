@@ -0,0 +1,175 @@
+use std::cell::{Cell, RefCell};
+use std::task::Poll;
+
+use nostd_rpc::Error;
+use nostd_rpc::http::HttpRequest;
+use nostd_rpc::websocket::{Message, WebSocket};
+use smoltcp::iface::SocketSet;
+use smoltcp::socket::tcp;
+use smoltcp::time::{Duration, Instant};
+
+use crate::link;
+
+/// The nonce of the handshake example in RFC 6455 section 1.3.
+const NONCE: [u8; 16] = *b"the sample nonce";
+
+/// Removes the first masked frame from `data`, returning its first byte and unmasked payload.
+fn unmask(data: &mut Vec<u8>) -> Option<(u8, Vec<u8>)> {
+    if data.len() < 2 {
+        return None;
+    }
+    assert_eq!(data[1] & 0x80, 0x80, "client frames are masked");
+    let (len, start) = match data[1] & 0x7f {
+        126 => (usize::from(u16::from_be_bytes([data[2], data[3]])), 4),
+        len => (usize::from(len), 2),
+    };
+    if data.len() < start + 4 + len {
+        return None;
+    }
+    let mask = [
+        data[start],
+        data[start + 1],
+        data[start + 2],
+        data[start + 3],
+    ];
+    let payload = data[start + 4..start + 4 + len]
+        .iter()
+        .zip(mask.iter().cycle())
+        .map(|(b, m)| b ^ m)
+        .collect();
+    let first = data[0];
+    data.drain(..start + 4 + len);
+    Some((first, payload))
+}
+
+/// Opens a WebSocket to a server that answers the handshake with `answer`, then echoes every
+/// frame, and returns the messages received until `send` has been called with each message
+/// received and the connection closes.
+fn exchange(
+    answer: &[u8],
+    mut send: impl FnMut(&mut WebSocket<'_, link::Pipe>, &Message),
+) -> (Vec<Result<Message, Error>>, String) {
+    let (mut local, mut remote) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let server_iface = link::interface(&mut remote, 2);
+    let mut sockets = SocketSet::new(vec![]);
+    let buffer = || tcp::SocketBuffer::new(vec![0; 1024]);
+    let mut socket = tcp::Socket::new(buffer(), buffer());
+    socket.listen(80).unwrap();
+    let handle = sockets.add(socket);
+    let server = RefCell::new((remote, server_iface, sockets, Vec::new(), String::new()));
+    let millis = Cell::new(0);
+    let clock = || {
+        millis.set(millis.get() + 1);
+        let now = Instant::from_millis(millis.get());
+        let (device, iface, sockets, received, head) = &mut *server.borrow_mut();
+        iface.poll(now, device, sockets);
+        let socket = sockets.get_mut::<tcp::Socket>(handle);
+        if socket.can_recv() {
+            socket
+                .recv(|data| {
+                    received.extend_from_slice(data);
+                    (data.len(), ())
+                })
+                .unwrap();
+        }
+        if head.is_empty()
+            && let Some(end) = received.windows(4).position(|w| w == b"\r\n\r\n")
+        {
+            *head = String::from_utf8(received.drain(..end + 4).collect()).unwrap();
+            socket.send_slice(answer).unwrap();
+        }
+        while let Some((first, payload)) = unmask(received) {
+            socket.send_slice(&[first, payload.len() as u8]).unwrap();
+            socket.send_slice(&payload).unwrap();
+        }
+        now
+    };
+
+    let request = HttpRequest::new()
+        .ipv4([10, 0, 0, 2])
+        .host("node.local")
+        .url("/ws")
+        .timeout(Duration::from_secs(1));
+    let mut websocket =
+        WebSocket::connect(&mut iface, &mut local, &request, NONCE, clock()).unwrap();
+    let mut messages = Vec::new();
+    loop {
+        match websocket.poll(clock()) {
+            Poll::Pending => {}
+            Poll::Ready(result) => {
+                let done = !matches!(result, Ok(Message::Text(_) | Message::Binary(_)));
+                if let Ok(message) = &result {
+                    send(&mut websocket, message);
+                }
+                messages.push(result);
+                if done {
+                    break;
+                }
+            }
+        }
+    }
+    let head = server.borrow().4.clone();
+    (messages, head)
+}
+
+#[test]
+fn websocket_echo() {
+    // The server greets the client in the same segment as the handshake.
+    let answer = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                   Connection: Upgrade\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n\
+                   \x81\x05hello";
+    let (messages, head) = exchange(answer, |websocket, message| match message {
+        Message::Text(text) if text == "hello" => {
+            websocket.send_text("subscribe").unwrap();
+            websocket.send_binary(&[0xde, 0xad]).unwrap();
+        }
+        Message::Binary(_) => websocket.close(1000).unwrap(),
+        _ => {}
+    });
+    assert_eq!(
+        head,
+        "GET /ws HTTP/1.1\r\nHost: node.local\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n"
+    );
+    assert_eq!(
+        messages,
+        [
+            Ok(Message::Text("hello".to_owned())),
+            Ok(Message::Text("subscribe".to_owned())),
+            Ok(Message::Binary(vec![0xde, 0xad])),
+            Ok(Message::Close(Some(1000))),
+        ]
+    );
+}
+
+#[test]
+fn websocket_handshake_rejected() {
+    let answer = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                   Connection: Upgrade\r\nSec-WebSocket-Accept: bm90IHRoZSBrZXk=\r\n\r\n";
+    let (messages, _) = exchange(answer, |_, _| {});
+    assert_eq!(messages, [Err(Error::WebSocketHandshake)]);
+
+    let answer = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+    let (messages, _) = exchange(answer, |_, _| {});
+    assert_eq!(messages, [Err(Error::Status(404))]);
+}
+
+#[test]
+fn websocket_without_ip() {
+    let (mut local, _) = link::pipe();
+    let mut iface = link::interface(&mut local, 1);
+    let request = HttpRequest::new().host("node.local").url("/ws");
+    let result = WebSocket::connect(&mut iface, &mut local, &request, NONCE, Instant::ZERO);
+    assert_eq!(result.err(), Some(Error::UnresolvedHost));
+}
+
+#[test]
+fn websocket_oversized_frame() {
+    // A frame claiming a 64-bit length is refused before its payload is buffered.
+    let answer = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                   Connection: Upgrade\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n\
+                   \x82\x7f\x00\x00\x01\x00\x00\x00\x00\x00data";
+    let (messages, _) = exchange(answer, |_, _| {});
+    assert_eq!(messages, [Err(Error::ResponseTooLarge)]);
+}